@@ -0,0 +1,140 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Interpreted against compiled encoding and decoding. Run with `cargo bench`.
+#[macro_use]
+extern crate bencher;
+extern crate rand;
+extern crate ravro;
+
+use bencher::Bencher;
+use rand::{SeedableRng, StdRng};
+
+use ravro::datum::Datum;
+use ravro::encoding::{binary, resolve};
+use ravro::random;
+use ravro::schema::{self, Schema};
+
+const EVENT: &'static str = r#"{"type": "record", "name": "Event", "namespace": "bench", "fields": [
+    {"name": "id", "type": "long"},
+    {"name": "timestamp", "type": "long"},
+    {"name": "name", "type": "string"},
+    {"name": "score", "type": "double"},
+    {"name": "tags", "type": {"type": "array", "items": "string"}},
+    {"name": "attributes", "type": {"type": "map", "values": "int"}},
+    {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["CLICK", "VIEW", "BUY"]}},
+    {"name": "source", "type": ["null", {"type": "record", "name": "Source", "fields": [
+        {"name": "host", "type": "string"},
+        {"name": "port", "type": "int"}
+    ]}]}
+]}"#;
+
+// A projection of Event, with a promoted field (the attributes' ints are read
+// as longs).
+const READER: &'static str = r#"{"type": "record", "name": "Event", "namespace": "bench", "fields": [
+    {"name": "id", "type": "long"},
+    {"name": "score", "type": "double"},
+    {"name": "attributes", "type": {"type": "map", "values": "long"}},
+    {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["CLICK", "VIEW", "BUY"]}}
+]}"#;
+
+const DATUMS: usize = 100;
+
+fn setup() -> (Schema, Vec<Datum>, Vec<u8>) {
+    let schema = schema::from_str(EVENT).unwrap();
+    let mut rng = StdRng::from_seed(&[1usize][..]);
+    let datums: Vec<Datum> = (0..DATUMS).map(|_| random::generate(&schema, &mut rng).unwrap()).collect();
+    let mut buf = Vec::new();
+    for datum in datums.iter() {
+        binary::encode(datum, &schema, &mut buf).unwrap();
+    }
+    (schema, datums, buf)
+}
+
+fn decode_interpreted(b: &mut Bencher) {
+    let (schema, _, buf) = setup();
+    b.bytes = buf.len() as u64;
+    b.iter(|| {
+        let mut input = &buf[..];
+        for _ in 0..DATUMS {
+            binary::decode(&schema, &mut input).unwrap();
+        }
+    });
+}
+
+fn decode_compiled(b: &mut Bencher) {
+    let (schema, _, buf) = setup();
+    let plan = schema.compile().unwrap();
+    b.bytes = buf.len() as u64;
+    b.iter(|| {
+        let mut input = &buf[..];
+        for _ in 0..DATUMS {
+            plan.decode(&mut input).unwrap();
+        }
+    });
+}
+
+fn resolve_interpreted(b: &mut Bencher) {
+    let (writer, _, buf) = setup();
+    let reader = schema::from_str(READER).unwrap();
+    b.bytes = buf.len() as u64;
+    b.iter(|| {
+        let mut input = &buf[..];
+        for _ in 0..DATUMS {
+            resolve::decode(&writer, &reader, &mut input).unwrap();
+        }
+    });
+}
+
+fn resolve_compiled(b: &mut Bencher) {
+    let (writer, _, buf) = setup();
+    let plan = writer.compile_for(&schema::from_str(READER).unwrap()).unwrap();
+    b.bytes = buf.len() as u64;
+    b.iter(|| {
+        let mut input = &buf[..];
+        for _ in 0..DATUMS {
+            plan.decode(&mut input).unwrap();
+        }
+    });
+}
+
+fn encode_interpreted(b: &mut Bencher) {
+    let (schema, datums, buf) = setup();
+    b.bytes = buf.len() as u64;
+    b.iter(|| {
+        let mut out = Vec::with_capacity(buf.len());
+        for datum in datums.iter() {
+            binary::encode(datum, &schema, &mut out).unwrap();
+        }
+        out
+    });
+}
+
+fn encode_compiled(b: &mut Bencher) {
+    let (schema, datums, buf) = setup();
+    let plan = schema.compile().unwrap();
+    b.bytes = buf.len() as u64;
+    b.iter(|| {
+        let mut out = Vec::with_capacity(buf.len());
+        for datum in datums.iter() {
+            plan.encode(datum, &mut out).unwrap();
+        }
+        out
+    });
+}
+
+benchmark_group!(decode, decode_interpreted, decode_compiled);
+benchmark_group!(resolve, resolve_interpreted, resolve_compiled);
+benchmark_group!(encode, encode_interpreted, encode_compiled);
+benchmark_main!(decode, resolve, encode);
@@ -0,0 +1,574 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Apache Arrow schemas and record batches for Avro data, so container files
+// can be loaded into Arrow-based query engines without going through JSON.
+//
+// Records become structs, and the top level record a whole Arrow schema. A
+// union of null and one other type is that type, nullable; any other union
+// is a dense union whose type ids are the Avro branch indexes. Enums are
+// dictionary encoded with their symbols as the dictionary. Recursive types
+// have no Arrow equivalent.
+use std::io::Read;
+use std::sync::Arc;
+
+use arrow_rs::array::{
+    ArrayRef,
+    BinaryArray,
+    BooleanArray,
+    Date32Array,
+    Decimal128Array,
+    DictionaryArray,
+    FixedSizeBinaryArray,
+    Float32Array,
+    Float64Array,
+    Int32Array,
+    Int64Array,
+    ListArray,
+    MapArray,
+    NullArray,
+    StringArray,
+    StructArray,
+    Time32MillisecondArray,
+    Time64MicrosecondArray,
+    TimestampMicrosecondArray,
+    TimestampMillisecondArray,
+    UnionArray
+};
+use arrow_rs::buffer::{NullBuffer, OffsetBuffer, ScalarBuffer};
+use arrow_rs::datatypes::{DataType, Field, Fields, Int32Type, Schema as ArrowSchema, SchemaRef, TimeUnit, UnionFields, UnionMode};
+use arrow_rs::error::ArrowError;
+use arrow_rs::record_batch::RecordBatch;
+use serde::json::Value;
+
+use container::Reader;
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+use schema::names::{self, Names, SchemaKind};
+
+// The widest decimal that fits in a Decimal128.
+const MAX_DECIMAL_PRECISION: u64 = 38;
+
+/// The Arrow schema for datums written with `schema`. A record's fields
+/// become the schema's fields; any other type is a single field, "value".
+pub fn to_arrow_schema(schema: &Schema) -> Result<ArrowSchema, Error> {
+    let names = Names::from_schema(schema);
+    let converter = Converter { names: &names };
+    Ok(ArrowSchema::new(try!(converter.top_fields(&Value::from(schema)))))
+}
+
+/// Reads a container file as Arrow record batches of up to `batch_size`
+/// datums each.
+pub struct BatchReader<R: Read> {
+    reader: Reader<R>,
+    schema_value: Value,
+    names: Names,
+    arrow_schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl<R: Read> BatchReader<R> {
+    pub fn new(reader: Reader<R>, batch_size: usize) -> Result<BatchReader<R>, Error> {
+        let schema = reader.schema().clone();
+        BatchReader::with_schema(reader, &schema, batch_size)
+    }
+
+    /// Like `new`, for a reader whose datums come out as `schema` rather than
+    /// the file's schema, as with `Reader::with_reader_schema`.
+    pub fn with_schema(reader: Reader<R>, schema: &Schema, batch_size: usize) -> Result<BatchReader<R>, Error> {
+        let arrow_schema = try!(to_arrow_schema(schema));
+        Ok(BatchReader {
+            reader: reader,
+            schema_value: Value::from(schema),
+            names: Names::from_schema(schema),
+            arrow_schema: Arc::new(arrow_schema),
+            batch_size: if batch_size == 0 { 1 } else { batch_size },
+        })
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.arrow_schema.clone()
+    }
+
+    /// Reads the next batch, or None at the end of the file.
+    pub fn read_batch(&mut self) -> Result<Option<RecordBatch>, Error> {
+        let mut datums = Vec::with_capacity(self.batch_size);
+        while datums.len() < self.batch_size {
+            match try!(self.reader.read()) {
+                Some(datum) => datums.push(datum),
+                None => break,
+            }
+        }
+        if datums.is_empty() {
+            return Ok(None);
+        }
+
+        let values: Vec<Option<&Datum>> = datums.iter().map(Some).collect();
+        let converter = Converter { names: &self.names };
+        let columns = try!(converter.top_columns(&self.schema_value, &values));
+        RecordBatch::try_new(self.arrow_schema.clone(), columns).map(Some).map_err(arrow_error)
+    }
+
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for BatchReader<R> {
+    type Item = Result<RecordBatch, Error>;
+
+    fn next(&mut self) -> Option<Result<RecordBatch, Error>> {
+        match self.read_batch() {
+            Ok(Some(batch)) => Some(Ok(batch)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+struct Converter<'a> {
+    names: &'a Names,
+}
+
+impl<'a> Converter<'a> {
+    fn top_fields(&self, schema: &Value) -> Result<Vec<Field>, Error> {
+        let mut records = Vec::new();
+        match try!(self.names.kind(schema, None)) {
+            (SchemaKind::Record(definition), ns) => self.record_fields(definition, ns.as_ref().map(|ns| &ns[..]), &mut records),
+            _ => Ok(vec![try!(self.field("value", schema, None, &mut records))]),
+        }
+    }
+
+    fn top_columns(&self, schema: &Value, values: &[Option<&Datum>]) -> Result<Vec<ArrayRef>, Error> {
+        match try!(self.names.kind(schema, None)) {
+            (SchemaKind::Record(definition), ns) => {
+                let ns = ns.as_ref().map(|ns| &ns[..]);
+                let mut columns = Vec::new();
+                for (name, field_type) in record_fields(definition) {
+                    let column: Vec<Option<&Datum>> = values.iter().map(|v| v.and_then(|d| d.field(name))).collect();
+                    columns.push(try!(self.array(field_type, ns, &column)));
+                }
+                Ok(columns)
+            },
+            _ => Ok(vec![try!(self.array(schema, None, values))]),
+        }
+    }
+
+    fn record_fields(&self, definition: &Value, ns: Option<&str>, records: &mut Vec<String>) -> Result<Vec<Field>, Error> {
+        // A record inside itself would make an infinitely deep struct.
+        let fullname = names::fullname_of(definition, ns);
+        if records.contains(&fullname) {
+            return Err(Error::EncodeError(ErrorCode::NotRepresentableInArrow));
+        }
+        records.push(fullname);
+
+        let mut fields = Vec::new();
+        for (name, field_type) in record_fields(definition) {
+            fields.push(try!(self.field(name, field_type, ns, records)));
+        }
+        records.pop();
+        Ok(fields)
+    }
+
+    fn field(&self, name: &str, schema: &Value, ns: Option<&str>, records: &mut Vec<String>) -> Result<Field, Error> {
+        let (data_type, nullable) = try!(self.data_type(schema, ns, records));
+        Ok(Field::new(name, data_type, nullable))
+    }
+
+    // The Arrow type for `schema`, and whether it's nullable.
+    fn data_type(&self, schema: &Value, ns: Option<&str>, records: &mut Vec<String>) -> Result<(DataType, bool), Error> {
+        let (kind, nested_ns) = try!(self.names.kind(schema, ns));
+        let nested_ns = nested_ns.as_ref().map(|ns| &ns[..]);
+        let data_type = match kind {
+            SchemaKind::Primitive(type_name) => {
+                return Ok((try!(primitive_type(type_name, schema)), type_name == "null"));
+            },
+            SchemaKind::Record(definition) => DataType::Struct(Fields::from(try!(self.record_fields(definition, nested_ns, records)))),
+            SchemaKind::Enum(_) => DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            SchemaKind::Fixed(definition) => {
+                match try!(decimal(definition)) {
+                    Some((precision, scale)) => DataType::Decimal128(precision, scale),
+                    None => DataType::FixedSizeBinary(fixed_size(definition)),
+                }
+            },
+            SchemaKind::Array(items) => DataType::List(Arc::new(try!(self.field("item", items, nested_ns, records)))),
+            SchemaKind::Map(values) => {
+                let entries = vec![Field::new("key", DataType::Utf8, false), try!(self.field("value", values, nested_ns, records))];
+                DataType::Map(Arc::new(Field::new("entries", DataType::Struct(Fields::from(entries)), false)), false)
+            },
+            SchemaKind::Union(branches) => {
+                if let Some(branch) = try!(self.nullable_branch(branches, nested_ns)) {
+                    let (data_type, _) = try!(self.data_type(&branches[branch], nested_ns, records));
+                    return Ok((data_type, true));
+                }
+                let mut fields = Vec::new();
+                for branch in branches.iter() {
+                    let (data_type, _) = try!(self.data_type(branch, nested_ns, records));
+                    fields.push(Field::new(branch_label(branch), data_type, true));
+                }
+                DataType::Union(UnionFields::new(0..branches.len() as i8, fields), UnionMode::Dense)
+            },
+        };
+        Ok((data_type, false))
+    }
+
+    // For a union of null and one other type, the other type's branch.
+    fn nullable_branch(&self, branches: &Vec<Value>, ns: Option<&str>) -> Result<Option<usize>, Error> {
+        if branches.len() != 2 {
+            return Ok(None);
+        }
+        let mut nulls = Vec::new();
+        for branch in branches.iter() {
+            nulls.push(match try!(self.names.kind(branch, ns)) {
+                (SchemaKind::Primitive("null"), _) => true,
+                _ => false,
+            });
+        }
+        Ok(match (nulls[0], nulls[1]) {
+            (true, false) => Some(1),
+            (false, true) => Some(0),
+            _ => None,
+        })
+    }
+
+    // The array for a column of datums, None for nulls.
+    fn array(&self, schema: &Value, ns: Option<&str>, values: &[Option<&Datum>]) -> Result<ArrayRef, Error> {
+        let (kind, nested_ns) = try!(self.names.kind(schema, ns));
+        let nested_ns = nested_ns.as_ref().map(|ns| &ns[..]);
+        if let SchemaKind::Union(branches) = kind {
+            return self.union_array(branches, nested_ns, values);
+        }
+
+        let values: Vec<Option<&Datum>> = values.iter().map(|v| v.and_then(plain)).collect();
+        let values = &values[..];
+        match kind {
+            SchemaKind::Primitive(type_name) => primitive_array(type_name, schema, values),
+            SchemaKind::Record(definition) => {
+                let mut fields = Vec::new();
+                let mut children = Vec::new();
+                for (name, field_type) in record_fields(definition) {
+                    let column: Vec<Option<&Datum>> = values.iter().map(|v| v.and_then(|d| d.field(name))).collect();
+                    let child = try!(self.array(field_type, nested_ns, &column));
+                    let (_, nullable) = try!(self.data_type(field_type, nested_ns, &mut Vec::new()));
+                    fields.push(Field::new(name, child.data_type().clone(), nullable));
+                    children.push(child);
+                }
+                if fields.is_empty() {
+                    return Ok(Arc::new(StructArray::new_empty_fields(values.len(), null_buffer(values))));
+                }
+                let array = try!(StructArray::try_new(Fields::from(fields), children, null_buffer(values)).map_err(arrow_error));
+                Ok(Arc::new(array))
+            },
+            SchemaKind::Enum(definition) => {
+                let symbols: Vec<&str> = match definition.find("symbols") {
+                    Some(&Value::Array(ref symbols)) => symbols.iter().filter_map(|s| s.as_string()).collect(),
+                    _ => Vec::new(),
+                };
+                let keys = try!(column(values, |d| match *d {
+                    Datum::Enum(ref symbol) => symbols.iter().position(|s| s == symbol).map(|i| i as i32),
+                    _ => None,
+                }));
+                let array = try!(DictionaryArray::<Int32Type>::try_new(Int32Array::from(keys), Arc::new(StringArray::from(symbols))).map_err(arrow_error));
+                Ok(Arc::new(array))
+            },
+            SchemaKind::Fixed(definition) => {
+                let bytes = try!(column(values, |d| match *d {
+                    Datum::Fixed(ref bytes) => Some(&bytes[..]),
+                    _ => None,
+                }));
+                match try!(decimal(definition)) {
+                    Some((precision, scale)) => decimal_array(&bytes, precision, scale),
+                    None => {
+                        let size = fixed_size(definition);
+                        let array = try!(FixedSizeBinaryArray::try_from_sparse_iter_with_size(bytes.into_iter(), size).map_err(arrow_error));
+                        Ok(Arc::new(array))
+                    },
+                }
+            },
+            SchemaKind::Array(items) => {
+                let mut lengths = Vec::with_capacity(values.len());
+                let mut flattened = Vec::new();
+                for value in values.iter() {
+                    match *value {
+                        Some(&Datum::Array(ref array)) => {
+                            lengths.push(array.len());
+                            flattened.extend(array.iter().map(Some));
+                        },
+                        Some(_) => return Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                        None => lengths.push(0),
+                    }
+                }
+                let (item_type, nullable) = try!(self.data_type(items, nested_ns, &mut Vec::new()));
+                let child = try!(self.array(items, nested_ns, &flattened));
+                let field = Arc::new(Field::new("item", item_type, nullable));
+                let array = try!(ListArray::try_new(field, OffsetBuffer::from_lengths(lengths), child, null_buffer(values)).map_err(arrow_error));
+                Ok(Arc::new(array))
+            },
+            SchemaKind::Map(value_type) => {
+                let mut lengths = Vec::with_capacity(values.len());
+                let mut keys = Vec::new();
+                let mut entries = Vec::new();
+                for value in values.iter() {
+                    match *value {
+                        Some(&Datum::Map(ref map)) => {
+                            lengths.push(map.len());
+                            for (key, entry) in map.iter() {
+                                keys.push(&key[..]);
+                                entries.push(Some(entry));
+                            }
+                        },
+                        Some(_) => return Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                        None => lengths.push(0),
+                    }
+                }
+                let (entry_type, nullable) = try!(self.data_type(value_type, nested_ns, &mut Vec::new()));
+                let entry_values = try!(self.array(value_type, nested_ns, &entries));
+                let fields = Fields::from(vec![Field::new("key", DataType::Utf8, false), Field::new("value", entry_type, nullable)]);
+                let key_array: ArrayRef = Arc::new(StringArray::from(keys));
+                let struct_array = try!(StructArray::try_new(fields.clone(), vec![key_array, entry_values], None).map_err(arrow_error));
+                let field = Arc::new(Field::new("entries", DataType::Struct(fields), false));
+                let array = try!(MapArray::try_new(field, OffsetBuffer::from_lengths(lengths), struct_array, null_buffer(values), false).map_err(arrow_error));
+                Ok(Arc::new(array))
+            },
+            SchemaKind::Union(_) => unreachable!(),
+        }
+    }
+
+    fn union_array(&self, branches: &Vec<Value>, ns: Option<&str>, values: &[Option<&Datum>]) -> Result<ArrayRef, Error> {
+        if let Some(branch) = try!(self.nullable_branch(branches, ns)) {
+            return self.array(&branches[branch], ns, values);
+        }
+
+        // A value missing altogether, inside a null record say, goes in the
+        // null branch if there is one, or as a null in the first.
+        let mut null_branch = 0;
+        for (i, branch) in branches.iter().enumerate() {
+            if let (SchemaKind::Primitive("null"), _) = try!(self.names.kind(branch, ns)) {
+                null_branch = i;
+                break;
+            }
+        }
+
+        let mut type_ids = Vec::with_capacity(values.len());
+        let mut offsets = Vec::with_capacity(values.len());
+        let mut per_branch: Vec<Vec<Option<&Datum>>> = vec![Vec::new(); branches.len()];
+        for value in values.iter() {
+            let (branch, datum) = match *value {
+                Some(&Datum::Union(i, ref datum)) => (i, Some(&**datum)),
+                Some(_) => return Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                None => (null_branch, None),
+            };
+            if branch >= branches.len() {
+                return Err(Error::EncodeError(ErrorCode::UnionIndexOutOfRange));
+            }
+            type_ids.push(branch as i8);
+            offsets.push(per_branch[branch].len() as i32);
+            per_branch[branch].push(datum);
+        }
+
+        let mut fields = Vec::new();
+        let mut children = Vec::new();
+        for (branch, branch_values) in branches.iter().zip(per_branch.iter()) {
+            let child = try!(self.array(branch, ns, branch_values));
+            fields.push(Field::new(branch_label(branch), child.data_type().clone(), true));
+            children.push(child);
+        }
+        let fields = UnionFields::new(0..branches.len() as i8, fields);
+        let array = try!(UnionArray::try_new(fields, ScalarBuffer::from(type_ids), Some(ScalarBuffer::from(offsets)), children).map_err(arrow_error));
+        Ok(Arc::new(array))
+    }
+}
+
+fn primitive_type(type_name: &str, schema: &Value) -> Result<DataType, Error> {
+    let data_type = match (type_name, logical_type(schema)) {
+        ("null", _) => DataType::Null,
+        ("boolean", _) => DataType::Boolean,
+        ("int", Some("date")) => DataType::Date32,
+        ("int", Some("time-millis")) => DataType::Time32(TimeUnit::Millisecond),
+        ("int", _) => DataType::Int32,
+        ("long", Some("time-micros")) => DataType::Time64(TimeUnit::Microsecond),
+        ("long", Some("timestamp-millis")) => DataType::Timestamp(TimeUnit::Millisecond, Some(Arc::from("UTC"))),
+        ("long", Some("timestamp-micros")) => DataType::Timestamp(TimeUnit::Microsecond, Some(Arc::from("UTC"))),
+        ("long", Some("local-timestamp-millis")) => DataType::Timestamp(TimeUnit::Millisecond, None),
+        ("long", Some("local-timestamp-micros")) => DataType::Timestamp(TimeUnit::Microsecond, None),
+        ("long", _) => DataType::Int64,
+        ("float", _) => DataType::Float32,
+        ("double", _) => DataType::Float64,
+        ("bytes", _) => {
+            match try!(decimal(schema)) {
+                Some((precision, scale)) => DataType::Decimal128(precision, scale),
+                None => DataType::Binary,
+            }
+        },
+        _ => DataType::Utf8,
+    };
+    Ok(data_type)
+}
+
+fn primitive_array(type_name: &str, schema: &Value, values: &[Option<&Datum>]) -> Result<ArrayRef, Error> {
+    let data_type = try!(primitive_type(type_name, schema));
+    let array: ArrayRef = match data_type {
+        DataType::Null => Arc::new(NullArray::new(values.len())),
+        DataType::Boolean => Arc::new(BooleanArray::from(try!(column(values, |d| match *d {
+            Datum::Boolean(b) => Some(b),
+            _ => None,
+        })))),
+        DataType::Int32 | DataType::Date32 | DataType::Time32(_) => {
+            let ints = try!(column(values, |d| match *d {
+                Datum::Int(n) => Some(n),
+                _ => None,
+            }));
+            match data_type {
+                DataType::Date32 => Arc::new(Date32Array::from(ints)),
+                DataType::Time32(_) => Arc::new(Time32MillisecondArray::from(ints)),
+                _ => Arc::new(Int32Array::from(ints)),
+            }
+        },
+        DataType::Int64 | DataType::Time64(_) | DataType::Timestamp(_, _) => {
+            let longs = try!(column(values, |d| match *d {
+                Datum::Long(n) => Some(n),
+                Datum::Int(n) => Some(n as i64),
+                _ => None,
+            }));
+            match data_type {
+                DataType::Time64(_) => Arc::new(Time64MicrosecondArray::from(longs)),
+                DataType::Timestamp(TimeUnit::Millisecond, tz) => Arc::new(TimestampMillisecondArray::from(longs).with_timezone_opt(tz)),
+                DataType::Timestamp(_, tz) => Arc::new(TimestampMicrosecondArray::from(longs).with_timezone_opt(tz)),
+                _ => Arc::new(Int64Array::from(longs)),
+            }
+        },
+        DataType::Float32 => Arc::new(Float32Array::from(try!(column(values, |d| match *d {
+            Datum::Float(n) => Some(n),
+            _ => None,
+        })))),
+        DataType::Float64 => Arc::new(Float64Array::from(try!(column(values, |d| match *d {
+            Datum::Double(n) => Some(n),
+            Datum::Float(n) => Some(n as f64),
+            _ => None,
+        })))),
+        DataType::Binary | DataType::Decimal128(_, _) => {
+            let bytes = try!(column(values, |d| match *d {
+                Datum::Bytes(ref bytes) => Some(&bytes[..]),
+                _ => None,
+            }));
+            match data_type {
+                DataType::Decimal128(precision, scale) => return decimal_array(&bytes, precision, scale),
+                _ => Arc::new(BinaryArray::from(bytes)),
+            }
+        },
+        _ => Arc::new(StringArray::from(try!(column(values, |d| match *d {
+            Datum::String(ref s) => Some(&s[..]),
+            _ => None,
+        })))),
+    };
+    Ok(array)
+}
+
+// Avro decimals are big-endian two's complement integers.
+fn decimal_array(values: &[Option<&[u8]>], precision: u8, scale: i8) -> Result<ArrayRef, Error> {
+    let mut unscaled = Vec::with_capacity(values.len());
+    for value in values.iter() {
+        unscaled.push(match *value {
+            Some(bytes) if bytes.len() > 16 => return Err(Error::EncodeError(ErrorCode::IntegerOutOfRange)),
+            Some(bytes) => {
+                let negative = bytes.first().map_or(false, |b| b & 0x80 != 0);
+                let initial: i128 = if negative { -1 } else { 0 };
+                Some(bytes.iter().fold(initial, |n, &b| (n << 8) | b as i128))
+            },
+            None => None,
+        });
+    }
+    let array = try!(Decimal128Array::from(unscaled).with_precision_and_scale(precision, scale).map_err(arrow_error));
+    Ok(Arc::new(array))
+}
+
+// The values in a column, failing on any that don't fit.
+fn column<'d, T, F>(values: &[Option<&'d Datum>], f: F) -> Result<Vec<Option<T>>, Error> where
+    F: Fn(&'d Datum) -> Option<T>
+{
+    values.iter().map(|value| match *value {
+        Some(datum) => f(datum).map(Some).ok_or(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+        None => Ok(None),
+    }).collect()
+}
+
+fn null_buffer(values: &[Option<&Datum>]) -> Option<NullBuffer> {
+    if values.iter().all(|v| v.is_some()) {
+        return None;
+    }
+    Some(NullBuffer::from(values.iter().map(|v| v.is_some()).collect::<Vec<bool>>()))
+}
+
+// A datum with any union wrapping removed, or None for null.
+fn plain(datum: &Datum) -> Option<&Datum> {
+    match *datum.unwrap_union() {
+        Datum::Null => None,
+        ref datum => Some(datum),
+    }
+}
+
+fn record_fields(definition: &Value) -> Vec<(&str, &Value)> {
+    match definition.find("fields") {
+        Some(&Value::Array(ref fields)) => {
+            fields.iter().filter_map(|f| {
+                match (f.find("name").and_then(|n| n.as_string()), f.find("type")) {
+                    (Some(name), Some(field_type)) => Some((name, field_type)),
+                    _ => None,
+                }
+            }).collect()
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn logical_type(schema: &Value) -> Option<&str> {
+    schema.find("logicalType").and_then(|l| l.as_string())
+}
+
+// The precision and scale of a decimal logical type, or None for anything else.
+fn decimal(schema: &Value) -> Result<Option<(u8, i8)>, Error> {
+    if logical_type(schema) != Some("decimal") {
+        return Ok(None);
+    }
+    let precision = schema.find("precision").and_then(|p| p.as_u64()).unwrap_or(0);
+    let scale = schema.find("scale").and_then(|s| s.as_u64()).unwrap_or(0);
+    if precision == 0 || precision > MAX_DECIMAL_PRECISION || scale > precision {
+        return Err(Error::EncodeError(ErrorCode::NotRepresentableInArrow));
+    }
+    Ok(Some((precision as u8, scale as i8)))
+}
+
+fn fixed_size(definition: &Value) -> i32 {
+    definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0) as i32
+}
+
+// A name for a union branch's child: the type's name.
+fn branch_label(branch: &Value) -> String {
+    match *branch {
+        Value::String(ref name) => name.clone(),
+        Value::Object(_) => {
+            match branch.find("name").and_then(|n| n.as_string()) {
+                Some(name) => String::from(name),
+                None => branch.find("type").and_then(|t| t.as_string()).map(String::from).unwrap_or(String::from("value")),
+            }
+        },
+        _ => String::from("null"),
+    }
+}
+
+fn arrow_error(error: ArrowError) -> Error {
+    debug!("arrow conversion failed: {}", error);
+    Error::EncodeError(ErrorCode::ArrowConversionFailed)
+}
@@ -0,0 +1,545 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// ravro: the essentials of avro-tools, for looking at container files on
+// machines without a JVM.
+extern crate rand;
+extern crate ravro;
+extern crate serde;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::process;
+use std::str::FromStr;
+
+use rand::{SeedableRng, StdRng};
+use serde::json;
+
+use ravro::container::{Codec, Reader, Writer};
+use ravro::encoding;
+use ravro::protocol::Protocol;
+use ravro::random;
+use ravro::schema::{self, Schema};
+use ravro::schema::docs::{DocWriter, Format};
+use ravro::schema::error::{Error, ErrorCode};
+
+const USAGE: &'static str = "usage: ravro <command> [options] [args]
+
+commands:
+    getschema FILE                       print the schema of a container file
+    getmeta [--key KEY] FILE             print the header metadata
+    tojson [--pretty] [--reader-schema SCHEMA] FILE
+                                         print the datums as Avro JSON, one per line
+    fromjson --schema SCHEMA [--reader-schema SCHEMA] [--codec CODEC] INPUT
+                                         write Avro JSON datums (one per line) to a
+                                         container file on stdout
+    count FILE...                        count the datums in container files
+    cat [--offset N] [--limit N] INPUT... OUTPUT
+                                         copy a range of datums to a new file
+    concat INPUT... OUTPUT               join files with the same schema
+    recodec [--codec CODEC] INPUT OUTPUT recompress a file (null or deflate)
+    random --schema SCHEMA --count N [--seed N] [--codec CODEC] OUTPUT
+                                         write N random datums to a container file
+    validate-schema SCHEMA               check that a schema file is valid
+    diff OLD NEW                         report the changes between two schemas and
+                                         whether they break compatibility
+    doc [--format markdown|html] [--title TITLE] SCHEMA...
+                                         document the named types in schema and
+                                         protocol files, in Markdown by default
+
+A FILE, INPUT or OUTPUT of - means stdin or stdout. With --reader-schema,
+datums are resolved against that schema as they're converted.
+
+exit status: 0 on success, 1 for bad usage, 2 for an invalid schema, 3 for an
+I/O error, 4 for data that can't be read and 5 for data that can't be written.";
+
+// Options that take a value, and those that don't.
+const VALUE_OPTIONS: [&'static str; 10] = ["--key", "--schema", "--reader-schema", "--offset", "--limit", "--codec", "--count", "--seed", "--format", "--title"];
+const FLAG_OPTIONS: [&'static str; 1] = ["--pretty"];
+
+enum CliError {
+    Usage(String),
+    Avro(Error),
+    // A schema file that can't be read, and its path.
+    Schema(String, Error),
+}
+
+impl From<Error> for CliError {
+    fn from(error: Error) -> CliError {
+        CliError::Avro(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> CliError {
+        CliError::Avro(Error::IoError(error))
+    }
+}
+
+struct Args {
+    options: BTreeMap<String, String>,
+    flags: Vec<String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, CliError> {
+        let mut parsed = Args { options: BTreeMap::new(), flags: Vec::new(), positional: Vec::new() };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if VALUE_OPTIONS.contains(&&arg[..]) {
+                match iter.next() {
+                    Some(value) => parsed.options.insert(arg.clone(), value.clone()),
+                    None => return Err(CliError::Usage(format!("{} needs a value", arg))),
+                };
+            } else if FLAG_OPTIONS.contains(&&arg[..]) {
+                parsed.flags.push(arg.clone());
+            } else if arg.starts_with("--") {
+                return Err(CliError::Usage(format!("unknown option {}", arg)));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn option(&self, name: &str) -> Option<&String> {
+        self.options.get(name)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    fn number(&self, name: &str) -> Result<Option<u64>, CliError> {
+        match self.options.get(name) {
+            Some(value) => {
+                match value.parse() {
+                    Ok(n) => Ok(Some(n)),
+                    Err(_) => Err(CliError::Usage(format!("{} needs a number, not {}", name, value))),
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Checks the number of positional arguments.
+    fn expect(&self, min: usize, max: Option<usize>) -> Result<(),CliError> {
+        let n = self.positional.len();
+        if n < min || max.map_or(false, |max| n > max) {
+            return Err(CliError::Usage(String::from("wrong number of arguments")));
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.clone(), rest),
+        None => {
+            let _ = writeln!(io::stderr(), "{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let result = Args::parse(rest).and_then(|args| run(&command, &args));
+    match result {
+        Ok(()) => (),
+        Err(CliError::Usage(message)) => {
+            let _ = writeln!(io::stderr(), "ravro: {}\n\n{}", message, USAGE);
+            process::exit(1);
+        },
+        Err(CliError::Avro(error)) => {
+            let _ = writeln!(io::stderr(), "ravro: {}", error);
+            process::exit(exit_code(&error));
+        },
+        Err(CliError::Schema(path, error)) => {
+            // Line 0 means the error isn't tied to a place in the file.
+            let _ = match error {
+                Error::SyntaxError(ref code, line, col) if line > 0 => {
+                    writeln!(io::stderr(), "ravro: {}:{}:{}: {:?}", path, line, col, code)
+                },
+                Error::SyntaxError(ref code, _, _) => writeln!(io::stderr(), "ravro: {}: {:?}", path, code),
+                ref error => writeln!(io::stderr(), "ravro: {}: {}", path, error),
+            };
+            process::exit(2);
+        },
+    }
+}
+
+fn exit_code(error: &Error) -> i32 {
+    match *error {
+        Error::SyntaxError(..) | Error::MissingAttributeError(_) => 2,
+        Error::IoError(_) => 3,
+        Error::DecodeError(_) | Error::RemoteError(_) => 4,
+        Error::EncodeError(_) => 5,
+    }
+}
+
+fn run(command: &str, args: &Args) -> Result<(),CliError> {
+    match command {
+        "getschema" => get_schema(args),
+        "getmeta" => get_meta(args),
+        "tojson" => to_json(args),
+        "fromjson" => from_json(args),
+        "count" => count(args),
+        "cat" => cat(args),
+        "concat" => concat(args),
+        "recodec" => recodec(args),
+        "random" => random(args),
+        "validate-schema" => validate_schema(args),
+        "diff" => diff(args),
+        "doc" => doc(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        _ => Err(CliError::Usage(format!("unknown command {}", command))),
+    }
+}
+
+fn get_schema(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, Some(1)));
+    let reader = try!(open_container(&args.positional[0]));
+    println!("{}", reader.schema());
+    Ok(())
+}
+
+fn get_meta(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, Some(1)));
+    let reader = try!(open_container(&args.positional[0]));
+    match args.option("--key") {
+        Some(key) => {
+            if let Some(value) = reader.meta().get(key) {
+                println!("{}", String::from_utf8_lossy(value));
+            }
+        },
+        None => {
+            for (key, value) in reader.meta().iter() {
+                println!("{}\t{}", key, String::from_utf8_lossy(value));
+            }
+        },
+    }
+    Ok(())
+}
+
+fn to_json(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, Some(1)));
+    let input = try!(open_input(&args.positional[0]));
+    let (reader, schema) = match args.option("--reader-schema") {
+        Some(path) => {
+            let reader_schema = try!(read_schema(path));
+            (try!(Reader::with_reader_schema(input, &reader_schema)), reader_schema)
+        },
+        None => {
+            let reader = try!(Reader::new(input));
+            let schema = reader.schema().clone();
+            (reader, schema)
+        },
+    };
+    let pretty = args.flag("--pretty");
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    for datum in reader {
+        let value = try!(encoding::json::encode(&try!(datum), &schema));
+        let text = if pretty { json::to_string_pretty(&value) } else { json::to_string(&value) };
+        // As in encoding::json::to_string, a value that can't be written out
+        // is a failure to write the datum, not an empty line.
+        let text = try!(text.map_err(|_| Error::EncodeError(ErrorCode::InvalidUtf8)));
+        try!(writeln!(out, "{}", text));
+    }
+    Ok(try!(out.flush()))
+}
+
+fn from_json(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, Some(1)));
+    let schema = match args.option("--schema") {
+        Some(path) => try!(read_schema(path)),
+        None => return Err(CliError::Usage(String::from("fromjson needs --schema"))),
+    };
+
+    // The file is written with the reader schema, when there is one.
+    let reader_schema = match args.option("--reader-schema") {
+        Some(path) => Some(try!(read_schema(path))),
+        None => None,
+    };
+    let codec = try!(codec_option(args));
+
+    let input = BufReader::new(try!(open_input(&args.positional[0])));
+    let output_schema = reader_schema.clone().unwrap_or(schema.clone());
+    let mut writer = Writer::new(try!(open_output("-")), output_schema, codec);
+    for line in input.lines() {
+        let line = try!(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let datum = try!(encoding::json::from_str(&line, &schema));
+        match reader_schema {
+            Some(ref reader_schema) => try!(writer.append(&try!(encoding::resolve::resolve(&datum, &schema, reader_schema)))),
+            None => try!(writer.append(&datum)),
+        }
+    }
+    try!(writer.flush());
+    Ok(())
+}
+
+fn count(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, None));
+    let mut total = 0;
+    for path in args.positional.iter() {
+        let mut reader = try!(open_container(path));
+        while let Some((count, _)) = try!(reader.read_raw_block()) {
+            total += count;
+        }
+    }
+    println!("{}", total);
+    Ok(())
+}
+
+fn cat(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(2, None));
+    let (inputs, output) = args.positional.split_at(args.positional.len() - 1);
+    let offset = try!(args.number("--offset")).unwrap_or(0);
+    let limit = try!(args.number("--limit"));
+
+    let mut writer: Option<Writer<Box<Write>>> = None;
+    let mut seen = 0;
+    let mut written = 0;
+    'inputs: for path in inputs.iter() {
+        let reader = try!(open_container(path));
+        if writer.is_none() {
+            writer = Some(try!(container_writer(&output[0], &reader, reader.codec())));
+        }
+        let writer = writer.as_mut().unwrap();
+        try!(check_same_schema(writer.schema(), reader.schema(), path));
+
+        for datum in reader {
+            let datum = try!(datum);
+            seen += 1;
+            if seen <= offset {
+                continue;
+            }
+            if limit.map_or(false, |limit| written >= limit) {
+                break 'inputs;
+            }
+            try!(writer.append(&datum));
+            written += 1;
+        }
+    }
+
+    if let Some(mut writer) = writer {
+        try!(writer.flush());
+    }
+    Ok(())
+}
+
+fn concat(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(2, None));
+    let (inputs, output) = args.positional.split_at(args.positional.len() - 1);
+
+    let mut writer: Option<Writer<Box<Write>>> = None;
+    for path in inputs.iter() {
+        let mut reader = try!(open_container(path));
+        if writer.is_none() {
+            writer = Some(try!(container_writer(&output[0], &reader, reader.codec())));
+        }
+        let writer = writer.as_mut().unwrap();
+        try!(check_same_schema(writer.schema(), reader.schema(), path));
+        try!(copy_blocks(&mut reader, writer));
+    }
+
+    if let Some(mut writer) = writer {
+        try!(writer.flush());
+    }
+    Ok(())
+}
+
+fn recodec(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(2, Some(2)));
+    let codec = try!(codec_option(args));
+
+    let mut reader = try!(open_container(&args.positional[0]));
+    let mut writer = try!(container_writer(&args.positional[1], &reader, codec));
+    try!(copy_blocks(&mut reader, &mut writer));
+    try!(writer.flush());
+    Ok(())
+}
+
+fn random(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, Some(1)));
+    let schema = match args.option("--schema") {
+        Some(path) => try!(read_schema(path)),
+        None => return Err(CliError::Usage(String::from("random needs --schema"))),
+    };
+    let count = match try!(args.number("--count")) {
+        Some(count) => count,
+        None => return Err(CliError::Usage(String::from("random needs --count"))),
+    };
+    let codec = try!(codec_option(args));
+    // A seed makes the output repeatable.
+    let mut rng = match try!(args.number("--seed")) {
+        Some(seed) => StdRng::from_seed(&[seed as usize][..]),
+        None => try!(StdRng::new()),
+    };
+
+    let mut writer = Writer::new(try!(open_output(&args.positional[0])), schema.clone(), codec);
+    for _ in 0..count {
+        try!(writer.append(&try!(random::generate(&schema, &mut rng))));
+    }
+    try!(writer.flush());
+    Ok(())
+}
+
+fn validate_schema(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, Some(1)));
+    try!(read_schema(&args.positional[0]));
+    println!("valid");
+    Ok(())
+}
+
+fn diff(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(2, Some(2)));
+    let old = try!(read_schema(&args.positional[0]));
+    let new = try!(read_schema(&args.positional[1]));
+    println!("{}", try!(schema::diff(&old, &new)));
+    Ok(())
+}
+
+fn doc(args: &Args) -> Result<(),CliError> {
+    try!(args.expect(1, None));
+    let format = match args.option("--format").map(|f| &f[..]) {
+        Some("markdown") | None => Format::Markdown,
+        Some("html") => Format::Html,
+        Some(other) => return Err(CliError::Usage(format!("unknown format {}", other))),
+    };
+
+    let mut writer = DocWriter::new(format);
+    let mut protocols = Vec::new();
+    for path in args.positional.iter() {
+        match try!(read_schema_or_protocol(path)) {
+            SchemaFile::Schema(schema) => writer.add_schema(&schema),
+            SchemaFile::Protocol(protocol) => {
+                writer.add_protocol(&protocol);
+                protocols.push(protocol.protocol.clone());
+            },
+        }
+    }
+    // A lone protocol titles the page with its name.
+    let title = match args.option("--title") {
+        Some(title) => title.clone(),
+        None if protocols.len() == 1 && args.positional.len() == 1 => protocols.remove(0),
+        None => String::from("Schemas"),
+    };
+
+    print!("{}", writer.render(&title));
+    Ok(())
+}
+
+// Copies blocks across without decoding them, recompressing only when the
+// codecs differ.
+fn copy_blocks<R: Read, W: Write>(reader: &mut Reader<R>, writer: &mut Writer<W>) -> Result<(),Error> {
+    while let Some((count, data)) = try!(reader.read_raw_block()) {
+        if reader.codec() == writer.codec() {
+            try!(writer.append_raw_block(count, &data));
+        } else {
+            let data = try!(writer.codec().compress(&try!(reader.codec().decompress(&data))));
+            try!(writer.append_raw_block(count, &data));
+        }
+    }
+    Ok(())
+}
+
+fn check_same_schema(expected: &Schema, actual: &Schema, path: &str) -> Result<(),CliError> {
+    if expected.fingerprint() != actual.fingerprint() {
+        return Err(CliError::Schema(String::from(path), Error::EncodeError(ErrorCode::ContainerSchemaMismatch)));
+    }
+    Ok(())
+}
+
+// A writer for `path` with the same schema and user metadata as `reader`.
+fn container_writer<R: Read>(path: &str, reader: &Reader<R>, codec: Codec) -> Result<Writer<Box<Write>>, Error> {
+    let mut writer = Writer::new(try!(open_output(path)), reader.schema().clone(), codec);
+    for (key, value) in reader.meta().iter() {
+        if !key.starts_with("avro.") {
+            try!(writer.set_meta(key, value));
+        }
+    }
+    Ok(writer)
+}
+
+// The --codec option, null by default.
+fn codec_option(args: &Args) -> Result<Codec, Error> {
+    match args.option("--codec") {
+        Some(name) => Codec::from_str(name),
+        None => Ok(Codec::Null),
+    }
+}
+
+fn read_schema(path: &str) -> Result<Schema, CliError> {
+    let parse = |text: &str| -> Result<Schema, Error> {
+        let schema = try!(schema::from_str(text));
+        try!(schema.is_valid());
+        Ok(schema)
+    };
+
+    let mut text = String::new();
+    try!(try!(open_input(path)).read_to_string(&mut text));
+    parse(&text).map_err(|error| CliError::Schema(String::from(path), error))
+}
+
+enum SchemaFile {
+    Schema(Schema),
+    Protocol(Protocol),
+}
+
+// A protocol if the file has a "protocol" attribute, otherwise a schema.
+fn read_schema_or_protocol(path: &str) -> Result<SchemaFile, CliError> {
+    let parse = |text: &str| -> Result<SchemaFile, Error> {
+        let value: json::Value = try!(json::from_str(text));
+        if value.find("protocol").is_some() {
+            return Ok(SchemaFile::Protocol(try!(Protocol::from_str(text))));
+        }
+        let schema = try!(schema::from_str(text));
+        try!(schema.is_valid());
+        Ok(SchemaFile::Schema(schema))
+    };
+
+    let mut text = String::new();
+    try!(try!(open_input(path)).read_to_string(&mut text));
+    parse(&text).map_err(|error| CliError::Schema(String::from(path), error))
+}
+
+fn open_container(path: &str) -> Result<Reader<Box<Read>>, Error> {
+    Reader::new(try!(open_input(path)))
+}
+
+fn open_input(path: &str) -> Result<Box<Read>, Error> {
+    if path == "-" {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(BufReader::new(try!(File::open(path)))))
+    }
+}
+
+fn open_output(path: &str) -> Result<Box<Write>, Error> {
+    if path == "-" {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(BufWriter::new(try!(File::create(path)))))
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The sort order defined by the Avro specification, on binary encoded data
+// and on datums. Records compare field by field following each field's
+// "order"; maps have no order and can't be compared.
+use std::cmp::Ordering;
+
+use serde::json::Value;
+
+use datum::Datum;
+use encoding::binary::{self, Decoder};
+use schema::{Field, FieldSortOrder, Schema};
+use schema::error::*;
+use schema::names::{Names, SchemaKind};
+
+/// Compares two datums in the binary encoding of `schema` without decoding
+/// them. Only as much of each as is needed to tell them apart is read.
+pub fn compare_encoded(a: &[u8], b: &[u8], schema: &Schema) -> Result<Ordering, Error> {
+    let names = Names::from_schema(schema);
+    let (mut a, mut b) = (a, b);
+    Comparator::new(&names).compare_encoded(&Value::from(schema), None, &mut a, &mut b)
+}
+
+/// Compares two datums that can be written with `schema`.
+pub fn compare_values(a: &Datum, b: &Datum, schema: &Schema) -> Result<Ordering, Error> {
+    let names = Names::from_schema(schema);
+    Comparator::new(&names).compare_values(a, b, &Value::from(schema), None)
+}
+
+pub struct Comparator<'a> {
+    names: &'a Names,
+}
+
+impl<'a> Comparator<'a> {
+    pub fn new(names: &'a Names) -> Comparator<'a> {
+        Comparator { names: names }
+    }
+
+    /// Compares the datums at the start of `a` and `b`, moving each slice past
+    /// what was read.
+    pub fn compare_encoded(&self, schema: &Value, ns: Option<&str>, a: &mut &[u8], b: &mut &[u8]) -> Result<Ordering, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => {
+                match type_name {
+                    "null" => Ok(Ordering::Equal),
+                    "boolean" => Ok(try!(binary::read_boolean(a)).cmp(&try!(binary::read_boolean(b)))),
+                    "int" | "long" => Ok(try!(binary::read_long(a)).cmp(&try!(binary::read_long(b)))),
+                    "float" => {
+                        let (x, y) = (try!(binary::read_float(a)), try!(binary::read_float(b)));
+                        Ok(compare_doubles(x as f64, y as f64))
+                    },
+                    "double" => Ok(compare_doubles(try!(binary::read_double(a)), try!(binary::read_double(b)))),
+                    // UTF-8 bytes sort the same as the code points they encode.
+                    "bytes" | "string" => Ok(try!(binary::read_bytes(a)).cmp(&try!(binary::read_bytes(b)))),
+                    _ => Err(Error::SyntaxError(ErrorCode::NotValidPrimitiveType, 0, 0)),
+                }
+            },
+            SchemaKind::Record(definition) => {
+                for (field_type, order) in try!(record_fields(definition)) {
+                    let ordering = match order {
+                        FieldSortOrder::Ignore => {
+                            // Nothing to compare, but both sides still have to
+                            // be moved past the field.
+                            let decoder = Decoder::new(self.names);
+                            try!(decoder.skip(field_type, ns, a));
+                            try!(decoder.skip(field_type, ns, b));
+                            Ordering::Equal
+                        },
+                        FieldSortOrder::Ascending => try!(self.compare_encoded(field_type, ns, a, b)),
+                        FieldSortOrder::Descending => try!(self.compare_encoded(field_type, ns, a, b)).reverse(),
+                    };
+                    if ordering != Ordering::Equal {
+                        return Ok(ordering);
+                    }
+                }
+                Ok(Ordering::Equal)
+            },
+            // Enums sort by the position of the symbol, which is what's encoded.
+            SchemaKind::Enum(_) => Ok(try!(binary::read_long(a)).cmp(&try!(binary::read_long(b)))),
+            SchemaKind::Fixed(definition) => {
+                let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0) as usize;
+                let x = try!(binary::read_exact(size, a));
+                Ok(x.cmp(&try!(binary::read_exact(size, b))))
+            },
+            SchemaKind::Array(items) => {
+                // The two sides may be split into blocks differently.
+                let mut left_a = try!(binary::read_block_count(a));
+                let mut left_b = try!(binary::read_block_count(b));
+                loop {
+                    match (left_a, left_b) {
+                        (0, 0) => return Ok(Ordering::Equal),
+                        (0, _) => return Ok(Ordering::Less),
+                        (_, 0) => return Ok(Ordering::Greater),
+                        _ => (),
+                    }
+
+                    let ordering = try!(self.compare_encoded(items, ns, a, b));
+                    if ordering != Ordering::Equal {
+                        return Ok(ordering);
+                    }
+
+                    left_a -= 1;
+                    if left_a == 0 {
+                        left_a = try!(binary::read_block_count(a));
+                    }
+                    left_b -= 1;
+                    if left_b == 0 {
+                        left_b = try!(binary::read_block_count(b));
+                    }
+                }
+            },
+            SchemaKind::Map(_) => Err(Error::DecodeError(ErrorCode::MapsNotComparable)),
+            SchemaKind::Union(branches) => {
+                let (x, y) = (try!(binary::read_long(a)), try!(binary::read_long(b)));
+                if x < 0 || x as usize >= branches.len() || y < 0 || y as usize >= branches.len() {
+                    return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+                }
+                match x.cmp(&y) {
+                    Ordering::Equal => self.compare_encoded(&branches[x as usize], ns, a, b),
+                    ordering => Ok(ordering),
+                }
+            },
+        }
+    }
+
+    pub fn compare_values(&self, a: &Datum, b: &Datum, schema: &Value, ns: Option<&str>) -> Result<Ordering, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => compare_primitives(a, b, type_name),
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                for (field, (field_type, order)) in fields.iter().zip(try!(record_fields(definition))) {
+                    if let FieldSortOrder::Ignore = order {
+                        continue;
+                    }
+                    let x = try!(self.field_value(a, field, field_type, ns));
+                    let y = try!(self.field_value(b, field, field_type, ns));
+                    let ordering = try!(self.compare_values(&x, &y, field_type, ns));
+                    let ordering = match order {
+                        FieldSortOrder::Descending => ordering.reverse(),
+                        _ => ordering,
+                    };
+                    if ordering != Ordering::Equal {
+                        return Ok(ordering);
+                    }
+                }
+                Ok(Ordering::Equal)
+            },
+            SchemaKind::Enum(definition) => {
+                match (a, b) {
+                    (&Datum::Enum(ref x), &Datum::Enum(ref y)) => {
+                        match (binary::symbol_index(definition, x), binary::symbol_index(definition, y)) {
+                            (Some(x), Some(y)) => Ok(x.cmp(&y)),
+                            _ => Err(Error::EncodeError(ErrorCode::EnumSymbolNotFound)),
+                        }
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Fixed(_) => {
+                match (a, b) {
+                    (&Datum::Fixed(ref x), &Datum::Fixed(ref y)) => Ok(x.cmp(y)),
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Array(items) => {
+                match (a, b) {
+                    (&Datum::Array(ref x), &Datum::Array(ref y)) => {
+                        for (x, y) in x.iter().zip(y.iter()) {
+                            let ordering = try!(self.compare_values(x, y, items, ns));
+                            if ordering != Ordering::Equal {
+                                return Ok(ordering);
+                            }
+                        }
+                        Ok(x.len().cmp(&y.len()))
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Map(_) => Err(Error::EncodeError(ErrorCode::MapsNotComparable)),
+            SchemaKind::Union(branches) => {
+                let (x, a) = try!(self.branch(a, branches, ns));
+                let (y, b) = try!(self.branch(b, branches, ns));
+                match x.cmp(&y) {
+                    Ordering::Equal => self.compare_values(a, b, &branches[x], ns),
+                    ordering => Ok(ordering),
+                }
+            },
+        }
+    }
+
+    // A record's value for a field, or the field's default when it's missing,
+    // the same as when the record is written.
+    fn field_value(&self, record: &Datum, field: &Value, field_type: &Value, ns: Option<&str>) -> Result<Datum, Error> {
+        if let Datum::Record(_) = *record {} else {
+            return Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch));
+        }
+        let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+        match (record.field(name), field.find("default")) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(default)) => binary::default_datum(self.names, default, field_type, ns),
+            (None, None) => Err(Error::EncodeError(ErrorCode::MissingFieldValue)),
+        }
+    }
+
+    fn branch<'d>(&self, datum: &'d Datum, branches: &Vec<Value>, ns: Option<&str>) -> Result<(usize, &'d Datum), Error> {
+        match *datum {
+            Datum::Union(idx, ref value) if idx < branches.len() => Ok((idx, &**value)),
+            Datum::Union(..) => Err(Error::EncodeError(ErrorCode::UnionIndexOutOfRange)),
+            _ => {
+                match binary::union_branch(self.names, datum, branches, ns) {
+                    Some(idx) => Ok((idx, datum)),
+                    None => Err(Error::EncodeError(ErrorCode::UnionBranchNotFound)),
+                }
+            }
+        }
+    }
+}
+
+// Each field's type and sort order.
+fn record_fields(definition: &Value) -> Result<Vec<(&Value, FieldSortOrder)>, Error> {
+    let fields = match definition.find("fields") {
+        Some(&Value::Array(ref fields)) => fields,
+        _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+    };
+
+    let mut result = Vec::with_capacity(fields.len());
+    for field in fields.iter() {
+        let field_type = match field.find("type") {
+            Some(t) => t,
+            None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+        };
+        result.push((field_type, try!(Field::from(field).order())));
+    }
+    Ok(result)
+}
+
+fn compare_primitives(a: &Datum, b: &Datum, type_name: &str) -> Result<Ordering, Error> {
+    match (type_name, a, b) {
+        ("null", &Datum::Null, &Datum::Null) => Ok(Ordering::Equal),
+        ("boolean", &Datum::Boolean(x), &Datum::Boolean(y)) => Ok(x.cmp(&y)),
+        ("int", &Datum::Int(x), &Datum::Int(y)) => Ok(x.cmp(&y)),
+        ("long", _, _) => {
+            match (as_long(a), as_long(b)) {
+                (Some(x), Some(y)) => Ok(x.cmp(&y)),
+                _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+            }
+        },
+        ("float", &Datum::Float(x), &Datum::Float(y)) => Ok(compare_doubles(x as f64, y as f64)),
+        ("double", _, _) => {
+            match (as_double(a), as_double(b)) {
+                (Some(x), Some(y)) => Ok(compare_doubles(x, y)),
+                _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+            }
+        },
+        ("bytes", &Datum::Bytes(ref x), &Datum::Bytes(ref y)) => Ok(x.cmp(y)),
+        ("string", &Datum::String(ref x), &Datum::String(ref y)) => Ok(x.cmp(y)),
+        _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+    }
+}
+
+// An int can be written as a long, and a float as a double.
+fn as_long(datum: &Datum) -> Option<i64> {
+    match *datum {
+        Datum::Int(n) => Some(n as i64),
+        Datum::Long(n) => Some(n),
+        _ => None,
+    }
+}
+
+fn as_double(datum: &Datum) -> Option<f64> {
+    match *datum {
+        Datum::Float(n) => Some(n as f64),
+        Datum::Double(n) => Some(n),
+        _ => None,
+    }
+}
+
+// The same total order as Java's Double.compare, which is what Hadoop's Avro
+// comparators use: -0.0 sorts before 0.0, and NaN after everything else.
+fn compare_doubles(x: f64, y: f64) -> Ordering {
+    match x.partial_cmp(&y) {
+        Some(Ordering::Equal) => y.is_sign_negative().cmp(&x.is_sign_negative()),
+        Some(ordering) => ordering,
+        None => x.is_nan().cmp(&y.is_nan()),
+    }
+}
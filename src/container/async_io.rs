@@ -0,0 +1,482 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Container files over tokio's AsyncRead and AsyncWrite. The blocking reader
+// and writer still parse the header and encode the blocks; this side only
+// moves bytes. The reader buffers until it holds a whole header or block,
+// looking at no more than the lengths to tell when that is.
+use std::collections::BTreeMap;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{Future, Sink, Stream};
+use serde::json::Value;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use datum::Datum;
+use encoding::binary::{self, Decoder};
+use encoding::resolve::Resolver;
+use schema::Schema;
+use schema::error::*;
+use schema::names::Names;
+
+use super::{Codec, Reader, Writer, MAGIC, SYNC_SIZE};
+
+// How much is asked of the underlying reader at a time.
+const READ_SIZE: usize = 8 * 1024;
+
+/// The largest block an `AsyncReader` buffers unless told otherwise. A block
+/// has to be held whole before it's decoded, so a corrupt or hostile size
+/// would otherwise have the reader buffer without end.
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 64 * 1024 * 1024;
+
+/// The largest header `Open` buffers unless told otherwise, for the same
+/// reason.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 16 * 1024 * 1024;
+
+// Unwraps a length, or returns Ok(None) so more gets read.
+macro_rules! need {
+    ($e:expr) => (match try!($e) {
+        Some(value) => value,
+        None => return Ok(None),
+    })
+}
+
+/// Reads a container file's header, and resolves to an `AsyncReader` for the
+/// rest of the file.
+pub struct Open<R> {
+    reader: Option<R>,
+    buf: Vec<u8>,
+    reader_schema: Option<Schema>,
+    max_header_size: usize,
+}
+
+impl<R> Open<R> {
+    /// Sets the largest header size that will be read. A bigger one is a
+    /// `CorruptBlock` error.
+    pub fn set_max_header_size(&mut self, max_header_size: usize) {
+        self.max_header_size = max_header_size;
+    }
+}
+
+impl<R: AsyncRead + Unpin> Future for Open<R> {
+    type Output = Result<AsyncReader<R>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<AsyncReader<R>, Error>> {
+        let this = self.get_mut();
+        loop {
+            match header_len(&this.buf, this.max_header_size) {
+                Ok(Some(len)) => {
+                    let reader = this.reader.take().expect("Open polled after it resolved");
+                    return Poll::Ready(AsyncReader::from_header(reader, &this.buf, len, this.reader_schema.take()));
+                },
+                Ok(None) => {},
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+
+            let reader = this.reader.as_mut().expect("Open polled after it resolved");
+            match poll_read_more(reader, cx, &mut this.buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(unexpected_eof())),
+                Poll::Ready(Ok(_)) => {},
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::IoError(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Reads the datums in an object container file as a `Stream`.
+pub struct AsyncReader<R> {
+    reader: R,
+    schema: Schema,
+    schema_value: Value,
+    names: Names,
+    meta: BTreeMap<String, Vec<u8>>,
+    codec: Codec,
+    sync: [u8; 16],
+    // The schema to read datums as, when it isn't the writer's.
+    reader_schema: Option<(Value, Names)>,
+    // Bytes read from the file but not yet taken as a block.
+    buf: Vec<u8>,
+    // The current block, decompressed, and how far into it we are.
+    block: Vec<u8>,
+    offset: usize,
+    remaining: i64,
+    max_block_size: usize,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    /// Reads the file header: the schema, codec and other metadata, and the
+    /// sync marker that follows every block.
+    pub fn new(reader: R) -> Open<R> {
+        Open {
+            reader: Some(reader),
+            buf: Vec::new(),
+            reader_schema: None,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+        }
+    }
+
+    /// Like `new`, but datums are resolved against `reader_schema` as they
+    /// are read.
+    pub fn with_reader_schema(reader: R, reader_schema: &Schema) -> Open<R> {
+        Open {
+            reader: Some(reader),
+            buf: Vec::new(),
+            reader_schema: Some(reader_schema.clone()),
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+        }
+    }
+
+    // The first `len` bytes of `buf` are the header; the rest belongs to the
+    // first block.
+    fn from_header(reader: R, buf: &[u8], len: usize, reader_schema: Option<Schema>) -> Result<AsyncReader<R>, Error> {
+        let header = try!(Reader::new(&buf[..len]));
+        let schema = header.schema().clone();
+        Ok(AsyncReader {
+            reader: reader,
+            schema_value: Value::from(&schema),
+            names: Names::from_schema(&schema),
+            schema: schema,
+            meta: header.meta().clone(),
+            codec: header.codec(),
+            sync: *header.sync_marker(),
+            reader_schema: reader_schema.map(|s| (Value::from(&s), Names::from_schema(&s))),
+            buf: buf[len..].to_vec(),
+            block: Vec::new(),
+            offset: 0,
+            remaining: 0,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            done: false,
+        })
+    }
+
+    /// The schema the file was written with.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// All of the header metadata, including avro.schema and avro.codec.
+    pub fn meta(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.meta
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn sync_marker(&self) -> &[u8; 16] {
+        &self.sync
+    }
+
+    /// Sets the largest block size, compressed, that will be read. A bigger
+    /// one is a `CorruptBlock` error.
+    pub fn set_max_block_size(&mut self, max_block_size: usize) {
+        self.max_block_size = max_block_size;
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn decode_next(&mut self) -> Result<Datum, Error> {
+        let mut cursor = &self.block[self.offset..];
+        let before = cursor.len();
+        let datum = match self.reader_schema {
+            Some((ref reader_value, ref reader_names)) => {
+                try!(Resolver::new(&self.names, reader_names).decode(&self.schema_value, None, reader_value, None, &mut cursor))
+            },
+            None => try!(Decoder::new(&self.names).decode(&self.schema_value, None, &mut cursor)),
+        };
+        self.offset += before - cursor.len();
+        self.remaining -= 1;
+        Ok(datum)
+    }
+
+    // Takes the next block out of the buffer, if all of it has been read.
+    fn take_block(&mut self) -> Result<bool, Error> {
+        let (count, start, end) = match try!(block_len(&self.buf, self.max_block_size)) {
+            Some(block) => block,
+            None => return Ok(false),
+        };
+        if self.buf[end..end + SYNC_SIZE] != self.sync[..] {
+            return Err(Error::DecodeError(ErrorCode::SyncMarkerMismatch));
+        }
+
+        self.block = match self.codec {
+            Codec::Null => self.buf[start..end].to_vec(),
+            codec => try!(codec.decompress(&self.buf[start..end])),
+        };
+        self.buf.drain(..end + SYNC_SIZE);
+        self.offset = 0;
+        self.remaining = count;
+        Ok(true)
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncReader<R> {
+    type Item = Result<Datum, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Result<Datum, Error>>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if this.remaining > 0 {
+                let result = this.decode_next();
+                this.done = result.is_err();
+                return Poll::Ready(Some(result));
+            }
+
+            match this.take_block() {
+                Ok(true) => continue,
+                Ok(false) => {},
+                Err(e) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                },
+            }
+            match poll_read_more(&mut this.reader, cx, &mut this.buf) {
+                Poll::Ready(Ok(0)) => {
+                    // A clean end of file can only come where a block would start.
+                    this.done = true;
+                    if !this.buf.is_empty() {
+                        return Poll::Ready(Some(Err(unexpected_eof())));
+                    }
+                },
+                Poll::Ready(Ok(_)) => {},
+                Poll::Ready(Err(e)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(Error::IoError(e))));
+                },
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Writes datums to an object container file as a `Sink`. Datums are put in
+/// blocks as by `Writer`, and each block is written out as it fills. Flushing
+/// writes out the current block however full it is; close the sink when done,
+/// or the last block is lost.
+pub struct AsyncWriter<W> {
+    writer: W,
+    // Builds the header and blocks in memory, until they're written out.
+    encoder: Writer<Vec<u8>>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWriter<W> {
+    pub fn new(writer: W, schema: Schema, codec: Codec) -> AsyncWriter<W> {
+        AsyncWriter {
+            writer: writer,
+            encoder: Writer::new(Vec::new(), schema, codec),
+            written: 0,
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        self.encoder.schema()
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.encoder.codec()
+    }
+
+    pub fn sync_marker(&self) -> &[u8; 16] {
+        self.encoder.sync_marker()
+    }
+
+    /// Adds a metadata entry to the header, which is written with the first
+    /// block.
+    pub fn set_meta(&mut self, key: &str, value: &[u8]) -> Result<(),Error> {
+        self.encoder.set_meta(key, value)
+    }
+
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.encoder.set_block_size(block_size);
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    // Writes out whatever the encoder has finished.
+    fn poll_write_encoded(&mut self, cx: &mut Context) -> Poll<Result<(),Error>> {
+        loop {
+            let encoded = self.encoder.get_mut();
+            if self.written == encoded.len() {
+                encoded.clear();
+                self.written = 0;
+                return Poll::Ready(Ok(()));
+            }
+            match Pin::new(&mut self.writer).poll_write(cx, &encoded[self.written..]) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(Error::IoError(io::Error::new(io::ErrorKind::WriteZero, "failed to write block"))));
+                },
+                Poll::Ready(Ok(n)) => self.written += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::IoError(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<Datum> for AsyncWriter<W> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(),Error>> {
+        self.get_mut().poll_write_encoded(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, datum: Datum) -> Result<(),Error> {
+        self.get_mut().encoder.append(&datum)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(),Error>> {
+        let this = self.get_mut();
+        // Only the first call does anything; after that the block is empty.
+        if let Err(e) = this.encoder.flush() {
+            return Poll::Ready(Err(e));
+        }
+        match this.poll_write_encoded(cx) {
+            Poll::Ready(Ok(())) => {},
+            other => return other,
+        }
+        Pin::new(&mut this.writer).poll_flush(cx).map_err(Error::IoError)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(),Error>> {
+        match self.as_mut().poll_flush(cx) {
+            Poll::Ready(Ok(())) => {},
+            other => return other,
+        }
+        Pin::new(&mut self.get_mut().writer).poll_shutdown(cx).map_err(Error::IoError)
+    }
+}
+
+// Reads whatever is ready onto the end of `buf`, returning how much that was;
+// 0 at the end of the file.
+fn poll_read_more<R: AsyncRead + Unpin>(reader: &mut R, cx: &mut Context, buf: &mut Vec<u8>) -> Poll<io::Result<usize>> {
+    let start = buf.len();
+    buf.resize(start + READ_SIZE, 0);
+    let (result, n) = {
+        let mut read = ReadBuf::new(&mut buf[start..]);
+        let result = Pin::new(reader).poll_read(cx, &mut read);
+        (result, read.filled().len())
+    };
+    buf.truncate(start + n);
+    result.map(|r| r.map(|()| n))
+}
+
+// The length of the header at the start of `buf`, or None if it isn't all
+// there yet.
+fn header_len(buf: &[u8], max_header_size: usize) -> Result<Option<usize>, Error> {
+    if buf.len() < MAGIC.len() {
+        return Ok(None);
+    }
+    if buf[..MAGIC.len()] != MAGIC[..] {
+        return Err(Error::DecodeError(ErrorCode::NotAContainerFile));
+    }
+
+    // The metadata is a map, in blocks of entries ending with an empty one.
+    let mut pos = MAGIC.len();
+    loop {
+        let (count, next) = need!(long_at(buf, pos));
+        pos = next;
+        if count == 0 {
+            break;
+        }
+        if count < 0 {
+            // A negative count is followed by the block's size in bytes.
+            let (size, next) = need!(long_at(buf, pos));
+            if size < 0 {
+                return Err(Error::DecodeError(ErrorCode::NegativeLength));
+            }
+            pos = try!(header_skip(next, size, max_header_size));
+            continue;
+        }
+        // Each entry is a string key and a bytes value.
+        let entries = match count.checked_mul(2) {
+            Some(entries) => entries,
+            None => return Err(unexpected_eof()),
+        };
+        for _ in 0..entries {
+            let (len, next) = need!(long_at(buf, pos));
+            if len < 0 {
+                return Err(Error::DecodeError(ErrorCode::NegativeLength));
+            }
+            pos = try!(header_skip(next, len, max_header_size));
+        }
+    }
+
+    let end = try!(header_skip(pos, SYNC_SIZE as i64, max_header_size));
+    Ok(if buf.len() >= end { Some(end) } else { None })
+}
+
+// Like `skip`, for a header that mustn't end past `max_header_size`.
+fn header_skip(pos: usize, len: i64, max_header_size: usize) -> Result<usize, Error> {
+    let end = try!(skip(pos, len));
+    if end > max_header_size {
+        return Err(Error::DecodeError(ErrorCode::CorruptBlock));
+    }
+    Ok(end)
+}
+
+// The object count of the block at the start of `buf`, and where its data
+// starts and ends, or None if it and its sync marker aren't all there yet.
+fn block_len(buf: &[u8], max_block_size: usize) -> Result<Option<(i64, usize, usize)>, Error> {
+    let (count, next) = need!(long_at(buf, 0));
+    let (size, start) = need!(long_at(buf, next));
+    if count < 0 || size < 0 {
+        return Err(Error::DecodeError(ErrorCode::NegativeLength));
+    }
+    if size as u64 > max_block_size as u64 {
+        return Err(Error::DecodeError(ErrorCode::CorruptBlock));
+    }
+    let end = try!(skip(start, size));
+    Ok(if buf.len() >= try!(skip(end, SYNC_SIZE as i64)) { Some((count, start, end)) } else { None })
+}
+
+// Where `len` bytes from `pos` end. A length that runs past what could be
+// addressed can't be followed by that much data.
+fn skip(pos: usize, len: i64) -> Result<usize, Error> {
+    if len as u64 > usize::max_value() as u64 {
+        return Err(unexpected_eof());
+    }
+    pos.checked_add(len as usize).ok_or_else(unexpected_eof)
+}
+
+// The long at `pos` and where it ends, or None if it isn't all there yet.
+fn long_at(buf: &[u8], pos: usize) -> Result<Option<(i64, usize)>, Error> {
+    let rest = match buf.get(pos..) {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    // The last byte of a long is the first without its high bit set.
+    match rest.iter().position(|b| b & 0x80 == 0) {
+        Some(last) => {
+            let mut cursor = &rest[..last + 1];
+            Ok(Some((try!(binary::read_long(&mut cursor)), pos + last + 1)))
+        },
+        None => Ok(None),
+    }
+}
+
+fn unexpected_eof() -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of data"))
+}
@@ -0,0 +1,83 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use schema::error::*;
+
+/// How the blocks of a container file are compressed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Codec {
+    Null,
+    /// Raw deflate (RFC 1951), with no zlib header or checksum.
+    Deflate,
+}
+
+impl Codec {
+    /// The name used for the codec in the avro.codec metadata.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Codec::Null => "null",
+            Codec::Deflate => "deflate",
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match *self {
+            Codec::Null => Ok(data.to_vec()),
+            Codec::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                try!(encoder.write_all(data));
+                Ok(try!(encoder.finish()))
+            },
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match *self {
+            Codec::Null => Ok(data.to_vec()),
+            Codec::Deflate => {
+                let mut out = Vec::new();
+                match DeflateDecoder::new(data).read_to_end(&mut out) {
+                    Ok(_) => Ok(out),
+                    Err(_) => Err(Error::DecodeError(ErrorCode::CorruptBlock)),
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Codec, Error> {
+        match s {
+            "null" => Ok(Codec::Null),
+            "deflate" => Ok(Codec::Deflate),
+            _ => Err(Error::DecodeError(ErrorCode::UnknownCodec)),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,43 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Object container files: a header (magic, metadata and a sync marker)
+// followed by blocks of datums, each one compressed with the file's codec
+// and followed by the sync marker.
+use schema::{self, Schema};
+
+#[cfg(feature = "async")]
+pub use self::async_io::{AsyncReader, AsyncWriter, Open, DEFAULT_MAX_BLOCK_SIZE, DEFAULT_MAX_HEADER_SIZE};
+pub use self::codec::Codec;
+pub use self::parallel::{ParallelReader, ParallelOptions};
+pub use self::reader::{Reader, BlockPosition, BlockPositions, LenientReader};
+pub use self::writer::{
+	Writer,
+	DEFAULT_BLOCK_SIZE
+};
+
+#[cfg(feature = "async")]
+mod async_io;
+mod codec;
+mod parallel;
+mod reader;
+mod writer;
+
+pub const MAGIC: [u8; 4] = [b'O', b'b', b'j', 1];
+pub const SYNC_SIZE: usize = 16;
+
+// The header metadata is a map of bytes.
+fn meta_schema() -> Schema {
+    schema::from_str(r#"{"type": "map", "values": "bytes"}"#).unwrap()
+}
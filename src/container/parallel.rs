@@ -0,0 +1,262 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Reading a container file on several threads. One thread reads the raw
+// blocks in order and hands them to a pool of workers, which decompress and
+// decode them; the reader puts the decoded blocks back in file order.
+//
+// Memory is bounded with permits: a block can only be read from the file
+// with a permit, and the permit only comes back once the block's datums
+// have been handed out.
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::thread;
+use std::vec;
+
+use serde::json::Value;
+
+use datum::Datum;
+use encoding::binary::Decoder;
+use encoding::resolve::Resolver;
+use schema::Schema;
+use schema::error::*;
+use schema::names::Names;
+
+use super::{Codec, Reader};
+
+/// How a `ParallelReader` goes about it.
+#[derive(Clone, Debug)]
+pub struct ParallelOptions {
+    /// The number of threads decompressing and decoding blocks.
+    pub threads: usize,
+    /// The most blocks held at once, whether raw, being decoded, or decoded
+    /// and waiting their turn.
+    pub max_blocks_in_flight: usize,
+    /// Whether datums come in file order. Out of order, each block's datums
+    /// still come together, but blocks come as they're decoded.
+    pub ordered: bool,
+    /// The schema to read datums as, when it isn't the writer's.
+    pub reader_schema: Option<Schema>,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> ParallelOptions {
+        ParallelOptions {
+            threads: 4,
+            max_blocks_in_flight: 16,
+            ordered: true,
+            reader_schema: None,
+        }
+    }
+}
+
+// A block's place in the file, and its datums.
+type Decoded = (u64, Result<Vec<Datum>, Error>);
+
+// What a worker needs to turn a raw block into datums.
+struct Decoding {
+    codec: Codec,
+    schema: Value,
+    names: Names,
+    reader_schema: Option<(Value, Names)>,
+}
+
+impl Decoding {
+    fn decode(&self, count: i64, data: &[u8]) -> Result<Vec<Datum>, Error> {
+        let block = try!(self.codec.decompress(data));
+        let mut cursor = &block[..];
+        // The count comes from the file, so it can't be trusted to size
+        // anything; a bad one runs out of data soon enough.
+        let mut datums = Vec::new();
+        for _ in 0..count {
+            let datum = match self.reader_schema {
+                Some((ref reader_value, ref reader_names)) => {
+                    try!(Resolver::new(&self.names, reader_names).decode(&self.schema, None, reader_value, None, &mut cursor))
+                },
+                None => try!(Decoder::new(&self.names).decode(&self.schema, None, &mut cursor)),
+            };
+            datums.push(datum);
+        }
+        Ok(datums)
+    }
+}
+
+/// Reads the datums in an object container file, decompressing and decoding
+/// blocks on a pool of threads.
+pub struct ParallelReader {
+    schema: Schema,
+    ordered: bool,
+    decoded: Receiver<Decoded>,
+    permits: SyncSender<()>,
+    // Decoded blocks that came before their turn, and whose turn it is.
+    waiting: BTreeMap<u64, Result<Vec<Datum>, Error>>,
+    next: u64,
+    current: vec::IntoIter<Datum>,
+    failed: bool,
+}
+
+impl ParallelReader {
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Result<ParallelReader, Error> {
+        ParallelReader::with_options(reader, ParallelOptions::default())
+    }
+
+    /// Reads the file header and starts the threads.
+    pub fn with_options<R: Read + Send + 'static>(reader: R, options: ParallelOptions) -> Result<ParallelReader, Error> {
+        let mut reader = try!(Reader::new(reader));
+        let schema = reader.schema().clone();
+        let decoding = Arc::new(Decoding {
+            codec: reader.codec(),
+            schema: Value::from(&schema),
+            names: Names::from_schema(&schema),
+            reader_schema: options.reader_schema.as_ref().map(|s| (Value::from(s), Names::from_schema(s))),
+        });
+
+        let in_flight = if options.max_blocks_in_flight == 0 { 1 } else { options.max_blocks_in_flight };
+        let (permits, permits_rx) = sync_channel(in_flight);
+        for _ in 0..in_flight {
+            let _ = permits.send(());
+        }
+        let (raw, raw_rx) = channel::<(u64, i64, Vec<u8>)>();
+        let (decoded, decoded_rx) = channel();
+
+        // The file is read on a thread of its own, so reading never waits on
+        // the caller. It stops at the end of the file, on an error, or when
+        // the ParallelReader is dropped and takes the permits with it.
+        let errors = decoded.clone();
+        thread::spawn(move || {
+            let mut seq = 0;
+            while permits_rx.recv().is_ok() {
+                match reader.read_raw_block() {
+                    Ok(Some((count, data))) => {
+                        if raw.send((seq, count, data)).is_err() {
+                            return;
+                        }
+                    },
+                    Ok(None) => return,
+                    Err(e) => {
+                        let _ = errors.send((seq, Err(e)));
+                        return;
+                    },
+                }
+                seq += 1;
+            }
+        });
+
+        let raw_rx = Arc::new(Mutex::new(raw_rx));
+        for _ in 0..options.threads.max(1) {
+            let (raw_rx, decoded, decoding) = (raw_rx.clone(), decoded.clone(), decoding.clone());
+            thread::spawn(move || work(&raw_rx, &decoded, &decoding));
+        }
+
+        Ok(ParallelReader {
+            schema: schema,
+            ordered: options.ordered,
+            decoded: decoded_rx,
+            permits: permits,
+            waiting: BTreeMap::new(),
+            next: 0,
+            current: Vec::new().into_iter(),
+            failed: false,
+        })
+    }
+
+    /// The schema the file was written with.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Reads the next datum, or None at the end of the file.
+    pub fn read(&mut self) -> Result<Option<Datum>, Error> {
+        loop {
+            if let Some(datum) = self.current.next() {
+                return Ok(Some(datum));
+            }
+            if self.failed {
+                return Ok(None);
+            }
+            match self.next_block() {
+                Ok(Some(datums)) => self.current = datums.into_iter(),
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    self.failed = true;
+                    return Err(e);
+                },
+            }
+        }
+    }
+
+    // The datums of the next block, in file order or as they come.
+    fn next_block(&mut self) -> Result<Option<Vec<Datum>>, Error> {
+        loop {
+            let ready = if self.ordered {
+                self.waiting.remove(&self.next)
+            } else {
+                let first = self.waiting.keys().next().cloned();
+                first.and_then(|seq| self.waiting.remove(&seq))
+            };
+            if let Some(result) = ready {
+                self.next += 1;
+                let _ = self.permits.send(());
+                return result.map(Some);
+            }
+
+            // Every thread is done once the channel closes, so whatever is
+            // missing was never read.
+            match self.decoded.recv() {
+                Ok((seq, result)) => {
+                    self.waiting.insert(seq, result);
+                },
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+}
+
+impl Iterator for ParallelReader {
+    type Item = Result<Datum, Error>;
+
+    fn next(&mut self) -> Option<Result<Datum, Error>> {
+        match self.read() {
+            Ok(Some(datum)) => Some(Ok(datum)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+fn work(raw: &Mutex<Receiver<(u64, i64, Vec<u8>)>>, decoded: &Sender<Decoded>, decoding: &Decoding) {
+    loop {
+        let block = match raw.lock() {
+            Ok(raw) => raw.recv(),
+            Err(_) => return,
+        };
+        match block {
+            Ok((seq, count, data)) => {
+                // Every block has to be answered for, or an ordered reader
+                // waits for it forever, so a panic is sent on as an error.
+                let result = match panic::catch_unwind(AssertUnwindSafe(|| decoding.decode(count, &data))) {
+                    Ok(result) => result,
+                    Err(_) => Err(Error::DecodeError(ErrorCode::CorruptBlock)),
+                };
+                if decoded.send((seq, result)).is_err() {
+                    return;
+                }
+            },
+            Err(_) => return,
+        }
+    }
+}
@@ -0,0 +1,480 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::str::{self, FromStr};
+
+use serde::json::Value;
+
+use datum::{Datum, ValueRef};
+use encoding::binary::{self, Decoder};
+use encoding::borrowed::BorrowedDecoder;
+use encoding::resolve::Resolver;
+use schema::{self, Schema};
+use schema::error::*;
+use schema::names::Names;
+
+use super::{meta_schema, Codec, MAGIC, SYNC_SIZE};
+
+/// Reads the datums in an object container file.
+pub struct Reader<R: Read> {
+    reader: R,
+    schema: Schema,
+    schema_value: Value,
+    names: Names,
+    meta: BTreeMap<String, Vec<u8>>,
+    codec: Codec,
+    sync: [u8; 16],
+    // The schema to read datums as, when it isn't the writer's.
+    reader_schema: Option<(Value, Names)>,
+    // How far into the file we've read, and where the current block starts.
+    position: u64,
+    block_start: u64,
+    // The length of the file, when it's known, so a corrupt block size is
+    // caught before trying to read that much.
+    length: Option<u64>,
+    // The current block, decompressed, and how far into it we are.
+    block: Vec<u8>,
+    offset: usize,
+    remaining: i64,
+    failed: bool,
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the file header: the schema, codec and other metadata, and the
+    /// sync marker that follows every block.
+    pub fn new(reader: R) -> Result<Reader<R>, Error> {
+        // Counted, so we know where the first block starts.
+        let mut counted = Counted { inner: reader, count: 0 };
+        let reader = &mut counted;
+        let magic = try!(binary::read_exact(MAGIC.len(), reader));
+        if magic[..] != MAGIC[..] {
+            return Err(Error::DecodeError(ErrorCode::NotAContainerFile));
+        }
+
+        let mut meta = BTreeMap::new();
+        if let Datum::Map(map) = try!(binary::decode(&meta_schema(), reader)) {
+            for (key, value) in map.into_iter() {
+                if let Datum::Bytes(bytes) = value {
+                    meta.insert(key, bytes);
+                }
+            }
+        }
+
+        let schema = match meta.get("avro.schema").map(|s| str::from_utf8(s)) {
+            Some(Ok(text)) => try!(schema::from_str(text)),
+            _ => return Err(Error::DecodeError(ErrorCode::MissingContainerSchema)),
+        };
+        let codec = match meta.get("avro.codec").map(|c| str::from_utf8(c)) {
+            Some(Ok(name)) => try!(Codec::from_str(name)),
+            Some(Err(_)) => return Err(Error::DecodeError(ErrorCode::UnknownCodec)),
+            None => Codec::Null,
+        };
+
+        let mut sync = [0u8; 16];
+        sync.copy_from_slice(&try!(binary::read_exact(SYNC_SIZE, reader)));
+
+        Ok(Reader {
+            position: counted.count,
+            block_start: counted.count,
+            length: None,
+            reader: counted.inner,
+            schema_value: Value::from(&schema),
+            names: Names::from_schema(&schema),
+            schema: schema,
+            meta: meta,
+            codec: codec,
+            sync: sync,
+            reader_schema: None,
+            block: Vec::new(),
+            offset: 0,
+            remaining: 0,
+            failed: false,
+        })
+    }
+
+    /// Like `new`, but datums are resolved against `reader_schema` as they
+    /// are read. Fields the reader schema leaves out are skipped over.
+    pub fn with_reader_schema(reader: R, reader_schema: &Schema) -> Result<Reader<R>, Error> {
+        let mut container = try!(Reader::new(reader));
+        container.reader_schema = Some((Value::from(reader_schema), Names::from_schema(reader_schema)));
+        Ok(container)
+    }
+
+    /// The schema the file was written with.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// All of the header metadata, including avro.schema and avro.codec.
+    pub fn meta(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.meta
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn sync_marker(&self) -> &[u8; 16] {
+        &self.sync
+    }
+
+    /// Reads the next block as it is in the file, still compressed, along
+    /// with the number of datums in it. Returns None at the end of the file.
+    ///
+    /// Mixing this with `read` skips whatever is left of the current block.
+    pub fn read_raw_block(&mut self) -> Result<Option<(i64, Vec<u8>)>, Error> {
+        let (count, size) = match try!(self.read_block_header()) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        if let Some(length) = self.length {
+            if self.position + size as u64 > length {
+                return Err(Error::DecodeError(ErrorCode::CorruptBlock));
+            }
+        }
+
+        let data = try!(binary::read_exact(size as usize, &mut self.reader));
+        try!(self.read_sync());
+        self.position += size as u64;
+        Ok(Some((count, data)))
+    }
+
+    /// Reads the next datum, or None at the end of the file.
+    pub fn read(&mut self) -> Result<Option<Datum>, Error> {
+        if !try!(self.fill_block()) {
+            return Ok(None);
+        }
+
+        let mut cursor = &self.block[self.offset..];
+        let before = cursor.len();
+        let datum = match self.reader_schema {
+            Some((ref reader_value, ref reader_names)) => {
+                try!(Resolver::new(&self.names, reader_names).decode(&self.schema_value, None, reader_value, None, &mut cursor))
+            },
+            None => try!(Decoder::new(&self.names).decode(&self.schema_value, None, &mut cursor)),
+        };
+        self.offset += before - cursor.len();
+        self.remaining -= 1;
+        Ok(Some(datum))
+    }
+
+    /// Reads the next datum without copying its strings, bytes and fixed
+    /// values out of the block, or None at the end of the file. The datum
+    /// borrows the reader until it's dropped; use `ValueRef::to_owned` to
+    /// keep it longer.
+    ///
+    /// Datums are read as written; this fails for a reader made with
+    /// `with_reader_schema`.
+    pub fn read_ref(&mut self) -> Result<Option<ValueRef>, Error> {
+        if self.reader_schema.is_some() {
+            return Err(Error::DecodeError(ErrorCode::ReaderSchemaNotSupported));
+        }
+        if !try!(self.fill_block()) {
+            return Ok(None);
+        }
+
+        let mut cursor = &self.block[self.offset..];
+        let before = cursor.len();
+        let datum = try!(BorrowedDecoder::new(&self.names).decode(&self.schema_value, None, &mut cursor));
+        self.offset += before - cursor.len();
+        self.remaining -= 1;
+        Ok(Some(datum))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Reads a block's object count and size in bytes, or None at the end of
+    // the file.
+    fn read_block_header(&mut self) -> Result<Option<(i64, i64)>, Error> {
+        self.remaining = 0;
+
+        // A clean end of file can only come where a block would start.
+        let mut first = [0u8; 1];
+        loop {
+            match self.reader.read(&mut first) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+
+        self.block_start = self.position;
+        let mut counted = Counted { inner: &mut self.reader, count: 0 };
+        let (count, size) = {
+            let mut reader = (&first[..]).chain(&mut counted);
+            (try!(binary::read_long(&mut reader)), try!(binary::read_long(&mut reader)))
+        };
+        if count < 0 || size < 0 {
+            return Err(Error::DecodeError(ErrorCode::NegativeLength));
+        }
+        self.position += 1 + counted.count;
+        Ok(Some((count, size)))
+    }
+
+    fn read_sync(&mut self) -> Result<(),Error> {
+        if try!(binary::read_exact(SYNC_SIZE, &mut self.reader))[..] != self.sync[..] {
+            return Err(Error::DecodeError(ErrorCode::SyncMarkerMismatch));
+        }
+        self.position += SYNC_SIZE as u64;
+        Ok(())
+    }
+
+    // Makes sure there's a datum left in the current block, reading the next
+    // block if need be. False at the end of the file.
+    fn fill_block(&mut self) -> Result<bool, Error> {
+        while self.remaining == 0 {
+            match try!(self.read_raw_block()) {
+                Some((count, data)) => {
+                    // An uncompressed block is used as it was read.
+                    self.block = match self.codec {
+                        Codec::Null => data,
+                        codec => try!(codec.decompress(&data)),
+                    };
+                    self.offset = 0;
+                    self.remaining = count;
+                },
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Splitting a file between readers. The file is cut into byte ranges, and
+/// each block belongs to the range its preceding sync marker starts in, so
+/// every block is read exactly once. A reader for the range from `start` to
+/// `end` calls `seek_to_sync(start)`, then reads until `past_sync(end)`.
+impl<R: Read + Seek> Reader<R> {
+    /// Moves to the first block after a sync marker starting at or after
+    /// `offset`, and returns where that block starts. With no marker after
+    /// `offset` the reader moves to the end of the file.
+    pub fn seek_to_sync(&mut self, offset: u64) -> Result<u64, Error> {
+        self.remaining = 0;
+        self.failed = false;
+
+        // `window` holds the bytes read from `start` on. When the marker isn't
+        // in it, all but the last few bytes (which may begin the marker) are
+        // dropped before reading more.
+        let mut start = try!(self.reader.seek(SeekFrom::Start(offset)));
+        let mut window = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = match self.reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::IoError(e)),
+            };
+            window.extend_from_slice(&chunk[..n]);
+
+            if let Some(idx) = window.windows(SYNC_SIZE).position(|w| w == &self.sync[..]) {
+                let position = start + (idx + SYNC_SIZE) as u64;
+                try!(self.reader.seek(SeekFrom::Start(position)));
+                self.position = position;
+                self.block_start = position;
+                return Ok(position);
+            }
+            if window.len() >= SYNC_SIZE {
+                let dropped = window.len() - (SYNC_SIZE - 1);
+                window.drain(..dropped);
+                start += dropped as u64;
+            }
+        }
+
+        let end = start + window.len() as u64;
+        self.position = end;
+        self.block_start = end;
+        Ok(end)
+    }
+
+    /// Whether the next datum comes from a block past the range ending at
+    /// `end`, or there are no more datums.
+    pub fn past_sync(&mut self, end: u64) -> Result<bool, Error> {
+        if !try!(self.fill_block()) {
+            return Ok(true);
+        }
+        Ok(self.block_start >= end + SYNC_SIZE as u64)
+    }
+
+    /// Turns this into a reader that skips damaged blocks instead of failing.
+    pub fn lenient(mut self) -> Result<LenientReader<R>, Error> {
+        let length = try!(self.reader.seek(SeekFrom::End(0)));
+        try!(self.reader.seek(SeekFrom::Start(self.position)));
+        self.length = Some(length);
+        Ok(LenientReader { reader: self, skipped_blocks: 0, skipped_bytes: 0, reported: false })
+    }
+
+    /// Lists the blocks from the reader's position on (the start of the file,
+    /// or wherever `seek_to_sync` left it) by reading their headers and
+    /// seeking past their data. Whatever is left of the current block is
+    /// skipped.
+    pub fn block_positions(&mut self) -> BlockPositions<R> {
+        self.remaining = 0;
+        BlockPositions { reader: self, done: false }
+    }
+}
+
+/// Where a block is in a container file, and what's in it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BlockPosition {
+    /// Where the block starts. Its sync marker is the `SYNC_SIZE` bytes
+    /// before it, so `seek_to_sync(offset - SYNC_SIZE)` moves to it.
+    pub offset: u64,
+    pub count: i64,
+    /// The size of the block's data, compressed.
+    pub size: u64,
+}
+
+pub struct BlockPositions<'a, R: 'a + Read + Seek> {
+    reader: &'a mut Reader<R>,
+    done: bool,
+}
+
+impl<'a, R: Read + Seek> BlockPositions<'a, R> {
+    fn next_position(&mut self) -> Result<Option<BlockPosition>, Error> {
+        let (count, size) = match try!(self.reader.read_block_header()) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        try!(self.reader.reader.seek(SeekFrom::Current(size)));
+        self.reader.position += size as u64;
+        try!(self.reader.read_sync());
+        Ok(Some(BlockPosition { offset: self.reader.block_start, count: count, size: size as u64 }))
+    }
+}
+
+impl<'a, R: Read + Seek> Iterator for BlockPositions<'a, R> {
+    type Item = Result<BlockPosition, Error>;
+
+    fn next(&mut self) -> Option<Result<BlockPosition, Error>> {
+        if self.done {
+            return None;
+        }
+        match self.next_position() {
+            Ok(Some(position)) => Some(Ok(position)),
+            Ok(None) => None,
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+/// A container reader that gets past damage. When a block can't be read,
+/// decompressed or decoded, the error is logged and reading carries on from
+/// the next sync marker. Datums already read from a damaged block are kept;
+/// the rest of it is lost.
+pub struct LenientReader<R: Read + Seek> {
+    reader: Reader<R>,
+    skipped_blocks: u64,
+    skipped_bytes: u64,
+    reported: bool,
+}
+
+impl<R: Read + Seek> LenientReader<R> {
+    pub fn schema(&self) -> &Schema {
+        self.reader.schema()
+    }
+
+    /// Reads the next datum that can be read, or None at the end of the file.
+    /// Errors only come from failing to move past a damaged block.
+    pub fn read(&mut self) -> Result<Option<Datum>, Error> {
+        loop {
+            match self.reader.read() {
+                Ok(Some(datum)) => return Ok(Some(datum)),
+                Ok(None) => {
+                    if self.skipped_blocks > 0 && !self.reported {
+                        warn!("skipped {} damaged block(s), {} bytes", self.skipped_blocks, self.skipped_bytes);
+                        self.reported = true;
+                    }
+                    return Ok(None);
+                },
+                Err(e) => {
+                    let start = self.reader.block_start;
+                    let next = try!(self.reader.seek_to_sync(start + 1));
+                    warn!("skipping damaged block at byte {} ({} bytes): {}", start, next - start, e);
+                    self.skipped_blocks += 1;
+                    self.skipped_bytes += next - start;
+                },
+            }
+        }
+    }
+
+    /// The number of blocks skipped so far.
+    pub fn skipped_blocks(&self) -> u64 {
+        self.skipped_blocks
+    }
+
+    /// The number of bytes skipped so far, counting from the start of each
+    /// damaged block to the next sync marker after it.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> Iterator for LenientReader<R> {
+    type Item = Result<Datum, Error>;
+
+    fn next(&mut self) -> Option<Result<Datum, Error>> {
+        match self.read() {
+            Ok(Some(datum)) => Some(Ok(datum)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+// Counts the bytes read through it.
+struct Counted<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = try!(self.inner.read(buf));
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Datum, Error>;
+
+    // Stops after the first error rather than trying to read on from a
+    // position that is probably wrong.
+    fn next(&mut self) -> Option<Result<Datum, Error>> {
+        if self.failed {
+            return None;
+        }
+        match self.read() {
+            Ok(Some(datum)) => Some(Ok(datum)),
+            Ok(None) => None,
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use md5;
+use serde::json::Value;
+
+use datum::Datum;
+use encoding::binary::{self, Encoder};
+use schema::Schema;
+use schema::error::*;
+use schema::names::Names;
+
+use super::{meta_schema, Codec, Reader, MAGIC};
+
+/// Roughly how many bytes of encoded datums go in a block before it's written.
+pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Writes datums to an object container file.
+///
+/// The header is written with the first block, so metadata can be set until
+/// then. Datums are buffered into blocks; call `flush` or `into_inner` when
+/// done, or the last block is lost.
+pub struct Writer<W: Write> {
+    writer: W,
+    schema: Schema,
+    schema_value: Value,
+    names: Names,
+    codec: Codec,
+    sync: [u8; 16],
+    meta: BTreeMap<String, Vec<u8>>,
+    block_size: usize,
+    buffer: Vec<u8>,
+    count: i64,
+    header_written: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W, schema: Schema, codec: Codec) -> Writer<W> {
+        Writer {
+            writer: writer,
+            schema_value: Value::from(&schema),
+            names: Names::from_schema(&schema),
+            schema: schema,
+            codec: codec,
+            sync: new_sync_marker(),
+            meta: BTreeMap::new(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            buffer: Vec::new(),
+            count: 0,
+            header_written: false,
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn sync_marker(&self) -> &[u8; 16] {
+        &self.sync
+    }
+
+    /// Adds a metadata entry to the header. Keys starting with "avro." are
+    /// reserved, and refused; avro.schema and avro.codec are always set by
+    /// the writer.
+    pub fn set_meta(&mut self, key: &str, value: &[u8]) -> Result<(),Error> {
+        if self.header_written {
+            return Err(Error::EncodeError(ErrorCode::HeaderAlreadyWritten));
+        }
+        if key.starts_with("avro.") {
+            return Err(Error::EncodeError(ErrorCode::ReservedMetadataKey));
+        }
+        self.meta.insert(String::from(key), value.to_vec());
+        Ok(())
+    }
+
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size;
+    }
+
+    pub fn append(&mut self, datum: &Datum) -> Result<(),Error> {
+        // Encode on the side so a datum that doesn't fit the schema doesn't
+        // leave half of itself in the block.
+        let mut buf = Vec::new();
+        try!(Encoder::new(&self.names).encode(datum, &self.schema_value, None, &mut buf));
+        self.buffer.extend(buf.into_iter());
+        self.count += 1;
+
+        if self.buffer.len() >= self.block_size {
+            try!(self.write_block());
+        }
+        Ok(())
+    }
+
+    /// Writes a block taken as is from another file with the same schema and
+    /// codec, saving decoding and recompressing it.
+    pub fn append_raw_block(&mut self, count: i64, data: &[u8]) -> Result<(),Error> {
+        try!(self.write_block());
+        self.write_raw_block(count, data)
+    }
+
+    /// Writes out the current block and flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<(),Error> {
+        try!(self.write_block());
+        try!(self.write_header());
+        Ok(try!(self.writer.flush()))
+    }
+
+    /// The underlying writer. Whatever's written to it directly lands
+    /// between blocks.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        try!(self.flush());
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> Result<(),Error> {
+        if self.header_written {
+            return Ok(());
+        }
+
+        let mut meta = BTreeMap::new();
+        for (key, value) in self.meta.iter() {
+            meta.insert(key.clone(), Datum::Bytes(value.clone()));
+        }
+        meta.insert(String::from("avro.schema"), Datum::Bytes(String::from(&self.schema).into_bytes()));
+        meta.insert(String::from("avro.codec"), Datum::Bytes(self.codec.name().as_bytes().to_vec()));
+
+        try!(self.writer.write_all(&MAGIC));
+        try!(binary::encode(&Datum::Map(meta), &meta_schema(), &mut self.writer));
+        try!(self.writer.write_all(&self.sync));
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> Result<(),Error> {
+        if self.count == 0 {
+            return Ok(());
+        }
+        let data = try!(self.codec.compress(&self.buffer));
+        let count = self.count;
+        self.buffer.clear();
+        self.count = 0;
+        self.write_raw_block(count, &data)
+    }
+
+    fn write_raw_block(&mut self, count: i64, data: &[u8]) -> Result<(),Error> {
+        try!(self.write_header());
+        try!(binary::write_long(count, &mut self.writer));
+        try!(binary::write_long(data.len() as i64, &mut self.writer));
+        try!(self.writer.write_all(data));
+        Ok(try!(self.writer.write_all(&self.sync)))
+    }
+}
+
+impl<W: Read + Write + Seek> Writer<W> {
+    /// Opens an existing container file to add more datums to it. The file's
+    /// codec, metadata and sync marker are kept, and new blocks go after the
+    /// last one. `schema` must have the same canonical form as the file's.
+    ///
+    /// Every block is checked on the way to the end, so a file whose last
+    /// block was cut short (or is otherwise damaged) is refused with the
+    /// error reading it gave, rather than having blocks added after the
+    /// damage where no reader would get to them.
+    pub fn append_to(mut file: W, schema: Schema) -> Result<Writer<W>, Error> {
+        try!(file.seek(SeekFrom::Start(0)));
+        let (codec, sync, meta) = {
+            let mut reader = try!(Reader::new(&mut file));
+            if reader.schema().canonical_form() != schema.canonical_form() {
+                return Err(Error::EncodeError(ErrorCode::ContainerSchemaMismatch));
+            }
+            // Walking the blocks leaves the file just past the last sync
+            // marker, which is where the next block goes.
+            for position in reader.block_positions() {
+                try!(position);
+            }
+            let meta = reader.meta().iter()
+                .filter(|&(key, _)| key != "avro.schema" && key != "avro.codec")
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            (reader.codec(), *reader.sync_marker(), meta)
+        };
+
+        let mut writer = Writer::new(file, schema, codec);
+        writer.sync = sync;
+        writer.meta = meta;
+        writer.header_written = true;
+        Ok(writer)
+    }
+}
+
+// The sync marker only has to be unlikely to turn up in the data.
+fn new_sync_marker() -> [u8; 16] {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seed = format!("{}.{}.{}", now.as_secs(), now.subsec_nanos(), process::id());
+    md5::compute(seed.as_bytes()).0
+}
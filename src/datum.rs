@@ -0,0 +1,163 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+/// A generic Avro datum. It only makes sense alongside the schema it was
+/// read with (or is going to be written with).
+#[derive(Clone, PartialEq, Debug)]
+pub enum Datum {
+    Null,
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(Vec<u8>),
+    String(String),
+    /// Field names and values, in schema order.
+    Record(Vec<(String, Datum)>),
+    /// The enum symbol.
+    Enum(String),
+    Array(Vec<Datum>),
+    Map(BTreeMap<String, Datum>),
+    /// The index of the union branch and the value for it.
+    Union(usize, Box<Datum>),
+    Fixed(Vec<u8>),
+}
+
+impl Datum {
+    /// Looks up a field of a record datum by name.
+    pub fn field(&self, name: &str) -> Option<&Datum> {
+        match *self {
+            Datum::Record(ref fields) => {
+                fields.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref d)| d)
+            },
+            _ => None
+        }
+    }
+
+    /// The datum with any union wrapping removed.
+    pub fn unwrap_union(&self) -> &Datum {
+        match *self {
+            Datum::Union(_, ref d) => d.unwrap_union(),
+            _ => self
+        }
+    }
+}
+
+/// A datum decoded without copying: strings, bytes and fixed values point
+/// into the buffer it was read from, and record field names and enum
+/// symbols into the schema. See encoding::borrowed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ValueRef<'a> {
+    Null,
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(&'a [u8]),
+    String(&'a str),
+    Record(Vec<(&'a str, ValueRef<'a>)>),
+    Enum(&'a str),
+    Array(Vec<ValueRef<'a>>),
+    Map(BTreeMap<&'a str, ValueRef<'a>>),
+    Union(usize, Box<ValueRef<'a>>),
+    Fixed(&'a [u8]),
+}
+
+impl<'a> ValueRef<'a> {
+    pub fn field(&self, name: &str) -> Option<&ValueRef<'a>> {
+        match *self {
+            ValueRef::Record(ref fields) => {
+                fields.iter().find(|&&(n, _)| n == name).map(|&(_, ref d)| d)
+            },
+            _ => None
+        }
+    }
+
+    pub fn unwrap_union(&self) -> &ValueRef<'a> {
+        match *self {
+            ValueRef::Union(_, ref d) => d.unwrap_union(),
+            _ => self
+        }
+    }
+
+    /// Copies the datum out of the buffer, for when it has to outlive it.
+    pub fn to_owned(&self) -> Datum {
+        match *self {
+            ValueRef::Null => Datum::Null,
+            ValueRef::Boolean(b) => Datum::Boolean(b),
+            ValueRef::Int(n) => Datum::Int(n),
+            ValueRef::Long(n) => Datum::Long(n),
+            ValueRef::Float(n) => Datum::Float(n),
+            ValueRef::Double(n) => Datum::Double(n),
+            ValueRef::Bytes(b) => Datum::Bytes(b.to_vec()),
+            ValueRef::String(s) => Datum::String(String::from(s)),
+            ValueRef::Record(ref fields) => {
+                Datum::Record(fields.iter().map(|&(n, ref d)| (String::from(n), d.to_owned())).collect())
+            },
+            ValueRef::Enum(s) => Datum::Enum(String::from(s)),
+            ValueRef::Array(ref vec) => Datum::Array(vec.iter().map(|d| d.to_owned()).collect()),
+            ValueRef::Map(ref map) => {
+                Datum::Map(map.iter().map(|(k, d)| (String::from(*k), d.to_owned())).collect())
+            },
+            ValueRef::Union(idx, ref d) => Datum::Union(idx, Box::new((**d).to_owned())),
+            ValueRef::Fixed(b) => Datum::Fixed(b.to_vec()),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Datum {
+    fn from(s: &'a str) -> Datum {
+        Datum::String(String::from(s))
+    }
+}
+
+impl From<String> for Datum {
+    fn from(s: String) -> Datum {
+        Datum::String(s)
+    }
+}
+
+impl From<bool> for Datum {
+    fn from(b: bool) -> Datum {
+        Datum::Boolean(b)
+    }
+}
+
+impl From<i32> for Datum {
+    fn from(n: i32) -> Datum {
+        Datum::Int(n)
+    }
+}
+
+impl From<i64> for Datum {
+    fn from(n: i64) -> Datum {
+        Datum::Long(n)
+    }
+}
+
+impl From<f32> for Datum {
+    fn from(n: f32) -> Datum {
+        Datum::Float(n)
+    }
+}
+
+impl From<f64> for Datum {
+    fn from(n: f64) -> Datum {
+        Datum::Double(n)
+    }
+}
@@ -0,0 +1,588 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use serde::json::Value;
+
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+use schema::names::{Names, SchemaKind};
+
+use super::json;
+
+/// Writes `datum` in the Avro binary encoding.
+pub fn encode<W: Write>(datum: &Datum, schema: &Schema, writer: &mut W) -> Result<(),Error> {
+    let names = Names::from_schema(schema);
+    Encoder::new(&names).encode(datum, &Value::from(schema), None, writer)
+}
+
+/// Reads a datum written with `schema` in the Avro binary encoding.
+pub fn decode<R: Read>(schema: &Schema, reader: &mut R) -> Result<Datum, Error> {
+    let names = Names::from_schema(schema);
+    Decoder::new(&names).decode(&Value::from(schema), None, reader)
+}
+
+pub fn to_vec(datum: &Datum, schema: &Schema) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    try!(encode(datum, schema, &mut buf));
+    Ok(buf)
+}
+
+pub struct Encoder<'a> {
+    names: &'a Names,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(names: &'a Names) -> Encoder<'a> {
+        Encoder { names: names }
+    }
+
+    pub fn encode<W: Write>(&self, datum: &Datum, schema: &Value, ns: Option<&str>, writer: &mut W) -> Result<(),Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => self.encode_primitive(datum, type_name, writer),
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                if let Datum::Record(_) = *datum {} else {
+                    return Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch));
+                }
+
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_type = match field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+
+                    match datum.field(name) {
+                        Some(value) => try!(self.encode(value, field_type, ns, writer)),
+                        None => {
+                            // Missing fields are written as their default, if they have one.
+                            match field.find("default") {
+                                Some(default) => {
+                                    let value = try!(default_datum(self.names, default, field_type, ns));
+                                    try!(self.encode(&value, field_type, ns, writer));
+                                },
+                                None => return Err(Error::EncodeError(ErrorCode::MissingFieldValue)),
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            },
+            SchemaKind::Enum(definition) => {
+                match *datum {
+                    Datum::Enum(ref symbol) => {
+                        match symbol_index(definition, symbol) {
+                            Some(idx) => write_long(idx as i64, writer),
+                            None => Err(Error::EncodeError(ErrorCode::EnumSymbolNotFound)),
+                        }
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Fixed(definition) => {
+                match *datum {
+                    Datum::Fixed(ref bytes) => {
+                        if Some(bytes.len() as u64) != definition.find("size").and_then(|s| s.as_u64()) {
+                            return Err(Error::EncodeError(ErrorCode::FixedSizeMismatch));
+                        }
+                        Ok(try!(writer.write_all(bytes)))
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Array(items) => {
+                match *datum {
+                    Datum::Array(ref vec) => {
+                        if !vec.is_empty() {
+                            try!(write_long(vec.len() as i64, writer));
+                            for item in vec.iter() {
+                                try!(self.encode(item, items, ns, writer));
+                            }
+                        }
+                        write_long(0, writer)
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Map(values) => {
+                match *datum {
+                    Datum::Map(ref map) => {
+                        if !map.is_empty() {
+                            try!(write_long(map.len() as i64, writer));
+                            for (key, value) in map.iter() {
+                                try!(write_string(key, writer));
+                                try!(self.encode(value, values, ns, writer));
+                            }
+                        }
+                        write_long(0, writer)
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            SchemaKind::Union(branches) => {
+                let (idx, value) = match *datum {
+                    Datum::Union(idx, ref value) => {
+                        if idx >= branches.len() {
+                            return Err(Error::EncodeError(ErrorCode::UnionIndexOutOfRange));
+                        }
+                        (idx, &**value)
+                    },
+                    _ => {
+                        match union_branch(self.names, datum, branches, ns) {
+                            Some(idx) => (idx, datum),
+                            None => return Err(Error::EncodeError(ErrorCode::UnionBranchNotFound)),
+                        }
+                    }
+                };
+                try!(write_long(idx as i64, writer));
+                self.encode(value, &branches[idx], ns, writer)
+            },
+        }
+    }
+
+    fn encode_primitive<W: Write>(&self, datum: &Datum, type_name: &str, writer: &mut W) -> Result<(),Error> {
+        match (type_name, datum) {
+            ("null", &Datum::Null) => Ok(()),
+            ("boolean", &Datum::Boolean(b)) => write_boolean(b, writer),
+            ("int", &Datum::Int(n)) => write_long(n as i64, writer),
+            ("long", &Datum::Int(n)) => write_long(n as i64, writer),
+            ("long", &Datum::Long(n)) => write_long(n, writer),
+            ("float", &Datum::Float(n)) => write_float(n, writer),
+            ("double", &Datum::Float(n)) => write_double(n as f64, writer),
+            ("double", &Datum::Double(n)) => write_double(n, writer),
+            ("bytes", &Datum::Bytes(ref b)) => write_bytes(b, writer),
+            ("string", &Datum::String(ref s)) => write_string(s, writer),
+            _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+        }
+    }
+}
+
+pub struct Decoder<'a> {
+    names: &'a Names,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(names: &'a Names) -> Decoder<'a> {
+        Decoder { names: names }
+    }
+
+    pub fn decode<R: Read>(&self, schema: &Value, ns: Option<&str>, reader: &mut R) -> Result<Datum, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => decode_primitive(type_name, reader),
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+
+                let mut record = Vec::with_capacity(fields.len());
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_type = match field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+                    let value = try!(self.decode(field_type, ns, reader));
+                    record.push((String::from(name), value));
+                }
+                Ok(Datum::Record(record))
+            },
+            SchemaKind::Enum(definition) => {
+                let idx = try!(read_long(reader));
+                match enum_symbol(definition, idx) {
+                    Some(symbol) => Ok(Datum::Enum(symbol)),
+                    None => Err(Error::DecodeError(ErrorCode::EnumIndexOutOfRange)),
+                }
+            },
+            SchemaKind::Fixed(definition) => {
+                let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                Ok(Datum::Fixed(try!(read_exact(size as usize, reader))))
+            },
+            SchemaKind::Array(items) => {
+                let mut vec = Vec::new();
+                loop {
+                    let count = try!(read_block_count(reader));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        vec.push(try!(self.decode(items, ns, reader)));
+                    }
+                }
+                Ok(Datum::Array(vec))
+            },
+            SchemaKind::Map(values) => {
+                let mut map = BTreeMap::new();
+                loop {
+                    let count = try!(read_block_count(reader));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        let key = try!(read_string(reader));
+                        let value = try!(self.decode(values, ns, reader));
+                        map.insert(key, value);
+                    }
+                }
+                Ok(Datum::Map(map))
+            },
+            SchemaKind::Union(branches) => {
+                let idx = try!(read_long(reader));
+                if idx < 0 || idx as usize >= branches.len() {
+                    return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+                }
+                let value = try!(self.decode(&branches[idx as usize], ns, reader));
+                Ok(Datum::Union(idx as usize, Box::new(value)))
+            },
+        }
+    }
+
+    /// Reads past a datum without building it. Strings, bytes and fixed are
+    /// skipped by their length, and array and map blocks written with a
+    /// byte size are skipped whole.
+    pub fn skip<R: Read>(&self, schema: &Value, ns: Option<&str>, reader: &mut R) -> Result<(),Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => {
+                match type_name {
+                    "null" => Ok(()),
+                    "boolean" => skip_exact(1, reader),
+                    "int" | "long" => read_long(reader).map(|_| ()),
+                    "float" => skip_exact(4, reader),
+                    "double" => skip_exact(8, reader),
+                    "bytes" | "string" => {
+                        let len = try!(read_long(reader));
+                        if len < 0 {
+                            return Err(Error::DecodeError(ErrorCode::NegativeLength));
+                        }
+                        skip_exact(len as u64, reader)
+                    },
+                    _ => Err(Error::SyntaxError(ErrorCode::NotValidPrimitiveType, 0, 0)),
+                }
+            },
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                for field in fields.iter() {
+                    match field.find("type") {
+                        Some(field_type) => try!(self.skip(field_type, ns, reader)),
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    }
+                }
+                Ok(())
+            },
+            SchemaKind::Enum(_) => read_long(reader).map(|_| ()),
+            SchemaKind::Fixed(definition) => {
+                let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                skip_exact(size, reader)
+            },
+            SchemaKind::Array(items) => self.skip_blocks(None, items, ns, reader),
+            SchemaKind::Map(values) => self.skip_blocks(Some(&Value::String(String::from("string"))), values, ns, reader),
+            SchemaKind::Union(branches) => {
+                let idx = try!(read_long(reader));
+                if idx < 0 || idx as usize >= branches.len() {
+                    return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+                }
+                self.skip(&branches[idx as usize], ns, reader)
+            },
+        }
+    }
+
+    // Array and map blocks. A negative count is followed by the block's size
+    // in bytes, so the whole block can be passed over at once.
+    fn skip_blocks<R: Read>(&self, key: Option<&Value>, items: &Value, ns: Option<&str>, reader: &mut R) -> Result<(),Error> {
+        loop {
+            let count = try!(read_long(reader));
+            if count == 0 {
+                return Ok(());
+            }
+            if count < 0 {
+                let size = try!(read_long(reader));
+                if size < 0 {
+                    return Err(Error::DecodeError(ErrorCode::NegativeLength));
+                }
+                try!(skip_exact(size as u64, reader));
+                continue;
+            }
+            for _ in 0..count {
+                if let Some(key) = key {
+                    try!(self.skip(key, ns, reader));
+                }
+                try!(self.skip(items, ns, reader));
+            }
+        }
+    }
+}
+
+pub fn decode_primitive<R: Read>(type_name: &str, reader: &mut R) -> Result<Datum, Error> {
+    match type_name {
+        "null" => Ok(Datum::Null),
+        "boolean" => Ok(Datum::Boolean(try!(read_boolean(reader)))),
+        "int" => Ok(Datum::Int(try!(read_int(reader)))),
+        "long" => Ok(Datum::Long(try!(read_long(reader)))),
+        "float" => Ok(Datum::Float(try!(read_float(reader)))),
+        "double" => Ok(Datum::Double(try!(read_double(reader)))),
+        "bytes" => Ok(Datum::Bytes(try!(read_bytes(reader)))),
+        "string" => Ok(Datum::String(try!(read_string(reader)))),
+        _ => Err(Error::SyntaxError(ErrorCode::NotValidPrimitiveType, 0, 0)),
+    }
+}
+
+/// Picks the union branch a datum that isn't explicitly wrapped in a
+/// Datum::Union should be written as. An exact type match wins over a
+/// branch the datum would have to be promoted to.
+pub fn union_branch(names: &Names, datum: &Datum, branches: &Vec<Value>, ns: Option<&str>) -> Option<usize> {
+    for exact in [true, false].iter() {
+        for (idx, branch) in branches.iter().enumerate() {
+            if matches(names, datum, branch, ns, *exact) {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+fn matches(names: &Names, datum: &Datum, schema: &Value, ns: Option<&str>, exact: bool) -> bool {
+    let (kind, ns) = match names.kind(schema, ns) {
+        Ok(k) => k,
+        Err(_) => return false,
+    };
+
+    match (kind, datum) {
+        (SchemaKind::Primitive(type_name), _) => {
+            match (type_name, datum) {
+                ("null", &Datum::Null) | ("boolean", &Datum::Boolean(_)) |
+                ("int", &Datum::Int(_)) | ("long", &Datum::Long(_)) |
+                ("float", &Datum::Float(_)) | ("double", &Datum::Double(_)) |
+                ("bytes", &Datum::Bytes(_)) | ("string", &Datum::String(_)) => true,
+                ("long", &Datum::Int(_)) | ("double", &Datum::Float(_)) => !exact,
+                _ => false,
+            }
+        },
+        (SchemaKind::Record(definition), &Datum::Record(_)) => {
+            // Every field has to either be there or have a default.
+            match definition.find("fields") {
+                Some(&Value::Array(ref fields)) => {
+                    fields.iter().all(|f| {
+                        let name = f.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                        datum.field(name).is_some() || f.find("default").is_some()
+                    })
+                },
+                _ => false,
+            }
+        },
+        (SchemaKind::Enum(definition), &Datum::Enum(ref symbol)) => symbol_index(definition, symbol).is_some(),
+        (SchemaKind::Fixed(definition), &Datum::Fixed(ref bytes)) => {
+            Some(bytes.len() as u64) == definition.find("size").and_then(|s| s.as_u64())
+        },
+        (SchemaKind::Array(items), &Datum::Array(ref vec)) => {
+            let ns = ns.as_ref().map(|s| s.as_ref());
+            vec.iter().all(|item| matches(names, item, items, ns, false))
+        },
+        (SchemaKind::Map(values), &Datum::Map(ref map)) => {
+            let ns = ns.as_ref().map(|s| s.as_ref());
+            map.values().all(|value| matches(names, value, values, ns, false))
+        },
+        _ => false,
+    }
+}
+
+/// Converts a field default (written as JSON in the schema) to a datum.
+pub fn default_datum(names: &Names, default: &Value, schema: &Value, ns: Option<&str>) -> Result<Datum, Error> {
+    json::Decoder::new(names).decode_default(default, schema, ns)
+}
+
+pub fn symbol_index(definition: &Value, symbol: &str) -> Option<usize> {
+    match definition.find("symbols") {
+        Some(&Value::Array(ref symbols)) => symbols.iter().position(|s| s.as_string() == Some(symbol)),
+        _ => None,
+    }
+}
+
+pub fn enum_symbol(definition: &Value, idx: i64) -> Option<String> {
+    match definition.find("symbols") {
+        Some(&Value::Array(ref symbols)) if idx >= 0 => {
+            symbols.get(idx as usize).and_then(|s| s.as_string()).map(|s| String::from(s))
+        },
+        _ => None,
+    }
+}
+
+pub fn write_boolean<W: Write>(b: bool, writer: &mut W) -> Result<(),Error> {
+    Ok(try!(writer.write_all(&[if b { 1 } else { 0 }])))
+}
+
+pub fn write_long<W: Write>(n: i64, writer: &mut W) -> Result<(),Error> {
+    // zig-zag, then 7 bits at a time, low order first
+    let mut z = ((n << 1) ^ (n >> 63)) as u64;
+    let mut buf = [0u8; 10];
+    let mut len = 0;
+    loop {
+        if z & !0x7f == 0 {
+            buf[len] = z as u8;
+            len += 1;
+            break;
+        }
+        buf[len] = ((z & 0x7f) | 0x80) as u8;
+        len += 1;
+        z >>= 7;
+    }
+    Ok(try!(writer.write_all(&buf[..len])))
+}
+
+pub fn write_float<W: Write>(n: f32, writer: &mut W) -> Result<(),Error> {
+    let bits = n.to_bits();
+    let buf = [bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8];
+    Ok(try!(writer.write_all(&buf)))
+}
+
+pub fn write_double<W: Write>(n: f64, writer: &mut W) -> Result<(),Error> {
+    let bits = n.to_bits();
+    let mut buf = [0u8; 8];
+    for i in 0..8 {
+        buf[i] = (bits >> (8 * i)) as u8;
+    }
+    Ok(try!(writer.write_all(&buf)))
+}
+
+pub fn write_bytes<W: Write>(bytes: &[u8], writer: &mut W) -> Result<(),Error> {
+    try!(write_long(bytes.len() as i64, writer));
+    Ok(try!(writer.write_all(bytes)))
+}
+
+pub fn write_string<W: Write>(s: &str, writer: &mut W) -> Result<(),Error> {
+    write_bytes(s.as_bytes(), writer)
+}
+
+pub fn read_byte<R: Read>(reader: &mut R) -> Result<u8, Error> {
+    let mut buf = [0u8; 1];
+    try!(reader.read_exact(&mut buf));
+    Ok(buf[0])
+}
+
+pub fn read_boolean<R: Read>(reader: &mut R) -> Result<bool, Error> {
+    match try!(read_byte(reader)) {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Error::DecodeError(ErrorCode::InvalidBoolean)),
+    }
+}
+
+pub fn read_long<R: Read>(reader: &mut R) -> Result<i64, Error> {
+    let mut z: u64 = 0;
+    let mut shift = 0;
+    loop {
+        if shift >= 64 {
+            return Err(Error::DecodeError(ErrorCode::IntegerOutOfRange));
+        }
+        let b = try!(read_byte(reader));
+        z |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    Ok((z >> 1) as i64 ^ -((z & 1) as i64))
+}
+
+pub fn read_int<R: Read>(reader: &mut R) -> Result<i32, Error> {
+    let n = try!(read_long(reader));
+    if n < i32::min_value() as i64 || n > i32::max_value() as i64 {
+        return Err(Error::DecodeError(ErrorCode::IntegerOutOfRange));
+    }
+    Ok(n as i32)
+}
+
+pub fn read_float<R: Read>(reader: &mut R) -> Result<f32, Error> {
+    let mut buf = [0u8; 4];
+    try!(reader.read_exact(&mut buf));
+    let bits = (buf[0] as u32) | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24;
+    Ok(f32::from_bits(bits))
+}
+
+pub fn read_double<R: Read>(reader: &mut R) -> Result<f64, Error> {
+    let mut buf = [0u8; 8];
+    try!(reader.read_exact(&mut buf));
+    let mut bits = 0u64;
+    for i in 0..8 {
+        bits |= (buf[i] as u64) << (8 * i);
+    }
+    Ok(f64::from_bits(bits))
+}
+
+/// Reads exactly `len` bytes. Reads through `take` rather than allocating
+/// `len` up front, so a corrupt length can't ask for a huge buffer.
+pub fn read_exact<R: Read>(len: usize, reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    try!(reader.by_ref().take(len as u64).read_to_end(&mut buf));
+    if buf.len() != len {
+        return Err(Error::IoError(::std::io::Error::new(::std::io::ErrorKind::UnexpectedEof, "unexpected end of data")));
+    }
+    Ok(buf)
+}
+
+/// Reads past exactly `len` bytes without keeping them.
+pub fn skip_exact<R: Read>(len: u64, reader: &mut R) -> Result<(),Error> {
+    let skipped = try!(io::copy(&mut reader.by_ref().take(len), &mut io::sink()));
+    if skipped != len {
+        return Err(Error::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of data")));
+    }
+    Ok(())
+}
+
+pub fn read_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let len = try!(read_long(reader));
+    if len < 0 {
+        return Err(Error::DecodeError(ErrorCode::NegativeLength));
+    }
+    read_exact(len as usize, reader)
+}
+
+pub fn read_string<R: Read>(reader: &mut R) -> Result<String, Error> {
+    let bytes = try!(read_bytes(reader));
+    String::from_utf8(bytes).map_err(|_| Error::DecodeError(ErrorCode::InvalidUtf8))
+}
+
+/// Reads the item count at the start of an array or map block. A negative
+/// count is followed by the size of the block in bytes, which isn't needed
+/// when every item is being read anyway.
+pub fn read_block_count<R: Read>(reader: &mut R) -> Result<i64, Error> {
+    let count = try!(read_long(reader));
+    if count < 0 {
+        try!(read_long(reader));
+        match count.checked_neg() {
+            Some(count) => Ok(count),
+            None => Err(Error::DecodeError(ErrorCode::IntegerOutOfRange)),
+        }
+    } else {
+        Ok(count)
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Zero-copy decoding of the binary encoding from a byte slice. The datum
+// borrows from the input (strings, bytes, fixed, map keys) and from the
+// schema (field names, enum symbols), so both have to outlive it.
+use std::collections::BTreeMap;
+use std::str;
+
+use serde::json::Value;
+
+use datum::ValueRef;
+use schema::error::*;
+use schema::names::{Names, SchemaKind};
+
+use super::binary;
+
+pub struct BorrowedDecoder<'a> {
+    names: &'a Names,
+}
+
+impl<'a> BorrowedDecoder<'a> {
+    pub fn new(names: &'a Names) -> BorrowedDecoder<'a> {
+        BorrowedDecoder { names: names }
+    }
+
+    /// Decodes the datum at the start of `input` and moves `input` past it.
+    pub fn decode(&self, schema: &'a Value, ns: Option<&str>, input: &mut &'a [u8]) -> Result<ValueRef<'a>, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => {
+                match type_name {
+                    "null" => Ok(ValueRef::Null),
+                    "boolean" => Ok(ValueRef::Boolean(try!(binary::read_boolean(input)))),
+                    "int" => Ok(ValueRef::Int(try!(binary::read_int(input)))),
+                    "long" => Ok(ValueRef::Long(try!(binary::read_long(input)))),
+                    "float" => Ok(ValueRef::Float(try!(binary::read_float(input)))),
+                    "double" => Ok(ValueRef::Double(try!(binary::read_double(input)))),
+                    "bytes" => Ok(ValueRef::Bytes(try!(read_bytes(input)))),
+                    "string" => Ok(ValueRef::String(try!(read_str(input)))),
+                    _ => Err(Error::SyntaxError(ErrorCode::NotValidPrimitiveType, 0, 0)),
+                }
+            },
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+
+                let mut record = Vec::with_capacity(fields.len());
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_type = match field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+                    record.push((name, try!(self.decode(field_type, ns, input))));
+                }
+                Ok(ValueRef::Record(record))
+            },
+            SchemaKind::Enum(definition) => {
+                let idx = try!(binary::read_long(input));
+                let symbol = match definition.find("symbols") {
+                    Some(&Value::Array(ref symbols)) if idx >= 0 => {
+                        symbols.get(idx as usize).and_then(|s| s.as_string())
+                    },
+                    _ => None,
+                };
+                match symbol {
+                    Some(symbol) => Ok(ValueRef::Enum(symbol)),
+                    None => Err(Error::DecodeError(ErrorCode::EnumIndexOutOfRange)),
+                }
+            },
+            SchemaKind::Fixed(definition) => {
+                let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                Ok(ValueRef::Fixed(try!(take(size, input))))
+            },
+            SchemaKind::Array(items) => {
+                let mut vec = Vec::new();
+                loop {
+                    let count = try!(binary::read_block_count(input));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        vec.push(try!(self.decode(items, ns, input)));
+                    }
+                }
+                Ok(ValueRef::Array(vec))
+            },
+            SchemaKind::Map(values) => {
+                let mut map = BTreeMap::new();
+                loop {
+                    let count = try!(binary::read_block_count(input));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        let key = try!(read_str(input));
+                        map.insert(key, try!(self.decode(values, ns, input)));
+                    }
+                }
+                Ok(ValueRef::Map(map))
+            },
+            SchemaKind::Union(branches) => {
+                let idx = try!(binary::read_long(input));
+                if idx < 0 || idx as usize >= branches.len() {
+                    return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+                }
+                let value = try!(self.decode(&branches[idx as usize], ns, input));
+                Ok(ValueRef::Union(idx as usize, Box::new(value)))
+            },
+        }
+    }
+}
+
+fn take<'a>(len: u64, input: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let data: &'a [u8] = *input;
+    if len > data.len() as u64 {
+        return Err(Error::IoError(::std::io::Error::new(::std::io::ErrorKind::UnexpectedEof, "unexpected end of data")));
+    }
+    let (head, tail) = data.split_at(len as usize);
+    *input = tail;
+    Ok(head)
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let len = try!(binary::read_long(input));
+    if len < 0 {
+        return Err(Error::DecodeError(ErrorCode::NegativeLength));
+    }
+    take(len as u64, input)
+}
+
+fn read_str<'a>(input: &mut &'a [u8]) -> Result<&'a str, Error> {
+    str::from_utf8(try!(read_bytes(input))).map_err(|_| Error::DecodeError(ErrorCode::InvalidUtf8))
+}
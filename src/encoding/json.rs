@@ -0,0 +1,380 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use serde::json::{self, Value};
+
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+use schema::names::{fullname_of, Names, SchemaKind};
+
+use super::binary::{symbol_index, union_branch};
+
+/// Converts `datum` to the Avro JSON encoding.
+pub fn encode(datum: &Datum, schema: &Schema) -> Result<Value, Error> {
+    let names = Names::from_schema(schema);
+    Encoder::new(&names).encode(datum, &Value::from(schema), None)
+}
+
+/// Converts a value in the Avro JSON encoding to a datum, checking it
+/// against `schema` as it goes.
+pub fn decode(value: &Value, schema: &Schema) -> Result<Datum, Error> {
+    let names = Names::from_schema(schema);
+    Decoder::new(&names).decode(value, &Value::from(schema), None)
+}
+
+pub fn to_string(datum: &Datum, schema: &Schema) -> Result<String, Error> {
+    let value = try!(encode(datum, schema));
+    json::to_string(&value).map_err(|_| Error::EncodeError(ErrorCode::InvalidUtf8))
+}
+
+pub fn from_str(s: &str, schema: &Schema) -> Result<Datum, Error> {
+    let value: Value = try!(json::from_str(s));
+    decode(&value, schema)
+}
+
+pub struct Encoder<'a> {
+    names: &'a Names,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(names: &'a Names) -> Encoder<'a> {
+        Encoder { names: names }
+    }
+
+    pub fn encode(&self, datum: &Datum, schema: &Value, ns: Option<&str>) -> Result<Value, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+        let mismatch = Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch));
+
+        match kind {
+            SchemaKind::Primitive(type_name) => {
+                match (type_name, datum) {
+                    ("null", &Datum::Null) => Ok(Value::Null),
+                    ("boolean", &Datum::Boolean(b)) => Ok(Value::Bool(b)),
+                    ("int", &Datum::Int(n)) => Ok(Value::I64(n as i64)),
+                    ("long", &Datum::Int(n)) => Ok(Value::I64(n as i64)),
+                    ("long", &Datum::Long(n)) => Ok(Value::I64(n)),
+                    ("float", &Datum::Float(n)) => Ok(Value::F64(n as f64)),
+                    ("double", &Datum::Float(n)) => Ok(Value::F64(n as f64)),
+                    ("double", &Datum::Double(n)) => Ok(Value::F64(n)),
+                    ("bytes", &Datum::Bytes(ref bytes)) => Ok(Value::String(latin1_string(bytes))),
+                    ("string", &Datum::String(ref s)) => Ok(Value::String(s.clone())),
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                if let Datum::Record(_) = *datum {} else {
+                    return mismatch;
+                }
+
+                let mut object = BTreeMap::new();
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_type = match field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+
+                    let value = match datum.field(name) {
+                        Some(value) => try!(self.encode(value, field_type, ns)),
+                        None => {
+                            match field.find("default") {
+                                Some(default) => {
+                                    let value = try!(Decoder::new(self.names).decode_default(default, field_type, ns));
+                                    try!(self.encode(&value, field_type, ns))
+                                },
+                                None => return Err(Error::EncodeError(ErrorCode::MissingFieldValue)),
+                            }
+                        }
+                    };
+                    object.insert(String::from(name), value);
+                }
+                Ok(Value::Object(object))
+            },
+            SchemaKind::Enum(definition) => {
+                match *datum {
+                    Datum::Enum(ref symbol) if symbol_index(definition, symbol).is_some() => Ok(Value::String(symbol.clone())),
+                    Datum::Enum(_) => Err(Error::EncodeError(ErrorCode::EnumSymbolNotFound)),
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Fixed(definition) => {
+                match *datum {
+                    Datum::Fixed(ref bytes) => {
+                        if Some(bytes.len() as u64) != definition.find("size").and_then(|s| s.as_u64()) {
+                            return Err(Error::EncodeError(ErrorCode::FixedSizeMismatch));
+                        }
+                        Ok(Value::String(latin1_string(bytes)))
+                    },
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Array(items) => {
+                match *datum {
+                    Datum::Array(ref vec) => {
+                        let mut array = Vec::with_capacity(vec.len());
+                        for item in vec.iter() {
+                            array.push(try!(self.encode(item, items, ns)));
+                        }
+                        Ok(Value::Array(array))
+                    },
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Map(values) => {
+                match *datum {
+                    Datum::Map(ref map) => {
+                        let mut object = BTreeMap::new();
+                        for (key, value) in map.iter() {
+                            object.insert(key.clone(), try!(self.encode(value, values, ns)));
+                        }
+                        Ok(Value::Object(object))
+                    },
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Union(branches) => {
+                let (idx, value) = match *datum {
+                    Datum::Union(idx, ref value) => {
+                        if idx >= branches.len() {
+                            return Err(Error::EncodeError(ErrorCode::UnionIndexOutOfRange));
+                        }
+                        (idx, &**value)
+                    },
+                    _ => {
+                        match union_branch(self.names, datum, branches, ns) {
+                            Some(idx) => (idx, datum),
+                            None => return Err(Error::EncodeError(ErrorCode::UnionBranchNotFound)),
+                        }
+                    }
+                };
+
+                // Null is written as is, anything else wrapped in an object
+                // naming its branch.
+                let encoded = try!(self.encode(value, &branches[idx], ns));
+                let branch_name = try!(type_name(self.names, &branches[idx], ns));
+                if branch_name == "null" {
+                    Ok(encoded)
+                } else {
+                    let mut object = BTreeMap::new();
+                    object.insert(branch_name, encoded);
+                    Ok(Value::Object(object))
+                }
+            },
+        }
+    }
+}
+
+pub struct Decoder<'a> {
+    names: &'a Names,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(names: &'a Names) -> Decoder<'a> {
+        Decoder { names: names }
+    }
+
+    pub fn decode(&self, value: &Value, schema: &Value, ns: Option<&str>) -> Result<Datum, Error> {
+        self.decode_value(value, schema, ns, false)
+    }
+
+    /// Converts a field default to a datum. Defaults are written like the
+    /// JSON encoding, except that a union's default isn't wrapped and always
+    /// belongs to its first branch.
+    pub fn decode_default(&self, default: &Value, schema: &Value, ns: Option<&str>) -> Result<Datum, Error> {
+        self.decode_value(default, schema, ns, true).map_err(|e| {
+            match e {
+                Error::DecodeError(_) => Error::SyntaxError(ErrorCode::FieldDefaultTypeMismatch, 0, 0),
+                e => e,
+            }
+        })
+    }
+
+    fn decode_value(&self, value: &Value, schema: &Value, ns: Option<&str>, default: bool) -> Result<Datum, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+        let mismatch = Err(Error::DecodeError(ErrorCode::DatumSchemaMismatch));
+
+        match kind {
+            SchemaKind::Primitive(type_name) => {
+                match (type_name, value) {
+                    ("null", &Value::Null) => Ok(Datum::Null),
+                    ("boolean", &Value::Bool(b)) => Ok(Datum::Boolean(b)),
+                    ("int", &Value::I64(_)) | ("int", &Value::U64(_)) => {
+                        match value.as_i64() {
+                            Some(n) if n >= i32::min_value() as i64 && n <= i32::max_value() as i64 => Ok(Datum::Int(n as i32)),
+                            _ => Err(Error::DecodeError(ErrorCode::IntegerOutOfRange)),
+                        }
+                    },
+                    ("long", &Value::I64(n)) => Ok(Datum::Long(n)),
+                    ("long", &Value::U64(n)) => {
+                        if n > i64::max_value() as u64 {
+                            return Err(Error::DecodeError(ErrorCode::IntegerOutOfRange));
+                        }
+                        Ok(Datum::Long(n as i64))
+                    },
+                    ("float", _) if value.is_number() => Ok(Datum::Float(value.as_f64().unwrap_or(0.0) as f32)),
+                    ("double", _) if value.is_number() => Ok(Datum::Double(value.as_f64().unwrap_or(0.0))),
+                    ("bytes", &Value::String(ref s)) => Ok(Datum::Bytes(try!(latin1_bytes(s)))),
+                    ("string", &Value::String(ref s)) => Ok(Datum::String(s.clone())),
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Record(definition) => {
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                if !value.is_object() {
+                    return mismatch;
+                }
+
+                let mut record = Vec::with_capacity(fields.len());
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_type = match field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+
+                    // A field left out takes its default, if it has one.
+                    let datum = match (value.find(name), field.find("default")) {
+                        (Some(v), _) => try!(self.decode_value(v, field_type, ns, default)),
+                        (None, Some(d)) => try!(self.decode_default(d, field_type, ns)),
+                        (None, None) => return Err(Error::DecodeError(ErrorCode::MissingFieldValue)),
+                    };
+                    record.push((String::from(name), datum));
+                }
+                Ok(Datum::Record(record))
+            },
+            SchemaKind::Enum(definition) => {
+                match *value {
+                    Value::String(ref symbol) if symbol_index(definition, symbol).is_some() => Ok(Datum::Enum(symbol.clone())),
+                    Value::String(_) => Err(Error::DecodeError(ErrorCode::EnumSymbolNotFound)),
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Fixed(definition) => {
+                match *value {
+                    Value::String(ref s) => {
+                        let bytes = try!(latin1_bytes(s));
+                        if Some(bytes.len() as u64) != definition.find("size").and_then(|s| s.as_u64()) {
+                            return Err(Error::DecodeError(ErrorCode::FixedSizeMismatch));
+                        }
+                        Ok(Datum::Fixed(bytes))
+                    },
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Array(items) => {
+                match *value {
+                    Value::Array(ref vec) => {
+                        let mut array = Vec::with_capacity(vec.len());
+                        for v in vec.iter() {
+                            array.push(try!(self.decode_value(v, items, ns, default)));
+                        }
+                        Ok(Datum::Array(array))
+                    },
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Map(values) => {
+                match *value {
+                    Value::Object(ref obj) => {
+                        let mut map = BTreeMap::new();
+                        for (k, v) in obj.iter() {
+                            map.insert(k.clone(), try!(self.decode_value(v, values, ns, default)));
+                        }
+                        Ok(Datum::Map(map))
+                    },
+                    _ => mismatch,
+                }
+            },
+            SchemaKind::Union(branches) => {
+                if default {
+                    return match branches.first() {
+                        Some(first) => Ok(Datum::Union(0, Box::new(try!(self.decode_value(value, first, ns, default))))),
+                        None => mismatch,
+                    };
+                }
+
+                let (branch_name, inner) = match *value {
+                    Value::Null => (String::from("null"), value),
+                    Value::Object(ref obj) if obj.len() == 1 => {
+                        let (k, v) = obj.iter().next().unwrap();
+                        (k.clone(), v)
+                    },
+                    _ => return Err(Error::DecodeError(ErrorCode::UnionBranchNotFound)),
+                };
+
+                for (idx, branch) in branches.iter().enumerate() {
+                    let name = try!(type_name(self.names, branch, ns));
+                    if name == branch_name || self.is_same_type(&branch_name, &name, ns) {
+                        let datum = try!(self.decode_value(inner, branch, ns, default));
+                        return Ok(Datum::Union(idx, Box::new(datum)));
+                    }
+                }
+                Err(Error::DecodeError(ErrorCode::UnionBranchNotFound))
+            },
+        }
+    }
+
+    // Union branches are labelled with full names, but a short name relative
+    // to the enclosing namespace is accepted too.
+    fn is_same_type(&self, label: &str, fullname: &str, ns: Option<&str>) -> bool {
+        match self.names.resolve(label, ns) {
+            Some(named) => fullname_of(&named.definition, named.namespace.as_ref().map(|s| s.as_ref())) == fullname,
+            None => false,
+        }
+    }
+}
+
+/// The name a union branch is labelled with in the JSON encoding: the full
+/// name of a named type, otherwise the type itself.
+pub fn type_name(names: &Names, schema: &Value, ns: Option<&str>) -> Result<String, Error> {
+    let (kind, ns) = try!(names.kind(schema, ns));
+    match kind {
+        SchemaKind::Primitive(type_name) => Ok(String::from(type_name)),
+        SchemaKind::Record(definition) | SchemaKind::Enum(definition) | SchemaKind::Fixed(definition) => {
+            Ok(fullname_of(definition, ns.as_ref().map(|s| s.as_ref())))
+        },
+        SchemaKind::Array(_) => Ok(String::from("array")),
+        SchemaKind::Map(_) => Ok(String::from("map")),
+        SchemaKind::Union(_) => Err(Error::SyntaxError(ErrorCode::CannotNestArrays, 0, 0)),
+    }
+}
+
+// Bytes and fixed values are written in JSON as strings whose code points
+// are the byte values (0-255).
+pub fn latin1_bytes(s: &str) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        if c as u32 > 255 {
+            return Err(Error::DecodeError(ErrorCode::DatumSchemaMismatch));
+        }
+        bytes.push(c as u32 as u8);
+    }
+    Ok(bytes)
+}
+
+pub fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
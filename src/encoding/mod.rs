@@ -0,0 +1,23 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Encodings for datums. Each one walks the schema alongside the datum, so
+// the schema's named types have to be collected (see schema::Names) first.
+pub mod binary;
+pub mod borrowed;
+pub mod json;
+pub mod plan;
+pub mod registry;
+pub mod resolve;
+pub mod single_object;
@@ -0,0 +1,720 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Compiled plans for the binary encoding. The interpreting encoders and
+// decoders look up names and match on type strings for every datum; a plan
+// does all of that once, up front, and leaves a flat list of instructions
+// that refer to each other by index. Recursive types are just instructions
+// that refer back to themselves.
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use serde::json::Value;
+
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+use schema::names::{fullname_of, Names, SchemaKind};
+
+use super::binary;
+use super::json;
+use super::resolve::{can_promote, has_alias, names_match, Resolver};
+
+impl Schema {
+    /// Compiles a plan for reading and writing data with this schema.
+    pub fn compile(&self) -> Result<Plan, Error> {
+        let names = Names::from_schema(self);
+        let value = Value::from(self);
+        let mut compiler = Compiler::new(&names, &names);
+        let entry = try!(compiler.compile(&value, None, &value, None));
+        Ok(Plan { ops: compiler.ops, decode_entry: entry, encode_entry: entry })
+    }
+
+    /// Compiles a plan for reading data written with this schema as
+    /// `reader`, resolving the two the way `resolve::decode` does. Writer
+    /// fields the reader doesn't have are skipped. The plan still encodes
+    /// with this schema.
+    pub fn compile_for(&self, reader: &Schema) -> Result<Plan, Error> {
+        let writer_names = Names::from_schema(self);
+        let reader_names = Names::from_schema(reader);
+        let (writer_value, reader_value) = (Value::from(self), Value::from(reader));
+
+        let mut compiler = Compiler::new(&writer_names, &reader_names);
+        let decode_entry = try!(compiler.compile(&writer_value, None, &reader_value, None));
+        compiler.reader = &writer_names;
+        compiler.records.clear();
+        let encode_entry = try!(compiler.compile(&writer_value, None, &writer_value, None));
+        Ok(Plan { ops: compiler.ops, decode_entry: decode_entry, encode_entry: encode_entry })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Primitive {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+}
+
+impl Primitive {
+    fn from_name(name: &str) -> Option<Primitive> {
+        match name {
+            "null" => Some(Primitive::Null),
+            "boolean" => Some(Primitive::Boolean),
+            "int" => Some(Primitive::Int),
+            "long" => Some(Primitive::Long),
+            "float" => Some(Primitive::Float),
+            "double" => Some(Primitive::Double),
+            "bytes" => Some(Primitive::Bytes),
+            "string" => Some(Primitive::String),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    /// Reads a primitive written as the first type and returns it as the second.
+    Read(Primitive, Primitive),
+    Record(RecordOp),
+    /// The reader's symbol for each of the writer's, and the reader's default
+    /// for symbols it doesn't have.
+    Enum(Vec<Option<String>>, Option<String>),
+    Fixed(usize),
+    /// Arrays and maps, with the instruction for their items or values.
+    Array(usize),
+    Map(usize),
+    /// A union on the writer's side: the instruction for each branch.
+    Union(Vec<usize>),
+    /// A value the reader reads as the given branch of its union.
+    Branch(usize, usize),
+    /// A writer's union branch the reader has no match for. It's only an
+    /// error if a datum actually uses it.
+    Fail(ErrorCode),
+
+    // Skipping values the reader doesn't want.
+    SkipBytes(u64),
+    SkipLong,
+    SkipLengthPrefixed,
+    SkipRecord(Vec<usize>),
+    SkipBlocks(bool, usize),
+    SkipUnion(Vec<usize>),
+}
+
+#[derive(Clone, Debug)]
+struct RecordOp {
+    /// In the writer's order, as the data comes.
+    fields: Vec<FieldOp>,
+    /// The reader's field names, in the order the datum is built in.
+    names: Vec<String>,
+    /// Reader fields the writer doesn't have, and their defaults.
+    defaults: Vec<(usize, Datum)>,
+}
+
+#[derive(Clone, Debug)]
+enum FieldOp {
+    /// Reads the field into the reader's slot. The writer's default is used
+    /// when encoding a datum without the field.
+    Read { slot: usize, op: usize, default: Option<Datum> },
+    Skip(usize),
+}
+
+/// A compiled schema (see `Schema::compile`).
+#[derive(Clone, Debug)]
+pub struct Plan {
+    ops: Vec<Op>,
+    decode_entry: usize,
+    encode_entry: usize,
+}
+
+impl Plan {
+    pub fn decode<R: Read>(&self, reader: &mut R) -> Result<Datum, Error> {
+        self.decode_at(self.decode_entry, reader)
+    }
+
+    pub fn encode<W: Write>(&self, datum: &Datum, writer: &mut W) -> Result<(),Error> {
+        self.encode_at(self.encode_entry, datum, writer)
+    }
+
+    pub fn to_vec(&self, datum: &Datum) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        try!(self.encode(datum, &mut buf));
+        Ok(buf)
+    }
+
+    /// The number of instructions.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    fn decode_at<R: Read>(&self, op: usize, reader: &mut R) -> Result<Datum, Error> {
+        match self.ops[op] {
+            Op::Read(writer, read_as) => read_primitive(writer, read_as, reader),
+            Op::Record(ref record) => {
+                let mut slots: Vec<Option<Datum>> = vec![None; record.names.len()];
+                for field in record.fields.iter() {
+                    match *field {
+                        FieldOp::Read { slot, op, .. } => slots[slot] = Some(try!(self.decode_at(op, reader))),
+                        FieldOp::Skip(op) => try!(self.skip_at(op, reader)),
+                    }
+                }
+                for &(slot, ref default) in record.defaults.iter() {
+                    slots[slot] = Some(default.clone());
+                }
+
+                let mut fields = Vec::with_capacity(slots.len());
+                for (name, value) in record.names.iter().zip(slots.into_iter()) {
+                    match value {
+                        Some(value) => fields.push((name.clone(), value)),
+                        None => return Err(Error::DecodeError(ErrorCode::MissingFieldValue)),
+                    }
+                }
+                Ok(Datum::Record(fields))
+            },
+            Op::Enum(ref symbols, ref default) => {
+                let idx = try!(binary::read_long(reader));
+                if idx < 0 || idx as usize >= symbols.len() {
+                    return Err(Error::DecodeError(ErrorCode::EnumIndexOutOfRange));
+                }
+                match (&symbols[idx as usize], default) {
+                    (&Some(ref symbol), _) | (&None, &Some(ref symbol)) => Ok(Datum::Enum(symbol.clone())),
+                    (&None, &None) => Err(Error::DecodeError(ErrorCode::EnumSymbolNotFound)),
+                }
+            },
+            Op::Fixed(size) => Ok(Datum::Fixed(try!(binary::read_exact(size, reader)))),
+            Op::Array(items) => {
+                let mut vec = Vec::new();
+                loop {
+                    let count = try!(binary::read_block_count(reader));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        vec.push(try!(self.decode_at(items, reader)));
+                    }
+                }
+                Ok(Datum::Array(vec))
+            },
+            Op::Map(values) => {
+                let mut map = BTreeMap::new();
+                loop {
+                    let count = try!(binary::read_block_count(reader));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        let key = try!(binary::read_string(reader));
+                        map.insert(key, try!(self.decode_at(values, reader)));
+                    }
+                }
+                Ok(Datum::Map(map))
+            },
+            Op::Union(ref branches) => {
+                let idx = try!(binary::read_long(reader));
+                if idx < 0 || idx as usize >= branches.len() {
+                    return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+                }
+                self.decode_at(branches[idx as usize], reader)
+            },
+            Op::Branch(idx, op) => Ok(Datum::Union(idx, Box::new(try!(self.decode_at(op, reader))))),
+            Op::Fail(ref code) => Err(Error::DecodeError(code.clone())),
+            _ => Err(Error::DecodeError(ErrorCode::Unknown)),
+        }
+    }
+
+    fn skip_at<R: Read>(&self, op: usize, reader: &mut R) -> Result<(),Error> {
+        match self.ops[op] {
+            Op::SkipBytes(len) => binary::skip_exact(len, reader),
+            Op::SkipLong => binary::read_long(reader).map(|_| ()),
+            Op::SkipLengthPrefixed => skip_length_prefixed(reader),
+            Op::SkipRecord(ref fields) => {
+                for field in fields.iter() {
+                    try!(self.skip_at(*field, reader));
+                }
+                Ok(())
+            },
+            Op::SkipBlocks(keys, items) => {
+                loop {
+                    let count = try!(binary::read_long(reader));
+                    if count == 0 {
+                        return Ok(());
+                    }
+                    if count < 0 {
+                        // The block's size in bytes follows a negative count.
+                        try!(skip_length_prefixed(reader));
+                        continue;
+                    }
+                    for _ in 0..count {
+                        if keys {
+                            try!(skip_length_prefixed(reader));
+                        }
+                        try!(self.skip_at(items, reader));
+                    }
+                }
+            },
+            Op::SkipUnion(ref branches) => {
+                let idx = try!(binary::read_long(reader));
+                if idx < 0 || idx as usize >= branches.len() {
+                    return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+                }
+                self.skip_at(branches[idx as usize], reader)
+            },
+            _ => Err(Error::DecodeError(ErrorCode::Unknown)),
+        }
+    }
+
+    // Encoding runs over the instructions compiled for the writer's schema
+    // against itself, so there are no promotions or skips to deal with.
+    fn encode_at<W: Write>(&self, op: usize, datum: &Datum, writer: &mut W) -> Result<(),Error> {
+        match self.ops[op] {
+            Op::Read(primitive, _) => write_primitive(primitive, datum, writer),
+            Op::Record(ref record) => {
+                if let Datum::Record(_) = *datum {} else {
+                    return Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch));
+                }
+                for field in record.fields.iter() {
+                    if let FieldOp::Read { slot, op, ref default } = *field {
+                        match (datum.field(&record.names[slot]), default.as_ref()) {
+                            (Some(value), _) | (None, Some(value)) => try!(self.encode_at(op, value, writer)),
+                            (None, None) => return Err(Error::EncodeError(ErrorCode::MissingFieldValue)),
+                        }
+                    }
+                }
+                Ok(())
+            },
+            Op::Enum(ref symbols, _) => {
+                match *datum {
+                    Datum::Enum(ref symbol) => {
+                        match symbols.iter().position(|s| s.as_ref() == Some(symbol)) {
+                            Some(idx) => binary::write_long(idx as i64, writer),
+                            None => Err(Error::EncodeError(ErrorCode::EnumSymbolNotFound)),
+                        }
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            Op::Fixed(size) => {
+                match *datum {
+                    Datum::Fixed(ref bytes) if bytes.len() == size => Ok(try!(writer.write_all(bytes))),
+                    Datum::Fixed(_) => Err(Error::EncodeError(ErrorCode::FixedSizeMismatch)),
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            Op::Array(items) => {
+                match *datum {
+                    Datum::Array(ref vec) => {
+                        if !vec.is_empty() {
+                            try!(binary::write_long(vec.len() as i64, writer));
+                            for item in vec.iter() {
+                                try!(self.encode_at(items, item, writer));
+                            }
+                        }
+                        binary::write_long(0, writer)
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            Op::Map(values) => {
+                match *datum {
+                    Datum::Map(ref map) => {
+                        if !map.is_empty() {
+                            try!(binary::write_long(map.len() as i64, writer));
+                            for (key, value) in map.iter() {
+                                try!(binary::write_string(key, writer));
+                                try!(self.encode_at(values, value, writer));
+                            }
+                        }
+                        binary::write_long(0, writer)
+                    },
+                    _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            Op::Union(ref branches) => {
+                let (idx, value) = match *datum {
+                    Datum::Union(idx, ref value) if idx < branches.len() => (idx, &**value),
+                    Datum::Union(..) => return Err(Error::EncodeError(ErrorCode::UnionIndexOutOfRange)),
+                    _ => {
+                        match self.union_branch(branches, datum) {
+                            Some(idx) => (idx, datum),
+                            None => return Err(Error::EncodeError(ErrorCode::UnionBranchNotFound)),
+                        }
+                    },
+                };
+                try!(binary::write_long(idx as i64, writer));
+                self.encode_at(branches[idx], value, writer)
+            },
+            Op::Branch(_, op) => self.encode_at(op, datum, writer),
+            _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+        }
+    }
+
+    // Same choice as binary::union_branch: an exact match first, then a
+    // branch the datum can be promoted to.
+    fn union_branch(&self, branches: &Vec<usize>, datum: &Datum) -> Option<usize> {
+        for exact in [true, false].iter() {
+            for (idx, op) in branches.iter().enumerate() {
+                if self.fits(*op, datum, *exact) {
+                    return Some(idx);
+                }
+            }
+        }
+        None
+    }
+
+    fn fits(&self, op: usize, datum: &Datum, exact: bool) -> bool {
+        use self::Primitive::*;
+
+        match (&self.ops[op], datum) {
+            (&Op::Branch(_, op), _) => self.fits(op, datum, exact),
+            (&Op::Read(primitive, _), _) => {
+                match (primitive, datum) {
+                    (Null, &Datum::Null) | (Boolean, &Datum::Boolean(_)) |
+                    (Int, &Datum::Int(_)) | (Long, &Datum::Long(_)) |
+                    (Float, &Datum::Float(_)) | (Double, &Datum::Double(_)) |
+                    (Bytes, &Datum::Bytes(_)) | (String, &Datum::String(_)) => true,
+                    (Long, &Datum::Int(_)) | (Double, &Datum::Float(_)) => !exact,
+                    _ => false,
+                }
+            },
+            (&Op::Record(ref record), &Datum::Record(_)) => {
+                record.fields.iter().all(|field| {
+                    match *field {
+                        FieldOp::Read { slot, ref default, .. } => {
+                            datum.field(&record.names[slot]).is_some() || default.is_some()
+                        },
+                        FieldOp::Skip(_) => true,
+                    }
+                })
+            },
+            (&Op::Enum(ref symbols, _), &Datum::Enum(ref symbol)) => symbols.iter().any(|s| s.as_ref() == Some(symbol)),
+            (&Op::Fixed(size), &Datum::Fixed(ref bytes)) => bytes.len() == size,
+            (&Op::Array(items), &Datum::Array(ref vec)) => vec.iter().all(|item| self.fits(items, item, false)),
+            (&Op::Map(values), &Datum::Map(ref map)) => map.values().all(|value| self.fits(values, value, false)),
+            _ => false,
+        }
+    }
+}
+
+fn read_primitive<R: Read>(writer: Primitive, read_as: Primitive, reader: &mut R) -> Result<Datum, Error> {
+    use self::Primitive::*;
+
+    match (writer, read_as) {
+        (Null, Null) => Ok(Datum::Null),
+        (Boolean, Boolean) => Ok(Datum::Boolean(try!(binary::read_boolean(reader)))),
+        (Int, Int) => Ok(Datum::Int(try!(binary::read_int(reader)))),
+        (Int, Long) => Ok(Datum::Long(try!(binary::read_int(reader)) as i64)),
+        (Int, Float) => Ok(Datum::Float(try!(binary::read_int(reader)) as f32)),
+        (Int, Double) => Ok(Datum::Double(try!(binary::read_int(reader)) as f64)),
+        (Long, Long) => Ok(Datum::Long(try!(binary::read_long(reader)))),
+        (Long, Float) => Ok(Datum::Float(try!(binary::read_long(reader)) as f32)),
+        (Long, Double) => Ok(Datum::Double(try!(binary::read_long(reader)) as f64)),
+        (Float, Float) => Ok(Datum::Float(try!(binary::read_float(reader)))),
+        (Float, Double) => Ok(Datum::Double(try!(binary::read_float(reader)) as f64)),
+        (Double, Double) => Ok(Datum::Double(try!(binary::read_double(reader)))),
+        (Bytes, Bytes) | (String, Bytes) => Ok(Datum::Bytes(try!(binary::read_bytes(reader)))),
+        (String, String) | (Bytes, String) => Ok(Datum::String(try!(binary::read_string(reader)))),
+        _ => Err(Error::DecodeError(ErrorCode::SchemaResolutionFailed)),
+    }
+}
+
+fn write_primitive<W: Write>(primitive: Primitive, datum: &Datum, writer: &mut W) -> Result<(),Error> {
+    use self::Primitive::*;
+
+    match (primitive, datum) {
+        (Null, &Datum::Null) => Ok(()),
+        (Boolean, &Datum::Boolean(b)) => binary::write_boolean(b, writer),
+        (Int, &Datum::Int(n)) | (Long, &Datum::Int(n)) => binary::write_long(n as i64, writer),
+        (Long, &Datum::Long(n)) => binary::write_long(n, writer),
+        (Float, &Datum::Float(n)) => binary::write_float(n, writer),
+        (Double, &Datum::Float(n)) => binary::write_double(n as f64, writer),
+        (Double, &Datum::Double(n)) => binary::write_double(n, writer),
+        (Bytes, &Datum::Bytes(ref b)) => binary::write_bytes(b, writer),
+        (String, &Datum::String(ref s)) => binary::write_string(s, writer),
+        _ => Err(Error::EncodeError(ErrorCode::DatumSchemaMismatch)),
+    }
+}
+
+fn skip_length_prefixed<R: Read>(reader: &mut R) -> Result<(),Error> {
+    let len = try!(binary::read_long(reader));
+    if len < 0 {
+        return Err(Error::DecodeError(ErrorCode::NegativeLength));
+    }
+    binary::skip_exact(len as u64, reader)
+}
+
+struct Compiler<'a> {
+    writer: &'a Names,
+    reader: &'a Names,
+    ops: Vec<Op>,
+    // Records already compiled, by writer and reader full name, so recursive
+    // and repeated types share their instructions.
+    records: BTreeMap<(String, String), usize>,
+    skips: BTreeMap<String, usize>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(writer: &'a Names, reader: &'a Names) -> Compiler<'a> {
+        Compiler {
+            writer: writer,
+            reader: reader,
+            ops: Vec::new(),
+            records: BTreeMap::new(),
+            skips: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    fn compile(&mut self, writer: &Value, writer_ns: Option<&str>, reader: &Value, reader_ns: Option<&str>) -> Result<usize, Error> {
+        let (writer_kind, writer_ns) = try!(self.writer.kind(writer, writer_ns));
+        let writer_ns = writer_ns.as_ref().map(|s| s.as_ref());
+
+        if let SchemaKind::Union(branches) = writer_kind {
+            let mut ops = Vec::with_capacity(branches.len());
+            for branch in branches.iter() {
+                let op = match self.compile(branch, writer_ns, reader, reader_ns) {
+                    Ok(op) => op,
+                    Err(Error::DecodeError(code)) => self.push(Op::Fail(code)),
+                    Err(e) => return Err(e),
+                };
+                ops.push(op);
+            }
+            return Ok(self.push(Op::Union(ops)));
+        }
+
+        let (reader_kind, reader_ns) = try!(self.reader.kind(reader, reader_ns));
+        let reader_ns = reader_ns.as_ref().map(|s| s.as_ref());
+        let mismatch = Err(Error::DecodeError(ErrorCode::SchemaResolutionFailed));
+
+        match (writer_kind, reader_kind) {
+            (ref writer_kind, SchemaKind::Union(branches)) => {
+                match Resolver::new(self.writer, self.reader).reader_branch(writer_kind, branches, reader_ns) {
+                    Some(idx) => {
+                        let op = try!(self.compile(writer, writer_ns, &branches[idx], reader_ns));
+                        Ok(self.push(Op::Branch(idx, op)))
+                    },
+                    None => mismatch,
+                }
+            },
+            (SchemaKind::Primitive(writer_type), SchemaKind::Primitive(reader_type)) => {
+                match (Primitive::from_name(writer_type), Primitive::from_name(reader_type)) {
+                    (Some(w), Some(r)) if w == r || can_promote(writer_type, reader_type) => Ok(self.push(Op::Read(w, r))),
+                    (Some(_), Some(_)) => mismatch,
+                    _ => Err(Error::SyntaxError(ErrorCode::NotValidPrimitiveType, 0, 0)),
+                }
+            },
+            (SchemaKind::Record(writer_def), SchemaKind::Record(reader_def)) => {
+                if !names_match(writer_def, reader_def) {
+                    return mismatch;
+                }
+                self.compile_record(writer_def, writer_ns, reader_def, reader_ns)
+            },
+            (SchemaKind::Enum(writer_def), SchemaKind::Enum(reader_def)) => {
+                if !names_match(writer_def, reader_def) {
+                    return mismatch;
+                }
+                let symbols = match writer_def.find("symbols") {
+                    Some(&Value::Array(ref symbols)) => {
+                        symbols.iter().map(|s| {
+                            let symbol = s.as_string().unwrap_or("");
+                            binary::symbol_index(reader_def, symbol).map(|_| String::from(symbol))
+                        }).collect()
+                    },
+                    _ => Vec::new(),
+                };
+                let default = reader_def.find("default").and_then(|d| d.as_string()).map(|d| String::from(d));
+                Ok(self.push(Op::Enum(symbols, default)))
+            },
+            (SchemaKind::Fixed(writer_def), SchemaKind::Fixed(reader_def)) => {
+                if !names_match(writer_def, reader_def) || writer_def.find("size") != reader_def.find("size") {
+                    return mismatch;
+                }
+                let size = writer_def.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                Ok(self.push(Op::Fixed(size as usize)))
+            },
+            (SchemaKind::Array(writer_items), SchemaKind::Array(reader_items)) => {
+                let items = try!(self.compile(writer_items, writer_ns, reader_items, reader_ns));
+                Ok(self.push(Op::Array(items)))
+            },
+            (SchemaKind::Map(writer_values), SchemaKind::Map(reader_values)) => {
+                let values = try!(self.compile(writer_values, writer_ns, reader_values, reader_ns));
+                Ok(self.push(Op::Map(values)))
+            },
+            _ => mismatch,
+        }
+    }
+
+    fn compile_record(&mut self, writer_def: &Value, writer_ns: Option<&str>, reader_def: &Value, reader_ns: Option<&str>) -> Result<usize, Error> {
+        let key = (fullname_of(writer_def, writer_ns), fullname_of(reader_def, reader_ns));
+        if let Some(op) = self.records.get(&key) {
+            return Ok(*op);
+        }
+        // Claim the slot first so fields of the record's own type refer to it.
+        let op = self.push(Op::Fail(ErrorCode::Unknown));
+        self.records.insert(key.clone(), op);
+
+        match self.record(writer_def, writer_ns, reader_def, reader_ns) {
+            Ok(record) => {
+                self.ops[op] = Op::Record(record);
+                Ok(op)
+            },
+            Err(e) => {
+                // Anything that already refers to the slot fails the same way.
+                self.records.remove(&key);
+                if let Error::DecodeError(ref code) = e {
+                    self.ops[op] = Op::Fail(code.clone());
+                }
+                Err(e)
+            },
+        }
+    }
+
+    fn record(&mut self, writer_def: &Value, writer_ns: Option<&str>, reader_def: &Value, reader_ns: Option<&str>) -> Result<RecordOp, Error> {
+        let writer_fields = match writer_def.find("fields") {
+            Some(&Value::Array(ref fields)) => fields,
+            _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+        };
+        let reader_fields = match reader_def.find("fields") {
+            Some(&Value::Array(ref fields)) => fields,
+            _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+        };
+
+        let mut record = RecordOp {
+            fields: Vec::with_capacity(writer_fields.len()),
+            names: reader_fields.iter()
+                .map(|f| String::from(f.find("name").and_then(|n| n.as_string()).unwrap_or("")))
+                .collect(),
+            defaults: Vec::new(),
+        };
+
+        let mut read = vec![false; reader_fields.len()];
+        for writer_field in writer_fields.iter() {
+            let writer_name = writer_field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+            let writer_type = match writer_field.find("type") {
+                Some(t) => t,
+                None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+            };
+            let reader_field = reader_fields.iter().position(|f| {
+                let name = f.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                writer_name == name || has_alias(f, writer_name)
+            });
+
+            match reader_field {
+                Some(slot) => {
+                    let reader_type = match reader_fields[slot].find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+                    let field_op = try!(self.compile(writer_type, writer_ns, reader_type, reader_ns));
+                    let default = match writer_field.find("default") {
+                        Some(default) => Some(try!(json::Decoder::new(self.writer).decode_default(default, writer_type, writer_ns))),
+                        None => None,
+                    };
+                    read[slot] = true;
+                    record.fields.push(FieldOp::Read { slot: slot, op: field_op, default: default });
+                },
+                None => {
+                    let skip = try!(self.compile_skip(writer_type, writer_ns));
+                    record.fields.push(FieldOp::Skip(skip));
+                },
+            }
+        }
+
+        for (slot, reader_field) in reader_fields.iter().enumerate() {
+            if read[slot] {
+                continue;
+            }
+            let reader_type = match reader_field.find("type") {
+                Some(t) => t,
+                None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+            };
+            match reader_field.find("default") {
+                Some(default) => {
+                    let datum = try!(json::Decoder::new(self.reader).decode_default(default, reader_type, reader_ns));
+                    record.defaults.push((slot, datum));
+                },
+                None => return Err(Error::DecodeError(ErrorCode::MissingFieldValue)),
+            }
+        }
+
+        Ok(record)
+    }
+
+    fn compile_skip(&mut self, writer: &Value, writer_ns: Option<&str>) -> Result<usize, Error> {
+        let (kind, writer_ns) = try!(self.writer.kind(writer, writer_ns));
+        let writer_ns = writer_ns.as_ref().map(|s| s.as_ref());
+
+        match kind {
+            SchemaKind::Primitive(type_name) => {
+                let op = match Primitive::from_name(type_name) {
+                    Some(Primitive::Null) => Op::SkipBytes(0),
+                    Some(Primitive::Boolean) => Op::SkipBytes(1),
+                    Some(Primitive::Int) | Some(Primitive::Long) => Op::SkipLong,
+                    Some(Primitive::Float) => Op::SkipBytes(4),
+                    Some(Primitive::Double) => Op::SkipBytes(8),
+                    Some(Primitive::Bytes) | Some(Primitive::String) => Op::SkipLengthPrefixed,
+                    None => return Err(Error::SyntaxError(ErrorCode::NotValidPrimitiveType, 0, 0)),
+                };
+                Ok(self.push(op))
+            },
+            SchemaKind::Record(definition) => {
+                let fullname = fullname_of(definition, writer_ns);
+                if let Some(op) = self.skips.get(&fullname) {
+                    return Ok(*op);
+                }
+                let op = self.push(Op::SkipRecord(Vec::new()));
+                self.skips.insert(fullname, op);
+
+                let mut fields = Vec::new();
+                if let Some(&Value::Array(ref writer_fields)) = definition.find("fields") {
+                    for field in writer_fields.iter() {
+                        match field.find("type") {
+                            Some(field_type) => fields.push(try!(self.compile_skip(field_type, writer_ns))),
+                            None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                        }
+                    }
+                }
+                self.ops[op] = Op::SkipRecord(fields);
+                Ok(op)
+            },
+            SchemaKind::Enum(_) => Ok(self.push(Op::SkipLong)),
+            SchemaKind::Fixed(definition) => {
+                let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                Ok(self.push(Op::SkipBytes(size)))
+            },
+            SchemaKind::Array(items) => {
+                let items = try!(self.compile_skip(items, writer_ns));
+                Ok(self.push(Op::SkipBlocks(false, items)))
+            },
+            SchemaKind::Map(values) => {
+                let values = try!(self.compile_skip(values, writer_ns));
+                Ok(self.push(Op::SkipBlocks(true, values)))
+            },
+            SchemaKind::Union(branches) => {
+                let mut ops = Vec::with_capacity(branches.len());
+                for branch in branches.iter() {
+                    ops.push(try!(self.compile_skip(branch, writer_ns)));
+                }
+                Ok(self.push(Op::SkipUnion(ops)))
+            },
+        }
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The schema registry wire format used by Confluent's Kafka serializers: a
+// zero magic byte, the writer schema's registry id (4 bytes, big-endian),
+// then the datum in the binary encoding.
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use datum::Datum;
+use schema::{self, Schema};
+use schema::error::*;
+
+use super::binary;
+use super::resolve;
+
+pub const MAGIC: u8 = 0;
+
+/// A client for a schema registry. Ids are assigned by the registry, and a
+/// subject keeps every version of the schemas registered under it.
+pub trait SchemaRegistry {
+    /// Registers `schema` under `subject` and returns its id. A schema the
+    /// registry already has keeps its id.
+    fn register(&mut self, subject: &str, schema: &Schema) -> Result<u32, Error>;
+
+    fn get_by_id(&mut self, id: u32) -> Result<Schema, Error>;
+
+    /// The id and schema of the latest version registered under `subject`.
+    fn latest(&mut self, subject: &str) -> Result<(u32, Schema), Error>;
+}
+
+/// Keeps schemas looked up by id, and the ids of schemas registered, so
+/// that only new schemas reach the registry behind it. `latest` always asks.
+pub struct CachingRegistry<R: SchemaRegistry> {
+    inner: R,
+    by_id: BTreeMap<u32, Schema>,
+    // (subject, fingerprint) -> id
+    registered: BTreeMap<(String, u64), u32>,
+}
+
+impl<R: SchemaRegistry> CachingRegistry<R> {
+    pub fn new(inner: R) -> CachingRegistry<R> {
+        CachingRegistry { inner: inner, by_id: BTreeMap::new(), registered: BTreeMap::new() }
+    }
+
+    pub fn inner(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: SchemaRegistry> SchemaRegistry for CachingRegistry<R> {
+    fn register(&mut self, subject: &str, schema: &Schema) -> Result<u32, Error> {
+        let key = (String::from(subject), schema.fingerprint());
+        if let Some(id) = self.registered.get(&key) {
+            return Ok(*id);
+        }
+
+        let id = try!(self.inner.register(subject, schema));
+        self.registered.insert(key, id);
+        self.by_id.insert(id, schema.clone());
+        Ok(id)
+    }
+
+    fn get_by_id(&mut self, id: u32) -> Result<Schema, Error> {
+        if let Some(schema) = self.by_id.get(&id) {
+            return Ok(schema.clone());
+        }
+
+        let schema = try!(self.inner.get_by_id(id));
+        self.by_id.insert(id, schema.clone());
+        Ok(schema)
+    }
+
+    fn latest(&mut self, subject: &str) -> Result<(u32, Schema), Error> {
+        let (id, schema) = try!(self.inner.latest(subject));
+        self.by_id.insert(id, schema.clone());
+        Ok((id, schema))
+    }
+}
+
+/// A registry held in memory, for tests and single process use.
+pub struct InMemoryRegistry {
+    // Ids start at 1; schema n lives at index n - 1.
+    schemas: Vec<Schema>,
+    subjects: BTreeMap<String, Vec<u32>>,
+}
+
+impl InMemoryRegistry {
+    pub fn new() -> InMemoryRegistry {
+        InMemoryRegistry { schemas: Vec::new(), subjects: BTreeMap::new() }
+    }
+}
+
+impl SchemaRegistry for InMemoryRegistry {
+    fn register(&mut self, subject: &str, schema: &Schema) -> Result<u32, Error> {
+        let fingerprint = schema.fingerprint();
+        let id = match self.schemas.iter().position(|s| s.fingerprint() == fingerprint) {
+            Some(idx) => idx as u32 + 1,
+            None => {
+                self.schemas.push(schema.clone());
+                self.schemas.len() as u32
+            }
+        };
+
+        let versions = self.subjects.entry(String::from(subject)).or_insert(Vec::new());
+        if !versions.contains(&id) {
+            versions.push(id);
+        }
+        Ok(id)
+    }
+
+    fn get_by_id(&mut self, id: u32) -> Result<Schema, Error> {
+        match self.schemas.get((id as usize).wrapping_sub(1)) {
+            Some(schema) => Ok(schema.clone()),
+            None => Err(Error::DecodeError(ErrorCode::UnknownSchemaId)),
+        }
+    }
+
+    fn latest(&mut self, subject: &str) -> Result<(u32, Schema), Error> {
+        let id = match self.subjects.get(subject).and_then(|versions| versions.last()) {
+            Some(id) => *id,
+            None => return Err(Error::DecodeError(ErrorCode::UnknownSubject)),
+        };
+        let schema = try!(self.get_by_id(id));
+        Ok((id, schema))
+    }
+}
+
+/// A registry kept in a directory, so it can be shared by checking it in or
+/// putting it on a shared drive. Schema n is stored as `schemas/n.avsc`, and
+/// `subjects/<subject>` lists the ids registered under a subject, oldest
+/// first, one per line. Subjects that aren't plain file names (empty, `.`,
+/// `..`, or with a path separator in them) are refused.
+pub struct FileSystemRegistry {
+    dir: PathBuf,
+    // A schema file never changes once written, so schemas read are kept,
+    // along with their fingerprints.
+    schemas: BTreeMap<u32, (Schema, u64)>,
+}
+
+impl FileSystemRegistry {
+    /// Opens the registry in `dir`, creating it if needs be.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<FileSystemRegistry, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        try!(fs::create_dir_all(dir.join("schemas")));
+        try!(fs::create_dir_all(dir.join("subjects")));
+        Ok(FileSystemRegistry { dir: dir, schemas: BTreeMap::new() })
+    }
+
+    fn schema_path(&self, id: u32) -> PathBuf {
+        self.dir.join("schemas").join(format!("{}.avsc", id))
+    }
+
+    fn subject_path(&self, subject: &str) -> Option<PathBuf> {
+        if subject.is_empty() || subject == "." || subject == ".." || subject.contains(|c: char| c == '/' || c == '\\' || c == '\0') {
+            return None;
+        }
+        Some(self.dir.join("subjects").join(subject))
+    }
+
+    // The schema stored as `id` and its fingerprint, read once.
+    fn load(&mut self, id: u32) -> Result<&(Schema, u64), Error> {
+        if !self.schemas.contains_key(&id) {
+            let path = self.schema_path(id);
+            if !path.exists() {
+                return Err(Error::DecodeError(ErrorCode::UnknownSchemaId));
+            }
+            let schema = try!(schema::from_str(&try!(read_file(&path))));
+            let fingerprint = schema.fingerprint();
+            self.schemas.insert(id, (schema, fingerprint));
+        }
+        Ok(&self.schemas[&id])
+    }
+
+    fn ids(&self) -> Result<Vec<u32>, Error> {
+        let mut ids = Vec::new();
+        for entry in try!(fs::read_dir(self.dir.join("schemas"))) {
+            let path = try!(entry).path();
+            if path.extension().map_or(false, |e| e == "avsc") {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok()) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn versions(&self, path: &Path) -> Result<Vec<u32>, Error> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = try!(read_file(path));
+        Ok(text.lines().filter_map(|line| line.trim().parse().ok()).collect())
+    }
+}
+
+impl SchemaRegistry for FileSystemRegistry {
+    fn register(&mut self, subject: &str, schema: &Schema) -> Result<u32, Error> {
+        let subject_path = match self.subject_path(subject) {
+            Some(path) => path,
+            None => return Err(Error::EncodeError(ErrorCode::InvalidSubject)),
+        };
+        let fingerprint = schema.fingerprint();
+        let ids = try!(self.ids());
+
+        let mut found = None;
+        for id in ids.iter() {
+            if try!(self.load(*id)).1 == fingerprint {
+                found = Some(*id);
+                break;
+            }
+        }
+        let id = match found {
+            Some(id) => id,
+            None => {
+                // Another registrant sharing the directory can take the
+                // next id first, so a schema file is only ever created and
+                // the next free id tried when it exists.
+                let mut id = ids.last().map_or(1, |id| id + 1);
+                let mut file = loop {
+                    match OpenOptions::new().write(true).create_new(true).open(self.schema_path(id)) {
+                        Ok(file) => break file,
+                        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => id += 1,
+                        Err(e) => return Err(Error::IoError(e)),
+                    }
+                };
+                try!(file.write_all(String::from(schema).as_bytes()));
+                self.schemas.insert(id, (schema.clone(), fingerprint));
+                id
+            }
+        };
+
+        if !try!(self.versions(&subject_path)).contains(&id) {
+            let mut file = try!(OpenOptions::new().create(true).append(true).open(&subject_path));
+            try!(writeln!(file, "{}", id));
+        }
+        Ok(id)
+    }
+
+    fn get_by_id(&mut self, id: u32) -> Result<Schema, Error> {
+        Ok(try!(self.load(id)).0.clone())
+    }
+
+    fn latest(&mut self, subject: &str) -> Result<(u32, Schema), Error> {
+        let subject_path = match self.subject_path(subject) {
+            Some(path) => path,
+            None => return Err(Error::DecodeError(ErrorCode::InvalidSubject)),
+        };
+        let id = match try!(self.versions(&subject_path)).last() {
+            Some(id) => *id,
+            None => return Err(Error::DecodeError(ErrorCode::UnknownSubject)),
+        };
+        let schema = try!(self.get_by_id(id));
+        Ok((id, schema))
+    }
+}
+
+pub fn encode_wire_format<W: Write>(id: u32, datum: &Datum, schema: &Schema, writer: &mut W) -> Result<(),Error> {
+    try!(writer.write_all(&[MAGIC, (id >> 24) as u8, (id >> 16) as u8, (id >> 8) as u8, id as u8]));
+    binary::encode(datum, schema, writer)
+}
+
+pub fn to_wire_format(id: u32, datum: &Datum, schema: &Schema) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    try!(encode_wire_format(id, datum, schema, &mut buf));
+    Ok(buf)
+}
+
+/// Reads a datum in the wire format, fetching the schema it was written with
+/// from `registry`, and resolves it against `reader_schema`. This works the
+/// writer schema out afresh each time; use a `WireFormatDecoder` to read
+/// more than a few datums.
+pub fn decode_wire_format<S: SchemaRegistry, R: Read>(registry: &mut S, reader_schema: &Schema, reader: &mut R) -> Result<Datum, Error> {
+    let id = try!(read_schema_id(reader));
+    let writer_schema = try!(registry.get_by_id(id));
+
+    if writer_schema.fingerprint() == reader_schema.fingerprint() {
+        binary::decode(&writer_schema, reader)
+    } else {
+        resolve::decode(&writer_schema, reader_schema, reader)
+    }
+}
+
+/// Reads datums in the wire format as one reader schema. Each writer schema
+/// is fetched, and compared with the reader schema, once per id.
+pub struct WireFormatDecoder<S: SchemaRegistry> {
+    registry: S,
+    reader_schema: Schema,
+    reader_fingerprint: u64,
+    // id -> (writer schema, whether it's the reader schema)
+    writers: BTreeMap<u32, (Schema, bool)>,
+}
+
+impl<S: SchemaRegistry> WireFormatDecoder<S> {
+    pub fn new(registry: S, reader_schema: Schema) -> WireFormatDecoder<S> {
+        WireFormatDecoder {
+            registry: registry,
+            reader_fingerprint: reader_schema.fingerprint(),
+            reader_schema: reader_schema,
+            writers: BTreeMap::new(),
+        }
+    }
+
+    pub fn registry(&mut self) -> &mut S {
+        &mut self.registry
+    }
+
+    pub fn decode<R: Read>(&mut self, reader: &mut R) -> Result<Datum, Error> {
+        let id = try!(read_schema_id(reader));
+        if !self.writers.contains_key(&id) {
+            let writer_schema = try!(self.registry.get_by_id(id));
+            let same = writer_schema.fingerprint() == self.reader_fingerprint;
+            self.writers.insert(id, (writer_schema, same));
+        }
+
+        let (ref writer_schema, same) = self.writers[&id];
+        if same {
+            binary::decode(writer_schema, reader)
+        } else {
+            resolve::decode(writer_schema, &self.reader_schema, reader)
+        }
+    }
+}
+
+/// Reads the magic byte and returns the writer schema's id.
+pub fn read_schema_id<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let header = try!(binary::read_exact(5, reader));
+    if header[0] != MAGIC {
+        return Err(Error::DecodeError(ErrorCode::BadWireFormatMagic));
+    }
+    Ok(header[1..].iter().fold(0u32, |id, b| (id << 8) | *b as u32))
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    let mut text = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut text));
+    Ok(text)
+}
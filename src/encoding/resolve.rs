@@ -0,0 +1,412 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Schema resolution: converting a datum read with the writer's schema into
+// the shape the reader's schema expects, following the "Schema Resolution"
+// rules of the spec.
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use serde::json::Value;
+
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+use schema::names::{short_name, Names, SchemaKind};
+
+use super::binary::{self, symbol_index, union_branch, Decoder};
+use super::json;
+
+/// Converts `datum`, written with `writer`, to a datum of the `reader` schema.
+pub fn resolve(datum: &Datum, writer: &Schema, reader: &Schema) -> Result<Datum, Error> {
+    let writer_names = Names::from_schema(writer);
+    let reader_names = Names::from_schema(reader);
+    Resolver::new(&writer_names, &reader_names)
+        .resolve(datum, &Value::from(writer), None, &Value::from(reader), None)
+}
+
+/// Reads a datum written with `writer` straight into the shape of `reader`.
+/// Writer fields the reader doesn't have are skipped rather than decoded, so
+/// a reader schema with only a few of the fields (see `Schema::project`)
+/// reads just those.
+pub fn decode<R: Read>(writer: &Schema, reader: &Schema, r: &mut R) -> Result<Datum, Error> {
+    let writer_names = Names::from_schema(writer);
+    let reader_names = Names::from_schema(reader);
+    Resolver::new(&writer_names, &reader_names)
+        .decode(&Value::from(writer), None, &Value::from(reader), None, r)
+}
+
+pub struct Resolver<'a> {
+    writer: &'a Names,
+    reader: &'a Names,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(writer: &'a Names, reader: &'a Names) -> Resolver<'a> {
+        Resolver { writer: writer, reader: reader }
+    }
+
+    pub fn resolve(&self, datum: &Datum, writer: &Value, writer_ns: Option<&str>,
+                   reader: &Value, reader_ns: Option<&str>) -> Result<Datum, Error> {
+        let (writer_kind, writer_ns) = try!(self.writer.kind(writer, writer_ns));
+        let writer_ns = writer_ns.as_ref().map(|s| s.as_ref());
+
+        // A writer's union is resolved by the branch the datum was written as.
+        if let SchemaKind::Union(branches) = writer_kind {
+            return match *datum {
+                Datum::Union(idx, ref inner) if idx < branches.len() => {
+                    self.resolve(inner, &branches[idx], writer_ns, reader, reader_ns)
+                },
+                Datum::Union(..) => Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange)),
+                _ => {
+                    match union_branch(self.writer, datum, branches, writer_ns) {
+                        Some(idx) => self.resolve(datum, &branches[idx], writer_ns, reader, reader_ns),
+                        None => Err(Error::DecodeError(ErrorCode::UnionBranchNotFound)),
+                    }
+                }
+            };
+        }
+
+        let (reader_kind, reader_ns) = try!(self.reader.kind(reader, reader_ns));
+        let reader_ns = reader_ns.as_ref().map(|s| s.as_ref());
+        let mismatch = Err(Error::DecodeError(ErrorCode::SchemaResolutionFailed));
+
+        match (writer_kind, reader_kind) {
+            (ref writer_kind, SchemaKind::Union(branches)) => {
+                match self.reader_branch(writer_kind, branches, reader_ns) {
+                    Some(idx) => {
+                        let resolved = try!(self.resolve(datum, writer, writer_ns, &branches[idx], reader_ns));
+                        Ok(Datum::Union(idx, Box::new(resolved)))
+                    },
+                    None => mismatch,
+                }
+            },
+            (SchemaKind::Primitive(_), SchemaKind::Primitive(reader_type)) => promote(datum, reader_type),
+            (SchemaKind::Record(writer_def), SchemaKind::Record(reader_def)) => {
+                if !names_match(writer_def, reader_def) {
+                    return mismatch;
+                }
+                let writer_fields = match writer_def.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                let reader_fields = match reader_def.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+
+                // Fields are matched by name (or a reader field's alias). The
+                // writer's other fields are dropped, and the reader's other
+                // fields take their defaults.
+                let mut record = Vec::with_capacity(reader_fields.len());
+                for reader_field in reader_fields.iter() {
+                    let name = reader_field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let reader_type = match reader_field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+
+                    let writer_field = writer_fields.iter().find(|f| {
+                        let writer_name = f.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                        writer_name == name || has_alias(reader_field, writer_name)
+                    });
+                    let value = match writer_field {
+                        Some(writer_field) => {
+                            let writer_name = writer_field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                            let writer_type = match writer_field.find("type") {
+                                Some(t) => t,
+                                None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                            };
+                            match datum.field(writer_name) {
+                                Some(value) => try!(self.resolve(value, writer_type, writer_ns, reader_type, reader_ns)),
+                                None => return Err(Error::DecodeError(ErrorCode::MissingFieldValue)),
+                            }
+                        },
+                        None => {
+                            match reader_field.find("default") {
+                                Some(default) => try!(json::Decoder::new(self.reader).decode_default(default, reader_type, reader_ns)),
+                                None => return Err(Error::DecodeError(ErrorCode::MissingFieldValue)),
+                            }
+                        },
+                    };
+                    record.push((String::from(name), value));
+                }
+                Ok(Datum::Record(record))
+            },
+            (SchemaKind::Enum(writer_def), SchemaKind::Enum(reader_def)) => {
+                if !names_match(writer_def, reader_def) {
+                    return mismatch;
+                }
+                match *datum {
+                    Datum::Enum(ref symbol) if symbol_index(reader_def, symbol).is_some() => Ok(Datum::Enum(symbol.clone())),
+                    // A symbol the reader doesn't know becomes its default, if it has one.
+                    Datum::Enum(_) => {
+                        match reader_def.find("default") {
+                            Some(&Value::String(ref symbol)) => Ok(Datum::Enum(symbol.clone())),
+                            _ => Err(Error::DecodeError(ErrorCode::EnumSymbolNotFound)),
+                        }
+                    },
+                    _ => Err(Error::DecodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            (SchemaKind::Fixed(writer_def), SchemaKind::Fixed(reader_def)) => {
+                if !names_match(writer_def, reader_def) || writer_def.find("size") != reader_def.find("size") {
+                    return mismatch;
+                }
+                Ok(datum.clone())
+            },
+            (SchemaKind::Array(writer_items), SchemaKind::Array(reader_items)) => {
+                match *datum {
+                    Datum::Array(ref vec) => {
+                        let mut array = Vec::with_capacity(vec.len());
+                        for item in vec.iter() {
+                            array.push(try!(self.resolve(item, writer_items, writer_ns, reader_items, reader_ns)));
+                        }
+                        Ok(Datum::Array(array))
+                    },
+                    _ => Err(Error::DecodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            (SchemaKind::Map(writer_values), SchemaKind::Map(reader_values)) => {
+                match *datum {
+                    Datum::Map(ref map) => {
+                        let mut resolved = BTreeMap::new();
+                        for (key, value) in map.iter() {
+                            resolved.insert(key.clone(), try!(self.resolve(value, writer_values, writer_ns, reader_values, reader_ns)));
+                        }
+                        Ok(Datum::Map(resolved))
+                    },
+                    _ => Err(Error::DecodeError(ErrorCode::DatumSchemaMismatch)),
+                }
+            },
+            _ => mismatch,
+        }
+    }
+
+    /// The streaming counterpart of `resolve`: reads the writer's encoding
+    /// and produces the reader's datum without decoding whole records first.
+    pub fn decode<R: Read>(&self, writer: &Value, writer_ns: Option<&str>,
+                           reader: &Value, reader_ns: Option<&str>, r: &mut R) -> Result<Datum, Error> {
+        let (writer_kind, writer_ns) = try!(self.writer.kind(writer, writer_ns));
+        let writer_ns = writer_ns.as_ref().map(|s| s.as_ref());
+
+        if let SchemaKind::Union(branches) = writer_kind {
+            let idx = try!(binary::read_long(r));
+            if idx < 0 || idx as usize >= branches.len() {
+                return Err(Error::DecodeError(ErrorCode::UnionIndexOutOfRange));
+            }
+            return self.decode(&branches[idx as usize], writer_ns, reader, reader_ns, r);
+        }
+
+        let (reader_kind, reader_ns) = try!(self.reader.kind(reader, reader_ns));
+        let reader_ns = reader_ns.as_ref().map(|s| s.as_ref());
+        let mismatch = Err(Error::DecodeError(ErrorCode::SchemaResolutionFailed));
+
+        match (writer_kind, reader_kind) {
+            (ref writer_kind, SchemaKind::Union(branches)) => {
+                match self.reader_branch(writer_kind, branches, reader_ns) {
+                    Some(idx) => {
+                        let resolved = try!(self.decode(writer, writer_ns, &branches[idx], reader_ns, r));
+                        Ok(Datum::Union(idx, Box::new(resolved)))
+                    },
+                    None => mismatch,
+                }
+            },
+            (SchemaKind::Primitive(writer_type), SchemaKind::Primitive(reader_type)) => {
+                promote(&try!(binary::decode_primitive(writer_type, r)), reader_type)
+            },
+            (SchemaKind::Record(writer_def), SchemaKind::Record(reader_def)) => {
+                if !names_match(writer_def, reader_def) {
+                    return mismatch;
+                }
+                let writer_fields = match writer_def.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+                let reader_fields = match reader_def.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+
+                // The data comes in the writer's field order; each field is
+                // either read into the reader field it matches or skipped.
+                let mut values = BTreeMap::new();
+                for writer_field in writer_fields.iter() {
+                    let writer_name = writer_field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let writer_type = match writer_field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+                    let reader_field = reader_fields.iter().enumerate().find(|&(_, f)| {
+                        let name = f.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                        writer_name == name || has_alias(f, writer_name)
+                    });
+                    match reader_field {
+                        Some((idx, reader_field)) => {
+                            let reader_type = match reader_field.find("type") {
+                                Some(t) => t,
+                                None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                            };
+                            let value = try!(self.decode(writer_type, writer_ns, reader_type, reader_ns, r));
+                            values.insert(idx, value);
+                        },
+                        None => try!(Decoder::new(self.writer).skip(writer_type, writer_ns, r)),
+                    }
+                }
+
+                let mut record = Vec::with_capacity(reader_fields.len());
+                for (idx, reader_field) in reader_fields.iter().enumerate() {
+                    let name = reader_field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let value = match values.remove(&idx) {
+                        Some(value) => value,
+                        None => {
+                            let reader_type = match reader_field.find("type") {
+                                Some(t) => t,
+                                None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                            };
+                            match reader_field.find("default") {
+                                Some(default) => try!(json::Decoder::new(self.reader).decode_default(default, reader_type, reader_ns)),
+                                None => return Err(Error::DecodeError(ErrorCode::MissingFieldValue)),
+                            }
+                        },
+                    };
+                    record.push((String::from(name), value));
+                }
+                Ok(Datum::Record(record))
+            },
+            (SchemaKind::Array(writer_items), SchemaKind::Array(reader_items)) => {
+                let mut array = Vec::new();
+                loop {
+                    let count = try!(binary::read_block_count(r));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        array.push(try!(self.decode(writer_items, writer_ns, reader_items, reader_ns, r)));
+                    }
+                }
+                Ok(Datum::Array(array))
+            },
+            (SchemaKind::Map(writer_values), SchemaKind::Map(reader_values)) => {
+                let mut map = BTreeMap::new();
+                loop {
+                    let count = try!(binary::read_block_count(r));
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        let key = try!(binary::read_string(r));
+                        map.insert(key, try!(self.decode(writer_values, writer_ns, reader_values, reader_ns, r)));
+                    }
+                }
+                Ok(Datum::Map(map))
+            },
+            // Enums and fixed are small; read them whole and resolve.
+            (SchemaKind::Enum(_), SchemaKind::Enum(_)) | (SchemaKind::Fixed(_), SchemaKind::Fixed(_)) => {
+                let datum = try!(Decoder::new(self.writer).decode(writer, writer_ns, r));
+                self.resolve(&datum, writer, writer_ns, reader, reader_ns)
+            },
+            _ => mismatch,
+        }
+    }
+
+    // The reader's union branch a writer's type resolves to: the first one of
+    // the same type, failing that the first one it can be promoted to.
+    pub fn reader_branch(&self, writer_kind: &SchemaKind, branches: &Vec<Value>, reader_ns: Option<&str>) -> Option<usize> {
+        for exact in [true, false].iter() {
+            for (idx, branch) in branches.iter().enumerate() {
+                let reader_kind = match self.reader.kind(branch, reader_ns) {
+                    Ok((kind, _)) => kind,
+                    Err(_) => continue,
+                };
+                if kinds_match(writer_kind, &reader_kind, *exact) {
+                    return Some(idx);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn kinds_match(writer: &SchemaKind, reader: &SchemaKind, exact: bool) -> bool {
+    match (writer, reader) {
+        (&SchemaKind::Primitive(w), &SchemaKind::Primitive(r)) => w == r || (!exact && can_promote(w, r)),
+        (&SchemaKind::Record(w), &SchemaKind::Record(r)) |
+        (&SchemaKind::Enum(w), &SchemaKind::Enum(r)) |
+        (&SchemaKind::Fixed(w), &SchemaKind::Fixed(r)) => names_match(w, r),
+        (&SchemaKind::Array(_), &SchemaKind::Array(_)) |
+        (&SchemaKind::Map(_), &SchemaKind::Map(_)) => true,
+        _ => false,
+    }
+}
+
+/// Whether a value of primitive type `writer_type` can be read as
+/// `reader_type`.
+pub fn can_promote(writer_type: &str, reader_type: &str) -> bool {
+    match (writer_type, reader_type) {
+        ("int", "long") | ("int", "float") | ("int", "double") |
+        ("long", "float") | ("long", "double") |
+        ("float", "double") |
+        ("string", "bytes") | ("bytes", "string") => true,
+        _ => false,
+    }
+}
+
+fn promote(datum: &Datum, reader_type: &str) -> Result<Datum, Error> {
+    match (datum, reader_type) {
+        (&Datum::Null, "null") | (&Datum::Boolean(_), "boolean") |
+        (&Datum::Int(_), "int") | (&Datum::Long(_), "long") |
+        (&Datum::Float(_), "float") | (&Datum::Double(_), "double") |
+        (&Datum::Bytes(_), "bytes") | (&Datum::String(_), "string") => Ok(datum.clone()),
+        (&Datum::Int(n), "long") => Ok(Datum::Long(n as i64)),
+        (&Datum::Int(n), "float") => Ok(Datum::Float(n as f32)),
+        (&Datum::Int(n), "double") => Ok(Datum::Double(n as f64)),
+        (&Datum::Long(n), "float") => Ok(Datum::Float(n as f32)),
+        (&Datum::Long(n), "double") => Ok(Datum::Double(n as f64)),
+        (&Datum::Float(n), "double") => Ok(Datum::Double(n as f64)),
+        (&Datum::String(ref s), "bytes") => Ok(Datum::Bytes(s.clone().into_bytes())),
+        (&Datum::Bytes(ref b), "string") => {
+            match String::from_utf8(b.clone()) {
+                Ok(s) => Ok(Datum::String(s)),
+                Err(_) => Err(Error::DecodeError(ErrorCode::InvalidUtf8)),
+            }
+        },
+        _ => Err(Error::DecodeError(ErrorCode::SchemaResolutionFailed)),
+    }
+}
+
+// Named types match on their unqualified names, or on one of the reader's
+// aliases for the type.
+pub fn names_match(writer: &Value, reader: &Value) -> bool {
+    let writer_name = short_name(writer);
+    if writer_name == short_name(reader) {
+        return true;
+    }
+    match writer.find("name").and_then(|n| n.as_string()) {
+        Some(full) => has_alias(reader, full) || has_alias(reader, &writer_name),
+        None => false,
+    }
+}
+
+/// Whether a named type or field lists `name`, full or short, as an alias.
+pub fn has_alias(value: &Value, name: &str) -> bool {
+    match value.find("aliases") {
+        Some(&Value::Array(ref aliases)) => {
+            aliases.iter().any(|a| {
+                a.as_string().map_or(false, |a| a == name || a.rsplit('.').next() == Some(name))
+            })
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Single object encoding: a two byte marker, the CRC-64-AVRO fingerprint of
+// the writer's schema (little-endian), then the datum in the binary encoding.
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+
+use super::binary;
+use super::resolve;
+
+pub const MARKER: [u8; 2] = [0xc3, 0x01];
+
+/// Somewhere to look up writers' schemas by fingerprint.
+pub trait SchemaStore {
+    fn find_by_fingerprint(&self, fingerprint: u64) -> Option<&Schema>;
+}
+
+/// A schema store that keeps its schemas in memory.
+pub struct InMemorySchemaStore {
+    schemas: BTreeMap<u64, Schema>,
+}
+
+impl InMemorySchemaStore {
+    pub fn new() -> InMemorySchemaStore {
+        InMemorySchemaStore { schemas: BTreeMap::new() }
+    }
+
+    /// Adds a schema to the store, returning its fingerprint.
+    pub fn add(&mut self, schema: Schema) -> u64 {
+        let fingerprint = schema.fingerprint();
+        self.schemas.insert(fingerprint, schema);
+        fingerprint
+    }
+
+    pub fn len(&self) -> usize {
+        self.schemas.len()
+    }
+}
+
+impl SchemaStore for InMemorySchemaStore {
+    fn find_by_fingerprint(&self, fingerprint: u64) -> Option<&Schema> {
+        self.schemas.get(&fingerprint)
+    }
+}
+
+pub fn encode_single_object<W: Write>(datum: &Datum, schema: &Schema, writer: &mut W) -> Result<(),Error> {
+    try!(writer.write_all(&MARKER));
+    try!(writer.write_all(&fingerprint_bytes(schema.fingerprint())));
+    binary::encode(datum, schema, writer)
+}
+
+pub fn to_single_object(datum: &Datum, schema: &Schema) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    try!(encode_single_object(datum, schema, &mut buf));
+    Ok(buf)
+}
+
+/// Reads a single object, looking up the schema it was written with in
+/// `store`, and resolves it against `reader_schema`.
+pub fn decode_single_object<S: SchemaStore, R: Read>(store: &S, reader_schema: &Schema, reader: &mut R) -> Result<Datum, Error> {
+    let fingerprint = try!(read_header(reader));
+    let writer_schema = match store.find_by_fingerprint(fingerprint) {
+        Some(schema) => schema,
+        None => return Err(Error::DecodeError(ErrorCode::UnknownFingerprint)),
+    };
+
+    if writer_schema.fingerprint() == reader_schema.fingerprint() {
+        binary::decode(writer_schema, reader)
+    } else {
+        resolve::decode(writer_schema, reader_schema, reader)
+    }
+}
+
+/// Reads the marker and returns the writer's schema fingerprint.
+pub fn read_header<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let marker = try!(binary::read_exact(2, reader));
+    if marker[..] != MARKER[..] {
+        return Err(Error::DecodeError(ErrorCode::BadSingleObjectMarker));
+    }
+
+    let bytes = try!(binary::read_exact(8, reader));
+    Ok(bytes.iter().rev().fold(0u64, |fp, b| (fp << 8) | *b as u64))
+}
+
+fn fingerprint_bytes(fingerprint: u64) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    for i in 0..8 {
+        bytes[i] = (fingerprint >> (8 * i)) as u8;
+    }
+    bytes
+}
@@ -0,0 +1,264 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Schemas inferred from sample JSON documents. Every sample is folded into a
+// Shape, which keeps at most one of each kind of JSON value it has seen, so
+// that conflicting samples become a union with one branch per kind. Numbers
+// widen from int to long to double, and a field that's null or missing in
+// any sample gets a null branch.
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::json::Value;
+
+use schema::{ArrayBuilder, MapBuilder, RecordBuilder, Schema, UnionBuilder};
+use schema::names::{sanitize_name, unique_name};
+
+/// How `from_json_samples_with` names and shapes what it infers.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The name of the record for the samples themselves. Nested records are
+    /// named after the fields they're found in.
+    pub name: String,
+    /// The namespace of the top-level record.
+    pub namespace: Option<String>,
+    /// Objects with more distinct keys than this, across all samples, become
+    /// maps rather than records.
+    pub map_threshold: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            name: String::from("Record"),
+            namespace: None,
+            map_threshold: None,
+        }
+    }
+}
+
+/// A schema that every one of `samples` fits, using the default options.
+///
+/// Keys that aren't valid Avro names have their other characters replaced
+/// with underscores, so such samples need the same done before they can be
+/// written with the schema.
+pub fn from_json_samples<I, V>(samples: I) -> Schema where
+    I: IntoIterator<Item = V>,
+    V: Borrow<Value>
+{
+    from_json_samples_with(samples, &Options::default())
+}
+
+pub fn from_json_samples_with<I, V>(samples: I, options: &Options) -> Schema where
+    I: IntoIterator<Item = V>,
+    V: Borrow<Value>
+{
+    let mut shape = Shape::default();
+    for sample in samples {
+        shape.add(sample.borrow());
+    }
+
+    let mut builder = Builder { options: options, names: BTreeSet::new() };
+    builder.schema(&shape, &options.name, options.namespace.as_ref().map(|ns| &ns[..]))
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+enum Number {
+    Int,
+    Long,
+    Double,
+}
+
+// Everything seen in one place across the samples.
+#[derive(Clone, Default, Debug)]
+struct Shape {
+    null: bool,
+    boolean: bool,
+    number: Option<Number>,
+    string: bool,
+    // Items, which stay empty if every array seen was.
+    array: Option<Box<Shape>>,
+    object: Option<BTreeMap<String, Field>>,
+}
+
+#[derive(Clone, Default, Debug)]
+struct Field {
+    shape: Shape,
+    // Whether some object seen didn't have this key.
+    missing: bool,
+}
+
+impl Shape {
+    fn add(&mut self, value: &Value) {
+        match *value {
+            Value::Null => self.null = true,
+            Value::Bool(_) => self.boolean = true,
+            Value::I64(n) => self.add_number(if n as i32 as i64 == n { Number::Int } else { Number::Long }),
+            Value::U64(n) => {
+                self.add_number(match n {
+                    n if n <= i32::max_value() as u64 => Number::Int,
+                    n if n <= i64::max_value() as u64 => Number::Long,
+                    _ => Number::Double,
+                });
+            },
+            Value::F64(_) => self.add_number(Number::Double),
+            Value::String(_) => self.string = true,
+            Value::Array(ref items) => {
+                let shape = self.array.get_or_insert_with(|| Box::new(Shape::default()));
+                for item in items.iter() {
+                    shape.add(item);
+                }
+            },
+            Value::Object(ref map) => {
+                let first = self.object.is_none();
+                let fields = self.object.get_or_insert_with(BTreeMap::new);
+                for (key, field) in fields.iter_mut() {
+                    if !map.contains_key(key) {
+                        field.missing = true;
+                    }
+                }
+                for (key, value) in map.iter() {
+                    let field = fields.entry(key.clone()).or_insert_with(|| Field { shape: Shape::default(), missing: !first });
+                    field.shape.add(value);
+                }
+            },
+        }
+    }
+
+    fn add_number(&mut self, number: Number) {
+        if self.number.map_or(true, |seen| number > seen) {
+            self.number = Some(number);
+        }
+    }
+
+    // Folds in everything seen in another place, for map values.
+    fn merge(&mut self, other: &Shape) {
+        self.null |= other.null;
+        self.boolean |= other.boolean;
+        if let Some(number) = other.number {
+            self.add_number(number);
+        }
+        self.string |= other.string;
+        if let Some(ref items) = other.array {
+            self.array.get_or_insert_with(|| Box::new(Shape::default())).merge(items);
+        }
+        if let Some(ref other_fields) = other.object {
+            let first = self.object.is_none();
+            let fields = self.object.get_or_insert_with(BTreeMap::new);
+            for (key, field) in fields.iter_mut() {
+                if !other_fields.contains_key(key) {
+                    field.missing = true;
+                }
+            }
+            for (key, other_field) in other_fields.iter() {
+                let field = fields.entry(key.clone()).or_insert_with(|| Field { shape: Shape::default(), missing: !first });
+                field.missing |= other_field.missing;
+                field.shape.merge(&other_field.shape);
+            }
+        }
+    }
+}
+
+struct Builder<'a> {
+    options: &'a Options,
+    // Record names already taken.
+    names: BTreeSet<String>,
+}
+
+impl<'a> Builder<'a> {
+    // The schema for a shape. Records found in it are named after `name`.
+    fn schema(&mut self, shape: &Shape, name: &str, namespace: Option<&str>) -> Schema {
+        let mut branches = Vec::new();
+        if shape.null {
+            branches.push(Schema::String(String::from("null")));
+        }
+        if shape.boolean {
+            branches.push(Schema::String(String::from("boolean")));
+        }
+        if let Some(number) = shape.number {
+            let type_name = match number {
+                Number::Int => "int",
+                Number::Long => "long",
+                Number::Double => "double",
+            };
+            branches.push(Schema::String(String::from(type_name)));
+        }
+        if shape.string {
+            branches.push(Schema::String(String::from("string")));
+        }
+        if let Some(ref items) = shape.array {
+            let items = self.schema(items, name, None);
+            branches.push(ArrayBuilder::new().items(items).unwrap());
+        }
+        if let Some(ref fields) = shape.object {
+            branches.push(self.object(fields, name, namespace));
+        }
+
+        // Nothing seen at all, as with the items of arrays that were always
+        // empty, can only be null.
+        match branches.len() {
+            0 => Schema::String(String::from("null")),
+            1 => branches.pop().unwrap(),
+            _ => branches.into_iter().fold(UnionBuilder::new(), |union, branch| union.push_schema(branch)).unwrap(),
+        }
+    }
+
+    fn object(&mut self, fields: &BTreeMap<String, Field>, name: &str, namespace: Option<&str>) -> Schema {
+        if self.options.map_threshold.map_or(false, |threshold| fields.len() > threshold) {
+            let mut values = Shape::default();
+            for field in fields.values() {
+                values.merge(&field.shape);
+            }
+            let values = self.schema(&values, name, None);
+            return MapBuilder::new().values(values).unwrap();
+        }
+
+        let record_name = self.record_name(name);
+        let mut field_names = BTreeSet::new();
+        let mut built = Vec::new();
+        for (key, field) in fields.iter() {
+            let field_name = unique_name(&sanitize_name(key), &mut field_names);
+            let mut shape = field.shape.clone();
+            shape.null |= field.missing;
+            let field_type = self.schema(&shape, &field_name, None);
+            built.push((field_name, field_type, shape.null));
+        }
+
+        let record = RecordBuilder::new().name(&record_name).fields(|mut array| {
+            for (field_name, field_type, nullable) in built.into_iter() {
+                // Null comes first in a nullable field's union, so it can be the default.
+                array = array.push(|f| {
+                    let f = f.name(&field_name).field_type(field_type);
+                    if nullable { f.default(Value::Null) } else { f }
+                });
+            }
+            array
+        });
+        match namespace {
+            Some(ns) => record.namespace(ns).unwrap(),
+            None => record.unwrap(),
+        }
+    }
+
+    // A record name after `name`, capitalized, and not yet used.
+    fn record_name(&mut self, name: &str) -> String {
+        let name = sanitize_name(name);
+        let mut chars = name.chars();
+        let capitalized: String = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => name.clone(),
+        };
+        unique_name(&capitalized, &mut self.names)
+    }
+}
@@ -15,7 +15,27 @@
 #[macro_use]
 extern crate log;
 
+extern crate flate2;
+extern crate md5;
+extern crate rand;
 extern crate serde;
 extern crate regex;
 
-pub mod schema;
\ No newline at end of file
+#[cfg(feature = "arrow")]
+extern crate arrow as arrow_rs;
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "async")]
+extern crate tokio;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod compare;
+pub mod container;
+pub mod datum;
+pub mod encoding;
+pub mod infer;
+pub mod protocol;
+pub mod random;
+pub mod rpc;
+pub mod schema;
@@ -0,0 +1,595 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use md5;
+use serde::json::{self, Value};
+
+use schema::Schema;
+use schema::error::*;
+use schema::idl::IdlWriter;
+use schema::names::Names;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Protocol {
+    pub protocol: String,
+    pub namespace: Option<String>,
+    pub doc: Option<String>,
+    pub types: Vec<Schema>,
+    /// The messages by name, in the order they're declared.
+    pub messages: Vec<(String, Message)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Message {
+    pub doc: Option<String>,
+    /// The parameters, in the same form as record field definitions.
+    pub request: Vec<Value>,
+    pub response: Schema,
+    /// Declared error types. The implicit "string" error isn't listed.
+    pub errors: Vec<Schema>,
+    pub one_way: bool,
+}
+
+impl Protocol {
+    pub fn new(name: &str) -> Protocol {
+        Protocol {
+            protocol: String::from(name),
+            namespace: None,
+            doc: None,
+            types: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Reads a protocol from its JSON. The messages come out sorted by name,
+    /// as JSON objects are read into sorted maps; `from_str` keeps the order
+    /// they're declared in.
+    pub fn from_value(value: &Value) -> Result<Protocol, Error> {
+        if !value.is_object() {
+            return Err(Error::SyntaxError(ErrorCode::NotValidProtocol, 0, 0));
+        }
+
+        let mut protocol = match value.find("protocol") {
+            Some(&Value::String(ref name)) => Protocol::new(name),
+            _ => return Err(Error::MissingAttributeError("protocol")),
+        };
+
+        if let Some(&Value::String(ref ns)) = value.find("namespace") {
+            protocol.namespace = Some(ns.clone());
+        }
+        if let Some(&Value::String(ref doc)) = value.find("doc") {
+            protocol.doc = Some(doc.clone());
+        }
+
+        match value.find("types") {
+            Some(&Value::Array(ref types)) => {
+                protocol.types = types.iter().map(|t| Schema::from(t)).collect();
+            },
+            Some(_) => return Err(Error::SyntaxError(ErrorCode::NotValidProtocol, 0, 0)),
+            None => (),
+        }
+
+        match value.find("messages") {
+            Some(&Value::Object(ref messages)) => {
+                for (name, message) in messages.iter() {
+                    let message = try!(Message::from_value(message));
+                    protocol.messages.push((name.clone(), message));
+                }
+            },
+            Some(_) => return Err(Error::SyntaxError(ErrorCode::NotValidProtocol, 0, 0)),
+            None => (),
+        }
+
+        Ok(protocol)
+    }
+
+    pub fn fullname(&self) -> String {
+        match self.namespace {
+            Some(ref ns) if !ns.is_empty() && !self.protocol.contains(".") => {
+                format!("{}.{}", ns, self.protocol)
+            },
+            _ => self.protocol.clone(),
+        }
+    }
+
+    pub fn message(&self, name: &str) -> Option<&Message> {
+        self.messages.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref message)| message)
+    }
+
+    /// Looks up one of the protocol's types by its full name, or by its short
+    /// name if it lives in the protocol namespace.
+    pub fn find_type(&self, name: &str) -> Option<&Schema> {
+        let fullname = self.qualify(name);
+        self.types.iter().find(|t| self.type_fullname(t).ok() == Some(fullname.clone()))
+    }
+
+    /// The named types defined by the protocol, for following type references
+    /// in messages.
+    pub fn names(&self) -> Names {
+        let mut names = Names::new();
+        for schema in self.types.iter() {
+            names.add(schema, self.namespace.as_ref().map(|ns| ns.as_ref()));
+        }
+        names
+    }
+
+    /// The protocol's MD5 hash, as exchanged in the RPC handshake: the hash
+    /// of its string form, which is written the way Java writes it.
+    pub fn md5(&self) -> [u8; 16] {
+        let digest = md5::compute(String::from(self).as_bytes());
+        let mut hash = [0u8; 16];
+        for (i, b) in digest.iter().enumerate() {
+            hash[i] = *b;
+        }
+        hash
+    }
+
+    pub fn is_valid(&self) -> Result<(),Error> {
+        debug!("is_valid({:?})", self);
+
+        let mut defined = BTreeSet::new();
+        for schema in self.types.iter() {
+            // Every type in a protocol has to be named, and must only be
+            // defined once.
+            let fullname = try!(self.type_fullname(schema));
+            try!(schema.is_valid());
+
+            if !defined.insert(fullname) {
+                return Err(Error::SyntaxError(ErrorCode::DuplicateNamedType, 0, 0));
+            }
+        }
+
+        for &(ref name, ref message) in self.messages.iter() {
+            try!(message.is_valid(name));
+        }
+
+        Ok(())
+    }
+
+    /// Renders the protocol as Avro IDL.
+    pub fn to_idl(&self) -> String {
+        let mut writer = IdlWriter::new(self.namespace.clone());
+        for schema in self.types.iter() {
+            writer.collect(schema);
+        }
+
+        let messages: Vec<String> = self.messages.iter()
+            .map(|&(ref name, ref message)| writer.message(name, message))
+            .collect();
+
+        writer.protocol(&self.protocol, self.doc.as_ref(), &messages)
+    }
+
+    fn type_fullname(&self, schema: &Schema) -> Result<String, Error> {
+        match *schema {
+            Schema::Object(ref value) => {
+                match value.find("type") {
+                    Some(&Value::String(ref t)) if t == "record" || t == "error" || t == "enum" || t == "fixed" => (),
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedNamedType, 0, 0)),
+                }
+
+                let fullname = try!(schema.fullname().map_err(|_|
+                    Error::SyntaxError(ErrorCode::NotWellFormedName, 0, 0)
+                ));
+
+                if value.find("namespace").is_some() {
+                    Ok(fullname)
+                } else {
+                    Ok(self.qualify(&fullname))
+                }
+            },
+            _ => Err(Error::SyntaxError(ErrorCode::ExpectedNamedType, 0, 0)),
+        }
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        match self.namespace {
+            Some(ref ns) if !ns.is_empty() && !name.contains(".") => format!("{}.{}", ns, name),
+            _ => String::from(name),
+        }
+    }
+}
+
+impl Message {
+    pub fn from_value(value: &Value) -> Result<Message, Error> {
+        if !value.is_object() {
+            return Err(Error::SyntaxError(ErrorCode::NotValidMessage, 0, 0));
+        }
+
+        let request = match value.find("request") {
+            Some(&Value::Array(ref params)) => params.clone(),
+            Some(_) => return Err(Error::SyntaxError(ErrorCode::NotValidMessage, 0, 0)),
+            None => return Err(Error::MissingAttributeError("request")),
+        };
+
+        let response = match value.find("response") {
+            Some(response) => Schema::from(response),
+            None => return Err(Error::MissingAttributeError("response")),
+        };
+
+        let errors = match value.find("errors") {
+            Some(&Value::Array(ref errors)) => errors.iter().map(|e| Schema::from(e)).collect(),
+            Some(_) => return Err(Error::SyntaxError(ErrorCode::NotValidMessage, 0, 0)),
+            None => Vec::new(),
+        };
+
+        let one_way = match value.find("one-way") {
+            Some(&Value::Bool(b)) => b,
+            Some(_) => return Err(Error::SyntaxError(ErrorCode::NotValidMessage, 0, 0)),
+            None => false,
+        };
+
+        Ok(Message {
+            doc: value.find("doc").and_then(|d| d.as_string()).map(|d| String::from(d)),
+            request: request,
+            response: response,
+            errors: errors,
+            one_way: one_way,
+        })
+    }
+
+    /// The request parameters as the record schema they're encoded with.
+    pub fn request_schema(&self, message_name: &str) -> Schema {
+        let mut record = BTreeMap::new();
+        record.insert(String::from("type"), Value::String(String::from("record")));
+        record.insert(String::from("name"), Value::String(String::from(message_name)));
+        record.insert(String::from("fields"), Value::Array(self.request.clone()));
+        Schema::Object(Value::Object(record))
+    }
+
+    /// The union the response is encoded with when the call fails: the
+    /// implicit "string" error followed by the declared errors.
+    pub fn errors_schema(&self) -> Schema {
+        let mut errors = vec![Schema::String(String::from("string"))];
+        errors.extend(self.errors.iter().cloned());
+        Schema::Union(errors)
+    }
+
+    fn is_valid(&self, name: &str) -> Result<(),Error> {
+        // The parameters follow the same rules as record fields, so
+        // validate them as one.
+        try!(self.request_schema(name).is_valid());
+        try!(self.response.is_valid());
+        // The errors are checked as the union they're encoded with, so a
+        // declared error can't repeat the implicit "string" branch.
+        try!(self.errors_schema().is_valid());
+
+        if self.one_way {
+            let null_response = match self.response {
+                Schema::String(ref s) => s == "null",
+                Schema::Null => true,
+                _ => false,
+            };
+            if !null_response || !self.errors.is_empty() {
+                return Err(Error::SyntaxError(ErrorCode::OneWayMessageHasResponse, 0, 0));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Protocol, Error> {
+        let value: Value = try!(json::from_str(s));
+        let mut protocol = try!(Protocol::from_value(&value));
+        let order = declared_order(s);
+        protocol.messages.sort_by_key(|&(ref name, _)| order.iter().position(|n| n == name));
+        try!(protocol.is_valid());
+        Ok(protocol)
+    }
+}
+
+impl<'a> From<&'a Message> for Value {
+    fn from(m: &'a Message) -> Value {
+        let mut message = BTreeMap::new();
+        if let Some(ref doc) = m.doc {
+            message.insert(String::from("doc"), Value::String(doc.clone()));
+        }
+        message.insert(String::from("request"), Value::Array(m.request.clone()));
+        message.insert(String::from("response"), Value::from(&m.response));
+        if !m.errors.is_empty() {
+            let errors = m.errors.iter().map(|e| Value::from(e)).collect();
+            message.insert(String::from("errors"), Value::Array(errors));
+        }
+        if m.one_way {
+            message.insert(String::from("one-way"), Value::Bool(true));
+        }
+        Value::Object(message)
+    }
+}
+
+/// The protocol as a JSON value. Its "messages" object is a sorted map like
+/// any other, so the declaration order is lost; the protocol's string form
+/// keeps it.
+impl<'a> From<&'a Protocol> for Value {
+    fn from(p: &'a Protocol) -> Value {
+        let mut protocol = BTreeMap::new();
+        protocol.insert(String::from("protocol"), Value::String(p.protocol.clone()));
+        if let Some(ref ns) = p.namespace {
+            protocol.insert(String::from("namespace"), Value::String(ns.clone()));
+        }
+        if let Some(ref doc) = p.doc {
+            protocol.insert(String::from("doc"), Value::String(doc.clone()));
+        }
+
+        let types = p.types.iter().map(|t| Value::from(t)).collect();
+        protocol.insert(String::from("types"), Value::Array(types));
+
+        let mut messages = BTreeMap::new();
+        for &(ref name, ref message) in p.messages.iter() {
+            messages.insert(name.clone(), Value::from(message));
+        }
+        protocol.insert(String::from("messages"), Value::Object(messages));
+
+        Value::Object(protocol)
+    }
+}
+
+/// The protocol's compact JSON, written the way the Java implementation's
+/// `Protocol.toString` writes it: members and schema attributes in the order
+/// Java emits them, the messages in their declared order, namespaces only
+/// where they differ from the enclosing one and named type references
+/// qualified relative to it. This is what `md5` hashes, so the hashes match
+/// Java peers' for protocols that declare their named types under "types".
+/// Attributes Java doesn't know are written after the ones it does, sorted.
+impl<'a> From<&'a Protocol> for String {
+    fn from(p: &'a Protocol) -> String {
+        // Written out member by member, since a Value's objects are sorted.
+        let space = p.namespace.as_ref().map_or("", |ns| &ns[..]);
+        let mut members = vec![member("protocol", &Value::String(p.protocol.clone()))];
+        if let Some(ref ns) = p.namespace {
+            members.push(member("namespace", &Value::String(ns.clone())));
+        }
+        if let Some(ref doc) = p.doc {
+            members.push(member("doc", &Value::String(doc.clone())));
+        }
+        let types: Vec<String> = p.types.iter().map(|t| schema_json(&Value::from(t), space)).collect();
+        members.push(format!("\"types\":[{}]", types.join(",")));
+
+        let messages: Vec<String> = p.messages.iter()
+            .map(|&(ref name, ref message)| format!("{}:{}", quote(name), message_json(message, space)))
+            .collect();
+        members.push(format!("\"messages\":{{{}}}", messages.join(",")));
+
+        format!("{{{}}}", members.join(","))
+    }
+}
+
+fn member(key: &str, value: &Value) -> String {
+    format!("{}:{}", quote(key), json::to_string(value).unwrap_or_default())
+}
+
+fn quote(s: &str) -> String {
+    json::to_string(&Value::String(String::from(s))).unwrap_or_default()
+}
+
+fn message_json(m: &Message, space: &str) -> String {
+    let mut members = Vec::new();
+    if let Some(ref doc) = m.doc {
+        members.push(member("doc", &Value::String(doc.clone())));
+    }
+    let request: Vec<String> = m.request.iter().map(|f| field_json(f, space)).collect();
+    members.push(format!("\"request\":[{}]", request.join(",")));
+    members.push(format!("\"response\":{}", schema_json(&Value::from(&m.response), space)));
+    if !m.errors.is_empty() {
+        let errors: Vec<String> = m.errors.iter().map(|e| schema_json(&Value::from(e), space)).collect();
+        members.push(format!("\"errors\":[{}]", errors.join(",")));
+    }
+    if m.one_way {
+        members.push(String::from("\"one-way\":true"));
+    }
+    format!("{{{}}}", members.join(","))
+}
+
+// A schema in Java's form, inside a type (or the protocol) whose namespace is
+// `space`.
+fn schema_json(value: &Value, space: &str) -> String {
+    match *value {
+        Value::String(ref name) => quote(&qualified(name, space)),
+        Value::Array(ref branches) => {
+            let branches: Vec<String> = branches.iter().map(|b| schema_json(b, space)).collect();
+            format!("[{}]", branches.join(","))
+        },
+        Value::Object(ref attributes) => object_json(attributes, space),
+        _ => json::to_string(value).unwrap_or_default(),
+    }
+}
+
+fn object_json(attributes: &BTreeMap<String, Value>, space: &str) -> String {
+    let type_name = match attributes.get("type") {
+        // {"type": "int"} and the like are written as the bare type.
+        Some(t) if attributes.len() == 1 => return schema_json(t, space),
+        Some(&Value::String(ref t)) => t.clone(),
+        _ => String::new(),
+    };
+
+    let mut members = vec![member("type", &Value::String(type_name.clone()))];
+    let mut written = vec!["type"];
+    match &type_name[..] {
+        "record" | "error" | "enum" | "fixed" => {
+            let (ns, name) = split_name(attributes, space);
+            members.push(member("name", &Value::String(name)));
+            if ns != space {
+                members.push(member("namespace", &Value::String(ns.clone())));
+            }
+            written.extend(&["name", "namespace"]);
+            push_plain(&mut members, &mut written, attributes, "doc");
+
+            match &type_name[..] {
+                "enum" => {
+                    push_plain(&mut members, &mut written, attributes, "symbols");
+                    push_plain(&mut members, &mut written, attributes, "default");
+                },
+                "fixed" => push_plain(&mut members, &mut written, attributes, "size"),
+                _ => {
+                    if let Some(&Value::Array(ref fields)) = attributes.get("fields") {
+                        let fields: Vec<String> = fields.iter().map(|f| field_json(f, &ns)).collect();
+                        members.push(format!("\"fields\":[{}]", fields.join(",")));
+                        written.push("fields");
+                    }
+                },
+            }
+        },
+        "array" | "map" => {
+            let key = if type_name == "array" { "items" } else { "values" };
+            if let Some(nested) = attributes.get(key) {
+                members.push(format!("{}:{}", quote(key), schema_json(nested, space)));
+                written.push(key);
+            }
+        },
+        _ => (),
+    }
+
+    push_rest(&mut members, &written, attributes);
+    format!("{{{}}}", members.join(","))
+}
+
+fn field_json(field: &Value, space: &str) -> String {
+    let attributes = match *field {
+        Value::Object(ref attributes) => attributes,
+        _ => return json::to_string(field).unwrap_or_default(),
+    };
+
+    let mut members = Vec::new();
+    let mut written = vec!["order"];
+    push_plain(&mut members, &mut written, attributes, "name");
+    if let Some(field_type) = attributes.get("type") {
+        members.push(format!("\"type\":{}", schema_json(field_type, space)));
+        written.push("type");
+    }
+    push_plain(&mut members, &mut written, attributes, "doc");
+    push_plain(&mut members, &mut written, attributes, "default");
+    // Java leaves out the default order.
+    match attributes.get("order") {
+        Some(&Value::String(ref order)) if order == "ascending" => (),
+        Some(order) => members.push(member("order", order)),
+        None => (),
+    }
+
+    push_rest(&mut members, &written, attributes);
+    format!("{{{}}}", members.join(","))
+}
+
+fn push_plain<'k>(members: &mut Vec<String>, written: &mut Vec<&'k str>,
+                  attributes: &BTreeMap<String, Value>, key: &'k str) {
+    if let Some(value) = attributes.get(key) {
+        members.push(member(key, value));
+    }
+    written.push(key);
+}
+
+// The attributes not yet written, with "aliases" last as Java has it.
+fn push_rest(members: &mut Vec<String>, written: &[&str], attributes: &BTreeMap<String, Value>) {
+    for (key, value) in attributes.iter() {
+        if key != "aliases" && !written.contains(&&key[..]) {
+            members.push(member(key, value));
+        }
+    }
+    if let Some(aliases) = attributes.get("aliases") {
+        members.push(member("aliases", aliases));
+    }
+}
+
+// A named type's namespace and short name, the namespace defaulting to the
+// enclosing one.
+fn split_name(attributes: &BTreeMap<String, Value>, space: &str) -> (String, String) {
+    let name = match attributes.get("name") {
+        Some(&Value::String(ref name)) => name.clone(),
+        _ => String::new(),
+    };
+    match name.rfind('.') {
+        Some(dot) => (String::from(&name[..dot]), String::from(&name[dot + 1..])),
+        None => {
+            let ns = match attributes.get("namespace") {
+                Some(&Value::String(ref ns)) => ns.clone(),
+                _ => String::from(space),
+            };
+            (ns, name)
+        },
+    }
+}
+
+// A type reference, shortened to the bare name when it's in `space`.
+fn qualified(name: &str, space: &str) -> String {
+    match name.rfind('.') {
+        Some(dot) if &name[..dot] == space => String::from(&name[dot + 1..]),
+        _ => String::from(name),
+    }
+}
+
+// The message names in the order `text` declares them, read off the text
+// itself since the parsed "messages" object is a sorted map. Only the keys of
+// the top level object's "messages" object are collected.
+fn declared_order(text: &str) -> Vec<String> {
+    let bytes = text.as_bytes();
+    let mut order = Vec::new();
+    // The objects and arrays the scan is inside of.
+    let mut open = Vec::new();
+    let mut after_messages_key = false;
+    let mut in_messages = false;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                let end = if i < bytes.len() { i + 1 } else { bytes.len() };
+                let is_key = bytes[end..].iter().find(|b| !(**b as char).is_whitespace()) == Some(&b':');
+                let string = json::from_str::<String>(&text[start..end]).ok();
+                if is_key && open.len() == 1 {
+                    after_messages_key = string.as_ref().map(|s| &s[..]) == Some("messages");
+                } else if is_key && in_messages && open.len() == 2 {
+                    order.extend(string);
+                } else if !is_key {
+                    after_messages_key = false;
+                }
+            },
+            b'{' => {
+                open.push(b'{');
+                in_messages = in_messages || (after_messages_key && open.len() == 2);
+                after_messages_key = false;
+            },
+            b'[' => {
+                open.push(b'[');
+                after_messages_key = false;
+            },
+            b'}' | b']' => {
+                if in_messages && open.len() == 2 {
+                    in_messages = false;
+                }
+                open.pop();
+            },
+            _ => (),
+        }
+        i += 1;
+    }
+    order
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from(self))
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Random datums for a schema, for load tests and fuzzing.
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::json::Value;
+
+use datum::Datum;
+use schema::Schema;
+use schema::error::*;
+use schema::names::{self, Names, SchemaKind};
+
+const ALPHANUMERIC: &'static [u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+// Upper bounds for the logical types, so dates and times stay readable:
+// days and milliseconds up to the start of 2100, and the length of a day.
+const MAX_DATE: i32 = 47482;
+const MAX_TIMESTAMP_MILLIS: i64 = 4102444800000;
+const MILLIS_PER_DAY: i64 = 86400000;
+
+/// Limits on the datums `generate_with` produces.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Arrays and maps get between 0 and this many entries.
+    pub max_collection_length: usize,
+    /// Strings and bytes get between 0 and this many characters or bytes.
+    pub max_string_length: usize,
+    /// How often a union with a null branch is null, from 0.0 to 1.0.
+    pub null_probability: f64,
+    /// How many times a record may turn up inside itself. Past this, arrays
+    /// and maps are empty and unions avoid records so the datum bottoms out.
+    pub max_recursion_depth: usize,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            max_collection_length: 5,
+            max_string_length: 16,
+            null_probability: 0.2,
+            max_recursion_depth: 3,
+        }
+    }
+}
+
+/// A random datum that can be written with `schema`, using the default options.
+pub fn generate<R: Rng>(schema: &Schema, rng: &mut R) -> Result<Datum, Error> {
+    generate_with(schema, &Options::default(), rng)
+}
+
+pub fn generate_with<R: Rng>(schema: &Schema, options: &Options, rng: &mut R) -> Result<Datum, Error> {
+    let names = Names::from_schema(schema);
+    Generator::new(&names, options).generate(&Value::from(schema), None, rng)
+}
+
+pub struct Generator<'a> {
+    names: &'a Names,
+    options: &'a Options,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(names: &'a Names, options: &'a Options) -> Generator<'a> {
+        Generator { names: names, options: options }
+    }
+
+    pub fn generate<R: Rng>(&self, schema: &Value, ns: Option<&str>, rng: &mut R) -> Result<Datum, Error> {
+        let mut records = Vec::new();
+        self.generate_in(schema, ns, &mut records, rng)
+    }
+
+    // `records` holds the full names of the records we're inside of.
+    fn generate_in<R: Rng>(&self, schema: &Value, ns: Option<&str>, records: &mut Vec<String>, rng: &mut R) -> Result<Datum, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+        let limited = self.at_depth_limit(records);
+
+        match kind {
+            SchemaKind::Primitive("bytes") if logical_type(schema) == Some("decimal") => {
+                Ok(Datum::Bytes(random_decimal(precision(schema, 8), None, rng)))
+            },
+            SchemaKind::Primitive(type_name) => Ok(self.primitive(type_name, logical_type(schema), rng)),
+            SchemaKind::Record(definition) => {
+                let fullname = names::fullname_of(definition, ns);
+                if records.iter().filter(|r| **r == fullname).count() > self.options.max_recursion_depth {
+                    return Err(Error::EncodeError(ErrorCode::RecursionLimitExceeded));
+                }
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields,
+                    _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+                };
+
+                records.push(fullname);
+                let mut record = Vec::with_capacity(fields.len());
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_type = match field.find("type") {
+                        Some(t) => t,
+                        None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+                    };
+                    let value = try!(self.generate_in(field_type, ns, records, rng));
+                    record.push((String::from(name), value));
+                }
+                records.pop();
+                Ok(Datum::Record(record))
+            },
+            SchemaKind::Enum(definition) => {
+                let symbols = match definition.find("symbols") {
+                    Some(&Value::Array(ref symbols)) if !symbols.is_empty() => symbols,
+                    _ => return Err(Error::EncodeError(ErrorCode::EnumSymbolNotFound)),
+                };
+                let symbol = symbols[rng.gen_range(0, symbols.len())].as_string().unwrap_or("");
+                Ok(Datum::Enum(String::from(symbol)))
+            },
+            SchemaKind::Fixed(definition) => {
+                let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0) as usize;
+                match logical_type(definition) {
+                    Some("decimal") => Ok(Datum::Fixed(random_decimal(precision(definition, size), Some(size), rng))),
+                    _ => Ok(Datum::Fixed(random_bytes(size, rng))),
+                }
+            },
+            SchemaKind::Array(items) => {
+                let len = if limited { 0 } else { rng.gen_range(0, self.options.max_collection_length + 1) };
+                let mut vec = Vec::with_capacity(len);
+                for _ in 0..len {
+                    vec.push(try!(self.generate_in(items, ns, records, rng)));
+                }
+                Ok(Datum::Array(vec))
+            },
+            SchemaKind::Map(values) => {
+                let len = if limited { 0 } else { rng.gen_range(0, self.options.max_collection_length + 1) };
+                let mut map = BTreeMap::new();
+                for _ in 0..len {
+                    let key = self.random_string(rng);
+                    map.insert(key, try!(self.generate_in(values, ns, records, rng)));
+                }
+                Ok(Datum::Map(map))
+            },
+            SchemaKind::Union(branches) => {
+                let idx = try!(self.choose_branch(branches, ns, limited, rng));
+                let value = try!(self.generate_in(&branches[idx], ns, records, rng));
+                Ok(Datum::Union(idx, Box::new(value)))
+            },
+        }
+    }
+
+    fn at_depth_limit(&self, records: &Vec<String>) -> bool {
+        records.iter().any(|r| records.iter().filter(|other| *other == r).count() > self.options.max_recursion_depth)
+    }
+
+    // Null with the configured probability, otherwise any other branch. At the
+    // depth limit, null or failing that anything but a record.
+    fn choose_branch<R: Rng>(&self, branches: &Vec<Value>, ns: Option<&str>, limited: bool, rng: &mut R) -> Result<usize, Error> {
+        if branches.is_empty() {
+            return Err(Error::EncodeError(ErrorCode::UnionBranchNotFound));
+        }
+
+        let mut null = None;
+        let mut others = Vec::new();
+        for (idx, branch) in branches.iter().enumerate() {
+            match try!(self.names.kind(branch, ns)).0 {
+                SchemaKind::Primitive("null") => null = Some(idx),
+                SchemaKind::Record(_) if limited => (),
+                _ => others.push(idx),
+            }
+        }
+
+        match null {
+            Some(idx) if limited || others.is_empty() || rng.next_f64() < self.options.null_probability => Ok(idx),
+            _ if others.is_empty() => Ok(rng.gen_range(0, branches.len())),
+            _ => Ok(others[rng.gen_range(0, others.len())]),
+        }
+    }
+
+    fn primitive<R: Rng>(&self, type_name: &str, logical: Option<&str>, rng: &mut R) -> Datum {
+        match (type_name, logical) {
+            ("int", Some("date")) => Datum::Int(rng.gen_range(0, MAX_DATE + 1)),
+            ("int", Some("time-millis")) => Datum::Int(rng.gen_range(0, MILLIS_PER_DAY as i32)),
+            ("long", Some("time-micros")) => Datum::Long(rng.gen_range(0, MILLIS_PER_DAY * 1000)),
+            ("long", Some("timestamp-millis")) | ("long", Some("local-timestamp-millis")) => {
+                Datum::Long(rng.gen_range(0, MAX_TIMESTAMP_MILLIS + 1))
+            },
+            ("long", Some("timestamp-micros")) | ("long", Some("local-timestamp-micros")) => {
+                Datum::Long(rng.gen_range(0, MAX_TIMESTAMP_MILLIS + 1) * 1000 + rng.gen_range(0, 1000))
+            },
+            ("string", Some("uuid")) => Datum::String(random_uuid(rng)),
+            ("null", _) => Datum::Null,
+            ("boolean", _) => Datum::Boolean(rng.gen()),
+            ("int", _) => Datum::Int(rng.gen()),
+            ("long", _) => Datum::Long(rng.gen()),
+            ("float", _) => Datum::Float(rng.gen_range(-1.0e6, 1.0e6)),
+            ("double", _) => Datum::Double(rng.gen_range(-1.0e12, 1.0e12)),
+            ("string", _) => Datum::String(self.random_string(rng)),
+            _ => {
+                let len = rng.gen_range(0, self.options.max_string_length + 1);
+                Datum::Bytes(random_bytes(len, rng))
+            },
+        }
+    }
+
+    fn random_string<R: Rng>(&self, rng: &mut R) -> String {
+        let len = rng.gen_range(0, self.options.max_string_length + 1);
+        (0..len).map(|_| ALPHANUMERIC[rng.gen_range(0, ALPHANUMERIC.len())] as char).collect()
+    }
+}
+
+fn logical_type(schema: &Value) -> Option<&str> {
+    schema.find("logicalType").and_then(|l| l.as_string())
+}
+
+// The precision of a decimal, capped at what an i64 unscaled value can hold
+// and, for fixed, at what fits in its size.
+fn precision(definition: &Value, size: usize) -> u32 {
+    let precision = definition.find("precision").and_then(|p| p.as_u64()).unwrap_or(1) as u32;
+    let fits = if size == 0 { 0 } else { ((8 * size - 1) as f64 * 2f64.log10()).floor() as u32 };
+    precision.min(fits).min(18)
+}
+
+// The big-endian two's complement bytes of a random unscaled value with at
+// most `digits` digits, sign-extended to `size` bytes when given.
+fn random_decimal<R: Rng>(digits: u32, size: Option<usize>, rng: &mut R) -> Vec<u8> {
+    let bound = 10i64.pow(digits);
+    let unscaled = rng.gen_range(-bound + 1, bound);
+    let bytes = unscaled.to_be_bytes();
+    match size {
+        Some(size) if size >= bytes.len() => {
+            let fill = if unscaled < 0 { 0xff } else { 0 };
+            let mut out = vec![fill; size - bytes.len()];
+            out.extend_from_slice(&bytes);
+            out
+        },
+        Some(size) => bytes[bytes.len() - size..].to_vec(),
+        None => {
+            // Drop leading bytes that only repeat the sign.
+            let mut start = 0;
+            while start < bytes.len() - 1 {
+                let redundant = (bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+                    || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0);
+                if !redundant {
+                    break;
+                }
+                start += 1;
+            }
+            bytes[start..].to_vec()
+        },
+    }
+}
+
+fn random_bytes<R: Rng>(len: usize, rng: &mut R) -> Vec<u8> {
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+// A version 4 UUID.
+fn random_uuid<R: Rng>(rng: &mut R) -> String {
+    let mut bytes = random_bytes(16, rng);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", hex[0..4].concat(), hex[4..6].concat(), hex[6..8].concat(),
+            hex[8..10].concat(), hex[10..16].concat())
+}
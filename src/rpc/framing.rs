@@ -0,0 +1,92 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Message framing: a message goes over the wire as a list of buffers, each
+// preceded by its length as a four byte big-endian integer. A zero length
+// buffer ends the message.
+
+use std::io::{self, Read, Write};
+
+use schema::error::*;
+
+/// Largest buffer a message is split into when it's framed.
+pub const BUFFER_SIZE: usize = 8192;
+
+/// Splits a message into buffers for framing.
+pub fn to_buffers(data: &[u8]) -> Vec<Vec<u8>> {
+    data.chunks(BUFFER_SIZE).map(|chunk| chunk.to_vec()).collect()
+}
+
+pub fn write_buffers<W: Write>(buffers: &[Vec<u8>], writer: &mut W) -> Result<(),Error> {
+    for buffer in buffers.iter() {
+        // A zero length buffer would end the message early.
+        if buffer.is_empty() {
+            continue;
+        }
+        try!(writer.write_all(&length_prefix(buffer.len())));
+        try!(writer.write_all(buffer));
+    }
+    try!(writer.write_all(&length_prefix(0)));
+    Ok(try!(writer.flush()))
+}
+
+/// Reads one framed message. Returns `None` if the stream is closed before
+/// a new message starts.
+pub fn read_buffers<R: Read>(reader: &mut R) -> Result<Option<Vec<Vec<u8>>>, Error> {
+    let mut buffers = Vec::new();
+    loop {
+        let mut prefix = [0u8; 4];
+        if buffers.is_empty() {
+            // Only a clean end of stream between messages is allowed.
+            let read = try!(read_fully(reader, &mut prefix));
+            if read == 0 {
+                return Ok(None);
+            } else if read < prefix.len() {
+                return Err(Error::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame")));
+            }
+        } else {
+            try!(reader.read_exact(&mut prefix));
+        }
+
+        let len = (prefix[0] as usize) << 24 | (prefix[1] as usize) << 16 |
+                  (prefix[2] as usize) << 8 | prefix[3] as usize;
+        if len == 0 {
+            return Ok(Some(buffers));
+        }
+
+        let mut buffer = Vec::new();
+        try!(reader.by_ref().take(len as u64).read_to_end(&mut buffer));
+        if buffer.len() != len {
+            return Err(Error::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame")));
+        }
+        buffers.push(buffer);
+    }
+}
+
+fn length_prefix(len: usize) -> [u8; 4] {
+    [(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8]
+}
+
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::IoError(e)),
+        }
+    }
+    Ok(read)
+}
@@ -0,0 +1,202 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use datum::Datum;
+use encoding::binary;
+use schema::{self, Schema};
+use schema::error::*;
+
+pub const HANDSHAKE_REQUEST_SCHEMA: &'static str = r#"{
+    "type": "record", "name": "HandshakeRequest", "namespace": "org.apache.avro.ipc",
+    "fields": [
+        {"name": "clientHash", "type": {"type": "fixed", "name": "MD5", "size": 16}},
+        {"name": "clientProtocol", "type": ["null", "string"]},
+        {"name": "serverHash", "type": "MD5"},
+        {"name": "meta", "type": ["null", {"type": "map", "values": "bytes"}]}
+    ]
+}"#;
+
+pub const HANDSHAKE_RESPONSE_SCHEMA: &'static str = r#"{
+    "type": "record", "name": "HandshakeResponse", "namespace": "org.apache.avro.ipc",
+    "fields": [
+        {"name": "match", "type": {"type": "enum", "name": "HandshakeMatch", "symbols": ["BOTH", "CLIENT", "NONE"]}},
+        {"name": "serverProtocol", "type": ["null", "string"]},
+        {"name": "serverHash", "type": ["null", {"type": "fixed", "name": "MD5", "size": 16}]},
+        {"name": "meta", "type": ["null", {"type": "map", "values": "bytes"}]}
+    ]
+}"#;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum HandshakeMatch {
+    /// The server knows the client's protocol, and the client had the right
+    /// server protocol.
+    Both,
+    /// The server knows the client's protocol, but the client needs the
+    /// server protocol that's sent back.
+    Client,
+    /// The server doesn't know the client's protocol; the client has to
+    /// send it and try again.
+    None,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HandshakeRequest {
+    pub client_hash: [u8; 16],
+    pub client_protocol: Option<String>,
+    pub server_hash: [u8; 16],
+    pub meta: Option<BTreeMap<String, Vec<u8>>>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HandshakeResponse {
+    pub handshake_match: HandshakeMatch,
+    pub server_protocol: Option<String>,
+    pub server_hash: Option<[u8; 16]>,
+    pub meta: Option<BTreeMap<String, Vec<u8>>>,
+}
+
+impl HandshakeRequest {
+    pub fn encode<W: Write>(&self, writer: &mut W) -> Result<(),Error> {
+        let schema = try!(schema::from_str(HANDSHAKE_REQUEST_SCHEMA));
+        let datum = Datum::Record(vec![
+            (String::from("clientHash"), Datum::Fixed(self.client_hash.to_vec())),
+            (String::from("clientProtocol"), optional_string(&self.client_protocol)),
+            (String::from("serverHash"), Datum::Fixed(self.server_hash.to_vec())),
+            (String::from("meta"), optional_meta(&self.meta)),
+        ]);
+        binary::encode(&datum, &schema, writer)
+    }
+
+    pub fn decode<R: Read>(reader: &mut R) -> Result<HandshakeRequest, Error> {
+        let schema = try!(schema::from_str(HANDSHAKE_REQUEST_SCHEMA));
+        let datum = try!(binary::decode(&schema, reader));
+
+        Ok(HandshakeRequest {
+            client_hash: try!(hash(datum.field("clientHash"))),
+            client_protocol: try!(string_field(datum.field("clientProtocol"))),
+            server_hash: try!(hash(datum.field("serverHash"))),
+            meta: try!(meta_field(datum.field("meta"))),
+        })
+    }
+}
+
+impl HandshakeResponse {
+    pub fn encode<W: Write>(&self, writer: &mut W) -> Result<(),Error> {
+        let schema = try!(schema::from_str(HANDSHAKE_RESPONSE_SCHEMA));
+        let handshake_match = match self.handshake_match {
+            HandshakeMatch::Both => "BOTH",
+            HandshakeMatch::Client => "CLIENT",
+            HandshakeMatch::None => "NONE",
+        };
+        let server_hash = match self.server_hash {
+            Some(ref h) => Datum::Union(1, Box::new(Datum::Fixed(h.to_vec()))),
+            None => Datum::Union(0, Box::new(Datum::Null)),
+        };
+        let datum = Datum::Record(vec![
+            (String::from("match"), Datum::Enum(String::from(handshake_match))),
+            (String::from("serverProtocol"), optional_string(&self.server_protocol)),
+            (String::from("serverHash"), server_hash),
+            (String::from("meta"), optional_meta(&self.meta)),
+        ]);
+        binary::encode(&datum, &schema, writer)
+    }
+
+    pub fn decode<R: Read>(reader: &mut R) -> Result<HandshakeResponse, Error> {
+        let schema = try!(schema::from_str(HANDSHAKE_RESPONSE_SCHEMA));
+        let datum = try!(binary::decode(&schema, reader));
+
+        let handshake_match = match datum.field("match") {
+            Some(&Datum::Enum(ref s)) if s == "BOTH" => HandshakeMatch::Both,
+            Some(&Datum::Enum(ref s)) if s == "CLIENT" => HandshakeMatch::Client,
+            Some(&Datum::Enum(ref s)) if s == "NONE" => HandshakeMatch::None,
+            _ => return Err(Error::DecodeError(ErrorCode::HandshakeFailed)),
+        };
+        let server_hash = match datum.field("serverHash").map(|d| d.unwrap_union()) {
+            Some(&Datum::Null) => None,
+            h => Some(try!(hash(h))),
+        };
+
+        Ok(HandshakeResponse {
+            handshake_match: handshake_match,
+            server_protocol: try!(string_field(datum.field("serverProtocol"))),
+            server_hash: server_hash,
+            meta: try!(meta_field(datum.field("meta"))),
+        })
+    }
+}
+
+/// The schema call metadata (in requests and responses) is written with.
+pub fn meta_schema() -> Schema {
+    schema::from_str(r#"{"type": "map", "values": "bytes"}"#).unwrap()
+}
+
+fn optional_string(s: &Option<String>) -> Datum {
+    match *s {
+        Some(ref s) => Datum::Union(1, Box::new(Datum::String(s.clone()))),
+        None => Datum::Union(0, Box::new(Datum::Null)),
+    }
+}
+
+/// Call metadata as a datum to write with `meta_schema()`.
+pub fn meta_datum(meta: &BTreeMap<String, Vec<u8>>) -> Datum {
+    Datum::Map(meta.iter().map(|(k, v)| (k.clone(), Datum::Bytes(v.clone()))).collect())
+}
+
+fn optional_meta(meta: &Option<BTreeMap<String, Vec<u8>>>) -> Datum {
+    match *meta {
+        Some(ref meta) => Datum::Union(1, Box::new(meta_datum(meta))),
+        None => Datum::Union(0, Box::new(Datum::Null)),
+    }
+}
+
+fn hash(datum: Option<&Datum>) -> Result<[u8; 16], Error> {
+    match datum.map(|d| d.unwrap_union()) {
+        Some(&Datum::Fixed(ref bytes)) if bytes.len() == 16 => {
+            let mut h = [0u8; 16];
+            for (i, b) in bytes.iter().enumerate() {
+                h[i] = *b;
+            }
+            Ok(h)
+        },
+        _ => Err(Error::DecodeError(ErrorCode::HandshakeFailed)),
+    }
+}
+
+fn string_field(datum: Option<&Datum>) -> Result<Option<String>, Error> {
+    match datum.map(|d| d.unwrap_union()) {
+        Some(&Datum::Null) => Ok(None),
+        Some(&Datum::String(ref s)) => Ok(Some(s.clone())),
+        _ => Err(Error::DecodeError(ErrorCode::HandshakeFailed)),
+    }
+}
+
+pub fn meta_field(datum: Option<&Datum>) -> Result<Option<BTreeMap<String, Vec<u8>>>, Error> {
+    match datum.map(|d| d.unwrap_union()) {
+        Some(&Datum::Null) => Ok(None),
+        Some(&Datum::Map(ref map)) => {
+            let mut meta = BTreeMap::new();
+            for (k, v) in map.iter() {
+                match *v {
+                    Datum::Bytes(ref b) => { meta.insert(k.clone(), b.clone()); },
+                    _ => return Err(Error::DecodeError(ErrorCode::HandshakeFailed)),
+                }
+            }
+            Ok(Some(meta))
+        },
+        _ => Err(Error::DecodeError(ErrorCode::HandshakeFailed)),
+    }
+}
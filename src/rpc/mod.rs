@@ -0,0 +1,38 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Avro RPC: the protocol handshake and call encoding, over any transceiver.
+// Transports are treated as stateless, so every request carries a handshake.
+pub use self::handshake::{
+	HandshakeMatch,
+	HandshakeRequest,
+	HandshakeResponse
+};
+pub use self::requestor::Requestor;
+pub use self::responder::{
+	Handler,
+	Responder
+};
+pub use self::transceiver::{
+	LocalTransceiver,
+	TcpServer,
+	TcpTransceiver,
+	Transceiver
+};
+
+pub mod framing;
+pub mod handshake;
+mod requestor;
+mod responder;
+mod transceiver;
@@ -0,0 +1,187 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::json::Value;
+
+use datum::Datum;
+use encoding::binary::{self, Encoder};
+use encoding::resolve::Resolver;
+use protocol::{Message, Protocol};
+use schema::error::*;
+
+use super::framing::to_buffers;
+use super::handshake::{self, HandshakeMatch, HandshakeRequest, HandshakeResponse};
+use super::transceiver::Transceiver;
+
+/// Client side of the protocol: makes calls through a transceiver, doing the
+/// handshake with every request.
+pub struct Requestor<T: Transceiver> {
+    local: Protocol,
+    local_hash: [u8; 16],
+    transceiver: T,
+    remote: Option<Protocol>,
+    remote_hash: Option<[u8; 16]>,
+    // Set once the server has said it doesn't know our protocol.
+    send_protocol: bool,
+    meta: BTreeMap<String, Vec<u8>>,
+    handshake_meta: Option<BTreeMap<String, Vec<u8>>>,
+    response_meta: BTreeMap<String, Vec<u8>>,
+    response_handshake_meta: Option<BTreeMap<String, Vec<u8>>>,
+}
+
+impl<T: Transceiver> Requestor<T> {
+    pub fn new(local: Protocol, transceiver: T) -> Requestor<T> {
+        let hash = local.md5();
+        Requestor {
+            local: local,
+            local_hash: hash,
+            transceiver: transceiver,
+            remote: None,
+            remote_hash: None,
+            send_protocol: false,
+            meta: BTreeMap::new(),
+            handshake_meta: None,
+            response_meta: BTreeMap::new(),
+            response_handshake_meta: None,
+        }
+    }
+
+    pub fn local(&self) -> &Protocol {
+        &self.local
+    }
+
+    /// The server's protocol, once a handshake has completed.
+    pub fn remote(&self) -> Option<&Protocol> {
+        self.remote.as_ref()
+    }
+
+    pub fn transceiver(&mut self) -> &mut T {
+        &mut self.transceiver
+    }
+
+    /// Sets the metadata sent with every call.
+    pub fn set_meta(&mut self, meta: BTreeMap<String, Vec<u8>>) {
+        self.meta = meta;
+    }
+
+    /// Sets the metadata sent with every handshake request.
+    pub fn set_handshake_meta(&mut self, meta: Option<BTreeMap<String, Vec<u8>>>) {
+        self.handshake_meta = meta;
+    }
+
+    /// The call metadata of the last response.
+    pub fn response_meta(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.response_meta
+    }
+
+    /// The handshake metadata of the last response.
+    pub fn response_handshake_meta(&self) -> Option<&BTreeMap<String, Vec<u8>>> {
+        self.response_handshake_meta.as_ref()
+    }
+
+    /// Calls `message_name` with the parameters in `request` (a record datum,
+    /// one field per parameter). An error sent back by the server comes back
+    /// as `Error::RemoteError`.
+    pub fn request(&mut self, message_name: &str, request: &Datum) -> Result<Datum, Error> {
+        let message = match self.local.message(message_name) {
+            Some(m) => m.clone(),
+            None => return Err(Error::EncodeError(ErrorCode::UnknownMessage)),
+        };
+
+        let mut call = Vec::new();
+        try!(binary::encode(&handshake::meta_datum(&self.meta), &handshake::meta_schema(), &mut call));
+        try!(binary::write_string(message_name, &mut call));
+        let names = self.local.names();
+        let ns = self.local.namespace.clone();
+        try!(Encoder::new(&names).encode(request, &Value::from(&message.request_schema(message_name)),
+                                         ns.as_ref().map(|s| s.as_ref()), &mut call));
+
+        // If the server doesn't know our protocol the first attempt only gets
+        // a handshake back, and the call has to be sent again.
+        for _ in 0..2 {
+            let mut buf = Vec::new();
+            let handshake = HandshakeRequest {
+                client_hash: self.local_hash,
+                client_protocol: if self.send_protocol { Some(self.local.to_string()) } else { None },
+                server_hash: self.remote_hash.unwrap_or(self.local_hash),
+                meta: self.handshake_meta.clone(),
+            };
+            try!(handshake.encode(&mut buf));
+            buf.extend(call.iter().cloned());
+
+            let response = try!(self.transceiver.transceive(to_buffers(&buf))).concat();
+            let mut reader = &response[..];
+
+            let handshake = try!(HandshakeResponse::decode(&mut reader));
+            self.response_handshake_meta = handshake.meta.clone();
+            self.response_meta = BTreeMap::new();
+            if let Some(ref server_protocol) = handshake.server_protocol {
+                self.remote = Some(try!(Protocol::from_str(server_protocol)));
+                self.remote_hash = handshake.server_hash;
+            }
+
+            match handshake.handshake_match {
+                HandshakeMatch::None => {
+                    self.send_protocol = true;
+                },
+                HandshakeMatch::Both | HandshakeMatch::Client => {
+                    if self.remote.is_none() {
+                        // BOTH on the first call: the server is running our protocol.
+                        self.remote = Some(self.local.clone());
+                        self.remote_hash = Some(self.local_hash);
+                    }
+                    self.send_protocol = false;
+                    return self.read_response(message_name, &message, &mut reader);
+                },
+            }
+        }
+
+        Err(Error::DecodeError(ErrorCode::HandshakeFailed))
+    }
+
+    fn read_response(&mut self, message_name: &str, local_message: &Message, reader: &mut &[u8]) -> Result<Datum, Error> {
+        if local_message.one_way {
+            return Ok(Datum::Null);
+        }
+
+        let meta = try!(binary::decode(&handshake::meta_schema(), reader));
+        self.response_meta = try!(handshake::meta_field(Some(&meta))).unwrap_or(BTreeMap::new());
+        let is_error = try!(binary::read_boolean(reader));
+
+        // The response was written with the server's definition of the
+        // message, and is resolved to ours.
+        let remote = self.remote.as_ref().unwrap_or(&self.local);
+        let (remote, remote_message) = match remote.message(message_name) {
+            Some(m) => (remote, m),
+            None => (&self.local, local_message),
+        };
+        let remote_names = remote.names();
+        let local_names = self.local.names();
+        let resolver = Resolver::new(&remote_names, &local_names);
+        let remote_ns = remote.namespace.as_ref().map(|s| s.as_ref());
+        let local_ns = self.local.namespace.as_ref().map(|s| s.as_ref());
+
+        if is_error {
+            let error = try!(resolver.decode(&Value::from(&remote_message.errors_schema()), remote_ns,
+                                             &Value::from(&local_message.errors_schema()), local_ns, reader));
+            Err(Error::RemoteError(error.unwrap_union().clone()))
+        } else {
+            resolver.decode(&Value::from(&remote_message.response), remote_ns,
+                            &Value::from(&local_message.response), local_ns, reader)
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::json::Value;
+
+use datum::Datum;
+use encoding::binary::{self, Encoder};
+use encoding::resolve::Resolver;
+use protocol::{Message, Protocol};
+use schema::error::*;
+
+use super::framing::to_buffers;
+use super::handshake::{self, HandshakeMatch, HandshakeRequest, HandshakeResponse};
+
+/// Server side message handling.
+pub trait Handler {
+    /// Handles a call to `message`. A failed call returns its error as a
+    /// datum, which is either a string or one of the message's declared errors.
+    fn handle(&mut self, message: &str, request: &Datum) -> Result<Datum, Datum>;
+}
+
+impl<F> Handler for F where F: FnMut(&str, &Datum) -> Result<Datum, Datum> {
+    fn handle(&mut self, message: &str, request: &Datum) -> Result<Datum, Datum> {
+        self(message, request)
+    }
+}
+
+/// Answers framed call requests for the local protocol, handshake included.
+pub struct Responder<H: Handler> {
+    local: Protocol,
+    local_hash: [u8; 16],
+    handler: H,
+    // Client protocols seen so far, by hash, so clients only have to send
+    // theirs once.
+    remotes: BTreeMap<Vec<u8>, Protocol>,
+    meta: BTreeMap<String, Vec<u8>>,
+    handshake_meta: Option<BTreeMap<String, Vec<u8>>>,
+    request_meta: BTreeMap<String, Vec<u8>>,
+    request_handshake_meta: Option<BTreeMap<String, Vec<u8>>>,
+}
+
+impl<H: Handler> Responder<H> {
+    pub fn new(local: Protocol, handler: H) -> Responder<H> {
+        let hash = local.md5();
+        // A client running our own protocol needn't send it.
+        let mut remotes = BTreeMap::new();
+        remotes.insert(hash.to_vec(), local.clone());
+        Responder {
+            local: local,
+            local_hash: hash,
+            handler: handler,
+            remotes: remotes,
+            meta: BTreeMap::new(),
+            handshake_meta: None,
+            request_meta: BTreeMap::new(),
+            request_handshake_meta: None,
+        }
+    }
+
+    pub fn local(&self) -> &Protocol {
+        &self.local
+    }
+
+    /// Sets the metadata sent with every call response.
+    pub fn set_meta(&mut self, meta: BTreeMap<String, Vec<u8>>) {
+        self.meta = meta;
+    }
+
+    /// Sets the metadata sent with every handshake response.
+    pub fn set_handshake_meta(&mut self, meta: Option<BTreeMap<String, Vec<u8>>>) {
+        self.handshake_meta = meta;
+    }
+
+    /// The call metadata of the last request answered.
+    pub fn request_meta(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.request_meta
+    }
+
+    /// The handshake metadata of the last request answered.
+    pub fn request_handshake_meta(&self) -> Option<&BTreeMap<String, Vec<u8>>> {
+        self.request_handshake_meta.as_ref()
+    }
+
+    /// Handles one request (the handshake followed by the call) and returns
+    /// the response buffers. Only a request whose handshake can't be read or
+    /// answered is an error; once the handshake is written, failures go back
+    /// to the client as system errors.
+    pub fn respond(&mut self, request: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+        let data = request.concat();
+        let mut reader = &data[..];
+        let mut out = Vec::new();
+
+        let handshake = try!(HandshakeRequest::decode(&mut reader));
+        self.request_handshake_meta = handshake.meta.clone();
+        self.request_meta = BTreeMap::new();
+        let remote = match try!(self.handshake(&handshake, &mut out)) {
+            Some(remote) => remote,
+            // The client has to try again with its protocol; the call
+            // itself isn't answered.
+            None => return Ok(to_buffers(&out)),
+        };
+
+        let call = match self.call(&remote, &mut reader) {
+            Ok(call) => call,
+            Err(e) => {
+                try!(self.write_system_error(&e.to_string(), &mut out));
+                return Ok(to_buffers(&out));
+            },
+        };
+        let (message_name, local_message, params) = match call {
+            Some(call) => call,
+            None => {
+                try!(self.write_system_error("unknown message", &mut out));
+                return Ok(to_buffers(&out));
+            },
+        };
+
+        let result = self.handler.handle(&message_name, &params);
+        if local_message.one_way {
+            return Ok(to_buffers(&out));
+        }
+
+        // The datum is encoded on its own first, so one that doesn't match
+        // its schema can still be answered with a system error.
+        let local_names = self.local.names();
+        let local_ns = self.local.namespace.clone();
+        let encoder = Encoder::new(&local_names);
+        let mut body = Vec::new();
+        let (is_error, encoded) = match result {
+            Ok(response) => (false, encoder.encode(&response, &Value::from(&local_message.response),
+                                                   local_ns.as_ref().map(|s| s.as_ref()), &mut body)),
+            Err(error) => (true, encoder.encode(&error, &Value::from(&local_message.errors_schema()),
+                                                local_ns.as_ref().map(|s| s.as_ref()), &mut body)),
+        };
+        if let Err(e) = encoded {
+            try!(self.write_system_error(&e.to_string(), &mut out));
+            return Ok(to_buffers(&out));
+        }
+
+        try!(binary::encode(&handshake::meta_datum(&self.meta), &handshake::meta_schema(), &mut out));
+        try!(binary::write_boolean(is_error, &mut out));
+        out.extend(body);
+        Ok(to_buffers(&out))
+    }
+
+    // Reads the call after the handshake: its metadata, the message name
+    // and the parameters. An unknown message is None.
+    fn call(&mut self, remote: &Protocol, reader: &mut &[u8]) -> Result<Option<(String, Message, Datum)>, Error> {
+        let meta = try!(binary::decode(&handshake::meta_schema(), reader));
+        self.request_meta = try!(handshake::meta_field(Some(&meta))).unwrap_or(BTreeMap::new());
+        let message_name = try!(binary::read_string(reader));
+
+        let (remote_message, local_message) = match (remote.message(&message_name), self.local.message(&message_name)) {
+            (Some(r), Some(l)) => (r.clone(), l.clone()),
+            _ => return Ok(None),
+        };
+
+        // Parameters were written with the client's definition of the
+        // message, and are resolved to ours for the handler.
+        let remote_names = remote.names();
+        let local_names = self.local.names();
+        let params = try!(Resolver::new(&remote_names, &local_names).decode(
+            &Value::from(&remote_message.request_schema(&message_name)), remote.namespace.as_ref().map(|s| s.as_ref()),
+            &Value::from(&local_message.request_schema(&message_name)), self.local.namespace.as_ref().map(|s| s.as_ref()),
+            reader));
+
+        Ok(Some((message_name, local_message, params)))
+    }
+
+    // Failures that aren't the handler's are sent back as the implicit string error.
+    fn write_system_error(&self, message: &str, out: &mut Vec<u8>) -> Result<(),Error> {
+        try!(binary::encode(&handshake::meta_datum(&self.meta), &handshake::meta_schema(), out));
+        try!(binary::write_boolean(true, out));
+        try!(binary::write_long(0, out));
+        binary::write_string(message, out)
+    }
+
+    // Writes the handshake response, and returns the client's protocol if
+    // the call can go ahead.
+    fn handshake(&mut self, request: &HandshakeRequest, out: &mut Vec<u8>) -> Result<Option<Protocol>, Error> {
+        let client_hash = request.client_hash.to_vec();
+        if let Some(ref client_protocol) = request.client_protocol {
+            let remote = try!(Protocol::from_str(client_protocol));
+            self.remotes.insert(client_hash.clone(), remote);
+        }
+
+        let remote = self.remotes.get(&client_hash).cloned();
+        let response = match remote {
+            None => {
+                HandshakeResponse {
+                    handshake_match: HandshakeMatch::None,
+                    server_protocol: Some(self.local.to_string()),
+                    server_hash: Some(self.local_hash),
+                    meta: self.handshake_meta.clone(),
+                }
+            },
+            Some(_) if request.server_hash == self.local_hash => {
+                HandshakeResponse {
+                    handshake_match: HandshakeMatch::Both,
+                    server_protocol: None,
+                    server_hash: None,
+                    meta: self.handshake_meta.clone(),
+                }
+            },
+            Some(_) => {
+                HandshakeResponse {
+                    handshake_match: HandshakeMatch::Client,
+                    server_protocol: Some(self.local.to_string()),
+                    server_hash: Some(self.local_hash),
+                    meta: self.handshake_meta.clone(),
+                }
+            },
+        };
+
+        try!(response.encode(out));
+        Ok(remote)
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use schema::error::*;
+
+use super::framing::{read_buffers, write_buffers};
+use super::responder::{Handler, Responder};
+
+/// Carries a request to a responder and brings back its response.
+pub trait Transceiver {
+    fn transceive(&mut self, request: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, Error>;
+}
+
+/// Hands requests straight to a responder in the same process.
+pub struct LocalTransceiver<H: Handler> {
+    responder: Responder<H>,
+}
+
+impl<H: Handler> LocalTransceiver<H> {
+    pub fn new(responder: Responder<H>) -> LocalTransceiver<H> {
+        LocalTransceiver { responder: responder }
+    }
+
+    pub fn responder(&mut self) -> &mut Responder<H> {
+        &mut self.responder
+    }
+}
+
+impl<H: Handler> Transceiver for LocalTransceiver<H> {
+    fn transceive(&mut self, request: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, Error> {
+        self.responder.respond(&request)
+    }
+}
+
+/// Sends framed requests over a TCP connection.
+pub struct TcpTransceiver {
+    stream: TcpStream,
+}
+
+impl TcpTransceiver {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpTransceiver, Error> {
+        Ok(TcpTransceiver { stream: try!(TcpStream::connect(addr)) })
+    }
+}
+
+impl Transceiver for TcpTransceiver {
+    fn transceive(&mut self, request: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, Error> {
+        try!(write_buffers(&request, &mut self.stream));
+        match try!(read_buffers(&mut self.stream)) {
+            Some(response) => Ok(response),
+            None => Err(Error::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"))),
+        }
+    }
+}
+
+/// Answers framed requests arriving over TCP.
+pub struct TcpServer<H: Handler> {
+    listener: TcpListener,
+    responder: Responder<H>,
+}
+
+impl<H: Handler> TcpServer<H> {
+    pub fn bind<A: ToSocketAddrs>(addr: A, responder: Responder<H>) -> Result<TcpServer<H>, Error> {
+        Ok(TcpServer { listener: try!(TcpListener::bind(addr)), responder: responder })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(try!(self.listener.local_addr()))
+    }
+
+    /// Accepts one connection and answers requests on it until the client
+    /// closes it. A request that can't be answered at all (its handshake is
+    /// unreadable) ends the connection with an error.
+    pub fn accept(&mut self) -> Result<(),Error> {
+        let (mut stream, _) = try!(self.listener.accept());
+        loop {
+            let request = match try!(read_buffers(&mut stream)) {
+                Some(request) => request,
+                None => return Ok(()),
+            };
+            let response = try!(self.responder.respond(&request));
+            try!(write_buffers(&response, &mut stream));
+        }
+    }
+
+    /// Answers connections one at a time, forever. A connection that fails
+    /// is dropped, and the server goes on to the next one.
+    pub fn serve(&mut self) -> Result<(),Error> {
+        loop {
+            if let Err(e) = self.accept() {
+                warn!("dropped connection: {}", e);
+            }
+        }
+    }
+}
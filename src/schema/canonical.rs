@@ -0,0 +1,175 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Parsing Canonical Form and fingerprints, as described in the
+// "Schema Fingerprints" section of the spec.
+use std::collections::BTreeSet;
+
+use serde::json::{self, Value};
+
+use super::model::Schema;
+use super::names::{fullname_of, is_primitive_type_name, namespace_of, Names};
+
+const EMPTY: u64 = 0xc15d213aa4d7a795;
+
+impl Schema {
+    /// The schema in Parsing Canonical Form: full names everywhere, only the
+    /// attributes that matter when reading data, in a fixed order and with no
+    /// whitespace. Two schemas with the same canonical form read data the
+    /// same way.
+    pub fn canonical_form(&self) -> String {
+        let mut out = String::new();
+        let mut defined = BTreeSet::new();
+        let names = Names::from_schema(self);
+        write_canonical(&names, &Value::from(self), None, &mut defined, &mut out);
+        out
+    }
+
+    /// The CRC-64-AVRO fingerprint of the schema's canonical form.
+    pub fn fingerprint(&self) -> u64 {
+        crc64_avro(self.canonical_form().as_bytes())
+    }
+}
+
+/// The 64-bit Rabin fingerprint the spec calls CRC-64-AVRO.
+pub fn crc64_avro(data: &[u8]) -> u64 {
+    let table = fingerprint_table();
+    let mut fp = EMPTY;
+    for b in data.iter() {
+        fp = (fp >> 8) ^ table[((fp ^ *b as u64) & 0xff) as usize];
+    }
+    fp
+}
+
+fn fingerprint_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    for i in 0..256 {
+        let mut fp = i as u64;
+        for _ in 0..8 {
+            fp = (fp >> 1) ^ (EMPTY & (0u64.wrapping_sub(fp & 1)));
+        }
+        table[i] = fp;
+    }
+    table
+}
+
+fn write_canonical(names: &Names, value: &Value, ns: Option<&str>, defined: &mut BTreeSet<String>, out: &mut String) {
+    match *value {
+        Value::Null => out.push_str("\"null\""),
+        Value::String(ref name) => write_name_ref(names, name, ns, out),
+        Value::Array(ref branches) => {
+            out.push('[');
+            for (i, branch) in branches.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(names, branch, ns, defined, out);
+            }
+            out.push(']');
+        },
+        Value::Object(_) => {
+            let type_name = match value.find("type") {
+                Some(&Value::String(ref t)) => t.clone(),
+                Some(nested) => return write_canonical(names, nested, ns, defined, out),
+                None => return,
+            };
+
+            match type_name.as_ref() {
+                "record" | "error" | "enum" | "fixed" => {
+                    let ns = namespace_of(value, ns);
+                    let ns = ns.as_ref().map(|s| s.as_ref());
+                    let fullname = fullname_of(value, ns);
+
+                    // Later uses of a named type are written as its name.
+                    if defined.contains(&fullname) {
+                        return out.push_str(&json_string(&fullname));
+                    }
+                    defined.insert(fullname.clone());
+
+                    out.push_str("{\"name\":");
+                    out.push_str(&json_string(&fullname));
+                    out.push_str(",\"type\":");
+                    // Errors read exactly like records.
+                    out.push_str(&json_string(if type_name == "error" { "record" } else { &type_name }));
+
+                    match type_name.as_ref() {
+                        "enum" => {
+                            out.push_str(",\"symbols\":[");
+                            if let Some(&Value::Array(ref symbols)) = value.find("symbols") {
+                                let symbols: Vec<String> = symbols.iter().map(|s| json_text(s)).collect();
+                                out.push_str(&symbols.join(","));
+                            }
+                            out.push(']');
+                        },
+                        "fixed" => {
+                            out.push_str(",\"size\":");
+                            out.push_str(&value.find("size").and_then(|s| s.as_u64()).unwrap_or(0).to_string());
+                        },
+                        _ => {
+                            out.push_str(",\"fields\":[");
+                            if let Some(&Value::Array(ref fields)) = value.find("fields") {
+                                for (i, field) in fields.iter().enumerate() {
+                                    if i > 0 {
+                                        out.push(',');
+                                    }
+                                    out.push_str("{\"name\":");
+                                    out.push_str(&json_text(field.find("name").unwrap_or(&Value::Null)));
+                                    out.push_str(",\"type\":");
+                                    write_canonical(names, field.find("type").unwrap_or(&Value::Null), ns, defined, out);
+                                    out.push('}');
+                                }
+                            }
+                            out.push(']');
+                        },
+                    }
+                    out.push('}');
+                },
+                "array" => {
+                    out.push_str("{\"type\":\"array\",\"items\":");
+                    write_canonical(names, value.find("items").unwrap_or(&Value::Null), ns, defined, out);
+                    out.push('}');
+                },
+                "map" => {
+                    out.push_str("{\"type\":\"map\",\"values\":");
+                    write_canonical(names, value.find("values").unwrap_or(&Value::Null), ns, defined, out);
+                    out.push('}');
+                },
+                // A primitive with attributes, a logicalType say, is just the primitive.
+                _ => write_name_ref(names, &type_name, ns, out),
+            }
+        },
+        _ => (),
+    }
+}
+
+fn write_name_ref(names: &Names, name: &str, ns: Option<&str>, out: &mut String) {
+    if is_primitive_type_name(name) {
+        return out.push_str(&json_string(name));
+    }
+    match names.resolve(name, ns) {
+        Some(named) => {
+            let fullname = fullname_of(&named.definition, named.namespace.as_ref().map(|s| s.as_ref()));
+            out.push_str(&json_string(&fullname))
+        },
+        None => out.push_str(&json_string(name)),
+    }
+}
+
+fn json_text(value: &Value) -> String {
+    json::to_string(value).unwrap_or(String::from("null"))
+}
+
+fn json_string(s: &str) -> String {
+    json_text(&Value::String(String::from(s)))
+}
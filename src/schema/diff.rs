@@ -0,0 +1,488 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// What changed between two versions of a schema, for reviewing schema
+// changes. Fields and named types are matched up the way schema resolution
+// matches them, by name or through the new version's aliases, and each
+// change is judged by whether data still resolves across it in each
+// direction.
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::json::{self, Value};
+
+use encoding::resolve::{can_promote, has_alias, names_match};
+
+use super::error::*;
+use super::model::{Field, Schema};
+use super::names::{fullname_of, short_name, Names, SchemaKind};
+
+/// The kinds of change `diff` reports.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChangeKind {
+    FieldAdded(String),
+    FieldRemoved(String),
+    /// A field renamed from the first name to the second, as found through
+    /// the new field's aliases.
+    FieldRenamed(String, String),
+    /// A field (or array items, map values and so on) changed type, from the
+    /// first to the second as they're written in a report.
+    TypeChanged(String, String),
+    /// A default added, removed or changed.
+    DefaultChanged(Option<Value>, Option<Value>),
+    SymbolsAdded(Vec<String>),
+    SymbolsRemoved(Vec<String>),
+    /// A named type renamed, as found through the new type's aliases.
+    TypeRenamed(String, String),
+    NamespaceMoved(Option<String>, Option<String>),
+    /// Only the doc of a field or named type changed.
+    DocChanged,
+}
+
+/// One change, and whether it breaks compatibility.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Change {
+    /// Where the change is: the top-level type's name and then field names,
+    /// as in "User.address.city". Array items add "[]" and map values "{}".
+    pub path: String,
+    pub kind: ChangeKind,
+    /// Whether data written with the old schema can still be read with the
+    /// new one.
+    pub backward: bool,
+    /// Whether data written with the new schema can still be read with the
+    /// old one.
+    pub forward: bool,
+}
+
+/// Everything that changed between two schemas. Displaying it gives a report
+/// with one line per change.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn is_backward_compatible(&self) -> bool {
+        self.changes.iter().all(|c| c.backward)
+    }
+
+    pub fn is_forward_compatible(&self) -> bool {
+        self.changes.iter().all(|c| c.forward)
+    }
+}
+
+/// The changes from `old` to `new`.
+pub fn diff(old: &Schema, new: &Schema) -> Result<Diff, Error> {
+    let old_names = Names::from_schema(old);
+    let new_names = Names::from_schema(new);
+    let old_value = Value::from(old);
+    let new_value = Value::from(new);
+
+    let mut differ = Differ {
+        old: &old_names,
+        new: &new_names,
+        compared: BTreeSet::new(),
+        changes: Vec::new(),
+    };
+    try!(differ.compare(&short_name(&old_value), &old_value, None, &new_value, None));
+    Ok(Diff { changes: differ.changes })
+}
+
+struct Differ<'a> {
+    old: &'a Names,
+    new: &'a Names,
+    // Named types already compared, by old and new full name, so each is
+    // reported once and recursive types end.
+    compared: BTreeSet<(String, String)>,
+    changes: Vec<Change>,
+}
+
+impl<'a> Differ<'a> {
+    fn compare(&mut self, path: &str, old: &Value, old_ns: Option<&str>, new: &Value, new_ns: Option<&str>) -> Result<(), Error> {
+        let (old_names, new_names) = (self.old, self.new);
+        let (old_kind, old_nested) = try!(old_names.kind(old, old_ns));
+        let (new_kind, new_nested) = try!(new_names.kind(new, new_ns));
+        let old_nested = old_nested.as_ref().map(|ns| &ns[..]);
+        let new_nested = new_nested.as_ref().map(|ns| &ns[..]);
+
+        match (old_kind, new_kind) {
+            (SchemaKind::Record(old_def), SchemaKind::Record(new_def)) if names_match(old_def, new_def) => {
+                if self.compare_named(path, old_def, old_nested, new_def, new_nested) {
+                    try!(self.compare_fields(path, old_def, old_nested, new_def, new_nested));
+                }
+                return Ok(());
+            },
+            (SchemaKind::Enum(old_def), SchemaKind::Enum(new_def)) if names_match(old_def, new_def) => {
+                if self.compare_named(path, old_def, old_nested, new_def, new_nested) {
+                    self.compare_symbols(path, old_def, new_def);
+                }
+                return Ok(());
+            },
+            (SchemaKind::Fixed(old_def), SchemaKind::Fixed(new_def)) if names_match(old_def, new_def) => {
+                if old_def.find("size") != new_def.find("size") {
+                    self.push(path, ChangeKind::TypeChanged(type_text(old_def), type_text(new_def)), false, false);
+                } else {
+                    self.compare_named(path, old_def, old_nested, new_def, new_nested);
+                }
+                return Ok(());
+            },
+            (SchemaKind::Array(old_items), SchemaKind::Array(new_items)) => {
+                return self.compare(&format!("{}[]", path), old_items, old_nested, new_items, new_nested);
+            },
+            (SchemaKind::Map(old_values), SchemaKind::Map(new_values)) => {
+                return self.compare(&format!("{}{{}}", path), old_values, old_nested, new_values, new_nested);
+            },
+            (SchemaKind::Union(old_branches), SchemaKind::Union(new_branches)) => {
+                // The same branches, perhaps reordered, are compared one by
+                // one. Anything else changes the union as a whole.
+                if let Some(pairs) = self.pair_branches(old_branches, old_nested, new_branches, new_nested) {
+                    for (old_branch, new_branch) in pairs.into_iter() {
+                        try!(self.compare(path, old_branch, old_nested, new_branch, new_nested));
+                    }
+                    return Ok(());
+                }
+            },
+            _ => (),
+        }
+
+        let (old_text, new_text) = (type_text(old), type_text(new));
+        if old_text != new_text {
+            let backward = Reads::new(old_names, new_names).can_read(old, old_ns, new, new_ns);
+            let forward = Reads::new(new_names, old_names).can_read(new, new_ns, old, old_ns);
+            self.push(path, ChangeKind::TypeChanged(old_text, new_text), backward, forward);
+        }
+        Ok(())
+    }
+
+    // Reports renames, namespace moves and doc changes to a named type, and
+    // whether its contents still need comparing.
+    fn compare_named(&mut self, path: &str, old: &Value, old_ns: Option<&str>, new: &Value, new_ns: Option<&str>) -> bool {
+        if !self.compared.insert((fullname_of(old, old_ns), fullname_of(new, new_ns))) {
+            return false;
+        }
+
+        let (old_name, new_name) = (short_name(old), short_name(new));
+        if old_name != new_name {
+            // Only the new type has the alias, so only the new schema can read
+            // across the rename.
+            let forward = names_match(new, old);
+            self.push(path, ChangeKind::TypeRenamed(old_name, new_name), true, forward);
+        }
+        if old_ns != new_ns {
+            let moved = ChangeKind::NamespaceMoved(old_ns.map(String::from), new_ns.map(String::from));
+            self.push(path, moved, true, true);
+        }
+        if Schema::Object(old.clone()).doc() != Schema::Object(new.clone()).doc() {
+            self.push(path, ChangeKind::DocChanged, true, true);
+        }
+        true
+    }
+
+    fn compare_fields(&mut self, path: &str, old: &Value, old_ns: Option<&str>, new: &Value, new_ns: Option<&str>) -> Result<(), Error> {
+        let old_fields = fields(old);
+        let new_fields = fields(new);
+        let mut matched = BTreeSet::new();
+
+        for new_field in new_fields.iter() {
+            let new_name = new_field.name().cloned().unwrap_or(String::new());
+            let old_field = old_fields.iter().find(|f| f.name() == Some(&new_name))
+                .or_else(|| old_fields.iter().find(|f| f.name().map_or(false, |n| has_alias(new_field.as_value(), n))));
+
+            let old_field = match old_field {
+                Some(old_field) => old_field,
+                None => {
+                    // Old data has no value for the field, so needs a default.
+                    let backward = new_field.default().is_some();
+                    self.push(path, ChangeKind::FieldAdded(new_name), backward, true);
+                    continue;
+                },
+            };
+            let old_name = old_field.name().cloned().unwrap_or(String::new());
+            matched.insert(old_name.clone());
+
+            if old_name != new_name {
+                let forward = old_field.default().is_some();
+                self.push(path, ChangeKind::FieldRenamed(old_name, new_name.clone()), true, forward);
+            }
+
+            let field_path = if path.is_empty() { new_name.clone() } else { format!("{}.{}", path, new_name) };
+            if let (Some(old_type), Some(new_type)) = (old_field.as_value().find("type"), new_field.as_value().find("type")) {
+                try!(self.compare(&field_path, old_type, old_ns, new_type, new_ns));
+            }
+            if old_field.default() != new_field.default() {
+                let changed = ChangeKind::DefaultChanged(old_field.default().cloned(), new_field.default().cloned());
+                self.push(&field_path, changed, true, true);
+            }
+            if old_field.doc() != new_field.doc() {
+                self.push(&field_path, ChangeKind::DocChanged, true, true);
+            }
+        }
+
+        for old_field in old_fields.iter() {
+            let old_name = old_field.name().cloned().unwrap_or(String::new());
+            if !matched.contains(&old_name) {
+                // New data has no value for the field, so old readers need a default.
+                let forward = old_field.default().is_some();
+                self.push(path, ChangeKind::FieldRemoved(old_name), true, forward);
+            }
+        }
+        Ok(())
+    }
+
+    fn compare_symbols(&mut self, path: &str, old: &Value, new: &Value) {
+        let old_symbols = Schema::Object(old.clone()).symbols().unwrap_or_else(Vec::new);
+        let new_symbols = Schema::Object(new.clone()).symbols().unwrap_or_else(Vec::new);
+
+        // A symbol a reader doesn't know becomes the reader's default, if it
+        // has one.
+        let added: Vec<String> = new_symbols.iter().filter(|s| !old_symbols.contains(s)).cloned().collect();
+        if !added.is_empty() {
+            let forward = old.find("default").is_some();
+            self.push(path, ChangeKind::SymbolsAdded(added), true, forward);
+        }
+        let removed: Vec<String> = old_symbols.iter().filter(|s| !new_symbols.contains(s)).cloned().collect();
+        if !removed.is_empty() {
+            let backward = new.find("default").is_some();
+            self.push(path, ChangeKind::SymbolsRemoved(removed), backward, true);
+        }
+        if old.find("default") != new.find("default") {
+            let changed = ChangeKind::DefaultChanged(old.find("default").cloned(), new.find("default").cloned());
+            self.push(path, changed, true, true);
+        }
+    }
+
+    // Each old branch with the new branch of the same type, if the two
+    // unions have the same types.
+    fn pair_branches<'v>(&self, old: &'v Vec<Value>, old_ns: Option<&str>, new: &'v Vec<Value>, new_ns: Option<&str>) -> Option<Vec<(&'v Value, &'v Value)>> {
+        if old.len() != new.len() {
+            return None;
+        }
+        let mut pairs = Vec::new();
+        for old_branch in old.iter() {
+            let old_kind = match self.old.kind(old_branch, old_ns) {
+                Ok((kind, _)) => kind,
+                Err(_) => return None,
+            };
+            let new_branch = new.iter().find(|new_branch| {
+                match self.new.kind(new_branch, new_ns) {
+                    Ok((new_kind, _)) => same_branch(&old_kind, &new_kind),
+                    Err(_) => false,
+                }
+            });
+            match new_branch {
+                Some(new_branch) => pairs.push((old_branch, new_branch)),
+                None => return None,
+            }
+        }
+        Some(pairs)
+    }
+
+    fn push(&mut self, path: &str, kind: ChangeKind, backward: bool, forward: bool) {
+        self.changes.push(Change {
+            path: String::from(path),
+            kind: kind,
+            backward: backward,
+            forward: forward,
+        });
+    }
+}
+
+// Whether a writer schema's data resolves to a reader schema.
+struct Reads<'a> {
+    writer: &'a Names,
+    reader: &'a Names,
+    // Record pairs already being checked, taken to be readable so that
+    // recursive records end.
+    assumed: BTreeSet<(String, String)>,
+}
+
+impl<'a> Reads<'a> {
+    fn new(writer: &'a Names, reader: &'a Names) -> Reads<'a> {
+        Reads { writer: writer, reader: reader, assumed: BTreeSet::new() }
+    }
+
+    fn can_read(&mut self, writer: &Value, writer_ns: Option<&str>, reader: &Value, reader_ns: Option<&str>) -> bool {
+        let (writer_names, reader_names) = (self.writer, self.reader);
+        let (writer_kind, writer_nested) = match writer_names.kind(writer, writer_ns) {
+            Ok(kind) => kind,
+            Err(_) => return false,
+        };
+        let writer_nested = writer_nested.as_ref().map(|ns| &ns[..]);
+
+        // Every branch the writer might have used has to be readable.
+        if let SchemaKind::Union(branches) = writer_kind {
+            return branches.iter().all(|branch| self.can_read(branch, writer_nested, reader, reader_ns));
+        }
+
+        let (reader_kind, reader_nested) = match reader_names.kind(reader, reader_ns) {
+            Ok(kind) => kind,
+            Err(_) => return false,
+        };
+        let reader_nested = reader_nested.as_ref().map(|ns| &ns[..]);
+
+        match (writer_kind, reader_kind) {
+            (_, SchemaKind::Union(branches)) => {
+                branches.iter().any(|branch| self.can_read(writer, writer_ns, branch, reader_nested))
+            },
+            (SchemaKind::Primitive(w), SchemaKind::Primitive(r)) => w == r || can_promote(w, r),
+            (SchemaKind::Record(w), SchemaKind::Record(r)) => {
+                if !names_match(w, r) {
+                    return false;
+                }
+                if !self.assumed.insert((fullname_of(w, writer_nested), fullname_of(r, reader_nested))) {
+                    return true;
+                }
+                let writer_fields = fields(w);
+                for reader_field in fields(r).iter() {
+                    let writer_field = writer_fields.iter().find(|f| {
+                        f.name() == reader_field.name() || f.name().map_or(false, |n| has_alias(reader_field.as_value(), n))
+                    });
+                    let readable = match writer_field {
+                        Some(writer_field) => {
+                            match (writer_field.as_value().find("type"), reader_field.as_value().find("type")) {
+                                (Some(w), Some(r)) => self.can_read(w, writer_nested, r, reader_nested),
+                                _ => false,
+                            }
+                        },
+                        None => reader_field.default().is_some(),
+                    };
+                    if !readable {
+                        return false;
+                    }
+                }
+                true
+            },
+            (SchemaKind::Enum(w), SchemaKind::Enum(r)) => {
+                let reader_symbols = Schema::Object(r.clone()).symbols().unwrap_or_else(Vec::new);
+                let writer_symbols = Schema::Object(w.clone()).symbols().unwrap_or_else(Vec::new);
+                names_match(w, r) && (r.find("default").is_some() || writer_symbols.iter().all(|s| reader_symbols.contains(s)))
+            },
+            (SchemaKind::Fixed(w), SchemaKind::Fixed(r)) => names_match(w, r) && w.find("size") == r.find("size"),
+            (SchemaKind::Array(w), SchemaKind::Array(r)) |
+            (SchemaKind::Map(w), SchemaKind::Map(r)) => self.can_read(w, writer_nested, r, reader_nested),
+            _ => false,
+        }
+    }
+}
+
+fn fields(record: &Value) -> Vec<Field> {
+    match record.find("fields") {
+        Some(&Value::Array(ref fields)) => fields.iter().map(Field::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Union branches that stand for the same type in both versions.
+fn same_branch(old: &SchemaKind, new: &SchemaKind) -> bool {
+    match (old, new) {
+        (&SchemaKind::Primitive(o), &SchemaKind::Primitive(n)) => o == n,
+        (&SchemaKind::Record(o), &SchemaKind::Record(n)) |
+        (&SchemaKind::Enum(o), &SchemaKind::Enum(n)) |
+        (&SchemaKind::Fixed(o), &SchemaKind::Fixed(n)) => names_match(o, n),
+        (&SchemaKind::Array(_), &SchemaKind::Array(_)) |
+        (&SchemaKind::Map(_), &SchemaKind::Map(_)) => true,
+        _ => false,
+    }
+}
+
+// A type as a report shows it: named types by name, fixed with their size.
+fn type_text(value: &Value) -> String {
+    match *value {
+        Value::String(ref name) => name.clone(),
+        Value::Array(ref branches) => {
+            let branches: Vec<String> = branches.iter().map(type_text).collect();
+            format!("union<{}>", branches.join(", "))
+        },
+        Value::Object(_) => {
+            match value.find("type") {
+                Some(&Value::String(ref type_name)) => {
+                    match type_name.as_ref() {
+                        "record" | "error" | "enum" => short_name(value),
+                        "fixed" => format!("{}({})", short_name(value), value.find("size").and_then(|s| s.as_u64()).unwrap_or(0)),
+                        "array" => format!("array<{}>", value.find("items").map(type_text).unwrap_or(String::from("null"))),
+                        "map" => format!("map<{}>", value.find("values").map(type_text).unwrap_or(String::from("null"))),
+                        _ => {
+                            match value.find("logicalType").and_then(|l| l.as_string()) {
+                                Some(logical) => format!("{} ({})", type_name, logical),
+                                None => type_name.clone(),
+                            }
+                        },
+                    }
+                },
+                Some(nested) => type_text(nested),
+                None => String::from("null"),
+            }
+        },
+        _ => String::from("null"),
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChangeKind::FieldAdded(ref name) => write!(f, "field {} added", name),
+            ChangeKind::FieldRemoved(ref name) => write!(f, "field {} removed", name),
+            ChangeKind::FieldRenamed(ref from, ref to) => write!(f, "field {} renamed to {}", from, to),
+            ChangeKind::TypeChanged(ref from, ref to) => write!(f, "type changed from {} to {}", from, to),
+            ChangeKind::DefaultChanged(ref from, ref to) => write!(f, "default changed from {} to {}", default_text(from), default_text(to)),
+            ChangeKind::SymbolsAdded(ref symbols) => write!(f, "symbols added: {}", symbols.join(", ")),
+            ChangeKind::SymbolsRemoved(ref symbols) => write!(f, "symbols removed: {}", symbols.join(", ")),
+            ChangeKind::TypeRenamed(ref from, ref to) => write!(f, "type renamed from {} to {}", from, to),
+            ChangeKind::NamespaceMoved(ref from, ref to) => {
+                write!(f, "namespace moved from {} to {}", from.as_ref().map_or("(none)", |ns| &ns[..]), to.as_ref().map_or("(none)", |ns| &ns[..]))
+            },
+            ChangeKind::DocChanged => write!(f, "doc changed"),
+        }
+    }
+}
+
+fn default_text(default: &Option<Value>) -> String {
+    match *default {
+        Some(ref value) => json::to_string(value).unwrap_or(String::new()),
+        None => String::from("(none)"),
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let compatibility = match (self.backward, self.forward) {
+            (true, true) => "compatible",
+            (false, true) => "breaks backward compatibility",
+            (true, false) => "breaks forward compatibility",
+            (false, false) => "breaks backward and forward compatibility",
+        };
+        if self.path.is_empty() {
+            write!(f, "{} ({})", self.kind, compatibility)
+        } else {
+            write!(f, "{}: {} ({})", self.path, self.kind, compatibility)
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        for change in self.changes.iter() {
+            try!(writeln!(f, "{}", change));
+        }
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        write!(f, "backward compatible: {}, forward compatible: {}", yes_no(self.is_backward_compatible()), yes_no(self.is_forward_compatible()))
+    }
+}
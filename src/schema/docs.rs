@@ -0,0 +1,432 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Reference documentation for a set of schemas or a protocol, as Markdown or
+// HTML. Every named type gets a section of its own, anchored by its full
+// name, and every reference to a named type links to that section.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::json::{self, Value};
+
+use protocol::{Message, Protocol};
+
+use super::model::{Field, Schema};
+use super::names::{fullname_of, is_primitive_type_name, namespace_of, short_name, Names};
+
+/// The markup to write.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Markdown,
+    /// A complete HTML document.
+    Html,
+}
+
+/// Collects the named types of any number of schemas and protocols, in the
+/// order they're first defined, and renders them as one document.
+pub struct DocWriter {
+    format: Format,
+    names: Names,
+    // Full name, definition and the namespace references inside it are
+    // relative to, for each named type.
+    types: Vec<(String, Value, Option<String>)>,
+    defined: BTreeSet<String>,
+    // Protocol messages, with their names and the protocol's namespace.
+    messages: Vec<(String, Message, Option<String>)>,
+}
+
+impl DocWriter {
+    pub fn new(format: Format) -> DocWriter {
+        DocWriter {
+            format: format,
+            names: Names::new(),
+            types: Vec::new(),
+            defined: BTreeSet::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn add_schema(&mut self, schema: &Schema) {
+        self.names.add(schema, None);
+        self.collect(&Value::from(schema), None);
+    }
+
+    /// Adds the protocol's types, and a section for each of its messages.
+    pub fn add_protocol(&mut self, protocol: &Protocol) {
+        let ns = protocol.namespace.as_ref().map(|ns| &ns[..]);
+        for schema in protocol.types.iter() {
+            self.names.add(schema, ns);
+            self.collect(&Value::from(schema), ns);
+        }
+        for &(ref name, ref message) in protocol.messages.iter() {
+            self.messages.push((name.clone(), message.clone(), protocol.namespace.clone()));
+        }
+    }
+
+    pub fn render(&self, title: &str) -> String {
+        let mut out = String::new();
+        if self.format == Format::Html {
+            out.push_str(&format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n", escape_html(title)));
+        }
+        out.push_str(&self.heading(1, &self.escape(title), None));
+
+        // A table of contents.
+        let mut contents: Vec<String> = self.types.iter().map(|&(ref fullname, _, _)| self.type_link(fullname)).collect();
+        if !self.messages.is_empty() {
+            contents.push(self.link("Messages", "messages"));
+        }
+        out.push_str(&self.list(&contents));
+
+        for &(ref fullname, ref definition, ref ns) in self.types.iter() {
+            out.push_str(&self.type_section(fullname, definition, ns.as_ref().map(|ns| &ns[..])));
+        }
+
+        if !self.messages.is_empty() {
+            out.push_str(&self.heading(2, "Messages", Some("messages")));
+            for &(ref name, ref message, ref ns) in self.messages.iter() {
+                out.push_str(&self.message_section(name, message, ns.as_ref().map(|ns| &ns[..])));
+            }
+        }
+
+        if self.format == Format::Html {
+            out.push_str("</body>\n</html>\n");
+        }
+        out
+    }
+
+    // Named types in `value`, each before the types it uses, as with any
+    // reading order that starts at the top.
+    fn collect(&mut self, value: &Value, enclosing_ns: Option<&str>) {
+        match *value {
+            Value::Array(ref branches) => {
+                for branch in branches.iter() {
+                    self.collect(branch, enclosing_ns);
+                }
+            },
+            Value::Object(_) => {
+                let type_name = match value.find("type") {
+                    Some(&Value::String(ref t)) => t.clone(),
+                    Some(nested) => return self.collect(nested, enclosing_ns),
+                    None => return,
+                };
+
+                match type_name.as_ref() {
+                    "record" | "error" | "enum" | "fixed" => {
+                        let ns = namespace_of(value, enclosing_ns);
+                        let fullname = fullname_of(value, ns.as_ref().map(|ns| &ns[..]));
+                        if !self.defined.insert(fullname.clone()) {
+                            return;
+                        }
+                        self.types.push((fullname, value.clone(), ns.clone()));
+
+                        if let Some(&Value::Array(ref fields)) = value.find("fields") {
+                            for field in fields.iter() {
+                                if let Some(field_type) = field.find("type") {
+                                    self.collect(field_type, ns.as_ref().map(|ns| &ns[..]));
+                                }
+                            }
+                        }
+                    },
+                    "array" => {
+                        if let Some(items) = value.find("items") {
+                            self.collect(items, enclosing_ns);
+                        }
+                    },
+                    "map" => {
+                        if let Some(values) = value.find("values") {
+                            self.collect(values, enclosing_ns);
+                        }
+                    },
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn type_section(&self, fullname: &str, definition: &Value, ns: Option<&str>) -> String {
+        let schema = Schema::Object(definition.clone());
+        let kind = definition.find("type").and_then(|t| t.as_string()).unwrap_or("");
+
+        let mut out = self.heading(2, &self.escape(&self.display_name(fullname)), Some(fullname));
+        out.push_str(&self.paragraph(&format!("{} {}", self.emphasis(kind), self.code(fullname))));
+        if let Some(doc) = schema.doc() {
+            out.push_str(&self.paragraph(&self.escape(doc)));
+        }
+        if let Some(aliases) = schema.aliases() {
+            let aliases: Vec<String> = aliases.iter().map(|a| self.code(a)).collect();
+            out.push_str(&self.paragraph(&format!("Aliases: {}", aliases.join(", "))));
+        }
+
+        match kind {
+            "record" | "error" => {
+                let rows: Vec<Vec<String>> = schema.fields().unwrap_or_else(Vec::new).iter().map(|field| {
+                    let order = match field.as_value().find("order") {
+                        Some(_) => field.order().map(|o| o.to_string()).unwrap_or(String::new()),
+                        None => String::new(),
+                    };
+                    vec![
+                        self.code(field.name().map(|n| &n[..]).unwrap_or("")),
+                        self.field_type(field, ns),
+                        self.default(field),
+                        order,
+                        field.aliases().map(|a| a.iter().map(|a| self.code(a)).collect::<Vec<String>>().join(", ")).unwrap_or(String::new()),
+                        field.doc().map(|d| self.escape(&one_line(d))).unwrap_or(String::new()),
+                    ]
+                }).collect();
+                out.push_str(&self.table(&["Field", "Type", "Default", "Order", "Aliases", "Doc"], &rows));
+            },
+            "enum" => {
+                let symbols: Vec<String> = schema.symbols().unwrap_or_else(Vec::new).iter().map(|s| self.code(s)).collect();
+                out.push_str(&self.paragraph(&format!("Symbols: {}", symbols.join(", "))));
+                if let Some(default) = definition.find("default").and_then(|d| d.as_string()) {
+                    out.push_str(&self.paragraph(&format!("Default: {}", self.code(default))));
+                }
+            },
+            "fixed" => {
+                out.push_str(&self.paragraph(&format!("Size: {} bytes", schema.size().unwrap_or(0))));
+                if let Some(logical) = logical_type(definition) {
+                    out.push_str(&self.paragraph(&format!("Logical type: {}", self.escape(&logical))));
+                }
+            },
+            _ => (),
+        }
+        out
+    }
+
+    fn message_section(&self, name: &str, message: &Message, ns: Option<&str>) -> String {
+        let mut out = self.heading(3, &self.escape(name), Some(&format!("messages.{}", name)));
+        if let Some(ref doc) = message.doc {
+            out.push_str(&self.paragraph(&self.escape(doc)));
+        }
+
+        let rows: Vec<Vec<String>> = message.request.iter().map(|param| {
+            let param = Field::from(param);
+            vec![
+                self.code(param.name().map(|n| &n[..]).unwrap_or("")),
+                self.field_type(&param, ns),
+                self.default(&param),
+                param.doc().map(|d| self.escape(&one_line(d))).unwrap_or(String::new()),
+            ]
+        }).collect();
+        if !rows.is_empty() {
+            out.push_str(&self.table(&["Parameter", "Type", "Default", "Doc"], &rows));
+        }
+
+        if message.one_way {
+            out.push_str(&self.paragraph("One-way: there is no response."));
+        } else {
+            out.push_str(&self.paragraph(&format!("Response: {}", self.type_ref(&Value::from(&message.response), ns))));
+        }
+        if !message.errors.is_empty() {
+            let errors: Vec<String> = message.errors.iter().map(|e| self.type_ref(&Value::from(e), ns)).collect();
+            out.push_str(&self.paragraph(&format!("Errors: {}", errors.join(", "))));
+        }
+        out
+    }
+
+    fn field_type(&self, field: &Field, ns: Option<&str>) -> String {
+        match field.as_value().find("type") {
+            Some(field_type) => self.type_ref(field_type, ns),
+            None => String::new(),
+        }
+    }
+
+    fn default(&self, field: &Field) -> String {
+        match field.default() {
+            Some(default) => self.code(&json::to_string(default).unwrap_or(String::new())),
+            None => String::new(),
+        }
+    }
+
+    // A type as written in a field, with named types linked to their
+    // sections.
+    fn type_ref(&self, value: &Value, ns: Option<&str>) -> String {
+        match *value {
+            Value::Null => String::from("null"),
+            Value::String(ref name) => self.name_ref(name, ns),
+            Value::Array(ref branches) => {
+                let branches: Vec<String> = branches.iter().map(|b| self.type_ref(b, ns)).collect();
+                format!("union{}{}{}", self.escape("<"), branches.join(", "), self.escape(">"))
+            },
+            Value::Object(_) => {
+                let type_name = match value.find("type") {
+                    Some(&Value::String(ref t)) => t.clone(),
+                    Some(nested) => return self.type_ref(nested, ns),
+                    None => return String::from("null"),
+                };
+
+                match type_name.as_ref() {
+                    "record" | "error" | "enum" | "fixed" => {
+                        let nested_ns = namespace_of(value, ns);
+                        self.type_link(&fullname_of(value, nested_ns.as_ref().map(|ns| &ns[..])))
+                    },
+                    "array" | "map" => {
+                        let inner = value.find(if type_name == "array" { "items" } else { "values" });
+                        let inner = inner.map(|i| self.type_ref(i, ns)).unwrap_or(String::from("null"));
+                        format!("{}{}{}{}", type_name, self.escape("<"), inner, self.escape(">"))
+                    },
+                    _ => {
+                        let base = self.name_ref(&type_name, ns);
+                        match logical_type(value) {
+                            Some(logical) => format!("{} ({})", base, self.escape(&logical)),
+                            None => base,
+                        }
+                    },
+                }
+            },
+            _ => String::from("null"),
+        }
+    }
+
+    fn name_ref(&self, name: &str, ns: Option<&str>) -> String {
+        if is_primitive_type_name(name) {
+            return String::from(name);
+        }
+        match self.names.resolve(name, ns) {
+            Some(named) => self.type_link(&fullname_of(&named.definition, named.namespace.as_ref().map(|ns| &ns[..]))),
+            None => self.escape(name),
+        }
+    }
+
+    fn type_link(&self, fullname: &str) -> String {
+        self.link(&self.escape(&self.display_name(fullname)), fullname)
+    }
+
+    // Types go by their short names, unless two types share one.
+    fn display_name(&self, fullname: &str) -> String {
+        let name = match fullname.rfind('.') {
+            Some(idx) => &fullname[idx + 1..],
+            None => fullname,
+        };
+        let mut short_names = BTreeMap::new();
+        for &(_, ref definition, _) in self.types.iter() {
+            *short_names.entry(short_name(definition)).or_insert(0) += 1;
+        }
+        if short_names.get(name).map_or(false, |&n| n > 1) {
+            String::from(fullname)
+        } else {
+            String::from(name)
+        }
+    }
+
+    fn heading(&self, level: usize, text: &str, anchor: Option<&str>) -> String {
+        match (self.format, anchor) {
+            (Format::Markdown, Some(anchor)) => format!("<a id=\"{}\"></a>\n{} {}\n\n", escape_html(anchor), "#".repeat(level), text),
+            (Format::Markdown, None) => format!("{} {}\n\n", "#".repeat(level), text),
+            (Format::Html, Some(anchor)) => format!("<h{} id=\"{}\">{}</h{}>\n", level, escape_html(anchor), text, level),
+            (Format::Html, None) => format!("<h{}>{}</h{}>\n", level, text, level),
+        }
+    }
+
+    fn paragraph(&self, text: &str) -> String {
+        match self.format {
+            Format::Markdown => format!("{}\n\n", text),
+            Format::Html => format!("<p>{}</p>\n", text),
+        }
+    }
+
+    fn list(&self, items: &[String]) -> String {
+        if items.is_empty() {
+            return String::new();
+        }
+        match self.format {
+            Format::Markdown => format!("{}\n\n", items.iter().map(|i| format!("- {}", i)).collect::<Vec<String>>().join("\n")),
+            Format::Html => format!("<ul>\n{}</ul>\n", items.iter().map(|i| format!("<li>{}</li>\n", i)).collect::<String>()),
+        }
+    }
+
+    fn table(&self, headers: &[&str], rows: &[Vec<String>]) -> String {
+        match self.format {
+            Format::Markdown => {
+                let mut out = format!("| {} |\n", headers.join(" | "));
+                out.push_str(&format!("|{}\n", headers.iter().map(|_| " --- |").collect::<String>()));
+                for row in rows.iter() {
+                    out.push_str(&format!("| {} |\n", row.join(" | ")));
+                }
+                out.push('\n');
+                out
+            },
+            Format::Html => {
+                let mut out = String::from("<table>\n<thead>\n<tr>");
+                for header in headers.iter() {
+                    out.push_str(&format!("<th>{}</th>", header));
+                }
+                out.push_str("</tr>\n</thead>\n<tbody>\n");
+                for row in rows.iter() {
+                    out.push_str("<tr>");
+                    for cell in row.iter() {
+                        out.push_str(&format!("<td>{}</td>", cell));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</tbody>\n</table>\n");
+                out
+            },
+        }
+    }
+
+    fn link(&self, text: &str, anchor: &str) -> String {
+        match self.format {
+            Format::Markdown => format!("[{}](#{})", text, anchor),
+            Format::Html => format!("<a href=\"#{}\">{}</a>", escape_html(anchor), text),
+        }
+    }
+
+    fn code(&self, text: &str) -> String {
+        match self.format {
+            Format::Markdown => format!("`{}`", text.replace("`", "'").replace("|", "\\|")),
+            Format::Html => format!("<code>{}</code>", escape_html(text)),
+        }
+    }
+
+    fn emphasis(&self, text: &str) -> String {
+        match self.format {
+            Format::Markdown => format!("*{}*", text),
+            Format::Html => format!("<em>{}</em>", escape_html(text)),
+        }
+    }
+
+    // Plain text, made safe for the markup. In Markdown that includes table
+    // cells, where a bare "|" would end the cell.
+    fn escape(&self, text: &str) -> String {
+        match self.format {
+            Format::Markdown => escape_html(text).replace("|", "\\|"),
+            Format::Html => escape_html(text),
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+// Table cells have to fit on one line.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// A logical type as it reads in the docs: decimals with their precision and
+// scale.
+fn logical_type(value: &Value) -> Option<String> {
+    let logical = match value.find("logicalType").and_then(|l| l.as_string()) {
+        Some(logical) => logical,
+        None => return None,
+    };
+    if logical == "decimal" {
+        let precision = value.find("precision").and_then(|p| p.as_u64()).unwrap_or(0);
+        let scale = value.find("scale").and_then(|s| s.as_u64()).unwrap_or(0);
+        return Some(format!("decimal({}, {})", precision, scale));
+    }
+    Some(String::from(logical))
+}
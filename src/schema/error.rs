@@ -17,6 +17,8 @@ use std::fmt;
 use std::io;
 use serde::json::{self};
 
+use datum::Datum;
+
 #[derive(Clone, PartialEq)]
 pub enum ErrorCode {
     Unknown,
@@ -36,6 +38,49 @@ pub enum ErrorCode {
     NotWellFormedName,
     FieldNameNotWellFormed,
     CannotNestArrays,
+    CannotNestUnions,
+    NotValidProtocol,
+    ExpectedNamedType,
+    DuplicateNamedType,
+    NotValidMessage,
+    OneWayMessageHasResponse,
+    DatumSchemaMismatch,
+    UnknownNamedType,
+    MissingFieldValue,
+    UnionBranchNotFound,
+    UnionIndexOutOfRange,
+    EnumSymbolNotFound,
+    EnumIndexOutOfRange,
+    FixedSizeMismatch,
+    IntegerOutOfRange,
+    NegativeLength,
+    InvalidBoolean,
+    InvalidUtf8,
+    UnknownMessage,
+    HandshakeFailed,
+    SchemaResolutionFailed,
+    BadSingleObjectMarker,
+    UnknownFingerprint,
+    BadWireFormatMagic,
+    UnknownSchemaId,
+    UnknownSubject,
+    InvalidSubject,
+    NotAContainerFile,
+    MissingContainerSchema,
+    UnknownCodec,
+    CorruptBlock,
+    SyncMarkerMismatch,
+    HeaderAlreadyWritten,
+    ReservedMetadataKey,
+    RecursionLimitExceeded,
+    MapsNotComparable,
+    NotValidSortOrder,
+    UnknownProjectedField,
+    ReaderSchemaNotSupported,
+    ContainerSchemaMismatch,
+    NotRepresentableInArrow,
+    ArrowConversionFailed,
+    NotRepresentableInSql,
     JsonErrorCode(json::ErrorCode)
 }
 
@@ -56,11 +101,54 @@ impl fmt::Debug for ErrorCode {
             ErrorCode::ExpectedItemsAttribute   => "expected items attribute for array type".fmt(f),
             ErrorCode::ExpectedValuesAttribute  => "expected values attribute for map type".fmt(f),
             ErrorCode::FieldDefaultTypeMismatch => "field default type does not match field type".fmt(f),
-            ErrorCode::FieldTooManyElementsOfSameType => "field union type has too many elements of the same schema type".fmt(f),
+            ErrorCode::FieldTooManyElementsOfSameType => "union type has too many elements of the same schema type".fmt(f),
             ErrorCode::UnknownFieldType         => "field type is unexpected/unknown".fmt(f),
             ErrorCode::NotWellFormedName        => "name is not valid/well formed".fmt(f),
             ErrorCode::FieldNameNotWellFormed   => "field name is not valid/well formed".fmt(f),
             ErrorCode::CannotNestArrays         => "arrays cannot be nested".fmt(f),
+            ErrorCode::CannotNestUnions         => "unions cannot directly contain other unions".fmt(f),
+            ErrorCode::NotValidProtocol         => "not a valid protocol definition".fmt(f),
+            ErrorCode::ExpectedNamedType        => "expected a named type (record, error, enum or fixed)".fmt(f),
+            ErrorCode::DuplicateNamedType       => "named type is defined more than once".fmt(f),
+            ErrorCode::NotValidMessage          => "not a valid protocol message definition".fmt(f),
+            ErrorCode::OneWayMessageHasResponse => "one-way messages must have a null response and no errors".fmt(f),
+            ErrorCode::DatumSchemaMismatch      => "datum does not match the schema".fmt(f),
+            ErrorCode::UnknownNamedType         => "reference to an undefined named type".fmt(f),
+            ErrorCode::MissingFieldValue        => "record field has no value and no default".fmt(f),
+            ErrorCode::UnionBranchNotFound      => "datum does not match any branch of the union".fmt(f),
+            ErrorCode::UnionIndexOutOfRange     => "union branch index is out of range".fmt(f),
+            ErrorCode::EnumSymbolNotFound       => "symbol is not in the enum".fmt(f),
+            ErrorCode::EnumIndexOutOfRange      => "enum symbol index is out of range".fmt(f),
+            ErrorCode::FixedSizeMismatch        => "fixed value is not the declared size".fmt(f),
+            ErrorCode::IntegerOutOfRange        => "integer is out of range for its type".fmt(f),
+            ErrorCode::NegativeLength           => "length of bytes or string is negative".fmt(f),
+            ErrorCode::InvalidBoolean           => "boolean is neither 0 nor 1".fmt(f),
+            ErrorCode::InvalidUtf8              => "string is not valid UTF-8".fmt(f),
+            ErrorCode::UnknownMessage           => "message is not defined by the protocol".fmt(f),
+            ErrorCode::HandshakeFailed          => "protocol handshake failed".fmt(f),
+            ErrorCode::SchemaResolutionFailed   => "writer's schema cannot be read with the reader's schema".fmt(f),
+            ErrorCode::BadSingleObjectMarker    => "data does not start with the single object marker".fmt(f),
+            ErrorCode::UnknownFingerprint       => "no schema with that fingerprint is known".fmt(f),
+            ErrorCode::BadWireFormatMagic       => "data does not start with the schema registry magic byte".fmt(f),
+            ErrorCode::UnknownSchemaId          => "no schema is registered with that id".fmt(f),
+            ErrorCode::UnknownSubject           => "no schema is registered under that subject".fmt(f),
+            ErrorCode::InvalidSubject           => "subject cannot be used as a file name".fmt(f),
+            ErrorCode::NotAContainerFile        => "data is not an Avro object container file".fmt(f),
+            ErrorCode::MissingContainerSchema   => "container file header has no avro.schema".fmt(f),
+            ErrorCode::UnknownCodec             => "compression codec is not supported".fmt(f),
+            ErrorCode::CorruptBlock             => "block is corrupt or cannot be decompressed".fmt(f),
+            ErrorCode::SyncMarkerMismatch       => "block is not followed by the file's sync marker".fmt(f),
+            ErrorCode::HeaderAlreadyWritten     => "metadata cannot be set once the header is written".fmt(f),
+            ErrorCode::ReservedMetadataKey      => "metadata keys starting with avro. are reserved".fmt(f),
+            ErrorCode::RecursionLimitExceeded   => "recursive record cannot end within the depth limit".fmt(f),
+            ErrorCode::MapsNotComparable        => "maps have no sort order and cannot be compared".fmt(f),
+            ErrorCode::NotValidSortOrder        => "field order must be ascending, descending or ignore".fmt(f),
+            ErrorCode::UnknownProjectedField    => "projected field path does not name a field of a record".fmt(f),
+            ErrorCode::ReaderSchemaNotSupported => "borrowed reads cannot resolve against a reader schema".fmt(f),
+            ErrorCode::ContainerSchemaMismatch  => "schema does not match the container file's schema".fmt(f),
+            ErrorCode::NotRepresentableInArrow  => "schema has no Arrow equivalent (a recursive type or a decimal over 38 digits)".fmt(f),
+            ErrorCode::ArrowConversionFailed    => "datums could not be built into Arrow arrays".fmt(f),
+            ErrorCode::NotRepresentableInSql    => "schema has no SQL table equivalent (not a record, or a record inside itself)".fmt(f),
             ErrorCode::JsonErrorCode(ref ec)    => ec.fmt(f),
         }
     }
@@ -72,6 +160,12 @@ pub enum Error {
     SyntaxError(ErrorCode, usize, usize),
     IoError(io::Error),
     MissingAttributeError(&'static str),
+    /// A datum couldn't be written with the given schema.
+    EncodeError(ErrorCode),
+    /// Encoded data couldn't be read with the given schema.
+    DecodeError(ErrorCode),
+    /// The error value returned by the remote side of an RPC call.
+    RemoteError(Datum),
 }
 
 impl error::Error for Error {
@@ -80,6 +174,9 @@ impl error::Error for Error {
             Error::SyntaxError(..) => "syntax error",
             Error::IoError(ref error) => error::Error::description(error),
             Error::MissingAttributeError(_) => "missing required attribute",
+            Error::EncodeError(..) => "encoding error",
+            Error::DecodeError(..) => "decoding error",
+            Error::RemoteError(..) => "remote error",
         }
     }
 
@@ -102,6 +199,19 @@ impl fmt::Display for Error {
             Error::MissingAttributeError(ref attribute) => {
                 write!(fmt, "missing attribute {}", attribute)
             }
+            Error::EncodeError(ref code) => write!(fmt, "cannot encode: {:?}", code),
+            Error::DecodeError(ref code) => write!(fmt, "cannot decode: {:?}", code),
+            Error::RemoteError(ref datum) => write!(fmt, "remote error: {:?}", datum),
+        }
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(error: json::Error) -> Error {
+        match error {
+            json::Error::SyntaxError(code, line, col) => Error::SyntaxError(ErrorCode::JsonErrorCode(code), line, col),
+            json::Error::IoError(error) => Error::IoError(error),
+            json::Error::MissingFieldError(field) => Error::MissingAttributeError(field),
         }
     }
 }
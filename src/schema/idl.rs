@@ -0,0 +1,439 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use serde::json::{self, Value};
+
+use protocol::Message;
+
+use super::model::Schema;
+use super::names::{namespace_of, fullname_of, short_name};
+
+// Identifiers that have to be escaped with backticks when used as a name.
+const IDL_KEYWORDS: [&'static str; 31] = [
+    "array", "boolean", "bytes", "date", "decimal", "double", "enum", "error",
+    "false", "fixed", "float", "idl", "import", "int", "local_timestamp_ms",
+    "long", "map", "namespace", "null", "oneway", "protocol", "record", "schema",
+    "string", "throws", "time_ms", "timestamp_ms", "true", "union", "uuid",
+    "void",
+];
+
+impl Schema {
+    /// Renders the schema as an Avro IDL schema file. Named types are declared
+    /// before anything that refers to them, followed by the `schema` declaration
+    /// for the top level type.
+    pub fn to_idl(&self) -> String {
+        let mut writer = IdlWriter::new(self.idl_namespace());
+        writer.collect(self);
+
+        let mut idl = String::new();
+        if let Some(ref ns) = writer.namespace {
+            idl.push_str(&format!("namespace {};\n\n", ns));
+        }
+        idl.push_str(&format!("schema {};\n", writer.type_ref_schema(self)));
+        for decl in writer.decls.iter() {
+            idl.push_str("\n");
+            idl.push_str(decl);
+        }
+        idl
+    }
+
+    /// Renders the named types in the schema as the body of an Avro IDL protocol.
+    pub fn to_idl_protocol(&self, protocol_name: &str) -> String {
+        let mut writer = IdlWriter::new(self.idl_namespace());
+        writer.collect(self);
+        writer.protocol(protocol_name, None, &[])
+    }
+
+    // The namespace of the top level named type becomes the namespace of the
+    // IDL file, so that everything declared in it can use its short name.
+    fn idl_namespace(&self) -> Option<String> {
+        match *self {
+            Schema::Object(ref value) => namespace_of(value, None),
+            _ => None,
+        }
+    }
+}
+
+/// Accumulates the named type declarations for a schema (or set of schemas),
+/// in dependency order.
+pub struct IdlWriter {
+    pub namespace: Option<String>,
+    pub decls: Vec<String>,
+    defined: BTreeSet<String>,
+}
+
+impl IdlWriter {
+    pub fn new(namespace: Option<String>) -> IdlWriter {
+        IdlWriter { namespace: namespace, decls: Vec::new(), defined: BTreeSet::new() }
+    }
+
+    pub fn collect(&mut self, schema: &Schema) {
+        match *schema {
+            Schema::Object(ref value) => {
+                let ns = self.namespace.clone();
+                self.collect_value(value, ns.as_ref().map(|s| s.as_ref()));
+            },
+            Schema::Union(ref vec) => {
+                for s in vec.iter() {
+                    self.collect(s);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Wraps the collected declarations (and any already rendered messages) in
+    /// a protocol declaration.
+    pub fn protocol(&self, protocol_name: &str, doc: Option<&String>, messages: &[String]) -> String {
+        let mut idl = String::new();
+        if let Some(doc) = doc {
+            idl.push_str(&doc_comment(doc, ""));
+        }
+        if let Some(ref ns) = self.namespace {
+            idl.push_str(&format!("@namespace({})\n", json_string(ns)));
+        }
+        idl.push_str(&format!("protocol {} {{\n", identifier(protocol_name)));
+
+        let mut first = true;
+        for block in self.decls.iter().chain(messages.iter()) {
+            if !first {
+                idl.push_str("\n");
+            }
+            first = false;
+            for line in block.lines() {
+                if line.is_empty() {
+                    idl.push_str("\n");
+                } else {
+                    idl.push_str(&format!("  {}\n", line));
+                }
+            }
+        }
+        idl.push_str("}\n");
+        idl
+    }
+
+    /// Renders a protocol message declaration.
+    pub fn message(&self, name: &str, message: &Message) -> String {
+        let ns = self.namespace.clone();
+        let ns = ns.as_ref().map(|s| s.as_ref());
+
+        let mut decl = String::new();
+        if let Some(ref doc) = message.doc {
+            decl.push_str(&doc_comment(doc, ""));
+        }
+
+        let response = match message.response {
+            Schema::Null => String::from("void"),
+            Schema::String(ref s) if s == "null" => String::from("void"),
+            _ => self.type_ref(&Value::from(&message.response), ns),
+        };
+
+        let params: Vec<String> = message.request.iter().map(|param| {
+            let param_type = param.find("type").map(|t| self.type_ref(t, ns)).unwrap_or(String::from("null"));
+            let param_name = param.find("name").and_then(|n| n.as_string()).unwrap_or("");
+            match param.find("default") {
+                Some(default) => format!("{} {} = {}", param_type, identifier(param_name), json_text(default)),
+                None => format!("{} {}", param_type, identifier(param_name)),
+            }
+        }).collect();
+
+        decl.push_str(&format!("{} {}({})", response, identifier(name), params.join(", ")));
+        if !message.errors.is_empty() {
+            let errors: Vec<String> = message.errors.iter().map(|e| self.type_ref(&Value::from(e), ns)).collect();
+            decl.push_str(&format!(" throws {}", errors.join(", ")));
+        }
+        if message.one_way {
+            decl.push_str(" oneway");
+        }
+        decl.push_str(";\n");
+        decl
+    }
+
+    fn collect_value(&mut self, value: &Value, enclosing_ns: Option<&str>) {
+        match *value {
+            Value::Array(ref vec) => {
+                for v in vec.iter() {
+                    self.collect_value(v, enclosing_ns);
+                }
+            },
+            Value::Object(_) => {
+                let type_name = match value.find("type") {
+                    Some(&Value::String(ref t)) => t.clone(),
+                    Some(nested) => {
+                        // {"type": {"type": "array", ...}} is just a wrapped type.
+                        self.collect_value(nested, enclosing_ns);
+                        return;
+                    },
+                    None => return,
+                };
+
+                match type_name.as_ref() {
+                    "record" | "error" | "enum" | "fixed" => {
+                        let ns = namespace_of(value, enclosing_ns);
+                        let fullname = fullname_of(value, ns.as_ref().map(|s| s.as_ref()));
+                        if self.defined.contains(&fullname) {
+                            return;
+                        }
+                        self.defined.insert(fullname);
+
+                        if let Some(&Value::Array(ref fields)) = value.find("fields") {
+                            for field in fields.iter() {
+                                if let Some(field_type) = field.find("type") {
+                                    self.collect_value(field_type, ns.as_ref().map(|s| s.as_ref()));
+                                }
+                            }
+                        }
+
+                        let decl = self.declaration(&type_name, value, ns.as_ref().map(|s| s.as_ref()));
+                        self.decls.push(decl);
+                    },
+                    "array" => {
+                        if let Some(items) = value.find("items") {
+                            self.collect_value(items, enclosing_ns);
+                        }
+                    },
+                    "map" => {
+                        if let Some(values) = value.find("values") {
+                            self.collect_value(values, enclosing_ns);
+                        }
+                    },
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn declaration(&self, type_name: &str, value: &Value, ns: Option<&str>) -> String {
+        let mut decl = String::new();
+        if let Some(&Value::String(ref doc)) = value.find("doc") {
+            decl.push_str(&doc_comment(doc, ""));
+        }
+        if ns.map(|s| String::from(s)) != self.namespace {
+            decl.push_str(&format!("@namespace({})\n", json_string(ns.unwrap_or(""))));
+        }
+        if let Some(aliases) = value.find("aliases") {
+            decl.push_str(&format!("@aliases({})\n", json_text(aliases)));
+        }
+
+        let name = short_name(value);
+        match type_name {
+            "record" | "error" => {
+                decl.push_str(&format!("{} {} {{\n", type_name, identifier(&name)));
+                if let Some(&Value::Array(ref fields)) = value.find("fields") {
+                    for field in fields.iter() {
+                        decl.push_str(&self.field(field, ns));
+                    }
+                }
+                decl.push_str("}\n");
+            },
+            "enum" => {
+                let symbols: Vec<String> = match value.find("symbols") {
+                    Some(&Value::Array(ref vec)) => {
+                        vec.iter().filter_map(|s| s.as_string()).map(|s| identifier(s)).collect()
+                    },
+                    _ => Vec::new(),
+                };
+                decl.push_str(&format!("enum {} {{\n  {}\n}}", identifier(&name), symbols.join(", ")));
+                if let Some(&Value::String(ref default)) = value.find("default") {
+                    decl.push_str(&format!(" = {};", identifier(default)));
+                }
+                decl.push_str("\n");
+            },
+            "fixed" => {
+                let size = value.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                decl.push_str(&format!("fixed {}({});\n", identifier(&name), size));
+            },
+            _ => unreachable!(),
+        }
+        decl
+    }
+
+    fn field(&self, field: &Value, ns: Option<&str>) -> String {
+        let mut line = String::new();
+        if let Some(&Value::String(ref doc)) = field.find("doc") {
+            line.push_str(&doc_comment(doc, "  "));
+        }
+
+        let field_type = match field.find("type") {
+            Some(t) => self.type_ref(t, ns),
+            None => String::from("null"),
+        };
+        line.push_str(&format!("  {}", field_type));
+
+        if let Some(&Value::String(ref order)) = field.find("order") {
+            line.push_str(&format!(" @order({})", json_string(order)));
+        }
+        if let Some(aliases) = field.find("aliases") {
+            line.push_str(&format!(" @aliases({})", json_text(aliases)));
+        }
+
+        let name = match field.find("name") {
+            Some(&Value::String(ref n)) => n.clone(),
+            _ => String::new(),
+        };
+        line.push_str(&format!(" {}", identifier(&name)));
+
+        if let Some(default) = field.find("default") {
+            line.push_str(&format!(" = {}", json_text(default)));
+        }
+        line.push_str(";\n");
+        line
+    }
+
+    /// The IDL spelling of a type used as a field type, message parameter,
+    /// response and so on. Named types are referred to by name.
+    pub fn type_ref(&self, value: &Value, enclosing_ns: Option<&str>) -> String {
+        match *value {
+            Value::Null => String::from("null"),
+            Value::String(ref s) => self.name_ref(s, enclosing_ns),
+            Value::Array(ref vec) => {
+                let branches: Vec<String> = vec.iter().map(|v| self.type_ref(v, enclosing_ns)).collect();
+                format!("union {{ {} }}", branches.join(", "))
+            },
+            Value::Object(ref map) => {
+                let type_name = match value.find("type") {
+                    Some(&Value::String(ref t)) => t.clone(),
+                    Some(nested) => return self.type_ref(nested, enclosing_ns),
+                    None => return String::from("null"),
+                };
+
+                match type_name.as_ref() {
+                    "record" | "error" | "enum" | "fixed" => {
+                        let ns = namespace_of(value, enclosing_ns);
+                        let fullname = fullname_of(value, ns.as_ref().map(|s| s.as_ref()));
+                        self.relative_name(&fullname)
+                    },
+                    "array" => {
+                        let items = value.find("items").map(|v| self.type_ref(v, enclosing_ns));
+                        format!("array<{}>", items.unwrap_or(String::from("null")))
+                    },
+                    "map" => {
+                        let values = value.find("values").map(|v| self.type_ref(v, enclosing_ns));
+                        format!("map<{}>", values.unwrap_or(String::from("null")))
+                    },
+                    _ => {
+                        if let Some(logical) = logical_type_keyword(&type_name, value) {
+                            return logical;
+                        }
+
+                        // Anything else on a primitive (or a reference) is carried
+                        // along as an annotation on the type.
+                        let mut annotated = String::new();
+                        for (key, prop) in map.iter() {
+                            if key != "type" {
+                                annotated.push_str(&format!("@{}({}) ", key, json_text(prop)));
+                            }
+                        }
+                        annotated.push_str(&self.name_ref(&type_name, enclosing_ns));
+                        annotated
+                    }
+                }
+            },
+            _ => String::from("null"),
+        }
+    }
+
+    fn type_ref_schema(&self, schema: &Schema) -> String {
+        let ns = self.namespace.clone();
+        match *schema {
+            Schema::Null => String::from("null"),
+            Schema::String(ref s) => self.name_ref(s, ns.as_ref().map(|s| s.as_ref())),
+            Schema::Object(ref value) => self.type_ref(value, ns.as_ref().map(|s| s.as_ref())),
+            Schema::Union(ref vec) => {
+                let branches: Vec<String> = vec.iter().map(|s| self.type_ref_schema(s)).collect();
+                format!("union {{ {} }}", branches.join(", "))
+            }
+        }
+    }
+
+    fn name_ref(&self, name: &str, enclosing_ns: Option<&str>) -> String {
+        match name {
+            "null" | "boolean" | "int" | "long" | "float" | "double" | "bytes" | "string" => String::from(name),
+            _ => {
+                if name.contains(".") {
+                    self.relative_name(name)
+                } else {
+                    match enclosing_ns {
+                        Some(ns) if !ns.is_empty() => self.relative_name(&format!("{}.{}", ns, name)),
+                        _ => identifier(name),
+                    }
+                }
+            }
+        }
+    }
+
+    // Names in the file (or protocol) namespace are written short, anything
+    // else has to be fully qualified.
+    fn relative_name(&self, fullname: &str) -> String {
+        let (ns, name) = match fullname.rfind('.') {
+            Some(idx) => (&fullname[..idx], &fullname[idx + 1..]),
+            None => ("", fullname),
+        };
+
+        let same_ns = match self.namespace {
+            Some(ref file_ns) => file_ns == ns,
+            None => ns.is_empty(),
+        };
+
+        if same_ns {
+            identifier(name)
+        } else {
+            fullname.split('.').map(|segment| identifier(segment)).collect::<Vec<String>>().join(".")
+        }
+    }
+}
+
+fn logical_type_keyword(type_name: &str, value: &Value) -> Option<String> {
+    let logical = match value.find("logicalType") {
+        Some(&Value::String(ref l)) => l.clone(),
+        _ => return None,
+    };
+
+    match (logical.as_ref(), type_name) {
+        ("date", "int")                     => Some(String::from("date")),
+        ("time-millis", "int")              => Some(String::from("time_ms")),
+        ("timestamp-millis", "long")        => Some(String::from("timestamp_ms")),
+        ("local-timestamp-millis", "long")  => Some(String::from("local_timestamp_ms")),
+        ("uuid", "string")                  => Some(String::from("uuid")),
+        ("decimal", "bytes") => {
+            let precision = value.find("precision").and_then(|p| p.as_u64());
+            let scale = value.find("scale").and_then(|s| s.as_u64()).unwrap_or(0);
+            precision.map(|p| format!("decimal({}, {})", p, scale))
+        },
+        _ => None,
+    }
+}
+
+fn identifier(name: &str) -> String {
+    if IDL_KEYWORDS.contains(&name) {
+        format!("`{}`", name)
+    } else {
+        String::from(name)
+    }
+}
+
+pub fn doc_comment(doc: &str, indent: &str) -> String {
+    format!("{}/** {} */\n", indent, doc.replace("*/", "*\\/"))
+}
+
+fn json_text(value: &Value) -> String {
+    json::to_string(value).unwrap_or(String::from("null"))
+}
+
+fn json_string(s: &str) -> String {
+    json_text(&Value::String(String::from(s)))
+}
@@ -0,0 +1,779 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Conversion between Avro schemas and JSON Schema (draft-07). The JSON Schema
+// describes datums as plain JSON, not the Avro JSON encoding: union values
+// aren't wrapped in an object naming their branch.
+//
+// Named types go in "definitions" and are referred to with "$ref", so
+// recursive types come out right; the top level type is written in place and
+// referred to as "#". Whatever one side can say and the other can't is left
+// out with a warning.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::json::Value;
+
+use super::model::Schema;
+use super::names::{self, Names};
+
+const DRAFT: &'static str = "http://json-schema.org/draft-07/schema#";
+
+// Avro primitives with a logical type, and the JSON Schema type and format
+// they become.
+const FORMATS: [(&'static str, &'static str, &'static str, &'static str); 6] = [
+    ("string", "uuid", "string", "uuid"),
+    ("int", "date", "integer", "date"),
+    ("int", "time-millis", "integer", "time-millis"),
+    ("long", "time-micros", "integer", "time-micros"),
+    ("long", "timestamp-millis", "integer", "timestamp-millis"),
+    ("long", "timestamp-micros", "integer", "timestamp-micros"),
+];
+
+// JSON Schema keywords that only constrain values, which Avro can't express.
+const CONSTRAINTS: [&'static str; 21] = [
+    "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf",
+    "minLength", "maxLength", "pattern", "minItems", "maxItems", "uniqueItems",
+    "contains", "minProperties", "maxProperties", "patternProperties",
+    "propertyNames", "dependencies", "if", "then", "else", "not",
+];
+
+/// Something that couldn't be carried over in a conversion, and where. Paths
+/// are dotted Avro names going to JSON Schema and JSON pointers coming back.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Warning {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// A converted schema, with warnings for whatever was lost on the way.
+#[derive(Clone, Debug)]
+pub struct Conversion<T> {
+    pub schema: T,
+    pub warnings: Vec<Warning>,
+}
+
+/// The JSON Schema for datums written with `schema`.
+pub fn to_json_schema(schema: &Schema) -> Conversion<Value> {
+    let names = Names::from_schema(schema);
+    let value = Value::from(schema);
+    let root = match value.find("type").and_then(|t| t.as_string()) {
+        Some("record") | Some("error") | Some("enum") | Some("fixed") => {
+            Some(names::fullname_of(&value, names::namespace_of(&value, None).as_ref().map(|ns| &ns[..])))
+        },
+        _ => None,
+    };
+
+    let mut converter = ToJsonSchema {
+        names: &names,
+        root: root,
+        definitions: BTreeMap::new(),
+        converted: BTreeSet::new(),
+        warnings: Vec::new(),
+    };
+    let mut json_schema = match converter.convert(&value, None, "") {
+        Value::Object(map) => map,
+        _ => BTreeMap::new(),
+    };
+    json_schema.insert(String::from("$schema"), Value::String(String::from(DRAFT)));
+    if !converter.definitions.is_empty() {
+        json_schema.insert(String::from("definitions"), Value::Object(converter.definitions));
+    }
+
+    Conversion { schema: Value::Object(json_schema), warnings: converter.warnings }
+}
+
+/// A best-effort Avro schema for the JSON Schema `json_schema`. The top level
+/// record, if there is one, is called `name` unless the JSON Schema has a
+/// title; nested records are named after their titles or properties.
+pub fn from_json_schema(json_schema: &Value, name: &str) -> Conversion<Schema> {
+    let mut converter = FromJsonSchema {
+        root: json_schema,
+        names: BTreeSet::new(),
+        refs: BTreeMap::new(),
+        following: BTreeSet::new(),
+        warnings: Vec::new(),
+    };
+    let value = converter.convert(json_schema, name, "", Some(String::from("#")));
+    Conversion { schema: Schema::from(&value), warnings: converter.warnings }
+}
+
+struct ToJsonSchema<'a> {
+    names: &'a Names,
+    // The full name of the top level type, when it's a named type.
+    root: Option<String>,
+    definitions: BTreeMap<String, Value>,
+    // Named types written out or being written out.
+    converted: BTreeSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> ToJsonSchema<'a> {
+    fn warn(&mut self, path: &str, message: String) {
+        self.warnings.push(Warning { path: String::from(path), message: message });
+    }
+
+    fn convert(&mut self, schema: &Value, ns: Option<&str>, path: &str) -> Value {
+        match *schema {
+            Value::Null => object(vec![("type", string("null"))]),
+            Value::String(ref name) => self.by_name(name, schema, ns, path),
+            Value::Array(ref branches) => self.union(branches, ns, path),
+            Value::Object(_) => {
+                match schema.find("type") {
+                    Some(&Value::String(ref type_name)) => {
+                        match type_name.as_ref() {
+                            "record" | "error" | "enum" | "fixed" => {
+                                let named_ns = names::namespace_of(schema, ns);
+                                self.named(schema, named_ns.as_ref().map(|ns| &ns[..]))
+                            },
+                            "array" => {
+                                let items = schema.find("items").cloned().unwrap_or(Value::Null);
+                                let items = self.convert(&items, ns, path);
+                                object(vec![("type", string("array")), ("items", items)])
+                            },
+                            "map" => {
+                                let values = schema.find("values").cloned().unwrap_or(Value::Null);
+                                let values = self.convert(&values, ns, path);
+                                object(vec![("type", string("object")), ("additionalProperties", values)])
+                            },
+                            _ => self.by_name(type_name, schema, ns, path),
+                        }
+                    },
+                    Some(nested) => self.convert(nested, ns, path),
+                    None => {
+                        self.warn(path, String::from("type has no \"type\" attribute; anything goes"));
+                        Value::Object(BTreeMap::new())
+                    },
+                }
+            },
+            _ => {
+                self.warn(path, String::from("not a type; anything goes"));
+                Value::Object(BTreeMap::new())
+            },
+        }
+    }
+
+    // A primitive, or a reference to a named type. `schema` is where any
+    // logical type is.
+    fn by_name(&mut self, name: &str, schema: &Value, ns: Option<&str>, path: &str) -> Value {
+        if names::is_primitive_type_name(name) {
+            return self.primitive(name, schema, path);
+        }
+        let named = match self.names.resolve(name, ns) {
+            Some(named) => named.clone(),
+            None => {
+                self.warn(path, format!("{} is not defined; anything goes", name));
+                return Value::Object(BTreeMap::new());
+            },
+        };
+        self.named(&named.definition, named.namespace.as_ref().map(|ns| &ns[..]))
+    }
+
+    fn primitive(&mut self, name: &str, schema: &Value, path: &str) -> Value {
+        let logical = schema.find("logicalType").and_then(|l| l.as_string());
+        if let Some(logical) = logical {
+            for &(avro_type, logical_type, json_type, format) in FORMATS.iter() {
+                if avro_type == name && logical_type == logical {
+                    return object(vec![("type", string(json_type)), ("format", string(format))]);
+                }
+            }
+            self.warn(path, format!("logical type {} on {} has no format; only the {} is kept", logical, name, name));
+        }
+
+        match name {
+            "null" => object(vec![("type", string("null"))]),
+            "boolean" => object(vec![("type", string("boolean"))]),
+            "int" => object(vec![("type", string("integer")), ("format", string("int32"))]),
+            "long" => object(vec![("type", string("integer")), ("format", string("int64"))]),
+            "float" => object(vec![("type", string("number")), ("format", string("float"))]),
+            "double" => object(vec![("type", string("number")), ("format", string("double"))]),
+            "bytes" => {
+                self.warn(path, String::from("bytes become a string, one character per byte"));
+                object(vec![("type", string("string"))])
+            },
+            _ => object(vec![("type", string("string"))]),
+        }
+    }
+
+    // A record, enum or fixed: written in place if it's the top level type
+    // the first time round, otherwise added to the definitions and referred to.
+    fn named(&mut self, definition: &Value, ns: Option<&str>) -> Value {
+        let fullname = names::fullname_of(definition, ns);
+        let is_root = self.root.as_ref() == Some(&fullname);
+        let reference = if is_root {
+            object(vec![("$ref", string("#"))])
+        } else {
+            object(vec![("$ref", Value::String(format!("#/definitions/{}", fullname)))])
+        };
+        if self.converted.contains(&fullname) {
+            return reference;
+        }
+        self.converted.insert(fullname.clone());
+
+        let body = self.definition(definition, ns, &fullname);
+        if is_root {
+            return body;
+        }
+        self.definitions.insert(fullname, body);
+        reference
+    }
+
+    fn definition(&mut self, definition: &Value, ns: Option<&str>, path: &str) -> Value {
+        let mut body = BTreeMap::new();
+        body.insert(String::from("title"), Value::String(names::short_name(definition)));
+        if let Some(&Value::String(ref doc)) = definition.find("doc") {
+            body.insert(String::from("description"), Value::String(doc.clone()));
+        }
+        if definition.find("aliases").is_some() {
+            self.warn(path, String::from("aliases are dropped"));
+        }
+
+        let type_name = definition.find("type").and_then(|t| t.as_string()).unwrap_or("");
+        match type_name {
+            "enum" => {
+                body.insert(String::from("type"), string("string"));
+                let symbols = definition.find("symbols").cloned().unwrap_or(Value::Array(Vec::new()));
+                body.insert(String::from("enum"), symbols);
+                if definition.find("default").is_some() {
+                    self.warn(path, String::from("the enum's default for unknown symbols is dropped"));
+                }
+            },
+            "fixed" => {
+                let size = definition.find("size").cloned().unwrap_or(Value::U64(0));
+                body.insert(String::from("type"), string("string"));
+                body.insert(String::from("minLength"), size.clone());
+                body.insert(String::from("maxLength"), size);
+                self.warn(path, String::from("fixed becomes a string, one character per byte"));
+                if let Some(logical) = definition.find("logicalType").and_then(|l| l.as_string()) {
+                    self.warn(path, format!("logical type {} on fixed has no format", logical));
+                }
+            },
+            _ => {
+                let mut properties = BTreeMap::new();
+                let mut required = Vec::new();
+                let fields = match definition.find("fields") {
+                    Some(&Value::Array(ref fields)) => fields.clone(),
+                    _ => Vec::new(),
+                };
+                for field in fields.iter() {
+                    let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                    let field_path = format!("{}.{}", path, name);
+                    let field_type = field.find("type").cloned().unwrap_or(Value::Null);
+                    let mut property = match self.convert(&field_type, ns, &field_path) {
+                        Value::Object(map) => map,
+                        _ => BTreeMap::new(),
+                    };
+                    // A $ref can't have siblings in draft-07, so it's wrapped.
+                    if property.contains_key("$ref") && (field.find("doc").is_some() || field.find("default").is_some()) {
+                        let mut wrapped = BTreeMap::new();
+                        wrapped.insert(String::from("allOf"), Value::Array(vec![Value::Object(property)]));
+                        property = wrapped;
+                    }
+                    if let Some(&Value::String(ref doc)) = field.find("doc") {
+                        property.insert(String::from("description"), Value::String(doc.clone()));
+                    }
+                    match field.find("default") {
+                        Some(default) => {
+                            property.insert(String::from("default"), default.clone());
+                        },
+                        None => required.push(Value::String(String::from(name))),
+                    }
+                    if field.find("aliases").is_some() {
+                        self.warn(&field_path, String::from("aliases are dropped"));
+                    }
+                    match field.find("order").and_then(|o| o.as_string()) {
+                        Some("ascending") | None => (),
+                        Some(order) => self.warn(&field_path, format!("sort order {} is dropped", order)),
+                    }
+                    properties.insert(String::from(name), Value::Object(property));
+                }
+
+                body.insert(String::from("type"), string("object"));
+                body.insert(String::from("properties"), Value::Object(properties));
+                body.insert(String::from("required"), Value::Array(required));
+                body.insert(String::from("additionalProperties"), Value::Bool(false));
+            },
+        }
+        Value::Object(body)
+    }
+
+    fn union(&mut self, branches: &Vec<Value>, ns: Option<&str>, path: &str) -> Value {
+        let nullable = branches.iter().any(|b| is_null(b));
+        let mut converted = Vec::new();
+        for branch in branches.iter().filter(|b| !is_null(b)) {
+            converted.push(self.convert(branch, ns, path));
+        }
+
+        // A nullable type that has a "type" of its own just gets null added
+        // to it; anything else goes in a oneOf.
+        if converted.len() == 1 {
+            let mut branch = converted.pop().unwrap();
+            if !nullable {
+                return branch;
+            }
+            if let Value::Object(ref mut map) = branch {
+                if let Some(Value::String(type_name)) = map.get("type").cloned() {
+                    map.insert(String::from("type"), Value::Array(vec![Value::String(type_name), string("null")]));
+                    if let Some(&mut Value::Array(ref mut symbols)) = map.get_mut("enum") {
+                        symbols.push(Value::Null);
+                    }
+                    return branch;
+                }
+            }
+            converted.push(branch);
+        }
+        if nullable {
+            converted.insert(0, object(vec![("type", string("null"))]));
+        }
+        if converted.len() == 1 {
+            return converted.pop().unwrap();
+        }
+
+        let mut seen = BTreeSet::new();
+        for branch in converted.iter() {
+            if let Some(&Value::String(ref type_name)) = branch.find("type") {
+                if !seen.insert(type_name.clone()) {
+                    self.warn(path, format!("more than one branch of the union is a JSON {}, so oneOf can't tell them apart", type_name));
+                }
+            }
+        }
+        object(vec![("oneOf", Value::Array(converted))])
+    }
+}
+
+struct FromJsonSchema<'a> {
+    root: &'a Value,
+    // Record and enum names taken.
+    names: BTreeSet<String>,
+    // The Avro names given to references already converted or underway.
+    refs: BTreeMap<String, String>,
+    // References being followed, to catch ones that lead back to themselves
+    // without a record or enum in between to name.
+    following: BTreeSet<String>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> FromJsonSchema<'a> {
+    fn warn(&mut self, path: &str, message: String) {
+        let path = if path.is_empty() { "/" } else { path };
+        self.warnings.push(Warning { path: String::from(path), message: message });
+    }
+
+    // The Avro type for `schema`. Records and enums are named after `name`
+    // unless there's a title; `reference` is the $ref that leads here, if
+    // any, so that references back to a record being converted can use its
+    // name.
+    fn convert(&mut self, schema: &Value, name: &str, path: &str, reference: Option<String>) -> Value {
+        let map = match *schema {
+            Value::Object(ref map) => map,
+            _ => {
+                self.warn(path, String::from("boolean schemas have no Avro equivalent; using string"));
+                return string("string");
+            },
+        };
+        for keyword in CONSTRAINTS.iter() {
+            if map.contains_key(*keyword) {
+                self.warn(path, format!("{} has no Avro equivalent and is dropped", keyword));
+            }
+        }
+        if map.contains_key("const") {
+            self.warn(path, String::from("const has no Avro equivalent and is dropped"));
+        }
+
+        if let Some(&Value::String(ref target)) = schema.find("$ref") {
+            return self.reference(target, path);
+        }
+        for keyword in ["oneOf", "anyOf"].iter() {
+            if let Some(&Value::Array(ref branches)) = schema.find(keyword) {
+                let mut converted = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    converted.push(self.convert(branch, name, &format!("{}/{}/{}", path, keyword, i), None));
+                }
+                return self.union(converted, path);
+            }
+        }
+        if let Some(&Value::Array(ref parts)) = schema.find("allOf") {
+            if parts.len() != 1 {
+                self.warn(path, String::from("allOf is only followed as far as its first schema"));
+            }
+            if let Some(first) = parts.first() {
+                return self.convert(first, name, &format!("{}/allOf/0", path), reference);
+            }
+        }
+        if let Some(&Value::Array(ref symbols)) = schema.find("enum") {
+            return self.enumeration(schema, symbols, name, path, reference);
+        }
+
+        match schema.find("type") {
+            Some(&Value::String(ref type_name)) => self.typed(schema, type_name, name, path, reference),
+            Some(&Value::Array(ref type_names)) => {
+                let mut converted = Vec::new();
+                for type_name in type_names.iter().filter_map(|t| t.as_string()) {
+                    converted.push(self.typed(schema, type_name, name, path, None));
+                }
+                self.union(converted, path)
+            },
+            _ => {
+                if schema.find("properties").is_some() {
+                    self.typed(schema, "object", name, path, reference)
+                } else {
+                    self.warn(path, String::from("no type is given; using string"));
+                    string("string")
+                }
+            },
+        }
+    }
+
+    fn typed(&mut self, schema: &Value, type_name: &str, name: &str, path: &str, reference: Option<String>) -> Value {
+        let format = schema.find("format").and_then(|f| f.as_string());
+        match type_name {
+            "null" => string("null"),
+            "boolean" => string("boolean"),
+            "integer" => {
+                for &(avro_type, logical_type, json_type, known) in FORMATS.iter() {
+                    if json_type == "integer" && format == Some(known) {
+                        return object(vec![("type", string(avro_type)), ("logicalType", string(logical_type))]);
+                    }
+                }
+                match format {
+                    Some("int32") => string("int"),
+                    Some("int64") | None => string("long"),
+                    Some(format) => {
+                        self.warn(path, format!("format {} is dropped", format));
+                        string("long")
+                    },
+                }
+            },
+            "number" => {
+                match format {
+                    Some("float") => string("float"),
+                    Some("double") | None => string("double"),
+                    Some(format) => {
+                        self.warn(path, format!("format {} is dropped", format));
+                        string("double")
+                    },
+                }
+            },
+            "string" => {
+                match format {
+                    Some("uuid") => object(vec![("type", string("string")), ("logicalType", string("uuid"))]),
+                    None => string("string"),
+                    Some(format) => {
+                        self.warn(path, format!("format {} is dropped", format));
+                        string("string")
+                    },
+                }
+            },
+            "array" => {
+                match schema.find("items") {
+                    Some(&Value::Array(ref items)) => {
+                        self.warn(path, String::from("tuple items become an array of the union of their types"));
+                        let mut converted = Vec::new();
+                        for (i, item) in items.iter().enumerate() {
+                            converted.push(self.convert(item, name, &format!("{}/items/{}", path, i), None));
+                        }
+                        let items = self.union(converted, path);
+                        object(vec![("type", string("array")), ("items", items)])
+                    },
+                    Some(items) => {
+                        let items = self.convert(items, name, &format!("{}/items", path), None);
+                        object(vec![("type", string("array")), ("items", items)])
+                    },
+                    None => {
+                        self.warn(path, String::from("array has no items; using an array of strings"));
+                        object(vec![("type", string("array")), ("items", string("string"))])
+                    },
+                }
+            },
+            "object" => self.object(schema, name, path, reference),
+            _ => {
+                self.warn(path, format!("{} is not a JSON Schema type; using string", type_name));
+                string("string")
+            },
+        }
+    }
+
+    fn object(&mut self, schema: &Value, name: &str, path: &str, reference: Option<String>) -> Value {
+        let additional = schema.find("additionalProperties");
+        let properties = match schema.find("properties") {
+            Some(&Value::Object(ref properties)) => properties,
+            _ => {
+                return match additional {
+                    Some(values @ &Value::Object(_)) => {
+                        let values = self.convert(values, name, &format!("{}/additionalProperties", path), None);
+                        object(vec![("type", string("map")), ("values", values)])
+                    },
+                    _ => {
+                        self.warn(path, String::from("object has no properties; using a map of strings"));
+                        object(vec![("type", string("map")), ("values", string("string"))])
+                    },
+                };
+            },
+        };
+        if let Some(&Value::Object(_)) = additional {
+            self.warn(path, String::from("additionalProperties alongside properties are dropped"));
+        }
+
+        let record_name = self.type_name(schema, name, reference);
+        let required: BTreeSet<&str> = match schema.find("required") {
+            Some(&Value::Array(ref required)) => required.iter().filter_map(|r| r.as_string()).collect(),
+            _ => BTreeSet::new(),
+        };
+
+        let mut field_names = BTreeSet::new();
+        let mut fields = Vec::new();
+        for (key, property) in properties.iter() {
+            let property_path = format!("{}/properties/{}", path, key);
+            let field_name = names::unique_name(&names::sanitize_name(key), &mut field_names);
+            if &field_name != key {
+                self.warn(&property_path, format!("renamed to {}", field_name));
+            }
+
+            let mut field = BTreeMap::new();
+            let mut field_type = self.convert(property, key, &property_path, None);
+            match property.find("default") {
+                Some(default) => {
+                    // A union's default belongs to its first branch, so the
+                    // branch it fits goes first.
+                    match first_for_default(&field_type, default) {
+                        Some(reordered) => {
+                            field_type = reordered;
+                            field.insert(String::from("default"), default.clone());
+                        },
+                        None => self.warn(&property_path, String::from("the default doesn't fit the type and is dropped")),
+                    }
+                },
+                None if !required.contains(&key[..]) => {
+                    // Optional properties can be left out, which Avro has to
+                    // take as null.
+                    field_type = self.union(vec![string("null"), field_type], &property_path);
+                    field.insert(String::from("default"), Value::Null);
+                },
+                None => (),
+            }
+            field.insert(String::from("name"), Value::String(field_name));
+            field.insert(String::from("type"), field_type);
+            if let Some(&Value::String(ref description)) = property.find("description") {
+                field.insert(String::from("doc"), Value::String(description.clone()));
+            }
+            fields.push(Value::Object(field));
+        }
+
+        let mut record = BTreeMap::new();
+        record.insert(String::from("type"), string("record"));
+        record.insert(String::from("name"), Value::String(record_name));
+        record.insert(String::from("fields"), Value::Array(fields));
+        if let Some(&Value::String(ref description)) = schema.find("description") {
+            record.insert(String::from("doc"), Value::String(description.clone()));
+        }
+        Value::Object(record)
+    }
+
+    fn enumeration(&mut self, schema: &Value, symbols: &Vec<Value>, name: &str, path: &str, reference: Option<String>) -> Value {
+        let nullable = symbols.iter().any(|s| *s == Value::Null);
+        let strings: Vec<&str> = symbols.iter().filter_map(|s| s.as_string()).collect();
+        let all_names = strings.iter().all(|s| names::sanitize_name(s) == *s);
+        if strings.len() + nullable as usize != symbols.len() || !all_names {
+            self.warn(path, String::from("enum values that aren't all Avro names become a plain type"));
+            let mut without = schema.clone();
+            if let Value::Object(ref mut map) = without {
+                map.remove("enum");
+            }
+            return self.convert(&without, name, path, reference);
+        }
+
+        let mut enumeration = BTreeMap::new();
+        enumeration.insert(String::from("type"), string("enum"));
+        enumeration.insert(String::from("name"), Value::String(self.type_name(schema, name, reference)));
+        enumeration.insert(String::from("symbols"), Value::Array(strings.iter().map(|s| string(s)).collect()));
+        if let Some(&Value::String(ref description)) = schema.find("description") {
+            enumeration.insert(String::from("doc"), Value::String(description.clone()));
+        }
+        let enumeration = Value::Object(enumeration);
+        if nullable { self.union(vec![string("null"), enumeration], path) } else { enumeration }
+    }
+
+    fn reference(&mut self, target: &str, path: &str) -> Value {
+        if let Some(name) = self.refs.get(target) {
+            return Value::String(name.clone());
+        }
+        if self.following.contains(target) {
+            self.warn(path, format!("{} refers back to itself without a record in between; using string", target));
+            return string("string");
+        }
+
+        let definition = if target == "#" {
+            Some(self.root)
+        } else if target.starts_with("#/") {
+            target[2..].split('/').fold(Some(self.root), |value, segment| value.and_then(|v| v.find(segment)))
+        } else {
+            None
+        };
+        match definition {
+            Some(definition) => {
+                let name = target.rsplit('/').next().unwrap_or("");
+                self.following.insert(String::from(target));
+                let converted = self.convert(definition, name, target, Some(String::from(target)));
+                self.following.remove(target);
+                converted
+            },
+            None => {
+                self.warn(path, format!("{} can't be followed; using string", target));
+                string("string")
+            },
+        }
+    }
+
+    // Avro branches as a union: nested unions flattened, duplicates dropped and
+    // null first, so it can be a default. Avro only allows one branch of each
+    // unnamed type, so later ones (a second map, say) are dropped too.
+    fn union(&mut self, branches: Vec<Value>, path: &str) -> Value {
+        let mut flattened = Vec::new();
+        let mut unnamed = BTreeSet::new();
+        for branch in branches.into_iter() {
+            let nested = match branch {
+                Value::Array(nested) => nested,
+                other => vec![other],
+            };
+            for branch in nested.into_iter() {
+                if flattened.contains(&branch) {
+                    continue;
+                }
+                if let Some(type_name) = unnamed_type(&branch) {
+                    if !unnamed.insert(String::from(type_name)) {
+                        self.warn(path, format!("a union can only have one {} branch; the others are dropped", type_name));
+                        continue;
+                    }
+                }
+                flattened.push(branch);
+            }
+        }
+        if let Some(null) = flattened.iter().position(|b| is_null(b)) {
+            let null = flattened.remove(null);
+            flattened.insert(0, null);
+        }
+        if flattened.len() == 1 { flattened.pop().unwrap() } else { Value::Array(flattened) }
+    }
+
+    // A record or enum name from the title or `name`, and registered for
+    // `reference`.
+    fn type_name(&mut self, schema: &Value, name: &str, reference: Option<String>) -> String {
+        let base = names::sanitize_name(schema.find("title").and_then(|t| t.as_string()).unwrap_or(name));
+        let mut chars = base.chars();
+        let capitalized: String = match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => base.clone(),
+        };
+        let type_name = names::unique_name(&capitalized, &mut self.names);
+        if let Some(reference) = reference {
+            self.refs.insert(reference, type_name.clone());
+        }
+        type_name
+    }
+}
+
+// The type of an unnamed schema (a primitive, with or without a logical
+// type, an array or a map), which a union can only have one branch of.
+fn unnamed_type(schema: &Value) -> Option<&str> {
+    let type_name = match *schema {
+        Value::String(ref s) => &s[..],
+        _ => match schema.find("type").and_then(|t| t.as_string()) {
+            Some(type_name) => type_name,
+            None => return None,
+        },
+    };
+    if names::is_primitive_type_name(type_name) || type_name == "array" || type_name == "map" {
+        Some(type_name)
+    } else {
+        None
+    }
+}
+
+// `field_type` with the first branch `default` fits moved to the front, or
+// None if it fits none of them.
+fn first_for_default(field_type: &Value, default: &Value) -> Option<Value> {
+    match *field_type {
+        Value::Array(ref branches) => {
+            branches.iter().position(|b| fits(default, b)).map(|i| {
+                let mut branches = branches.clone();
+                let first = branches.remove(i);
+                branches.insert(0, first);
+                Value::Array(branches)
+            })
+        },
+        ref other if fits(default, other) => Some(other.clone()),
+        _ => None,
+    }
+}
+
+// Whether `default` has the JSON form of a `schema` value, going by its JSON
+// type. A name could be a record or an enum, so it takes an object or a
+// string.
+fn fits(default: &Value, schema: &Value) -> bool {
+    let type_name = match *schema {
+        Value::String(ref s) => &s[..],
+        _ => match schema.find("type").and_then(|t| t.as_string()) {
+            Some(type_name) => type_name,
+            None => return false,
+        },
+    };
+    match (type_name, default) {
+        ("null", &Value::Null) => true,
+        ("boolean", &Value::Bool(_)) => true,
+        ("int", &Value::I64(_)) | ("int", &Value::U64(_)) => true,
+        ("long", &Value::I64(_)) | ("long", &Value::U64(_)) => true,
+        ("float", &Value::I64(_)) | ("float", &Value::U64(_)) | ("float", &Value::F64(_)) => true,
+        ("double", &Value::I64(_)) | ("double", &Value::U64(_)) | ("double", &Value::F64(_)) => true,
+        ("string", &Value::String(_)) | ("bytes", &Value::String(_)) | ("fixed", &Value::String(_)) => true,
+        ("enum", &Value::String(ref symbol)) => {
+            match schema.find("symbols") {
+                Some(&Value::Array(ref symbols)) => symbols.iter().any(|s| s.as_string() == Some(&symbol[..])),
+                _ => false,
+            }
+        },
+        ("array", &Value::Array(_)) => true,
+        ("record", &Value::Object(_)) | ("map", &Value::Object(_)) => true,
+        (name, &Value::Object(_)) | (name, &Value::String(_)) => !names::is_primitive_type_name(name) && !is_complex(name),
+        _ => false,
+    }
+}
+
+fn is_complex(type_name: &str) -> bool {
+    match type_name {
+        "record" | "enum" | "fixed" | "array" | "map" => true,
+        _ => false,
+    }
+}
+
+fn is_null(schema: &Value) -> bool {
+    match *schema {
+        Value::Null => true,
+        Value::String(ref s) => s == "null",
+        _ => schema.find("type") == Some(&string("null")),
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::String(String::from(s))
+}
+
+fn object(pairs: Vec<(&str, Value)>) -> Value {
+    Value::Object(pairs.into_iter().map(|(key, value)| (String::from(key), value)).collect())
+}
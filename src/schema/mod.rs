@@ -20,6 +20,9 @@ pub use self::ser::{
 pub use self::de::{
 	from_str
 };
+pub use self::diff::{
+	diff
+};
 pub use self::builder::{
 	ArrayBuilder,
 	EnumBuilder,
@@ -32,8 +35,16 @@ pub use self::builder::{
 	UnionBuilder
 };
 
+pub mod canonical;
 pub mod de;
+pub mod diff;
+pub mod docs;
 pub mod error;
+pub mod idl;
+pub mod jsonschema;
+pub mod names;
 pub mod ser;
+pub mod sql;
 mod builder;
 mod model;
+mod project;
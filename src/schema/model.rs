@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
 use std::fmt;
+use std::str::FromStr;
 
 use regex::Regex;
 use serde::json::Value;
@@ -29,6 +31,8 @@ pub enum Schema {
     Object(Value)
 }
 
+/// How a record field takes part in comparisons (the field's "order").
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum FieldSortOrder {
     Ascending,
     Descending,
@@ -51,6 +55,87 @@ impl fmt::Display for FieldSortOrder {
     }
 }
 
+// A field without an order is sorted ascending.
+impl Default for FieldSortOrder {
+    fn default() -> FieldSortOrder {
+        FieldSortOrder::Ascending
+    }
+}
+
+impl FromStr for FieldSortOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<FieldSortOrder, Error> {
+        match s {
+            "ascending"  => Ok(FieldSortOrder::Ascending),
+            "descending" => Ok(FieldSortOrder::Descending),
+            "ignore"     => Ok(FieldSortOrder::Ignore),
+            _ => Err(Error::SyntaxError(ErrorCode::NotValidSortOrder, 0, 0)),
+        }
+    }
+}
+
+/// A field of a parsed record schema.
+#[derive(Clone, Copy, Debug)]
+pub struct Field<'a> {
+    value: &'a Value,
+}
+
+// A field definition from a record's raw JSON "fields".
+impl<'a> From<&'a Value> for Field<'a> {
+    fn from(value: &'a Value) -> Field<'a> {
+        Field { value: value }
+    }
+}
+
+impl<'a> Field<'a> {
+    pub fn name(&self) -> Option<&'a String> {
+        match self.value.find("name") {
+            Some(&Value::String(ref name)) => Some(name),
+            _ => None
+        }
+    }
+
+    pub fn field_type(&self) -> Option<Schema> {
+        self.value.find("type").map(|t| Schema::from(t))
+    }
+
+    pub fn doc(&self) -> Option<&'a String> {
+        match self.value.find("doc") {
+            Some(&Value::String(ref doc)) => Some(doc),
+            _ => None
+        }
+    }
+
+    pub fn aliases(&self) -> Option<Vec<String>> {
+        match self.value.find("aliases") {
+            Some(&Value::Array(ref aliases)) => {
+                Some(aliases.iter().filter_map(|a| a.as_string()).map(|a| String::from(a)).collect())
+            },
+            _ => None
+        }
+    }
+
+    /// The default value, as the JSON it's written in.
+    pub fn default(&self) -> Option<&'a Value> {
+        self.value.find("default")
+    }
+
+    /// The sort order, ascending when the field doesn't give one.
+    pub fn order(&self) -> Result<FieldSortOrder, Error> {
+        match self.value.find("order") {
+            Some(&Value::String(ref order)) => FieldSortOrder::from_str(order),
+            Some(_) => Err(Error::SyntaxError(ErrorCode::NotValidSortOrder, 0, 0)),
+            None => Ok(FieldSortOrder::default()),
+        }
+    }
+
+    /// The field definition as it is in the schema's JSON.
+    pub fn as_value(&self) -> &'a Value {
+        self.value
+    }
+}
+
 impl Schema {
     pub fn is_primitive(&self) -> bool {
         match *self {
@@ -258,12 +343,12 @@ impl Schema {
         }
     }
 
-    // Get the raw fields in a record
-    pub fn fields(&self) -> Option<&Vec<Value>> {
+    /// The fields of a record.
+    pub fn fields(&self) -> Option<Vec<Field>> {
         match *self {
             Schema::Object(ref value) => {
                 if let Some(&Value::Array(ref array)) = value.find("fields") {
-                    Some(&*array)
+                    Some(array.iter().map(|v| Field::from(v)).collect())
                 } else {
                     None
                 }
@@ -317,7 +402,47 @@ impl Schema {
     }
 
     fn is_valid_schema_union(&self, vec: &Vec<Schema>) -> Result<(),Error> {
-        Err(Error::SyntaxError(ErrorCode::Unknown, 0, 0)) // catch all punt
+        // A top-level union is held to the same rules as a union field type.
+        let branches: Vec<Value> = vec.iter().map(|s| Value::from(s)).collect();
+        Ok(try!(self.is_valid_union_branches(&branches)))
+    }
+
+    fn is_valid_union_branches(&self, value_vec: &Vec<Value>) -> Result<(),Error> {
+        // Unions may not directly contain other unions, and may hold only one
+        // branch of each unnamed type (primitives, "array" and "map") and one
+        // branch of each named type, by fullname.
+        let mut seen = BTreeSet::new();
+
+        for value in value_vec.iter() {
+            if let Value::Array(_) = *value {
+                return Err(Error::SyntaxError(ErrorCode::CannotNestUnions, 0, 0));
+            }
+
+            try!(self.is_valid_field_type(&value));
+
+            if !seen.insert(self.union_branch_key(value)) {
+                return Err(Error::SyntaxError(ErrorCode::FieldTooManyElementsOfSameType, 0, 0));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn union_branch_key(&self, value: &Value) -> String {
+        match *value {
+            Value::String(ref s) => s.clone(),
+            _ => {
+                match value.find("type") {
+                    Some(&Value::String(ref t)) => {
+                        match t.as_ref() {
+                            "record" | "error" | "enum" | "fixed" => self.object_fullname(value),
+                            _ => t.clone(),
+                        }
+                    },
+                    _ => String::new(),
+                }
+            }
+        }
     }
 
     fn is_valid_schema_object(&self, json_val: &Value) -> Result<(),Error> {
@@ -325,7 +450,8 @@ impl Schema {
 
         if let Some(&Value::String(ref type_name)) = json_val.find("type") {
             match type_name.as_ref() {
-                "record" => Ok(try!(self.is_valid_schema_record(json_val))),
+                // Protocol error types are records in every respect but the type name.
+                "record" | "error" => Ok(try!(self.is_valid_schema_record(json_val))),
                 "array"  => Ok(try!(self.is_valid_array(json_val))),
                 "map"    => Ok(try!(self.is_valid_map(json_val))),
                 _ => {
@@ -359,6 +485,10 @@ impl Schema {
                     let schema = Schema::Object(values_val.clone());
                     Ok(try!(schema.is_valid()))
                 },
+                Value::Array(_) => {
+                    // A union of value types is held to the same rules as a union field type.
+                    Ok(try!(self.is_valid_field_type(values_val)))
+                },
                 _ => {
                     Err(Error::SyntaxError(ErrorCode::NotValidMapValuesType, 0, 0))
                 }
@@ -384,6 +514,10 @@ impl Schema {
                     let schema = Schema::Object(items_value.clone());
                     Ok(try!(schema.is_valid()))
                 },
+                Value::Array(_) => {
+                    // A union of item types is held to the same rules as a union field type.
+                    Ok(try!(self.is_valid_field_type(items_value)))
+                },
                 _ => {
                     Err(Error::SyntaxError(ErrorCode::NotValidArrayItemsType, 0, 0))
                 }
@@ -424,29 +558,8 @@ impl Schema {
                 Ok(try!(Schema::String(s.clone()).is_valid()))
             },
             Value::Array(ref value_vec) => {
-                // For unions (which are represented by JSON arrays), we have the added
-                // restriction that we can have only one "array" or "map" type within
-                // the union.
-                let mut array_count = 0;
-                let mut map_count = 0;
-
-                for value in value_vec.iter() {
-                    try!(self.is_valid_field_type(&value));
-
-                    if let Some(&Value::String(ref type_name)) = value.find("type") {
-                        match type_name.as_ref() {
-                            "array" => { array_count += 1; },
-                            "map"   => { map_count += 1; },
-                            _       => { /* don't care */ },
-                        }
-                    }
-                }
-
-                if array_count > 1 || map_count > 1 {
-                    return Err(Error::SyntaxError(ErrorCode::FieldTooManyElementsOfSameType, 0, 0));
-                }
-
-                Ok(())
+                // Unions are represented by JSON arrays.
+                Ok(try!(self.is_valid_union_branches(value_vec)))
             },
             Value::Object(_) => {
                 Ok(try!(Schema::Object(field_type.clone()).is_valid()))
@@ -579,6 +692,8 @@ impl Schema {
         // to the Avro type.
         try!(self.is_valid_field_default(field_value, field_type));
 
+        try!(Field::from(field_value).order());
+
         Ok(())
     }
 
@@ -646,3 +761,29 @@ impl fmt::Display for Schema {
         write!(f, "{}", String::from(self))
     }
 }
+
+// The JSON value a schema is written as when it's nested inside another
+// definition (field types, protocol types, message responses, ...).
+impl<'a> From<&'a Schema> for Value {
+    fn from(s: &'a Schema) -> Value {
+        match *s {
+            Schema::Null => Value::String(String::from("null")),
+            Schema::String(ref s) => Value::String(s.clone()),
+            Schema::Object(ref v) => v.clone(),
+            Schema::Union(ref vec) => Value::Array(vec.iter().map(|e| Value::from(e)).collect()),
+        }
+    }
+}
+
+// Nested types (field types, array items, map values) are kept as raw JSON
+// inside a Schema::Object, so this gets them back out as a Schema.
+impl<'a> From<&'a Value> for Schema {
+    fn from(v: &'a Value) -> Schema {
+        match *v {
+            Value::Null => Schema::Null,
+            Value::String(ref s) => Schema::String(s.clone()),
+            Value::Array(ref vec) => Schema::Union(vec.iter().map(|e| Schema::from(e)).collect()),
+            _ => Schema::Object(v.clone()),
+        }
+    }
+}
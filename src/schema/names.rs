@@ -0,0 +1,269 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::json::Value;
+
+use super::error::*;
+use super::model::Schema;
+
+/// The named types (records, errors, enums and fixed) defined in one or more
+/// schemas, keyed by full name. Anything that has to follow a type reference
+/// such as `"type": "Foo"` back to its definition looks it up here.
+#[derive(Clone, Debug)]
+pub struct Names {
+    types: BTreeMap<String, NamedType>,
+}
+
+/// A named type definition, along with the namespace references inside it
+/// are relative to.
+#[derive(Clone, Debug)]
+pub struct NamedType {
+    pub definition: Value,
+    pub namespace: Option<String>,
+}
+
+impl Names {
+    pub fn new() -> Names {
+        Names { types: BTreeMap::new() }
+    }
+
+    pub fn from_schema(schema: &Schema) -> Names {
+        let mut names = Names::new();
+        names.add(schema, None);
+        names
+    }
+
+    /// Adds every named type defined in `schema`. Types without a namespace of
+    /// their own get `default_ns`, which is how protocol types inherit the
+    /// protocol namespace.
+    pub fn add(&mut self, schema: &Schema, default_ns: Option<&str>) {
+        self.add_value(&Value::from(schema), default_ns);
+    }
+
+    pub fn get(&self, fullname: &str) -> Option<&NamedType> {
+        self.types.get(fullname)
+    }
+
+    /// Resolves a type name as written in a schema: either a full name, or a
+    /// short name relative to the enclosing namespace.
+    pub fn resolve(&self, name: &str, enclosing_ns: Option<&str>) -> Option<&NamedType> {
+        if !name.contains(".") {
+            if let Some(ns) = enclosing_ns {
+                if let Some(named) = self.types.get(&format!("{}.{}", ns, name)) {
+                    return Some(named);
+                }
+            }
+        }
+        self.types.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Works out what a type expression refers to, following references to
+    /// named types. Also returns the namespace that applies to anything
+    /// nested inside the type.
+    pub fn kind<'a>(&'a self, schema: &'a Value, enclosing_ns: Option<&str>) -> Result<(SchemaKind<'a>, Option<String>), Error> {
+        let enclosing = enclosing_ns.map(|ns| String::from(ns));
+
+        match *schema {
+            Value::Null => Ok((SchemaKind::Primitive("null"), enclosing)),
+            Value::String(ref name) => {
+                if is_primitive_type_name(name) {
+                    Ok((SchemaKind::Primitive(name), enclosing))
+                } else {
+                    self.kind_of_named(name, enclosing_ns)
+                }
+            },
+            Value::Array(ref branches) => Ok((SchemaKind::Union(branches), enclosing)),
+            Value::Object(_) => {
+                match schema.find("type") {
+                    Some(&Value::String(ref type_name)) => {
+                        match type_name.as_ref() {
+                            "record" | "error" => Ok((SchemaKind::Record(schema), namespace_of(schema, enclosing_ns))),
+                            "enum" => Ok((SchemaKind::Enum(schema), namespace_of(schema, enclosing_ns))),
+                            "fixed" => Ok((SchemaKind::Fixed(schema), namespace_of(schema, enclosing_ns))),
+                            "array" => {
+                                match schema.find("items") {
+                                    Some(items) => Ok((SchemaKind::Array(items), enclosing)),
+                                    None => Err(Error::SyntaxError(ErrorCode::ExpectedItemsAttribute, 0, 0)),
+                                }
+                            },
+                            "map" => {
+                                match schema.find("values") {
+                                    Some(values) => Ok((SchemaKind::Map(values), enclosing)),
+                                    None => Err(Error::SyntaxError(ErrorCode::ExpectedValuesAttribute, 0, 0)),
+                                }
+                            },
+                            _ => {
+                                // {"type": "int", ...} is still an int, whatever else
+                                // (a logicalType, say) is attached to it.
+                                if is_primitive_type_name(type_name) {
+                                    Ok((SchemaKind::Primitive(type_name), enclosing))
+                                } else {
+                                    self.kind_of_named(type_name, enclosing_ns)
+                                }
+                            }
+                        }
+                    },
+                    Some(nested) => self.kind(nested, enclosing_ns),
+                    None => Err(Error::SyntaxError(ErrorCode::ExpectedTypeAttribute, 0, 0)),
+                }
+            },
+            _ => Err(Error::SyntaxError(ErrorCode::NotValidType, 0, 0)),
+        }
+    }
+
+    fn kind_of_named<'a>(&'a self, name: &str, enclosing_ns: Option<&str>) -> Result<(SchemaKind<'a>, Option<String>), Error> {
+        match self.resolve(name, enclosing_ns) {
+            Some(named) => self.kind(&named.definition, named.namespace.as_ref().map(|ns| ns.as_ref())),
+            None => Err(Error::SyntaxError(ErrorCode::UnknownNamedType, 0, 0)),
+        }
+    }
+
+    fn add_value(&mut self, value: &Value, enclosing_ns: Option<&str>) {
+        match *value {
+            Value::Array(ref vec) => {
+                for v in vec.iter() {
+                    self.add_value(v, enclosing_ns);
+                }
+            },
+            Value::Object(_) => {
+                let type_name = match value.find("type") {
+                    Some(&Value::String(ref t)) => t.clone(),
+                    Some(nested) => {
+                        self.add_value(nested, enclosing_ns);
+                        return;
+                    },
+                    None => return,
+                };
+
+                match type_name.as_ref() {
+                    "record" | "error" | "enum" | "fixed" => {
+                        let ns = namespace_of(value, enclosing_ns);
+                        let fullname = fullname_of(value, ns.as_ref().map(|s| s.as_ref()));
+                        if self.types.contains_key(&fullname) {
+                            return;
+                        }
+                        self.types.insert(fullname, NamedType { definition: value.clone(), namespace: ns.clone() });
+
+                        if let Some(&Value::Array(ref fields)) = value.find("fields") {
+                            for field in fields.iter() {
+                                if let Some(field_type) = field.find("type") {
+                                    self.add_value(field_type, ns.as_ref().map(|s| s.as_ref()));
+                                }
+                            }
+                        }
+                    },
+                    "array" => {
+                        if let Some(items) = value.find("items") {
+                            self.add_value(items, enclosing_ns);
+                        }
+                    },
+                    "map" => {
+                        if let Some(values) = value.find("values") {
+                            self.add_value(values, enclosing_ns);
+                        }
+                    },
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+/// A type expression with references to named types followed. Records, enums
+/// and fixed carry their definition; arrays and maps their items or values type.
+#[derive(Clone, Debug)]
+pub enum SchemaKind<'a> {
+    Primitive(&'a str),
+    Record(&'a Value),
+    Enum(&'a Value),
+    Fixed(&'a Value),
+    Array(&'a Value),
+    Map(&'a Value),
+    Union(&'a Vec<Value>),
+}
+
+pub fn is_primitive_type_name(name: &str) -> bool {
+    match name {
+        "null" | "boolean" | "int" | "long" | "float" | "double" | "bytes" | "string" => true,
+        _ => false,
+    }
+}
+
+// Namespace for a named type: explicit in a dotted name, given by the
+// "namespace" attribute, or inherited from the enclosing named type.
+pub fn namespace_of(value: &Value, enclosing_ns: Option<&str>) -> Option<String> {
+    if let Some(&Value::String(ref name)) = value.find("name") {
+        if let Some(idx) = name.rfind('.') {
+            return Some(String::from(&name[..idx]));
+        }
+    }
+
+    match value.find("namespace") {
+        Some(&Value::String(ref ns)) => {
+            if ns.is_empty() { None } else { Some(ns.clone()) }
+        },
+        _ => enclosing_ns.map(|ns| String::from(ns)),
+    }
+}
+
+pub fn fullname_of(value: &Value, ns: Option<&str>) -> String {
+    let name = short_name(value);
+    match ns {
+        Some(ns) if !ns.is_empty() => format!("{}.{}", ns, name),
+        _ => name,
+    }
+}
+
+pub fn short_name(value: &Value) -> String {
+    match value.find("name") {
+        Some(&Value::String(ref name)) => {
+            match name.rfind('.') {
+                Some(idx) => String::from(&name[idx + 1..]),
+                None => name.clone(),
+            }
+        },
+        _ => String::new(),
+    }
+}
+
+/// `name` with anything an Avro name can't have replaced by underscores, and
+/// an underscore in front if it would start with a digit.
+pub fn sanitize_name(name: &str) -> String {
+    let mut avro: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if avro.chars().next().map_or(true, |c| c.is_ascii_digit()) {
+        avro.insert(0, '_');
+    }
+    avro
+}
+
+/// `name`, or `name` with a number after it if it's taken, marked as taken.
+pub fn unique_name(name: &str, taken: &mut BTreeSet<String>) -> String {
+    let mut candidate = String::from(name);
+    let mut n = 2;
+    while taken.contains(&candidate) {
+        candidate = format!("{}{}", name, n);
+        n += 1;
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
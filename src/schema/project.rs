@@ -0,0 +1,193 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Projections: reader schemas with only some of a record's fields, so that
+// resolving decoders skip the rest.
+use std::collections::BTreeMap;
+
+use serde::json::Value;
+
+use super::error::*;
+use super::model::Schema;
+use super::names::{fullname_of, Names, SchemaKind};
+
+impl Schema {
+    /// A reader schema for this record with only the fields in `paths`. A
+    /// path is a field name, or dotted names into nested records ("b.c"),
+    /// looking through unions, arrays and maps on the way. Named types the
+    /// kept fields refer to are defined where they are first used.
+    pub fn project(&self, paths: &[&str]) -> Result<Schema, Error> {
+        let names = Names::from_schema(self);
+        let paths: Vec<String> = paths.iter().map(|p| String::from(*p)).collect();
+        let mut projector = Projector { names: &names, emitted: BTreeMap::new() };
+        let value = try!(projector.project(&Value::from(self), None, Some(&paths)));
+        Ok(Schema::from(&value))
+    }
+}
+
+struct Projector<'a> {
+    names: &'a Names,
+    // Named types already defined in the projection, with the paths each
+    // record was projected to (None when it's kept whole).
+    emitted: BTreeMap<String, Option<Vec<String>>>,
+}
+
+impl<'a> Projector<'a> {
+    // `paths` are the fields to keep of any record in `schema`, or None to
+    // keep everything.
+    fn project(&mut self, schema: &Value, ns: Option<&str>, paths: Option<&Vec<String>>) -> Result<Value, Error> {
+        let (kind, ns) = try!(self.names.kind(schema, ns));
+        let ns = ns.as_ref().map(|s| s.as_ref());
+        let not_a_record = Err(Error::SyntaxError(ErrorCode::UnknownProjectedField, 0, 0));
+
+        match kind {
+            SchemaKind::Primitive(_) => {
+                if paths.is_some() {
+                    return not_a_record;
+                }
+                Ok(schema.clone())
+            },
+            SchemaKind::Record(definition) => self.project_record(definition, ns, paths),
+            SchemaKind::Enum(definition) | SchemaKind::Fixed(definition) => {
+                if paths.is_some() {
+                    return not_a_record;
+                }
+                let fullname = fullname_of(definition, ns);
+                if self.emitted.contains_key(&fullname) {
+                    return Ok(Value::String(fullname));
+                }
+                self.emitted.insert(fullname.clone(), None);
+                Ok(relocated(definition, &fullname))
+            },
+            SchemaKind::Array(items) => {
+                let items = try!(self.project(items, ns, paths));
+                Ok(with_attribute(schema, "items", items))
+            },
+            SchemaKind::Map(values) => {
+                let values = try!(self.project(values, ns, paths));
+                Ok(with_attribute(schema, "values", values))
+            },
+            SchemaKind::Union(branches) => {
+                // The paths apply to the record branches; the others (null,
+                // usually) are kept as they are.
+                let mut projected = Vec::with_capacity(branches.len());
+                let mut any_record = false;
+                for branch in branches.iter() {
+                    let is_record = match try!(self.names.kind(branch, ns)).0 {
+                        SchemaKind::Record(_) | SchemaKind::Array(_) | SchemaKind::Map(_) => true,
+                        _ => false,
+                    };
+                    any_record = any_record || is_record;
+                    projected.push(try!(self.project(branch, ns, if is_record { paths } else { None })));
+                }
+                if paths.is_some() && !any_record {
+                    return not_a_record;
+                }
+                Ok(Value::Array(projected))
+            },
+        }
+    }
+
+    fn project_record(&mut self, definition: &Value, ns: Option<&str>, paths: Option<&Vec<String>>) -> Result<Value, Error> {
+        let fullname = fullname_of(definition, ns);
+        let key = paths.map(|paths| {
+            let mut key = paths.clone();
+            key.sort();
+            key
+        });
+        match self.emitted.get(&fullname) {
+            Some(emitted) if *emitted == key => return Ok(Value::String(fullname)),
+            // One name can't be defined twice with different fields.
+            Some(_) => return Err(Error::SyntaxError(ErrorCode::DuplicateNamedType, 0, 0)),
+            None => (),
+        }
+        self.emitted.insert(fullname.clone(), key);
+
+        let fields = match definition.find("fields") {
+            Some(&Value::Array(ref fields)) => fields,
+            _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+        };
+
+        // Field name to the paths within it, None meaning the whole field.
+        let wanted = match paths {
+            Some(paths) => {
+                let mut wanted: BTreeMap<&str, Option<Vec<String>>> = BTreeMap::new();
+                for path in paths.iter() {
+                    let (name, rest) = match path.find('.') {
+                        Some(idx) => (&path[..idx], Some(String::from(&path[idx + 1..]))),
+                        None => (&path[..], None),
+                    };
+                    if !fields.iter().any(|f| f.find("name").and_then(|n| n.as_string()) == Some(name)) {
+                        return Err(Error::SyntaxError(ErrorCode::UnknownProjectedField, 0, 0));
+                    }
+                    let entry = wanted.entry(name).or_insert(Some(Vec::new()));
+                    match rest {
+                        Some(rest) => {
+                            if let Some(ref mut nested) = *entry {
+                                nested.push(rest);
+                            }
+                        },
+                        None => *entry = None,
+                    }
+                }
+                Some(wanted)
+            },
+            None => None,
+        };
+
+        let mut projected = Vec::new();
+        for field in fields.iter() {
+            let name = field.find("name").and_then(|n| n.as_string()).unwrap_or("");
+            let nested = match wanted {
+                Some(ref wanted) => {
+                    match wanted.get(name) {
+                        Some(nested) => nested.as_ref(),
+                        None => continue,
+                    }
+                },
+                None => None,
+            };
+            let field_type = match field.find("type") {
+                Some(t) => t,
+                None => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldTypeAttribute, 0, 0)),
+            };
+            let field_type = try!(self.project(field_type, ns, nested));
+            projected.push(with_attribute(field, "type", field_type));
+        }
+
+        Ok(with_attribute(&relocated(definition, &fullname), "fields", Value::Array(projected)))
+    }
+}
+
+// A named type's definition, made to stand on its own wherever it ends up:
+// the full name as its name, and an explicit empty namespace when it has
+// none so it doesn't pick up the namespace of its new surroundings.
+fn relocated(definition: &Value, fullname: &str) -> Value {
+    let mut value = with_attribute(definition, "name", Value::String(String::from(fullname)));
+    if let Value::Object(ref mut map) = value {
+        map.remove("namespace");
+        if !fullname.contains('.') {
+            map.insert(String::from("namespace"), Value::String(String::new()));
+        }
+    }
+    value
+}
+
+fn with_attribute(value: &Value, key: &str, attribute: Value) -> Value {
+    let mut value = value.clone();
+    if let Value::Object(ref mut map) = value {
+        map.insert(String::from(key), attribute);
+    }
+    value
+}
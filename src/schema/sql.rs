@@ -0,0 +1,358 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// CREATE TABLE statements for tables that mirror a record schema, one column
+// per field. Fields of nested records are flattened into columns of their
+// own, named with the separator between each level's field name (and a
+// number after it, if that name is already a column's). Arrays,
+// maps and unions of more than one non-null type have no column type to
+// match, so they're kept as JSON.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::json::Value;
+
+use super::error::*;
+use super::model::Schema;
+use super::names::{fullname_of, short_name, unique_name, Names, SchemaKind};
+
+/// The flavour of SQL to write.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dialect {
+    /// Enums become enum types, and docs COMMENT ON statements.
+    Postgres,
+    /// Enums become CHECK constraints, and docs SQL comments.
+    Sqlite,
+    /// Standard SQL, with enums and docs as for SQLite.
+    Ansi,
+}
+
+/// How `to_sql_ddl_with` writes a table.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub dialect: Dialect,
+    /// Goes between the names of a nested record's field and the fields
+    /// inside it, so "address" and "city" make the column "address_city".
+    pub separator: String,
+    /// The table's name. The record's own name if not given.
+    pub table: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            dialect: Dialect::Ansi,
+            separator: String::from("_"),
+            table: None,
+        }
+    }
+}
+
+impl Schema {
+    /// The DDL for a table with a column for each field of this record.
+    pub fn to_sql_ddl(&self, dialect: Dialect) -> Result<String, Error> {
+        self.to_sql_ddl_with(&Options { dialect: dialect, ..Options::default() })
+    }
+
+    pub fn to_sql_ddl_with(&self, options: &Options) -> Result<String, Error> {
+        let names = Names::from_schema(self);
+        let value = Value::from(self);
+        let (definition, ns) = match try!(names.kind(&value, None)) {
+            (SchemaKind::Record(definition), ns) => (definition, ns),
+            _ => return Err(Error::EncodeError(ErrorCode::NotRepresentableInSql)),
+        };
+
+        let mut generator = Generator {
+            options: options,
+            names: &names,
+            columns: Vec::new(),
+            enum_types: Vec::new(),
+            enum_type_names: BTreeMap::new(),
+            taken: BTreeSet::new(),
+            column_names: BTreeSet::new(),
+            records: vec![fullname_of(definition, ns.as_ref().map(|ns| &ns[..]))],
+        };
+        try!(generator.add_record(self, ns.as_ref().map(|ns| &ns[..]), None, false));
+
+        let table = options.table.clone().unwrap_or_else(|| short_name(definition));
+        Ok(generator.render(&table, self.doc()))
+    }
+}
+
+struct Column {
+    name: String,
+    sql_type: String,
+    nullable: bool,
+    check: Option<String>,
+    doc: Option<String>,
+}
+
+struct Generator<'a> {
+    options: &'a Options,
+    names: &'a Names,
+    columns: Vec<Column>,
+    // Enum types to create first, for Postgres, as names and symbols.
+    enum_types: Vec<(String, Vec<String>)>,
+    // The type already created for each enum, by full name.
+    enum_type_names: BTreeMap<String, String>,
+    taken: BTreeSet<String>,
+    // Column names used so far. Flattening can make a name twice, as with a
+    // field "a_b" next to a record field "a" holding "b".
+    column_names: BTreeSet<String>,
+    // Records being flattened, by full name, to catch one inside itself.
+    records: Vec<String>,
+}
+
+impl<'a> Generator<'a> {
+    // Columns for each field of a record, their names after `prefix` when
+    // the record is itself a field.
+    fn add_record(&mut self, record: &Schema, ns: Option<&str>, prefix: Option<&str>, nullable: bool) -> Result<(), Error> {
+        for field in record.fields().unwrap_or_else(Vec::new) {
+            let (name, field_type) = match (field.name(), field.field_type()) {
+                (Some(name), Some(field_type)) => (name, field_type),
+                _ => return Err(Error::SyntaxError(ErrorCode::ExpectedFieldDefintion, 0, 0)),
+            };
+            let column = match prefix {
+                Some(prefix) => format!("{}{}{}", prefix, self.options.separator, name),
+                None => name.clone(),
+            };
+            try!(self.add_column(column, &Value::from(&field_type), ns, nullable, field.doc()));
+        }
+        Ok(())
+    }
+
+    fn add_column(&mut self, column: String, schema: &Value, ns: Option<&str>, nullable: bool, doc: Option<&String>) -> Result<(), Error> {
+        let names = self.names;
+        let dialect = self.options.dialect;
+        let (kind, nested_ns) = try!(names.kind(schema, ns));
+        let nested_ns = nested_ns.as_ref().map(|ns| &ns[..]);
+        // A record is only a prefix, and a union may pass the name on to its
+        // one other branch; the rest make a column here.
+        let column = match kind {
+            SchemaKind::Record(_) | SchemaKind::Union(_) => column,
+            _ => unique_name(&column, &mut self.column_names),
+        };
+
+        let (sql_type, check) = match kind {
+            SchemaKind::Primitive(type_name) => {
+                let nullable = nullable || type_name == "null";
+                return Ok(self.push(column, primitive_type(dialect, type_name, schema), nullable, None, doc));
+            },
+            SchemaKind::Record(definition) => {
+                let fullname = fullname_of(definition, nested_ns);
+                if self.records.contains(&fullname) {
+                    return Err(Error::EncodeError(ErrorCode::NotRepresentableInSql));
+                }
+                self.records.push(fullname);
+                try!(self.add_record(&Schema::Object(definition.clone()), nested_ns, Some(&column), nullable));
+                self.records.pop();
+                return Ok(());
+            },
+            SchemaKind::Enum(definition) => {
+                let symbols = Schema::Object(definition.clone()).symbols().unwrap_or_else(Vec::new);
+                match dialect {
+                    Dialect::Postgres => (quote_identifier(&self.enum_type(definition, nested_ns, symbols)), None),
+                    _ => {
+                        let literals: Vec<String> = symbols.iter().map(|s| quote_literal(s)).collect();
+                        let check = format!("{} IN ({})", quote_identifier(&column), literals.join(", "));
+                        let sql_type = match dialect {
+                            Dialect::Ansi => format!("VARCHAR({})", symbols.iter().map(|s| s.chars().count()).max().unwrap_or(1)),
+                            _ => String::from(text_type(dialect)),
+                        };
+                        (sql_type, Some(check))
+                    },
+                }
+            },
+            SchemaKind::Fixed(definition) => {
+                match decimal(definition) {
+                    Some(numeric) => (numeric, None),
+                    None => {
+                        let size = definition.find("size").and_then(|s| s.as_u64()).unwrap_or(0);
+                        (fixed_type(dialect, size), None)
+                    },
+                }
+            },
+            SchemaKind::Array(_) | SchemaKind::Map(_) => (String::from(json_type(dialect)), None),
+            SchemaKind::Union(branches) => {
+                let mut null = false;
+                let mut others = Vec::new();
+                for branch in branches.iter() {
+                    match try!(names.kind(branch, nested_ns)) {
+                        (SchemaKind::Primitive("null"), _) => null = true,
+                        _ => others.push(branch),
+                    }
+                }
+                if others.len() == 1 {
+                    return self.add_column(column, others[0], nested_ns, nullable || null, doc);
+                }
+                let column = unique_name(&column, &mut self.column_names);
+                match others.len() {
+                    0 => return Ok(self.push(column, String::from(text_type(dialect)), true, None, doc)),
+                    _ => return Ok(self.push(column, String::from(json_type(dialect)), nullable || null, None, doc)),
+                }
+            },
+        };
+        Ok(self.push(column, sql_type, nullable, check, doc))
+    }
+
+    fn push(&mut self, name: String, sql_type: String, nullable: bool, check: Option<String>, doc: Option<&String>) {
+        self.columns.push(Column {
+            name: name,
+            sql_type: sql_type,
+            nullable: nullable,
+            check: check,
+            doc: doc.cloned(),
+        });
+    }
+
+    // The name of the Postgres type for an enum, created the first time the
+    // enum is seen.
+    fn enum_type(&mut self, definition: &Value, ns: Option<&str>, symbols: Vec<String>) -> String {
+        let fullname = fullname_of(definition, ns);
+        if let Some(type_name) = self.enum_type_names.get(&fullname) {
+            return type_name.clone();
+        }
+        let type_name = unique_name(&short_name(definition), &mut self.taken);
+        self.enum_type_names.insert(fullname, type_name.clone());
+        self.enum_types.push((type_name.clone(), symbols));
+        type_name
+    }
+
+    fn render(&self, table: &str, table_doc: Option<&String>) -> String {
+        let postgres = self.options.dialect == Dialect::Postgres;
+        let table = quote_identifier(table);
+        let mut out = String::new();
+
+        for &(ref type_name, ref symbols) in self.enum_types.iter() {
+            let literals: Vec<String> = symbols.iter().map(|s| quote_literal(s)).collect();
+            out.push_str(&format!("CREATE TYPE {} AS ENUM ({});\n\n", quote_identifier(type_name), literals.join(", ")));
+        }
+
+        if let (false, Some(doc)) = (postgres, table_doc) {
+            out.push_str(&format!("-- {}\n", line_comment(doc)));
+        }
+        out.push_str(&format!("CREATE TABLE {} (\n", table));
+        for (i, column) in self.columns.iter().enumerate() {
+            out.push_str(&format!("    {} {} {}", quote_identifier(&column.name), column.sql_type, if column.nullable { "NULL" } else { "NOT NULL" }));
+            if let Some(ref check) = column.check {
+                out.push_str(&format!(" CHECK ({})", check));
+            }
+            if i + 1 < self.columns.len() {
+                out.push(',');
+            }
+            if let (false, Some(ref doc)) = (postgres, column.doc.as_ref()) {
+                out.push_str(&format!(" -- {}", line_comment(doc)));
+            }
+            out.push('\n');
+        }
+        out.push_str(");\n");
+
+        if postgres {
+            let mut comments = Vec::new();
+            if let Some(doc) = table_doc {
+                comments.push(format!("COMMENT ON TABLE {} IS {};\n", table, quote_literal(doc)));
+            }
+            for column in self.columns.iter() {
+                if let Some(ref doc) = column.doc {
+                    comments.push(format!("COMMENT ON COLUMN {}.{} IS {};\n", table, quote_identifier(&column.name), quote_literal(doc)));
+                }
+            }
+            if !comments.is_empty() {
+                out.push('\n');
+                out.push_str(&comments.concat());
+            }
+        }
+        out
+    }
+}
+
+fn primitive_type(dialect: Dialect, type_name: &str, schema: &Value) -> String {
+    if let Some(numeric) = decimal(schema) {
+        return numeric;
+    }
+    let logical_type = schema.find("logicalType").and_then(|l| l.as_string());
+
+    // SQLite has no date and time types, so those stay as the numbers Avro
+    // writes them as.
+    let sql_type = match (dialect, type_name, logical_type) {
+        (Dialect::Sqlite, "boolean", _) => "INTEGER",
+        (Dialect::Sqlite, "int", _) | (Dialect::Sqlite, "long", _) => "INTEGER",
+        (Dialect::Sqlite, "float", _) | (Dialect::Sqlite, "double", _) => "REAL",
+        (Dialect::Postgres, "string", Some("uuid")) => "UUID",
+        (_, "int", Some("date")) => "DATE",
+        (_, "int", Some("time-millis")) | (_, "long", Some("time-micros")) => "TIME",
+        (_, "long", Some("timestamp-millis")) | (_, "long", Some("timestamp-micros")) => "TIMESTAMP WITH TIME ZONE",
+        (_, "long", Some("local-timestamp-millis")) | (_, "long", Some("local-timestamp-micros")) => "TIMESTAMP",
+        (_, "boolean", _) => "BOOLEAN",
+        (_, "int", _) => "INTEGER",
+        (_, "long", _) => "BIGINT",
+        (_, "float", _) => "REAL",
+        (_, "double", _) => "DOUBLE PRECISION",
+        (_, "bytes", _) => return fixed_type(dialect, 0),
+        _ => text_type(dialect),
+    };
+    String::from(sql_type)
+}
+
+// The type for binary data, of a fixed size unless `size` is zero.
+fn fixed_type(dialect: Dialect, size: u64) -> String {
+    match dialect {
+        Dialect::Postgres => String::from("BYTEA"),
+        Dialect::Sqlite => String::from("BLOB"),
+        Dialect::Ansi if size > 0 => format!("BINARY({})", size),
+        Dialect::Ansi => String::from("BLOB"),
+    }
+}
+
+// Text of any length. Standard SQL's VARCHAR needs a length, so it's a CLOB
+// there.
+fn text_type(dialect: Dialect) -> &'static str {
+    match dialect {
+        Dialect::Ansi => "CLOB",
+        _ => "TEXT",
+    }
+}
+
+fn json_type(dialect: Dialect) -> &'static str {
+    match dialect {
+        Dialect::Postgres => "JSONB",
+        _ => text_type(dialect),
+    }
+}
+
+// NUMERIC(p,s) for a decimal logical type. One without a usable precision
+// gets a plain NUMERIC, as NUMERIC(0,0) isn't allowed.
+fn decimal(schema: &Value) -> Option<String> {
+    if schema.find("logicalType").and_then(|l| l.as_string()) != Some("decimal") {
+        return None;
+    }
+    let precision = schema.find("precision").and_then(|p| p.as_u64()).unwrap_or(0);
+    let scale = schema.find("scale").and_then(|s| s.as_u64()).unwrap_or(0);
+    if precision == 0 || scale > precision {
+        return Some(String::from("NUMERIC"));
+    }
+    Some(format!("NUMERIC({},{})", precision, scale))
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace("\"", "\"\""))
+}
+
+fn quote_literal(text: &str) -> String {
+    format!("'{}'", text.replace("'", "''"))
+}
+
+// A doc on one line, to follow "--".
+fn line_comment(doc: &str) -> String {
+    doc.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
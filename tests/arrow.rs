@@ -0,0 +1,241 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "arrow")]
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate arrow;
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const EVENT: &'static str = r#"{"type": "record", "name": "Event", "fields": [
+    {"name": "id", "type": "long"},
+    {"name": "label", "type": ["null", "string"]},
+    {"name": "level", "type": {"type": "enum", "name": "Level", "symbols": ["LOW", "HIGH"]}},
+    {"name": "tags", "type": {"type": "array", "items": "string"}}
+]}"#;
+
+fn event(i: i64) -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    let label = if i % 2 == 0 {
+        Datum::Union(1, Box::new(Datum::String(format!("event{}", i))))
+    } else {
+        Datum::Union(0, Box::new(Datum::Null))
+    };
+    Datum::Record(vec![
+        (String::from("id"), Datum::Long(i)),
+        (String::from("label"), label),
+        (String::from("level"), Datum::Enum(String::from(if i < 2 { "LOW" } else { "HIGH" }))),
+        (String::from("tags"), Datum::Array((0..i).map(|t| Datum::String(format!("t{}", t))).collect())),
+    ])
+}
+
+fn write_events(n: i64) -> Vec<u8> {
+    use ravro::container::{Codec, Writer};
+    use ravro::schema;
+
+    let mut writer = Writer::new(Vec::new(), schema::from_str(EVENT).unwrap(), Codec::Null);
+    for i in 0..n {
+        writer.append(&event(i)).unwrap();
+    }
+    writer.into_inner().unwrap()
+}
+
+mod schema {
+    use arrow::datatypes::{DataType, TimeUnit, UnionMode};
+    use ravro::arrow::to_arrow_schema;
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use EVENT;
+
+    test!{record_fields, {
+        let arrow_schema = to_arrow_schema(&schema::from_str(EVENT).unwrap()).unwrap();
+        let fields = arrow_schema.fields();
+        assert_eq!(fields.len(), 4);
+
+        assert_eq!(fields[0].name(), "id");
+        assert_eq!(fields[0].data_type(), &DataType::Int64);
+        assert!(!fields[0].is_nullable());
+
+        assert_eq!(fields[1].name(), "label");
+        assert_eq!(fields[1].data_type(), &DataType::Utf8);
+        assert!(fields[1].is_nullable());
+
+        assert_eq!(fields[2].data_type(), &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)));
+        match *fields[3].data_type() {
+            DataType::List(ref item) => assert_eq!(item.data_type(), &DataType::Utf8),
+            ref other => panic!("not a list: {:?}", other),
+        }
+    }}
+
+    test!{non_record, {
+        let arrow_schema = to_arrow_schema(&schema::from_str(r#""double""#).unwrap()).unwrap();
+        assert_eq!(arrow_schema.fields().len(), 1);
+        assert_eq!(arrow_schema.field(0).name(), "value");
+        assert_eq!(arrow_schema.field(0).data_type(), &DataType::Float64);
+    }}
+
+    test!{nested_record, {
+        let arrow_schema = to_arrow_schema(&schema::from_str(r#"{"type": "record", "name": "Outer", "fields": [
+            {"name": "inner", "type": {"type": "record", "name": "Inner", "fields": [{"name": "n", "type": "int"}]}}
+        ]}"#).unwrap()).unwrap();
+        match *arrow_schema.field(0).data_type() {
+            DataType::Struct(ref fields) => {
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].name(), "n");
+                assert_eq!(fields[0].data_type(), &DataType::Int32);
+            },
+            ref other => panic!("not a struct: {:?}", other),
+        }
+    }}
+
+    test!{dense_union, {
+        let arrow_schema = to_arrow_schema(&schema::from_str(r#"["null", "int", "string"]"#).unwrap()).unwrap();
+        match *arrow_schema.field(0).data_type() {
+            DataType::Union(ref fields, mode) => {
+                assert_eq!(mode, UnionMode::Dense);
+                let ids: Vec<i8> = fields.iter().map(|(id, _)| id).collect();
+                assert_eq!(ids, vec![0, 1, 2]);
+                let types: Vec<DataType> = fields.iter().map(|(_, f)| f.data_type().clone()).collect();
+                assert_eq!(types, vec![DataType::Null, DataType::Int32, DataType::Utf8]);
+            },
+            ref other => panic!("not a union: {:?}", other),
+        }
+    }}
+
+    test!{map, {
+        let arrow_schema = to_arrow_schema(&schema::from_str(r#"{"type": "map", "values": "long"}"#).unwrap()).unwrap();
+        match *arrow_schema.field(0).data_type() {
+            DataType::Map(ref entries, sorted) => {
+                assert!(!sorted);
+                match *entries.data_type() {
+                    DataType::Struct(ref fields) => {
+                        assert_eq!(fields[0].data_type(), &DataType::Utf8);
+                        assert_eq!(fields[1].data_type(), &DataType::Int64);
+                    },
+                    ref other => panic!("not a struct: {:?}", other),
+                }
+            },
+            ref other => panic!("not a map: {:?}", other),
+        }
+    }}
+
+    test!{logical_types, {
+        let arrow_schema = to_arrow_schema(&schema::from_str(r#"{"type": "record", "name": "Times", "fields": [
+            {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+            {"name": "local", "type": {"type": "long", "logicalType": "local-timestamp-millis"}},
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}}
+        ]}"#).unwrap()).unwrap();
+        assert_eq!(arrow_schema.field(0).data_type(), &DataType::Date32);
+        assert_eq!(arrow_schema.field(1).data_type(), &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())));
+        assert_eq!(arrow_schema.field(2).data_type(), &DataType::Timestamp(TimeUnit::Millisecond, None));
+        assert_eq!(arrow_schema.field(3).data_type(), &DataType::Decimal128(10, 2));
+    }}
+
+    test!{recursive_type, {
+        let list = schema::from_str(r#"{"type": "record", "name": "Node", "fields": [
+            {"name": "value", "type": "int"},
+            {"name": "next", "type": ["null", "Node"]}
+        ]}"#).unwrap();
+        match to_arrow_schema(&list) {
+            Err(Error::EncodeError(ErrorCode::NotRepresentableInArrow)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }}
+
+    test!{wide_decimal, {
+        let wide = schema::from_str(r#"{"type": "bytes", "logicalType": "decimal", "precision": 40}"#).unwrap();
+        match to_arrow_schema(&wide) {
+            Err(Error::EncodeError(ErrorCode::NotRepresentableInArrow)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }}
+}
+
+mod batches {
+    use arrow::array::{Array, DictionaryArray, Int64Array, ListArray, StringArray, UnionArray};
+    use arrow::datatypes::Int32Type;
+    use ravro::arrow::BatchReader;
+    use ravro::container::{Codec, Reader, Writer};
+    use ravro::datum::Datum;
+    use ravro::schema;
+    use write_events;
+
+    test!{split_into_batches, {
+        let buf = write_events(5);
+        let batches: Vec<_> = BatchReader::new(Reader::new(&buf[..]).unwrap(), 2).unwrap().map(|b| b.unwrap()).collect();
+        let rows: Vec<usize> = batches.iter().map(|b| b.num_rows()).collect();
+        assert_eq!(rows, vec![2, 2, 1]);
+    }}
+
+    test!{columns, {
+        let buf = write_events(3);
+        let mut reader = BatchReader::new(Reader::new(&buf[..]).unwrap(), 10).unwrap();
+        let batch = reader.read_batch().unwrap().unwrap();
+        assert!(reader.read_batch().unwrap().is_none());
+        assert_eq!(batch.schema(), reader.schema());
+
+        let ids = batch.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(ids.values(), &[0, 1, 2]);
+
+        let labels = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(labels.value(0), "event0");
+        assert!(labels.is_null(1));
+        assert_eq!(labels.value(2), "event2");
+
+        let levels = batch.column(2).as_any().downcast_ref::<DictionaryArray<Int32Type>>().unwrap();
+        assert_eq!(levels.keys().values(), &[0, 0, 1]);
+
+        let tags = batch.column(3).as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(tags.value_length(0), 0);
+        assert_eq!(tags.value_length(2), 2);
+    }}
+
+    test!{empty_file, {
+        let writer = Writer::new(Vec::new(), schema::from_str(::EVENT).unwrap(), Codec::Null);
+        let buf = writer.into_inner().unwrap();
+        let mut reader = BatchReader::new(Reader::new(&buf[..]).unwrap(), 10).unwrap();
+        assert!(reader.read_batch().unwrap().is_none());
+    }}
+
+    test!{union_column, {
+        let union = schema::from_str(r#"["null", "int", "string"]"#).unwrap();
+        let mut writer = Writer::new(Vec::new(), union, Codec::Null);
+        writer.append(&Datum::Union(1, Box::new(Datum::Int(7)))).unwrap();
+        writer.append(&Datum::Union(2, Box::new(Datum::String(String::from("seven"))))).unwrap();
+        writer.append(&Datum::Union(0, Box::new(Datum::Null))).unwrap();
+        writer.append(&Datum::Union(1, Box::new(Datum::Int(8)))).unwrap();
+        let buf = writer.into_inner().unwrap();
+
+        let batch = BatchReader::new(Reader::new(&buf[..]).unwrap(), 10).unwrap().read_batch().unwrap().unwrap();
+        let values = batch.column(0).as_any().downcast_ref::<UnionArray>().unwrap();
+        assert_eq!((0..4).map(|i| values.type_id(i)).collect::<Vec<_>>(), vec![1, 2, 0, 1]);
+        assert_eq!((0..4).map(|i| values.value_offset(i)).collect::<Vec<_>>(), vec![0, 0, 0, 1]);
+    }}
+}
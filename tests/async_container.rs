@@ -0,0 +1,230 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "async")]
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate futures;
+extern crate ravro;
+extern crate serde;
+extern crate tokio;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const USER: &'static str = r#"{"type": "record", "name": "User", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": "int"}
+]}"#;
+
+fn user(i: i32) -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    Datum::Record(vec![(String::from("name"), Datum::String(format!("user{}", i))), (String::from("age"), Datum::Int(i))])
+}
+
+fn block_on<F: futures::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+}
+
+mod round_trip {
+    use futures::{future, stream, StreamExt, TryFutureExt, TryStreamExt};
+    use tokio::io::duplex;
+
+    use ravro::container::{AsyncReader, AsyncWriter, Codec};
+    use ravro::datum::Datum;
+    use ravro::schema;
+    use {block_on, user, USER};
+
+    // Writes n users into one end of a pipe while reading them from the other.
+    fn through_pipe(n: i32, codec: Codec, pipe_size: usize) -> Vec<Datum> {
+        let (client, server) = duplex(pipe_size);
+        let mut writer = AsyncWriter::new(client, schema::from_str(USER).unwrap(), codec);
+        writer.set_block_size(64);
+        let writing = stream::iter((0..n).map(|i| Ok(user(i)))).forward(writer);
+        let reading = AsyncReader::new(server).and_then(|reader| reader.try_collect::<Vec<_>>());
+        let (written, read) = block_on(future::join(writing, reading));
+        written.unwrap();
+        read.unwrap()
+    }
+
+    test!{null_codec, {
+        assert_eq!(through_pipe(100, Codec::Null, 4096), (0..100).map(user).collect::<Vec<_>>());
+    }}
+
+    test!{deflate_codec, {
+        assert_eq!(through_pipe(100, Codec::Deflate, 4096), (0..100).map(user).collect::<Vec<_>>());
+    }}
+
+    test!{small_pipe, {
+        // Smaller than a block, so both sides have to wait on each other.
+        assert_eq!(through_pipe(100, Codec::Deflate, 7), (0..100).map(user).collect::<Vec<_>>());
+    }}
+
+    test!{empty_file, {
+        assert_eq!(through_pipe(0, Codec::Null, 64), Vec::new());
+    }}
+
+    test!{metadata, {
+        let (client, server) = duplex(4096);
+        let mut writer = AsyncWriter::new(client, schema::from_str(USER).unwrap(), Codec::Deflate);
+        writer.set_meta("owner", b"ingest").unwrap();
+        let writing = stream::iter(vec![Ok(user(1))]).forward(writer);
+        let (written, reader) = block_on(future::join(writing, AsyncReader::new(server)));
+        written.unwrap();
+
+        let reader = reader.unwrap();
+        assert_eq!(reader.schema(), &schema::from_str(USER).unwrap());
+        assert_eq!(reader.codec(), Codec::Deflate);
+        assert_eq!(reader.meta().get("owner"), Some(&b"ingest".to_vec()));
+        assert_eq!(block_on(reader.collect::<Vec<_>>()).len(), 1);
+    }}
+}
+
+mod blocking {
+    use std::io::Cursor;
+
+    use futures::{SinkExt, TryFutureExt, TryStreamExt};
+
+    use ravro::container::{AsyncReader, AsyncWriter, Codec, Reader, Writer};
+    use ravro::schema;
+    use {block_on, user, USER};
+
+    test!{async_reader_reads_blocking_writer, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Deflate);
+        writer.set_block_size(64);
+        for i in 0..100 {
+            writer.append(&user(i)).unwrap();
+        }
+        let buf = writer.into_inner().unwrap();
+
+        let read = block_on(AsyncReader::new(&buf[..]).and_then(|reader| reader.try_collect::<Vec<_>>())).unwrap();
+        assert_eq!(read, (0..100).map(user).collect::<Vec<_>>());
+    }}
+
+    test!{blocking_reader_reads_async_writer, {
+        let mut writer = AsyncWriter::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Deflate);
+        writer.set_block_size(64);
+        let mut datums = futures::stream::iter((0..100).map(|i| Ok(user(i))));
+        block_on(writer.send_all(&mut datums)).unwrap();
+
+        let read = Reader::new(Cursor::new(writer.into_inner())).unwrap().map(|d| d.unwrap()).collect::<Vec<_>>();
+        assert_eq!(read, (0..100).map(user).collect::<Vec<_>>());
+    }}
+}
+
+mod errors {
+    use futures::{StreamExt, TryFutureExt};
+
+    use ravro::container::{AsyncReader, Codec, Writer};
+    use ravro::encoding::binary;
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use {block_on, user, USER};
+
+    test!{not_a_container_file, {
+        let buf = b"not a container file".to_vec();
+        assert!(block_on(AsyncReader::new(&buf[..])).is_err());
+    }}
+
+    test!{truncated_header, {
+        let buf = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null).into_inner().unwrap();
+        assert!(block_on(AsyncReader::new(&buf[..buf.len() - 1])).is_err());
+    }}
+
+    test!{truncated_block, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null);
+        for i in 0..10 {
+            writer.append(&user(i)).unwrap();
+        }
+        let buf = writer.into_inner().unwrap();
+
+        let read = block_on(AsyncReader::new(&buf[..buf.len() - 3]).map_ok(|reader| reader.collect::<Vec<_>>()));
+        let results = block_on(read.unwrap());
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }}
+
+    test!{sync_marker_mismatch, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null);
+        writer.append(&user(1)).unwrap();
+        let mut buf = writer.into_inner().unwrap();
+        let last = buf.len() - 1;
+        buf[last] ^= 0xff;
+
+        let reader = block_on(AsyncReader::new(&buf[..])).unwrap();
+        let results = block_on(reader.collect::<Vec<_>>());
+        assert!(results[0].is_err());
+    }}
+
+    test!{block_over_the_maximum_size, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null);
+        for i in 0..10 {
+            writer.append(&user(i)).unwrap();
+        }
+        let buf = writer.into_inner().unwrap();
+
+        let mut reader = block_on(AsyncReader::new(&buf[..])).unwrap();
+        reader.set_max_block_size(8);
+        let results = block_on(reader.collect::<Vec<_>>());
+        match results[0] {
+            Err(Error::DecodeError(ErrorCode::CorruptBlock)) => (),
+            ref other => panic!("expected a corrupt block, got {:?}", other),
+        }
+    }}
+
+    test!{huge_header_length, {
+        // A metadata map of one entry whose key claims to be 2^62 bytes long,
+        // more than a usize holds on 32-bit targets.
+        let mut buf = vec![b'O', b'b', b'j', 1, 0x02];
+        buf.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01]);
+        buf.extend_from_slice(&[0u8; 64]);
+        assert!(block_on(AsyncReader::new(&buf[..])).is_err());
+    }}
+
+    test!{header_over_the_maximum_size, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null);
+        writer.set_meta("blob", &[0u8; 1024]).unwrap();
+        writer.append(&user(1)).unwrap();
+        let buf = writer.into_inner().unwrap();
+
+        let mut open = AsyncReader::new(&buf[..]);
+        open.set_max_header_size(512);
+        match block_on(open) {
+            Err(Error::DecodeError(ErrorCode::CorruptBlock)) => (),
+            other => panic!("expected a corrupt block, got {:?}", other.map(|_| ())),
+        }
+    }}
+
+    test!{header_length_over_the_maximum_size, {
+        // A key that claims to be 1 GiB long is refused before that much is
+        // buffered, rather than waited for.
+        let mut buf = vec![b'O', b'b', b'j', 1, 0x02];
+        binary::write_long(1 << 30, &mut buf).unwrap();
+        match block_on(AsyncReader::new(&buf[..])) {
+            Err(Error::DecodeError(ErrorCode::CorruptBlock)) => (),
+            other => panic!("expected a corrupt block, got {:?}", other.map(|_| ())),
+        }
+    }}
+}
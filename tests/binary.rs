@@ -0,0 +1,189 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+fn parse(s: &str) -> ravro::schema::Schema {
+    let value: serde::json::Value = serde::json::from_str(s).unwrap();
+    ravro::schema::Schema::from(&value)
+}
+
+mod primitive {
+    use ravro::datum::Datum;
+    use ravro::encoding::binary;
+    use parse;
+
+    fn bytes(datum: Datum, schema: &str) -> Vec<u8> {
+        binary::to_vec(&datum, &parse(schema)).unwrap()
+    }
+
+    fn round_trip(datum: Datum, schema: &str) -> Datum {
+        let schema = parse(schema);
+        let buf = binary::to_vec(&datum, &schema).unwrap();
+        binary::decode(&schema, &mut &buf[..]).unwrap()
+    }
+
+    test!{zigzag_longs, {
+        assert_eq!(bytes(Datum::Long(0), r#""long""#), vec![0x00]);
+        assert_eq!(bytes(Datum::Long(-1), r#""long""#), vec![0x01]);
+        assert_eq!(bytes(Datum::Long(1), r#""long""#), vec![0x02]);
+        assert_eq!(bytes(Datum::Long(-64), r#""long""#), vec![0x7f]);
+        assert_eq!(bytes(Datum::Long(64), r#""long""#), vec![0x80, 0x01]);
+    }}
+
+    test!{strings, {
+        assert_eq!(bytes(Datum::from("foo"), r#""string""#), vec![0x06, 0x66, 0x6f, 0x6f]);
+    }}
+
+    test!{round_trips, {
+        assert_eq!(round_trip(Datum::Null, r#""null""#), Datum::Null);
+        assert_eq!(round_trip(Datum::Boolean(true), r#""boolean""#), Datum::Boolean(true));
+        assert_eq!(round_trip(Datum::Int(i32::min_value()), r#""int""#), Datum::Int(i32::min_value()));
+        assert_eq!(round_trip(Datum::Long(i64::max_value()), r#""long""#), Datum::Long(i64::max_value()));
+        assert_eq!(round_trip(Datum::Float(1.5), r#""float""#), Datum::Float(1.5));
+        assert_eq!(round_trip(Datum::Double(-2.25), r#""double""#), Datum::Double(-2.25));
+        assert_eq!(round_trip(Datum::Bytes(vec![0, 255]), r#""bytes""#), Datum::Bytes(vec![0, 255]));
+    }}
+
+    test!{promotes_int_to_long, {
+        assert_eq!(bytes(Datum::Int(64), r#""long""#), vec![0x80, 0x01]);
+    }}
+
+    test!{int_out_of_range, {
+        let schema = parse(r#""int""#);
+        assert!(binary::decode(&schema, &mut &[0xff, 0xff, 0xff, 0xff, 0x0f][..]).is_ok());
+        assert!(binary::decode(&schema, &mut &[0xff, 0xff, 0xff, 0xff, 0x1f][..]).is_err());
+    }}
+
+    test!{truncated_input, {
+        let schema = parse(r#""string""#);
+        assert!(binary::decode(&schema, &mut &[0x06, 0x66][..]).is_err());
+    }}
+}
+
+mod complex {
+    use std::collections::BTreeMap;
+    use ravro::datum::Datum;
+    use ravro::encoding::binary;
+    use parse;
+
+    const PERSON: &'static str = r#"{"type": "record", "name": "Person", "namespace": "x", "fields": [
+        {"name": "name", "type": "string"},
+        {"name": "age", "type": ["null", "int"], "default": null},
+        {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["A", "B"]}, "default": "B"},
+        {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 2}},
+        {"name": "friends", "type": {"type": "array", "items": "Person"}, "default": []}
+    ]}"#;
+
+    test!{record_round_trip, {
+        let schema = parse(PERSON);
+        let friend = Datum::Record(vec![
+            (String::from("name"), Datum::from("b")),
+            (String::from("age"), Datum::Union(1, Box::new(Datum::Int(3)))),
+            (String::from("kind"), Datum::Enum(String::from("A"))),
+            (String::from("id"), Datum::Fixed(vec![1, 2])),
+            (String::from("friends"), Datum::Array(vec![])),
+        ]);
+        let person = Datum::Record(vec![
+            (String::from("name"), Datum::from("a")),
+            (String::from("age"), Datum::Union(0, Box::new(Datum::Null))),
+            (String::from("kind"), Datum::Enum(String::from("B"))),
+            (String::from("id"), Datum::Fixed(vec![3, 4])),
+            (String::from("friends"), Datum::Array(vec![friend])),
+        ]);
+
+        let buf = binary::to_vec(&person, &schema).unwrap();
+        assert_eq!(binary::decode(&schema, &mut &buf[..]).unwrap(), person);
+    }}
+
+    test!{missing_fields_use_defaults, {
+        let schema = parse(PERSON);
+        let person = Datum::Record(vec![
+            (String::from("name"), Datum::from("a")),
+            (String::from("id"), Datum::Fixed(vec![0, 0])),
+        ]);
+
+        let buf = binary::to_vec(&person, &schema).unwrap();
+        let decoded = binary::decode(&schema, &mut &buf[..]).unwrap();
+        assert_eq!(decoded.field("age"), Some(&Datum::Union(0, Box::new(Datum::Null))));
+        assert_eq!(decoded.field("kind"), Some(&Datum::Enum(String::from("B"))));
+        assert_eq!(decoded.field("friends"), Some(&Datum::Array(vec![])));
+    }}
+
+    test!{missing_field_without_default, {
+        let schema = parse(PERSON);
+        let person = Datum::Record(vec![(String::from("name"), Datum::from("a"))]);
+        assert!(binary::to_vec(&person, &schema).is_err());
+    }}
+
+    test!{union_branch_chosen_by_value, {
+        let schema = parse(r#"["null", "string", "long"]"#);
+        assert_eq!(binary::to_vec(&Datum::Null, &schema).unwrap(), vec![0x00]);
+        assert_eq!(binary::to_vec(&Datum::from("a"), &schema).unwrap(), vec![0x02, 0x02, 0x61]);
+        assert_eq!(binary::to_vec(&Datum::Int(1), &schema).unwrap(), vec![0x04, 0x02]);
+        assert!(binary::to_vec(&Datum::Boolean(true), &schema).is_err());
+    }}
+
+    test!{maps, {
+        let schema = parse(r#"{"type": "map", "values": "long"}"#);
+        let mut map = BTreeMap::new();
+        map.insert(String::from("a"), Datum::Long(1));
+
+        let buf = binary::to_vec(&Datum::Map(map.clone()), &schema).unwrap();
+        assert_eq!(buf, vec![0x02, 0x02, 0x61, 0x02, 0x00]);
+        assert_eq!(binary::decode(&schema, &mut &buf[..]).unwrap(), Datum::Map(map));
+    }}
+
+    test!{blocks_with_sizes, {
+        let schema = parse(r#"{"type": "array", "items": "int"}"#);
+        // A block of two items given as a negative count followed by its size in bytes.
+        let buf = vec![0x03, 0x04, 0x02, 0x04, 0x00];
+        assert_eq!(binary::decode(&schema, &mut &buf[..]).unwrap(),
+                   Datum::Array(vec![Datum::Int(1), Datum::Int(2)]));
+    }}
+
+    test!{most_negative_block_count, {
+        let schema = parse(r#"{"type": "array", "items": "int"}"#);
+        let mut buf = Vec::new();
+        binary::write_long(i64::min_value(), &mut buf).unwrap();
+        binary::write_long(0, &mut buf).unwrap();
+        assert!(binary::decode(&schema, &mut &buf[..]).is_err());
+    }}
+
+    test!{unknown_enum_symbol, {
+        let schema = parse(r#"{"type": "enum", "name": "E", "symbols": ["A"]}"#);
+        assert!(binary::to_vec(&Datum::Enum(String::from("Z")), &schema).is_err());
+        assert!(binary::decode(&schema, &mut &[0x02][..]).is_err());
+    }}
+
+    test!{fixed_size_mismatch, {
+        let schema = parse(r#"{"type": "fixed", "name": "F", "size": 2}"#);
+        assert!(binary::to_vec(&Datum::Fixed(vec![1]), &schema).is_err());
+    }}
+}
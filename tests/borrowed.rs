@@ -0,0 +1,149 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate rand;
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const USER: &'static str = r#"{"type": "record", "name": "User", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "avatar", "type": ["null", "bytes"]},
+    {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 4}},
+    {"name": "role", "type": {"type": "enum", "name": "Role", "symbols": ["ADMIN", "GUEST"]}},
+    {"name": "tags", "type": {"type": "map", "values": "long"}}
+]}"#;
+
+fn user() -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    let mut tags = ::std::collections::BTreeMap::new();
+    tags.insert(String::from("logins"), Datum::Long(12));
+    Datum::Record(vec![
+        (String::from("name"), Datum::from("alice")),
+        (String::from("avatar"), Datum::Union(1, Box::new(Datum::Bytes(vec![1, 2, 3])))),
+        (String::from("id"), Datum::Fixed(vec![9, 8, 7, 6])),
+        (String::from("role"), Datum::Enum(String::from("GUEST"))),
+        (String::from("tags"), Datum::Map(tags)),
+    ])
+}
+
+mod decode {
+    use ravro::datum::ValueRef;
+    use ravro::encoding::binary;
+    use ravro::encoding::borrowed::BorrowedDecoder;
+    use ravro::schema::names::Names;
+    use ravro::schema;
+    use serde::json::Value;
+    use {user, USER};
+
+    test!{points_into_the_input, {
+        let schema = schema::from_str(USER).unwrap();
+        let bytes = binary::to_vec(&user(), &schema).unwrap();
+        let names = Names::from_schema(&schema);
+        let value = Value::from(&schema);
+
+        let mut input = &bytes[..];
+        let datum = BorrowedDecoder::new(&names).decode(&value, None, &mut input).unwrap();
+        assert!(input.is_empty());
+
+        let range = bytes.as_ptr() as usize..bytes.as_ptr() as usize + bytes.len();
+        match datum.field("name") {
+            Some(&ValueRef::String(name)) => {
+                assert_eq!(name, "alice");
+                assert!(range.contains(&(name.as_ptr() as usize)));
+            },
+            other => panic!("unexpected name {:?}", other),
+        }
+        match datum.field("id") {
+            Some(&ValueRef::Fixed(id)) => assert!(range.contains(&(id.as_ptr() as usize))),
+            other => panic!("unexpected id {:?}", other),
+        }
+        assert_eq!(datum.field("avatar").unwrap().unwrap_union(), &ValueRef::Bytes(&[1, 2, 3]));
+        assert_eq!(datum.field("role"), Some(&ValueRef::Enum("GUEST")));
+    }}
+
+    test!{to_owned_matches_the_owned_decoder, {
+        use rand::{SeedableRng, StdRng};
+        use ravro::random;
+
+        let schema = schema::from_str(USER).unwrap();
+        let names = Names::from_schema(&schema);
+        let value = Value::from(&schema);
+        let mut rng = StdRng::from_seed(&[5usize][..]);
+        for _ in 0..50 {
+            let datum = random::generate(&schema, &mut rng).unwrap();
+            let bytes = binary::to_vec(&datum, &schema).unwrap();
+            let borrowed = BorrowedDecoder::new(&names).decode(&value, None, &mut &bytes[..]).unwrap();
+            assert_eq!(borrowed.to_owned(), binary::decode(&schema, &mut &bytes[..]).unwrap());
+        }
+    }}
+
+    test!{truncated_input, {
+        let schema = schema::from_str(r#""string""#).unwrap();
+        let names = Names::from_schema(&schema);
+        let value = Value::from(&schema);
+        // A length of 5 with only 2 bytes after it.
+        assert!(BorrowedDecoder::new(&names).decode(&value, None, &mut &[0x0a, b'h', b'i'][..]).is_err());
+        assert!(BorrowedDecoder::new(&names).decode(&value, None, &mut &[0x02, 0xff][..]).is_err());
+    }}
+}
+
+mod container {
+    use ravro::container::{Codec, Reader, Writer};
+    use ravro::schema;
+    use {user, USER};
+
+    fn file(codec: Codec) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), codec);
+        writer.set_block_size(16);
+        for _ in 0..5 {
+            writer.append(&user()).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    test!{read_ref_with_each_codec, {
+        for codec in [Codec::Null, Codec::Deflate].iter() {
+            let buf = file(*codec);
+            let mut reader = Reader::new(&buf[..]).unwrap();
+            let mut count = 0;
+            while let Some(datum) = reader.read_ref().unwrap() {
+                assert_eq!(datum.to_owned(), user());
+                count += 1;
+            }
+            assert_eq!(count, 5);
+        }
+    }}
+
+    test!{not_with_a_reader_schema, {
+        let buf = file(Codec::Null);
+        let projection = schema::from_str(USER).unwrap().project(&["name"]).unwrap();
+        let mut reader = Reader::with_reader_schema(&buf[..], &projection).unwrap();
+        assert!(reader.read_ref().is_err());
+    }}
+}
@@ -0,0 +1,415 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+pub const USER: &'static str = r#"{"type": "record", "name": "User", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": ["null", "int"]}
+]}"#;
+
+pub const USERS_JSON: &'static str = r#"{"name": "a", "age": {"int": 1}}
+{"name": "b", "age": null}
+{"name": "c", "age": {"int": 3}}
+"#;
+
+// A scratch directory for one test.
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ravro-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn write_file(path: &PathBuf, contents: &str) {
+    File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+}
+
+pub fn ravro(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ravro")).args(args).stdin(Stdio::null()).output().unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+// Writes USERS_JSON to users.avro in `dir` and returns its path.
+pub fn users_file(dir: &PathBuf) -> String {
+    let schema = dir.join("user.avsc");
+    let json = dir.join("users.json");
+    write_file(&schema, USER);
+    write_file(&json, USERS_JSON);
+
+    let output = ravro(&["fromjson", "--schema", schema.to_str().unwrap(), json.to_str().unwrap()]);
+    assert!(output.status.success());
+    let avro = dir.join("users.avro");
+    File::create(&avro).unwrap().write_all(&output.stdout).unwrap();
+    String::from(avro.to_str().unwrap())
+}
+
+mod inspect {
+    use std::fs;
+    use {ravro, scratch, stdout, users_file};
+
+    test!{getschema_and_getmeta, {
+        let dir = scratch("inspect");
+        let users = users_file(&dir);
+
+        let output = ravro(&["getschema", &users]);
+        assert!(output.status.success());
+        assert!(stdout(&output).contains("\"User\""));
+
+        let output = ravro(&["getmeta", "--key", "avro.codec", &users]);
+        assert_eq!(stdout(&output), "null\n");
+        assert!(stdout(&ravro(&["getmeta", &users])).contains("avro.schema\t"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{tojson_and_count, {
+        let dir = scratch("tojson");
+        let users = users_file(&dir);
+
+        let output = ravro(&["tojson", &users]);
+        assert_eq!(stdout(&output), concat!(
+            r#"{"age":{"int":1},"name":"a"}"#, "\n",
+            r#"{"age":null,"name":"b"}"#, "\n",
+            r#"{"age":{"int":3},"name":"c"}"#, "\n"));
+
+        assert_eq!(stdout(&ravro(&["count", &users, &users])), "6\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{tojson_pretty, {
+        let dir = scratch("tojson-pretty");
+        let users = users_file(&dir);
+
+        let output = ravro(&["tojson", "--pretty", &users]);
+        assert!(output.status.success());
+        let text = stdout(&output);
+        assert!(text.contains("\n  \"name\": \"a\""));
+        assert_eq!(text.matches("\"name\"").count(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
+
+mod convert {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use {ravro, scratch, stdout, users_file, write_file, USER, USERS_JSON};
+
+    // Just the names, with a default nickname.
+    const NAMES: &'static str = r#"{"type": "record", "name": "User", "fields": [
+        {"name": "name", "type": "string"},
+        {"name": "nickname", "type": "string", "default": "none"}
+    ]}"#;
+
+    test!{fromjson_with_codec, {
+        let dir = scratch("fromjson-codec");
+        let schema = dir.join("user.avsc");
+        let json = dir.join("users.json");
+        write_file(&schema, USER);
+        write_file(&json, USERS_JSON);
+
+        let output = ravro(&["fromjson", "--schema", schema.to_str().unwrap(), "--codec", "deflate", json.to_str().unwrap()]);
+        assert!(output.status.success());
+        let avro = dir.join("users.avro");
+        File::create(&avro).unwrap().write_all(&output.stdout).unwrap();
+        let avro = avro.to_str().unwrap();
+        assert_eq!(stdout(&ravro(&["getmeta", "--key", "avro.codec", avro])), "deflate\n");
+        assert_eq!(stdout(&ravro(&["count", avro])), "3\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{tojson_with_reader_schema, {
+        let dir = scratch("tojson-reader");
+        let users = users_file(&dir);
+        let names = dir.join("names.avsc");
+        write_file(&names, NAMES);
+
+        let output = ravro(&["tojson", "--reader-schema", names.to_str().unwrap(), &users]);
+        assert_eq!(stdout(&output), concat!(
+            r#"{"name":"a","nickname":"none"}"#, "\n",
+            r#"{"name":"b","nickname":"none"}"#, "\n",
+            r#"{"name":"c","nickname":"none"}"#, "\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{fromjson_with_reader_schema, {
+        let dir = scratch("fromjson-reader");
+        let schema = dir.join("user.avsc");
+        let names = dir.join("names.avsc");
+        let json = dir.join("users.json");
+        write_file(&schema, USER);
+        write_file(&names, NAMES);
+        write_file(&json, USERS_JSON);
+
+        let output = ravro(&["fromjson", "--schema", schema.to_str().unwrap(), "--reader-schema", names.to_str().unwrap(), json.to_str().unwrap()]);
+        assert!(output.status.success());
+        let avro = dir.join("names.avro");
+        File::create(&avro).unwrap().write_all(&output.stdout).unwrap();
+        let avro = avro.to_str().unwrap();
+        assert!(stdout(&ravro(&["getschema", avro])).contains("nickname"));
+        assert_eq!(stdout(&ravro(&["tojson", avro])).lines().next(), Some(r#"{"name":"a","nickname":"none"}"#));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{schema_errors_have_a_position, {
+        let dir = scratch("schema-position");
+        let schema = dir.join("broken.avsc");
+        let json = dir.join("users.json");
+        write_file(&schema, "{\"type\": \"record\",\n  \"name\": }");
+        write_file(&json, USERS_JSON);
+
+        let output = ravro(&["fromjson", "--schema", schema.to_str().unwrap(), json.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(stderr.starts_with(&format!("ravro: {}:2:", schema.to_str().unwrap())));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
+
+mod rewrite {
+    use std::fs;
+    use {ravro, scratch, stdout, users_file};
+
+    test!{cat_with_offset_and_limit, {
+        let dir = scratch("cat");
+        let users = users_file(&dir);
+        let out = dir.join("out.avro");
+        let out = out.to_str().unwrap();
+
+        assert!(ravro(&["cat", "--offset", "1", "--limit", "3", &users, &users, out]).status.success());
+        assert_eq!(stdout(&ravro(&["tojson", out])), concat!(
+            r#"{"age":null,"name":"b"}"#, "\n",
+            r#"{"age":{"int":3},"name":"c"}"#, "\n",
+            r#"{"age":{"int":1},"name":"a"}"#, "\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{concat_and_recodec, {
+        let dir = scratch("concat");
+        let users = users_file(&dir);
+        let joined = dir.join("joined.avro");
+        let joined = joined.to_str().unwrap();
+        let deflated = dir.join("deflated.avro");
+        let deflated = deflated.to_str().unwrap();
+
+        assert!(ravro(&["concat", &users, &users, joined]).status.success());
+        assert_eq!(stdout(&ravro(&["count", joined])), "6\n");
+
+        assert!(ravro(&["recodec", "--codec", "deflate", joined, deflated]).status.success());
+        assert_eq!(stdout(&ravro(&["getmeta", "--key", "avro.codec", deflated])), "deflate\n");
+        assert_eq!(stdout(&ravro(&["tojson", deflated])), stdout(&ravro(&["tojson", joined])));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
+
+mod errors {
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use {ravro, scratch, users_file, write_file};
+
+    test!{validate_schema, {
+        let dir = scratch("validate");
+        let good = dir.join("good.avsc");
+        let bad = dir.join("bad.avsc");
+        write_file(&good, r#"{"type": "enum", "name": "E", "symbols": ["A"]}"#);
+        write_file(&bad, r#"{"type": "record", "name": "1R", "fields": []}"#);
+
+        let output = ravro(&["validate-schema", good.to_str().unwrap()]);
+        assert!(output.status.success());
+
+        let output = ravro(&["validate-schema", bad.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("ravro: "));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{top_level_union, {
+        let dir = scratch("union");
+        let schema = dir.join("union.avsc");
+        let json = dir.join("union.json");
+        write_file(&schema, r#"["null", "string"]"#);
+        write_file(&json, "null\n{\"string\": \"a\"}\n");
+
+        assert!(ravro(&["validate-schema", schema.to_str().unwrap()]).status.success());
+        let output = ravro(&["fromjson", "--schema", schema.to_str().unwrap(), json.to_str().unwrap()]);
+        assert!(output.status.success());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{exit_codes, {
+        let dir = scratch("exit");
+        let not_avro = dir.join("not.avro");
+        write_file(&not_avro, "hello");
+
+        assert_eq!(ravro(&[]).status.code(), Some(1));
+        assert_eq!(ravro(&["frobnicate"]).status.code(), Some(1));
+        assert_eq!(ravro(&["count", "--bogus", "x"]).status.code(), Some(1));
+        assert_eq!(ravro(&["count", dir.join("missing.avro").to_str().unwrap()]).status.code(), Some(3));
+        assert_eq!(ravro(&["count", not_avro.to_str().unwrap()]).status.code(), Some(4));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{concat_different_schemas, {
+        let dir = scratch("mismatch");
+        let users = users_file(&dir);
+        let schema = dir.join("e.avsc");
+        let json = dir.join("e.json");
+        let other = dir.join("e.avro");
+        write_file(&schema, r#"{"type": "enum", "name": "E", "symbols": ["A"]}"#);
+        write_file(&json, "\"A\"\n");
+        let output = ravro(&["fromjson", "--schema", schema.to_str().unwrap(), json.to_str().unwrap()]);
+        assert!(output.status.success());
+        File::create(&other).unwrap().write_all(&output.stdout).unwrap();
+
+        let out = dir.join("out.avro");
+        let output = ravro(&["concat", &users, other.to_str().unwrap(), out.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("ravro: "));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
+
+mod diff {
+    use std::fs;
+    use {ravro, scratch, stdout, write_file, USER};
+
+    test!{report, {
+        let dir = scratch("diff");
+        let old = dir.join("old.avsc");
+        let new = dir.join("new.avsc");
+        write_file(&old, USER);
+        write_file(&new, r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "email", "type": ["null", "string"], "default": null}
+        ]}"#);
+
+        let output = ravro(&["diff", old.to_str().unwrap(), new.to_str().unwrap()]);
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), "\
+User: field email added (compatible)
+User: field age removed (breaks forward compatibility)
+backward compatible: yes, forward compatible: no
+");
+        assert_eq!(stdout(&ravro(&["diff", old.to_str().unwrap(), old.to_str().unwrap()])), "no changes\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
+
+mod doc {
+    use std::fs;
+    use {ravro, scratch, stdout, write_file, USER};
+
+    test!{markdown_and_html, {
+        let dir = scratch("doc");
+        let schema = dir.join("user.avsc");
+        write_file(&schema, USER);
+        let schema = schema.to_str().unwrap();
+
+        let markdown = stdout(&ravro(&["doc", "--title", "Users", schema]));
+        assert!(markdown.starts_with("# Users\n"));
+        assert!(markdown.contains("<a id=\"User\"></a>\n## User\n"));
+        assert!(markdown.contains("| `age` | union&lt;null, int&gt; |"));
+
+        let html = stdout(&ravro(&["doc", "--format", "html", schema]));
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h2 id=\"User\">User</h2>"));
+
+        assert_eq!(ravro(&["doc", "--format", "pdf", schema]).status.code(), Some(1));
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{protocol, {
+        let dir = scratch("doc-protocol");
+        let protocol = dir.join("greeter.avpr");
+        write_file(&protocol, r#"{"protocol": "Greeter", "namespace": "g", "types": [
+            {"type": "record", "name": "Greeting", "fields": [{"name": "text", "type": "string"}]}
+        ], "messages": {"hello": {"request": [{"name": "greeting", "type": "Greeting"}], "response": "Greeting"}}}"#);
+
+        let markdown = stdout(&ravro(&["doc", protocol.to_str().unwrap()]));
+        assert!(markdown.starts_with("# Greeter\n"));
+        assert!(markdown.contains("### hello"));
+        assert!(markdown.contains("| `greeting` | [Greeting](#g.Greeting) |"));
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
+
+mod random {
+    use std::fs;
+    use {ravro, scratch, stdout, write_file, USER};
+
+    test!{writes_count_datums, {
+        let dir = scratch("random");
+        let schema = dir.join("user.avsc");
+        write_file(&schema, USER);
+        let out = dir.join("random.avro");
+        let out = out.to_str().unwrap();
+
+        let output = ravro(&["random", "--schema", schema.to_str().unwrap(), "--count", "25", "--seed", "7", "--codec", "deflate", out]);
+        assert!(output.status.success());
+        assert_eq!(stdout(&ravro(&["count", out])), "25\n");
+        assert_eq!(stdout(&ravro(&["getmeta", "--key", "avro.codec", out])), "deflate\n");
+
+        // The same seed gives the same datums.
+        let first = stdout(&ravro(&["tojson", out]));
+        ravro(&["random", "--schema", schema.to_str().unwrap(), "--count", "25", "--seed", "7", out]);
+        assert_eq!(stdout(&ravro(&["tojson", out])), first);
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{needs_a_count, {
+        let dir = scratch("random-usage");
+        let schema = dir.join("user.avsc");
+        write_file(&schema, USER);
+        let output = ravro(&["random", "--schema", schema.to_str().unwrap(), "-"]);
+        assert_eq!(output.status.code(), Some(1));
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
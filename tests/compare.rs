@@ -0,0 +1,176 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate rand;
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+use std::cmp::Ordering;
+
+use ravro::datum::Datum;
+use ravro::encoding::binary;
+use ravro::schema::{self, Schema};
+
+const PERSON: &'static str = r#"{"type": "record", "name": "Person", "fields": [
+    {"name": "last", "type": "string"},
+    {"name": "age", "type": "int", "order": "descending"},
+    {"name": "note", "type": "string", "order": "ignore"},
+    {"name": "tags", "type": {"type": "array", "items": "string"}},
+    {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["Z", "A"]}},
+    {"name": "score", "type": ["null", "double"]}
+]}"#;
+
+fn person(last: &str, age: i32, note: &str, tags: &[&str], kind: &str, score: Option<f64>) -> Datum {
+    Datum::Record(vec![
+        (String::from("last"), Datum::from(last)),
+        (String::from("age"), Datum::Int(age)),
+        (String::from("note"), Datum::from(note)),
+        (String::from("tags"), Datum::Array(tags.iter().map(|t| Datum::from(*t)).collect())),
+        (String::from("kind"), Datum::Enum(String::from(kind))),
+        (String::from("score"), match score {
+            Some(s) => Datum::Union(1, Box::new(Datum::Double(s))),
+            None => Datum::Union(0, Box::new(Datum::Null)),
+        }),
+    ])
+}
+
+// Compares both ways and checks they agree.
+fn compare(a: &Datum, b: &Datum, schema: &Schema) -> Ordering {
+    use ravro::compare::{compare_encoded, compare_values};
+
+    let by_value = compare_values(a, b, schema).unwrap();
+    let x = binary::to_vec(a, schema).unwrap();
+    let y = binary::to_vec(b, schema).unwrap();
+    assert_eq!(compare_encoded(&x, &y, schema).unwrap(), by_value);
+    by_value
+}
+
+mod primitives {
+    use std::cmp::Ordering;
+    use ravro::datum::Datum;
+    use ravro::schema;
+    use compare;
+
+    test!{numbers_and_strings, {
+        let int = schema::from_str(r#""int""#).unwrap();
+        assert_eq!(compare(&Datum::Int(-5), &Datum::Int(3), &int), Ordering::Less);
+        assert_eq!(compare(&Datum::Int(7), &Datum::Int(7), &int), Ordering::Equal);
+
+        let long = schema::from_str(r#""long""#).unwrap();
+        assert_eq!(compare(&Datum::Long(1 << 40), &Datum::Long(-(1 << 40)), &long), Ordering::Greater);
+
+        let string = schema::from_str(r#""string""#).unwrap();
+        assert_eq!(compare(&Datum::from("ab"), &Datum::from("b"), &string), Ordering::Less);
+        assert_eq!(compare(&Datum::from("abc"), &Datum::from("ab"), &string), Ordering::Greater);
+        // By code point, so a character outside ASCII sorts after any ASCII one.
+        assert_eq!(compare(&Datum::from("\u{e9}"), &Datum::from("z"), &string), Ordering::Greater);
+
+        let bytes = schema::from_str(r#""bytes""#).unwrap();
+        assert_eq!(compare(&Datum::Bytes(vec![0xff]), &Datum::Bytes(vec![0x01, 0x00]), &bytes), Ordering::Greater);
+
+        let boolean = schema::from_str(r#""boolean""#).unwrap();
+        assert_eq!(compare(&Datum::Boolean(false), &Datum::Boolean(true), &boolean), Ordering::Less);
+    }}
+
+    test!{floating_point, {
+        let double = schema::from_str(r#""double""#).unwrap();
+        assert_eq!(compare(&Datum::Double(-1.5), &Datum::Double(0.25), &double), Ordering::Less);
+        assert_eq!(compare(&Datum::Double(-0.0), &Datum::Double(0.0), &double), Ordering::Less);
+        assert_eq!(compare(&Datum::Double(::std::f64::NAN), &Datum::Double(::std::f64::INFINITY), &double), Ordering::Greater);
+        assert_eq!(compare(&Datum::Double(::std::f64::NAN), &Datum::Double(::std::f64::NAN), &double), Ordering::Equal);
+
+        let float = schema::from_str(r#""float""#).unwrap();
+        assert_eq!(compare(&Datum::Float(2.5), &Datum::Float(2.25), &float), Ordering::Greater);
+    }}
+}
+
+mod complex {
+    use std::cmp::Ordering;
+    use ravro::compare::{compare_encoded, compare_values};
+    use ravro::datum::Datum;
+    use ravro::schema;
+    use {compare, person, PERSON};
+
+    test!{records_honor_field_order, {
+        let schema = schema::from_str(PERSON).unwrap();
+        let a = person("smith", 30, "x", &[], "Z", None);
+
+        assert_eq!(compare(&a, &person("jones", 30, "x", &[], "Z", None), &schema), Ordering::Greater);
+        // Descending: older sorts first.
+        assert_eq!(compare(&a, &person("smith", 40, "x", &[], "Z", None), &schema), Ordering::Greater);
+        // Ignored.
+        assert_eq!(compare(&a, &person("smith", 30, "completely different", &[], "Z", None), &schema), Ordering::Equal);
+    }}
+
+    test!{arrays_enums_and_unions, {
+        let schema = schema::from_str(PERSON).unwrap();
+        let base = person("a", 1, "", &["x", "y"], "A", Some(1.0));
+
+        // Arrays compare element by element, then by length.
+        assert_eq!(compare(&base, &person("a", 1, "", &["x"], "A", Some(1.0)), &schema), Ordering::Greater);
+        assert_eq!(compare(&base, &person("a", 1, "", &["x", "z"], "A", Some(1.0)), &schema), Ordering::Less);
+        // Enums by position in the schema, not by name.
+        assert_eq!(compare(&base, &person("a", 1, "", &["x", "y"], "Z", Some(1.0)), &schema), Ordering::Greater);
+        // Unions by branch first, then by value.
+        assert_eq!(compare(&base, &person("a", 1, "", &["x", "y"], "A", None), &schema), Ordering::Greater);
+        assert_eq!(compare(&base, &person("a", 1, "", &["x", "y"], "A", Some(2.0)), &schema), Ordering::Less);
+    }}
+
+    test!{arrays_in_different_blocks, {
+        let schema = schema::from_str(r#"{"type": "array", "items": "int"}"#).unwrap();
+        // [1, 2, 3] as a single block, and as blocks of one and two items
+        // with a byte size.
+        let one_block = [0x06, 0x02, 0x04, 0x06, 0x00];
+        let two_blocks = [0x01, 0x02, 0x02, 0x03, 0x04, 0x04, 0x06, 0x00];
+        assert_eq!(compare_encoded(&one_block, &two_blocks, &schema).unwrap(), Ordering::Equal);
+
+        let shorter = [0x04, 0x02, 0x04, 0x00];
+        assert_eq!(compare_encoded(&shorter, &two_blocks, &schema).unwrap(), Ordering::Less);
+    }}
+
+    test!{maps_cannot_be_compared, {
+        let schema = schema::from_str(r#"{"type": "map", "values": "int"}"#).unwrap();
+        let empty = Datum::Map(Default::default());
+        assert!(compare_values(&empty, &empty, &schema).is_err());
+        assert!(compare_encoded(&[0], &[0], &schema).is_err());
+    }}
+
+    test!{agrees_with_decoded_values, {
+        use rand::{SeedableRng, StdRng};
+        use ravro::random;
+
+        let schema = schema::from_str(PERSON).unwrap();
+        let mut rng = StdRng::from_seed(&[3usize][..]);
+        let datums: Vec<Datum> = (0..50).map(|_| random::generate(&schema, &mut rng).unwrap()).collect();
+        for a in datums.iter() {
+            for b in datums.iter() {
+                assert_eq!(compare(a, b, &schema), compare(b, a, &schema).reverse());
+            }
+        }
+    }}
+}
@@ -0,0 +1,486 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const USER: &'static str = r#"{"type": "record", "name": "User", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": "int"}
+]}"#;
+
+fn user(i: i32) -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    Datum::Record(vec![(String::from("name"), Datum::String(format!("user{}", i))), (String::from("age"), Datum::Int(i))])
+}
+
+fn write_users(n: i32, codec: ravro::container::Codec, block_size: usize) -> Vec<u8> {
+    use ravro::container::Writer;
+    use ravro::schema;
+
+    let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), codec);
+    writer.set_block_size(block_size);
+    for i in 0..n {
+        writer.append(&user(i)).unwrap();
+    }
+    writer.into_inner().unwrap()
+}
+
+mod round_trip {
+    use ravro::container::{Codec, Reader};
+    use ravro::schema;
+    use {user, write_users, USER};
+
+    test!{null_codec, {
+        let buf = write_users(10, Codec::Null, 1024);
+        assert_eq!(&buf[..4], b"Obj\x01");
+
+        let reader = Reader::new(&buf[..]).unwrap();
+        assert_eq!(reader.codec(), Codec::Null);
+        assert_eq!(reader.schema().fingerprint(), schema::from_str(USER).unwrap().fingerprint());
+
+        let users: Vec<_> = reader.map(|d| d.unwrap()).collect();
+        assert_eq!(users, (0..10).map(user).collect::<Vec<_>>());
+    }}
+
+    test!{deflate_codec, {
+        let buf = write_users(1000, Codec::Deflate, 1024);
+        let reader = Reader::new(&buf[..]).unwrap();
+        assert_eq!(reader.codec(), Codec::Deflate);
+        assert_eq!(reader.map(|d| d.unwrap()).count(), 1000);
+
+        // Repetitive data compresses.
+        assert!(buf.len() < write_users(1000, Codec::Null, 1024).len());
+    }}
+
+    test!{empty_file, {
+        let buf = write_users(0, Codec::Null, 1024);
+        let mut reader = Reader::new(&buf[..]).unwrap();
+        assert!(reader.read().unwrap().is_none());
+    }}
+
+    test!{blocks, {
+        let buf = write_users(100, Codec::Null, 100);
+        let mut reader = Reader::new(&buf[..]).unwrap();
+
+        let mut blocks = 0;
+        let mut total = 0;
+        while let Some((count, _)) = reader.read_raw_block().unwrap() {
+            blocks += 1;
+            total += count;
+        }
+        assert!(blocks > 1);
+        assert_eq!(total, 100);
+    }}
+}
+
+mod header {
+    use std::str;
+    use ravro::container::{Codec, Reader, Writer};
+    use ravro::datum::Datum;
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use {user, write_users, USER};
+
+    test!{metadata, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Deflate);
+        writer.set_meta("owner", b"ops").unwrap();
+        match writer.set_meta("avro.codec", b"null") {
+            Err(Error::EncodeError(ErrorCode::ReservedMetadataKey)) => (),
+            other => panic!("expected a reserved key error, got {:?}", other),
+        }
+        writer.append(&user(1)).unwrap();
+        writer.flush().unwrap();
+        assert!(writer.set_meta("late", b"x").is_err());
+
+        let buf = writer.into_inner().unwrap();
+        let reader = Reader::new(&buf[..]).unwrap();
+        assert_eq!(reader.meta().get("owner"), Some(&b"ops".to_vec()));
+        assert_eq!(reader.meta().get("avro.codec").map(|c| str::from_utf8(c).unwrap()), Some("deflate"));
+        assert!(reader.meta().contains_key("avro.schema"));
+    }}
+
+    test!{not_a_container, {
+        match Reader::new(&b"Obj\x02"[..]) {
+            Err(Error::DecodeError(ErrorCode::NotAContainerFile)) => (),
+            Err(e) => panic!("expected not a container file, got {:?}", e),
+            Ok(_) => panic!("expected not a container file"),
+        }
+    }}
+
+    test!{datum_not_matching_schema, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null);
+        assert!(writer.append(&Datum::Int(1)).is_err());
+        writer.append(&user(1)).unwrap();
+
+        let buf = writer.into_inner().unwrap();
+        assert_eq!(Reader::new(&buf[..]).unwrap().count(), 1);
+    }}
+
+    test!{sync_marker_mismatch, {
+        let mut buf = write_users(5, Codec::Null, 1024);
+        let len = buf.len();
+        buf[len - 1] ^= 0xff;
+
+        let mut reader = Reader::new(&buf[..]).unwrap();
+        match reader.read() {
+            Err(Error::DecodeError(ErrorCode::SyncMarkerMismatch)) => (),
+            other => panic!("expected a sync marker mismatch, got {:?}", other),
+        }
+        assert!(reader.next().is_none());
+    }}
+}
+
+mod split {
+    use std::io::Cursor;
+
+    use ravro::container::{Codec, Reader, SYNC_SIZE};
+    use {user, write_users};
+
+    // Reads the datums in [start, end) the way a split would.
+    fn read_split(buf: &[u8], start: u64, end: u64) -> Vec<i32> {
+        let mut reader = Reader::new(Cursor::new(buf)).unwrap();
+        reader.seek_to_sync(start).unwrap();
+        let mut ages = Vec::new();
+        while !reader.past_sync(end).unwrap() {
+            let datum = reader.read().unwrap().unwrap();
+            if let Some(&ravro::datum::Datum::Int(age)) = datum.field("age") {
+                ages.push(age);
+            }
+        }
+        ages
+    }
+
+    test!{block_positions, {
+        let buf = write_users(100, Codec::Deflate, 64);
+        let mut reader = Reader::new(Cursor::new(&buf[..])).unwrap();
+        let positions: Vec<_> = reader.block_positions().map(|p| p.unwrap()).collect();
+        assert!(positions.len() > 1);
+        assert_eq!(positions.iter().map(|p| p.count).sum::<i64>(), 100);
+
+        // Each block starts with the sync marker before it.
+        for position in positions.iter() {
+            let marker = (position.offset as usize - SYNC_SIZE)..(position.offset as usize);
+            assert_eq!(&buf[marker], &reader.sync_marker()[..]);
+        }
+    }}
+
+    test!{seek_to_a_block, {
+        let buf = write_users(100, Codec::Null, 64);
+        let mut reader = Reader::new(Cursor::new(&buf[..])).unwrap();
+        let positions: Vec<_> = reader.block_positions().map(|p| p.unwrap()).collect();
+        let skipped: i64 = positions[..2].iter().map(|p| p.count).sum();
+
+        let offset = positions[2].offset;
+        assert_eq!(reader.seek_to_sync(offset - SYNC_SIZE as u64).unwrap(), offset);
+        assert_eq!(reader.read().unwrap(), Some(user(skipped as i32)));
+
+        // Starting inside a block moves on to the next one.
+        assert_eq!(reader.seek_to_sync(offset - SYNC_SIZE as u64 + 1).unwrap(), positions[3].offset);
+        assert_eq!(reader.seek_to_sync(buf.len() as u64).unwrap(), buf.len() as u64);
+        assert_eq!(reader.read().unwrap(), None);
+    }}
+
+    test!{splits_cover_the_file_once, {
+        let buf = write_users(500, Codec::Deflate, 100);
+        let len = buf.len() as u64;
+        for splits in 1..8 {
+            let size = len / splits + 1;
+            let mut ages = Vec::new();
+            for i in 0..splits {
+                ages.extend(read_split(&buf, i * size, (i + 1) * size));
+            }
+            assert_eq!(ages, (0..500).collect::<Vec<_>>());
+        }
+    }}
+}
+
+mod append {
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    use ravro::container::{Codec, Reader, Writer};
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use {user, write_users, USER};
+
+    fn append_users(buf: Vec<u8>, from: i32, to: i32) -> Vec<u8> {
+        let mut writer = Writer::append_to(Cursor::new(buf), schema::from_str(USER).unwrap()).unwrap();
+        for i in from..to {
+            writer.append(&user(i)).unwrap();
+        }
+        writer.into_inner().unwrap().into_inner()
+    }
+
+    test!{adds_blocks_to_the_end, {
+        let buf = write_users(10, Codec::Deflate, 64);
+        let original = *Reader::new(&buf[..]).unwrap().sync_marker();
+        let buf = append_users(buf, 10, 25);
+
+        let reader = Reader::new(&buf[..]).unwrap();
+        assert_eq!(reader.codec(), Codec::Deflate);
+        assert_eq!(reader.sync_marker(), &original);
+        let users: Vec<_> = reader.map(|d| d.unwrap()).collect();
+        assert_eq!(users, (0..25).map(user).collect::<Vec<_>>());
+    }}
+
+    test!{keeps_metadata, {
+        let mut writer = Writer::new(Vec::new(), schema::from_str(USER).unwrap(), Codec::Null);
+        writer.set_meta("origin", b"ingest").unwrap();
+        writer.append(&user(0)).unwrap();
+        let buf = append_users(writer.into_inner().unwrap(), 1, 2);
+
+        let reader = Reader::new(&buf[..]).unwrap();
+        assert_eq!(reader.meta().get("origin"), Some(&b"ingest".to_vec()));
+        assert_eq!(reader.count(), 2);
+    }}
+
+    test!{same_canonical_form_is_enough, {
+        let buf = write_users(3, Codec::Null, 64);
+        let reordered = r#"{"fields": [{"type": "string", "name": "name", "doc": "added"},
+            {"name": "age", "type": "int"}], "name": "User", "type": "record"}"#;
+        let mut writer = Writer::append_to(Cursor::new(buf), schema::from_str(reordered).unwrap()).unwrap();
+        writer.append(&user(3)).unwrap();
+        let buf = writer.into_inner().unwrap().into_inner();
+        assert_eq!(Reader::new(&buf[..]).unwrap().count(), 4);
+    }}
+
+    test!{refuses_a_different_schema, {
+        let buf = write_users(3, Codec::Null, 64);
+        let other = schema::from_str(r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#).unwrap();
+        match Writer::append_to(Cursor::new(buf), other) {
+            Err(Error::EncodeError(ErrorCode::ContainerSchemaMismatch)) => (),
+            Err(e) => panic!("expected a schema mismatch, got {:?}", e),
+            Ok(_) => panic!("expected a schema mismatch"),
+        }
+    }}
+
+    test!{starts_from_the_beginning, {
+        let buf = write_users(5, Codec::Null, 64);
+        let mut file = Cursor::new(buf);
+        file.seek(SeekFrom::End(0)).unwrap();
+        let mut writer = Writer::append_to(file, schema::from_str(USER).unwrap()).unwrap();
+        writer.append(&user(5)).unwrap();
+        let buf = writer.into_inner().unwrap().into_inner();
+        assert_eq!(Reader::new(&buf[..]).unwrap().count(), 6);
+    }}
+
+    test!{refuses_a_truncated_last_block, {
+        let mut buf = write_users(10, Codec::Null, 64);
+        let len = buf.len();
+        buf.truncate(len - 5);
+        assert!(Writer::append_to(Cursor::new(buf), schema::from_str(USER).unwrap()).is_err());
+    }}
+
+    test!{refuses_a_file_that_is_not_a_container, {
+        let buf = b"not a container file".to_vec();
+        assert!(Writer::append_to(Cursor::new(buf), schema::from_str(USER).unwrap()).is_err());
+    }}
+}
+
+mod lenient {
+    use std::io::Cursor;
+
+    use ravro::container::{BlockPosition, Codec, Reader, SYNC_SIZE};
+    use ravro::datum::Datum;
+    use write_users;
+
+    fn blocks(buf: &[u8]) -> Vec<BlockPosition> {
+        let mut reader = Reader::new(Cursor::new(buf)).unwrap();
+        let positions = reader.block_positions().map(|p| p.unwrap()).collect();
+        positions
+    }
+
+    // The ages read leniently, and the number of blocks and bytes skipped.
+    fn read_ages(buf: &[u8]) -> (Vec<i32>, u64, u64) {
+        let mut reader = Reader::new(Cursor::new(buf)).unwrap().lenient().unwrap();
+        let mut ages = Vec::new();
+        while let Some(datum) = reader.read().unwrap() {
+            if let Some(&Datum::Int(age)) = datum.field("age") {
+                ages.push(age);
+            }
+        }
+        (ages, reader.skipped_blocks(), reader.skipped_bytes())
+    }
+
+    // The ages in the given blocks, for files written by write_users.
+    fn ages_in(blocks: &[BlockPosition], skip: &[usize]) -> Vec<i32> {
+        let mut ages = Vec::new();
+        let mut age = 0;
+        for (i, block) in blocks.iter().enumerate() {
+            for _ in 0..block.count {
+                if !skip.contains(&i) {
+                    ages.push(age);
+                }
+                age += 1;
+            }
+        }
+        ages
+    }
+
+    test!{intact_files, {
+        let buf = write_users(100, Codec::Deflate, 64);
+        assert_eq!(read_ages(&buf), ((0..100).collect(), 0, 0));
+    }}
+
+    test!{damaged_payload, {
+        let mut buf = write_users(100, Codec::Deflate, 64);
+        let positions = blocks(&buf);
+        let damaged = positions[1];
+        let start = (damaged.offset + 2) as usize;
+        for b in buf[start..start + damaged.size as usize].iter_mut() {
+            *b = 0xff;
+        }
+        assert!(Reader::new(&buf[..]).unwrap().map(|d| d.map(|_| ())).collect::<Result<Vec<_>, _>>().is_err());
+
+        let (ages, skipped_blocks, skipped_bytes) = read_ages(&buf);
+        assert_eq!(ages, ages_in(&positions, &[1]));
+        assert_eq!(skipped_blocks, 1);
+        assert_eq!(skipped_bytes, positions[2].offset - positions[1].offset);
+    }}
+
+    test!{damaged_sync_marker, {
+        let mut buf = write_users(100, Codec::Null, 64);
+        let positions = blocks(&buf);
+        // The marker ending block 2; block 3 goes with it, since the search for
+        // the next marker starts inside block 2.
+        buf[positions[3].offset as usize - SYNC_SIZE] ^= 0xff;
+
+        let (ages, skipped_blocks, skipped_bytes) = read_ages(&buf);
+        assert_eq!(ages, ages_in(&positions, &[2, 3]));
+        assert_eq!(skipped_blocks, 1);
+        assert_eq!(skipped_bytes, positions[4].offset - positions[2].offset);
+    }}
+
+    test!{damaged_block_size, {
+        let mut buf = write_users(100, Codec::Null, 64);
+        let positions = blocks(&buf);
+        // A count of 1 and a size far past the end of the file.
+        let start = positions[0].offset as usize;
+        buf[start] = 2;
+        buf[start + 1] = 0xfe;
+        assert!(Reader::new(&buf[..]).unwrap().read().is_err());
+
+        let (ages, skipped_blocks, _) = read_ages(&buf);
+        assert_eq!(ages, ages_in(&positions, &[0]));
+        assert_eq!(skipped_blocks, 1);
+    }}
+
+    test!{truncated_file, {
+        let buf = write_users(100, Codec::Deflate, 64);
+        let positions = blocks(&buf);
+        let last = positions.len() - 1;
+        let truncated = &buf[..(positions[last].offset + 3) as usize];
+
+        let (ages, skipped_blocks, skipped_bytes) = read_ages(truncated);
+        assert_eq!(ages, ages_in(&positions, &[last]));
+        assert_eq!(skipped_blocks, 1);
+        assert_eq!(skipped_bytes, 3);
+    }}
+}
+
+mod parallel {
+    use std::io::Cursor;
+
+    use ravro::container::{Codec, ParallelOptions, ParallelReader};
+    use ravro::datum::Datum;
+    use ravro::schema;
+    use write_users;
+
+    fn read_ages(buf: Vec<u8>, options: ParallelOptions) -> Vec<i32> {
+        let reader = ParallelReader::with_options(Cursor::new(buf), options).unwrap();
+        reader.map(|datum| match datum.unwrap().field("age") {
+            Some(&Datum::Int(age)) => age,
+            other => panic!("no age: {:?}", other),
+        }).collect()
+    }
+
+    test!{in_file_order, {
+        let buf = write_users(1000, Codec::Deflate, 64);
+        let options = ParallelOptions { threads: 3, max_blocks_in_flight: 4, ..ParallelOptions::default() };
+        assert_eq!(read_ages(buf, options), (0..1000).collect::<Vec<_>>());
+    }}
+
+    test!{out_of_order, {
+        let buf = write_users(1000, Codec::Deflate, 64);
+        let options = ParallelOptions { threads: 3, ordered: false, ..ParallelOptions::default() };
+        let mut ages = read_ages(buf, options);
+        ages.sort();
+        assert_eq!(ages, (0..1000).collect::<Vec<_>>());
+    }}
+
+    test!{one_block_in_flight, {
+        let buf = write_users(200, Codec::Null, 64);
+        let options = ParallelOptions { threads: 2, max_blocks_in_flight: 1, ..ParallelOptions::default() };
+        assert_eq!(read_ages(buf, options), (0..200).collect::<Vec<_>>());
+    }}
+
+    test!{empty_file, {
+        let buf = write_users(0, Codec::Deflate, 64);
+        assert_eq!(read_ages(buf, ParallelOptions::default()), Vec::<i32>::new());
+    }}
+
+    test!{with_reader_schema, {
+        let buf = write_users(100, Codec::Deflate, 64);
+        let reader_schema = schema::from_str(r#"{"type": "record", "name": "User", "fields": [
+            {"name": "age", "type": "int"}
+        ]}"#).unwrap();
+        let options = ParallelOptions { reader_schema: Some(reader_schema), ..ParallelOptions::default() };
+        assert_eq!(read_ages(buf, options), (0..100).collect::<Vec<_>>());
+    }}
+
+    test!{damaged_block, {
+        let mut buf = write_users(100, Codec::Deflate, 64);
+        let len = buf.len();
+        for b in buf[len - 40..len - 20].iter_mut() {
+            *b = 0xff;
+        }
+        let reader = ParallelReader::new(Cursor::new(buf)).unwrap();
+        let results: Vec<_> = reader.collect();
+        assert!(results.last().unwrap().is_err());
+    }}
+
+    test!{huge_block_count, {
+        use ravro::container::Reader;
+
+        let buf = write_users(100, Codec::Null, 64);
+        let start = Reader::new(Cursor::new(&buf[..])).unwrap().block_positions().next().unwrap().unwrap().offset as usize;
+
+        // Swap the first block's one byte count for 2^60, zigzag encoded.
+        let mut damaged = buf[..start].to_vec();
+        damaged.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x20]);
+        damaged.extend_from_slice(&buf[start + 1..]);
+
+        let options = ParallelOptions { threads: 2, max_blocks_in_flight: 2, ..ParallelOptions::default() };
+        let reader = ParallelReader::with_options(Cursor::new(damaged), options).unwrap();
+        let results: Vec<_> = reader.collect();
+        assert!(results[0].is_err());
+    }}
+}
@@ -0,0 +1,146 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+use serde::json::{self, Value};
+
+use ravro::infer::{self, Options};
+use ravro::schema::{self, Schema};
+
+fn samples(lines: &[&str]) -> Vec<Value> {
+    lines.iter().map(|line| json::from_str(line).unwrap()).collect()
+}
+
+// The inferred schema, checked for validity, next to the expected one.
+fn infer_with(lines: &[&str], options: &Options) -> Schema {
+    let schema = infer::from_json_samples_with(samples(lines), options);
+    schema.is_valid().unwrap();
+    schema
+}
+
+fn infer(lines: &[&str]) -> Schema {
+    infer_with(lines, &Options::default())
+}
+
+fn expected(text: &str) -> Schema {
+    schema::from_str(text).unwrap()
+}
+
+mod primitives {
+    use {expected, infer};
+
+    test!{every_kind, {
+        let schema = infer(&[r#"{"b": true, "i": 1, "d": 1.5, "s": "x", "n": null}"#]);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Record", "fields": [
+            {"name": "b", "type": "boolean"},
+            {"name": "d", "type": "double"},
+            {"name": "i", "type": "int"},
+            {"name": "n", "type": "null", "default": null},
+            {"name": "s", "type": "string"}
+        ]}"#));
+    }}
+
+    test!{numbers_widen, {
+        let schema = infer(&[r#"{"a": 1, "b": 1}"#, r#"{"a": 10000000000, "b": 2.5}"#]);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Record", "fields": [
+            {"name": "a", "type": "long"},
+            {"name": "b", "type": "double"}
+        ]}"#));
+    }}
+}
+
+mod nullable {
+    use {expected, infer};
+
+    test!{null_and_missing_fields, {
+        let schema = infer(&[r#"{"a": 1, "b": "x"}"#, r#"{"a": null}"#, r#"{"a": 2, "b": "y", "c": true}"#]);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Record", "fields": [
+            {"name": "a", "type": ["null", "int"], "default": null},
+            {"name": "b", "type": ["null", "string"], "default": null},
+            {"name": "c", "type": ["null", "boolean"], "default": null}
+        ]}"#));
+    }}
+
+    test!{conflicting_types_become_unions, {
+        let schema = infer(&[r#"{"a": 1}"#, r#"{"a": "one"}"#, r#"{"a": [1]}"#]);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Record", "fields": [
+            {"name": "a", "type": ["int", "string", {"type": "array", "items": {"type": "int"}}]}
+        ]}"#));
+    }}
+
+    test!{mixed_top_level_samples, {
+        // infer() checks the top-level union is valid.
+        let schema = infer(&[r#"{"a": 1}"#, "3", "null"]);
+        assert_eq!(schema, expected(r#"["null", "int", {"type": "record", "name": "Record", "fields": [
+            {"name": "a", "type": "int"}
+        ]}]"#));
+    }}
+}
+
+mod nested {
+    use {expected, infer, infer_with};
+    use ravro::infer::Options;
+
+    test!{records_and_arrays, {
+        let schema = infer(&[
+            r#"{"address": {"city": "x"}, "tags": ["a"], "friends": [{"name": "y"}]}"#,
+            r#"{"address": {"city": "z", "zip": 12345}, "tags": [], "friends": []}"#,
+        ]);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Record", "fields": [
+            {"name": "address", "type": {"type": "record", "name": "Address", "fields": [
+                {"name": "city", "type": "string"},
+                {"name": "zip", "type": ["null", "int"], "default": null}
+            ]}},
+            {"name": "friends", "type": {"type": "array", "items": {"type": "record", "name": "Friends", "fields": [
+                {"name": "name", "type": "string"}
+            ]}}},
+            {"name": "tags", "type": {"type": "array", "items": {"type": "string"}}}
+        ]}"#));
+    }}
+
+    test!{names_and_namespace, {
+        let options = Options { name: String::from("Event"), namespace: Some(String::from("com.example")), ..Options::default() };
+        let schema = infer_with(&[r#"{"event": {"id": 1}, "9 lives": 9}"#], &options);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Event", "namespace": "com.example", "fields": [
+            {"name": "_9_lives", "type": "int"},
+            {"name": "event", "type": {"type": "record", "name": "Event2", "fields": [
+                {"name": "id", "type": "int"}
+            ]}}
+        ]}"#));
+    }}
+
+    test!{maps_for_many_keys, {
+        let options = Options { map_threshold: Some(2), ..Options::default() };
+        let schema = infer_with(&[r#"{"counts": {"a": 1, "b": 2}}"#, r#"{"counts": {"c": 3, "d": null}}"#], &options);
+        assert_eq!(schema, expected(r#"{"type": "record", "name": "Record", "fields": [
+            {"name": "counts", "type": {"type": "map", "values": [{"type": "null"}, {"type": "int"}]}}
+        ]}"#));
+    }}
+}
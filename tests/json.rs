@@ -0,0 +1,153 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+fn parse(s: &str) -> ravro::schema::Schema {
+    let value: serde::json::Value = serde::json::from_str(s).unwrap();
+    ravro::schema::Schema::from(&value)
+}
+
+
+const PERSON: &'static str = r#"{"type": "record", "name": "Person", "namespace": "x", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": ["null", "int"], "default": null},
+    {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["A", "B"]}, "default": "B"},
+    {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 2}},
+    {"name": "data", "type": "bytes", "default": "ÿ"},
+    {"name": "scores", "type": {"type": "map", "values": "double"}, "default": {}}
+]}"#;
+
+mod encode {
+    use std::collections::BTreeMap;
+    use ravro::datum::Datum;
+    use ravro::encoding::json;
+    use {parse, PERSON};
+
+    test!{primitives, {
+        assert_eq!(json::to_string(&Datum::Null, &parse(r#""null""#)).unwrap(), "null");
+        assert_eq!(json::to_string(&Datum::Boolean(true), &parse(r#""boolean""#)).unwrap(), "true");
+        assert_eq!(json::to_string(&Datum::Int(-3), &parse(r#""int""#)).unwrap(), "-3");
+        assert_eq!(json::to_string(&Datum::Long(7), &parse(r#""long""#)).unwrap(), "7");
+        assert_eq!(json::to_string(&Datum::Double(1.5), &parse(r#""double""#)).unwrap(), "1.5");
+        assert_eq!(json::to_string(&Datum::from("hi"), &parse(r#""string""#)).unwrap(), r#""hi""#);
+    }}
+
+    test!{bytes_as_latin1, {
+        let s = json::to_string(&Datum::Bytes(vec![0x41, 0xff]), &parse(r#""bytes""#)).unwrap();
+        assert_eq!(s, "\"A\u{ff}\"");
+    }}
+
+    test!{unions_are_wrapped, {
+        let schema = parse(r#"["null", "string", {"type": "enum", "name": "E", "namespace": "n", "symbols": ["X"]}]"#);
+        assert_eq!(json::to_string(&Datum::Null, &schema).unwrap(), "null");
+        assert_eq!(json::to_string(&Datum::from("a"), &schema).unwrap(), r#"{"string":"a"}"#);
+        assert_eq!(json::to_string(&Datum::Enum(String::from("X")), &schema).unwrap(), r#"{"n.E":"X"}"#);
+    }}
+
+    test!{record_with_defaults, {
+        let person = Datum::Record(vec![
+            (String::from("name"), Datum::from("a")),
+            (String::from("age"), Datum::Int(4)),
+            (String::from("id"), Datum::Fixed(vec![0x61, 0x62])),
+        ]);
+        let value = json::encode(&person, &parse(PERSON)).unwrap();
+
+        assert_eq!(value.find("age").and_then(|a| a.find("int")).and_then(|n| n.as_i64()), Some(4));
+        assert_eq!(value.find("kind").and_then(|k| k.as_string()), Some("B"));
+        assert_eq!(value.find("id").and_then(|k| k.as_string()), Some("ab"));
+        assert_eq!(value.find("data").and_then(|k| k.as_string()), Some("\u{ff}"));
+        assert!(value.find("scores").map(|s| s.is_object()).unwrap_or(false));
+    }}
+
+    test!{maps_and_arrays, {
+        let schema = parse(r#"{"type": "map", "values": {"type": "array", "items": "long"}}"#);
+        let mut map = BTreeMap::new();
+        map.insert(String::from("k"), Datum::Array(vec![Datum::Long(1), Datum::Long(2)]));
+        assert_eq!(json::to_string(&Datum::Map(map), &schema).unwrap(), r#"{"k":[1,2]}"#);
+    }}
+
+    test!{mismatch, {
+        assert!(json::encode(&Datum::from("a"), &parse(r#""int""#)).is_err());
+        assert!(json::encode(&Datum::Boolean(true), &parse(r#"["null", "string"]"#)).is_err());
+    }}
+}
+
+mod decode {
+    use ravro::datum::Datum;
+    use ravro::encoding::json;
+    use {parse, PERSON};
+
+    test!{numbers_follow_the_schema, {
+        assert_eq!(json::from_str("1", &parse(r#""int""#)).unwrap(), Datum::Int(1));
+        assert_eq!(json::from_str("1", &parse(r#""long""#)).unwrap(), Datum::Long(1));
+        assert_eq!(json::from_str("1", &parse(r#""float""#)).unwrap(), Datum::Float(1.0));
+        assert_eq!(json::from_str("1.5", &parse(r#""double""#)).unwrap(), Datum::Double(1.5));
+    }}
+
+    test!{rejects_what_does_not_fit, {
+        assert!(json::from_str("1.5", &parse(r#""int""#)).is_err());
+        assert!(json::from_str("4294967296", &parse(r#""int""#)).is_err());
+        assert!(json::from_str(r#""1""#, &parse(r#""long""#)).is_err());
+        assert!(json::from_str("null", &parse(r#""string""#)).is_err());
+        assert!(json::from_str(r#""Ā""#, &parse(r#""bytes""#)).is_err());
+        assert!(json::from_str(r#""abc""#, &parse(r#"{"type": "fixed", "name": "F", "size": 2}"#)).is_err());
+        assert!(json::from_str(r#""Z""#, &parse(r#"{"type": "enum", "name": "E", "symbols": ["A"]}"#)).is_err());
+    }}
+
+    test!{unions, {
+        let schema = parse(r#"["null", "int", {"type": "record", "name": "R", "namespace": "n", "fields": []}]"#);
+        assert_eq!(json::from_str("null", &schema).unwrap(), Datum::Union(0, Box::new(Datum::Null)));
+        assert_eq!(json::from_str(r#"{"int": 3}"#, &schema).unwrap(), Datum::Union(1, Box::new(Datum::Int(3))));
+        assert_eq!(json::from_str(r#"{"n.R": {}}"#, &schema).unwrap(), Datum::Union(2, Box::new(Datum::Record(vec![]))));
+        assert!(json::from_str("3", &schema).is_err());
+        assert!(json::from_str(r#"{"long": 3}"#, &schema).is_err());
+    }}
+
+    test!{record, {
+        let person = json::from_str(r#"{"name": "a", "age": {"int": 4}, "kind": "A", "id": "ab"}"#, &parse(PERSON)).unwrap();
+        assert_eq!(person.field("name"), Some(&Datum::from("a")));
+        assert_eq!(person.field("age"), Some(&Datum::Union(1, Box::new(Datum::Int(4)))));
+        assert_eq!(person.field("kind"), Some(&Datum::Enum(String::from("A"))));
+        assert_eq!(person.field("id"), Some(&Datum::Fixed(vec![0x61, 0x62])));
+        assert_eq!(person.field("data"), Some(&Datum::Bytes(vec![0xff])));
+    }}
+
+    test!{record_missing_field, {
+        assert!(json::from_str(r#"{"name": "a"}"#, &parse(PERSON)).is_err());
+    }}
+
+    test!{round_trip, {
+        let text = r#"{"age":null,"data":"\u0001","id":"xy","kind":"B","name":"a","scores":{"s":2.5}}"#;
+        let schema = parse(PERSON);
+        let person = json::from_str(text, &schema).unwrap();
+        let again = json::to_string(&person, &schema).unwrap();
+        assert_eq!(json::from_str(&again, &schema).unwrap(), person);
+    }}
+}
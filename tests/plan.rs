@@ -0,0 +1,189 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate rand;
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+use rand::{SeedableRng, StdRng};
+
+fn rng() -> StdRng {
+    StdRng::from_seed(&[7usize][..])
+}
+
+const EVERYTHING: &'static str = r#"{"type": "record", "name": "Everything", "namespace": "test", "fields": [
+    {"name": "n", "type": "null"},
+    {"name": "b", "type": "boolean"},
+    {"name": "i", "type": "int"},
+    {"name": "l", "type": "long"},
+    {"name": "f", "type": "float"},
+    {"name": "d", "type": "double"},
+    {"name": "by", "type": "bytes"},
+    {"name": "s", "type": "string"},
+    {"name": "e", "type": {"type": "enum", "name": "Suit", "symbols": ["SPADES", "HEARTS"]}},
+    {"name": "fx", "type": {"type": "fixed", "name": "MD5", "size": 16}},
+    {"name": "a", "type": {"type": "array", "items": "Suit"}},
+    {"name": "m", "type": {"type": "map", "values": "MD5"}},
+    {"name": "u", "type": ["null", "string", "Suit"]}
+]}"#;
+
+// Everything with some fields gone, some promoted and one added.
+const READER: &'static str = r#"{"type": "record", "name": "Everything", "namespace": "test", "fields": [
+    {"name": "i", "type": "double"},
+    {"name": "l", "type": "float"},
+    {"name": "s", "type": "bytes"},
+    {"name": "e", "type": {"type": "enum", "name": "Suit", "symbols": ["HEARTS", "SPADES", "CLUBS"]}},
+    {"name": "u", "type": ["null", "Suit", "string"]},
+    {"name": "extra", "type": "string", "default": "none"}
+]}"#;
+
+const LIST: &'static str = r#"{"type": "record", "name": "Node", "fields": [
+    {"name": "value", "type": "int"},
+    {"name": "next", "type": ["null", "Node"]}
+]}"#;
+
+mod compile {
+    use ravro::datum::Datum;
+    use ravro::encoding::binary;
+    use ravro::random;
+    use ravro::schema;
+    use {rng, EVERYTHING, LIST};
+
+    test!{decodes_like_the_interpreter, {
+        let schema = schema::from_str(EVERYTHING).unwrap();
+        let plan = schema.compile().unwrap();
+        let mut rng = rng();
+        for _ in 0..50 {
+            let datum = random::generate(&schema, &mut rng).unwrap();
+            let buf = binary::to_vec(&datum, &schema).unwrap();
+            assert_eq!(plan.decode(&mut &buf[..]).unwrap(), binary::decode(&schema, &mut &buf[..]).unwrap());
+        }
+    }}
+
+    test!{encodes_like_the_interpreter, {
+        let schema = schema::from_str(EVERYTHING).unwrap();
+        let plan = schema.compile().unwrap();
+        let mut rng = rng();
+        for _ in 0..50 {
+            let datum = random::generate(&schema, &mut rng).unwrap();
+            assert_eq!(plan.to_vec(&datum).unwrap(), binary::to_vec(&datum, &schema).unwrap());
+        }
+    }}
+
+    test!{recursive_types, {
+        let schema = schema::from_str(LIST).unwrap();
+        let plan = schema.compile().unwrap();
+        let node = |value: i32, next: Datum| Datum::Record(vec![
+            (String::from("value"), Datum::Int(value)),
+            (String::from("next"), next),
+        ]);
+        let list = node(1, Datum::Union(1, Box::new(node(2, Datum::Union(0, Box::new(Datum::Null))))));
+
+        let buf = plan.to_vec(&list).unwrap();
+        assert_eq!(buf, binary::to_vec(&list, &schema).unwrap());
+        assert_eq!(plan.decode(&mut &buf[..]).unwrap(), list);
+    }}
+
+    test!{unwrapped_union_values, {
+        let schema = schema::from_str(r#"["null", "long", "string"]"#).unwrap();
+        let plan = schema.compile().unwrap();
+        assert_eq!(plan.to_vec(&Datum::from("x")).unwrap(), vec![4, 2, b'x']);
+        // An int goes in the long branch.
+        assert_eq!(plan.to_vec(&Datum::Int(1)).unwrap(), vec![2, 2]);
+        assert!(plan.to_vec(&Datum::Boolean(true)).is_err());
+    }}
+
+    test!{rejects_mismatched_datums, {
+        let schema = schema::from_str(EVERYTHING).unwrap();
+        let plan = schema.compile().unwrap();
+        assert!(plan.to_vec(&Datum::Int(1)).is_err());
+        assert!(plan.to_vec(&Datum::Record(vec![])).is_err());
+    }}
+}
+
+mod compile_for {
+    use ravro::datum::Datum;
+    use ravro::encoding::{binary, resolve};
+    use ravro::random;
+    use ravro::schema;
+    use {rng, EVERYTHING, READER};
+
+    test!{decodes_like_the_resolver, {
+        let writer = schema::from_str(EVERYTHING).unwrap();
+        let reader = schema::from_str(READER).unwrap();
+        let plan = writer.compile_for(&reader).unwrap();
+        let mut rng = rng();
+        for _ in 0..50 {
+            let datum = random::generate(&writer, &mut rng).unwrap();
+            let buf = binary::to_vec(&datum, &writer).unwrap();
+            let expected = resolve::decode(&writer, &reader, &mut &buf[..]).unwrap();
+            let mut input = &buf[..];
+            assert_eq!(plan.decode(&mut input).unwrap(), expected);
+            assert!(input.is_empty());
+            assert_eq!(expected.field("extra"), Some(&Datum::from("none")));
+        }
+    }}
+
+    test!{still_encodes_with_the_writer_schema, {
+        let writer = schema::from_str(EVERYTHING).unwrap();
+        let reader = schema::from_str(READER).unwrap();
+        let plan = writer.compile_for(&reader).unwrap();
+        let datum = random::generate(&writer, &mut rng()).unwrap();
+        assert_eq!(plan.to_vec(&datum).unwrap(), binary::to_vec(&datum, &writer).unwrap());
+    }}
+
+    test!{unresolvable_branches_fail_when_used, {
+        let writer = schema::from_str(r#"["int", "boolean"]"#).unwrap();
+        let reader = schema::from_str(r#"["null", "long"]"#).unwrap();
+        let plan = writer.compile_for(&reader).unwrap();
+
+        let buf = binary::to_vec(&Datum::Union(0, Box::new(Datum::Int(3))), &writer).unwrap();
+        assert_eq!(plan.decode(&mut &buf[..]).unwrap(), Datum::Union(1, Box::new(Datum::Long(3))));
+        let buf = binary::to_vec(&Datum::Union(1, Box::new(Datum::Boolean(true))), &writer).unwrap();
+        assert!(plan.decode(&mut &buf[..]).is_err());
+    }}
+
+    test!{aliases_match_like_the_resolver, {
+        let writer = schema::from_str(r#"{"type": "record", "name": "R", "fields": [{"name": "old", "type": "int"}]}"#).unwrap();
+        let reader = schema::from_str(r#"{"type": "record", "name": "R", "fields": [
+            {"name": "new", "type": "long", "aliases": ["ns.old"]}
+        ]}"#).unwrap();
+        let plan = writer.compile_for(&reader).unwrap();
+
+        let buf = binary::to_vec(&Datum::Record(vec![(String::from("old"), Datum::Int(4))]), &writer).unwrap();
+        let expected = resolve::decode(&writer, &reader, &mut &buf[..]).unwrap();
+        assert_eq!(plan.decode(&mut &buf[..]).unwrap(), expected);
+        assert_eq!(expected.field("new"), Some(&Datum::Long(4)));
+    }}
+
+    test!{incompatible_schemas, {
+        let writer = schema::from_str(r#""string""#).unwrap();
+        let reader = schema::from_str(r#""int""#).unwrap();
+        assert!(writer.compile_for(&reader).is_err());
+    }}
+}
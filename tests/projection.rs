@@ -0,0 +1,183 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const EVENT: &'static str = r#"{"type": "record", "name": "Event", "namespace": "test", "fields": [
+    {"name": "id", "type": "long"},
+    {"name": "payload", "type": {"type": "array", "items": "string"}},
+    {"name": "source", "type": {"type": "record", "name": "Source", "fields": [
+        {"name": "host", "type": "string"},
+        {"name": "port", "type": "int"},
+        {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["A", "B"]}}
+    ]}},
+    {"name": "previous", "type": ["null", "Source"]},
+    {"name": "kind", "type": "Kind"}
+]}"#;
+
+fn event() -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    let source = |host: &str, port: i32| Datum::Record(vec![
+        (String::from("host"), Datum::from(host)),
+        (String::from("port"), Datum::Int(port)),
+        (String::from("kind"), Datum::Enum(String::from("B"))),
+    ]);
+    Datum::Record(vec![
+        (String::from("id"), Datum::Long(7)),
+        (String::from("payload"), Datum::Array(vec![Datum::from("x"), Datum::from("y")])),
+        (String::from("source"), source("here", 80)),
+        (String::from("previous"), Datum::Union(1, Box::new(source("there", 81)))),
+        (String::from("kind"), Datum::Enum(String::from("A"))),
+    ])
+}
+
+mod project {
+    use ravro::schema::{self, Schema};
+    use EVENT;
+
+    fn field_names(schema: &Schema) -> Vec<String> {
+        schema.fields().unwrap().iter().map(|f| f.name().unwrap().clone()).collect()
+    }
+
+    test!{keeps_listed_fields_in_order, {
+        let schema = schema::from_str(EVENT).unwrap();
+        let projected = schema.project(&["kind", "id"]).unwrap();
+        assert!(projected.is_valid().is_ok());
+        assert_eq!(projected.fullname().unwrap(), "test.Event");
+        assert_eq!(field_names(&projected), vec!["id", "kind"]);
+
+        // Kind was defined inside the dropped source field, so it's defined
+        // where it's used now.
+        let kind = projected.fields().unwrap()[1].field_type().unwrap();
+        assert_eq!(kind.symbols().unwrap(), vec!["A", "B"]);
+    }}
+
+    test!{nested_paths, {
+        let schema = schema::from_str(EVENT).unwrap();
+        let projected = schema.project(&["source.port", "previous.port"]).unwrap();
+        assert!(projected.is_valid().is_ok());
+        assert_eq!(field_names(&projected), vec!["source", "previous"]);
+
+        let source = projected.fields().unwrap()[0].field_type().unwrap();
+        assert_eq!(field_names(&source), vec!["port"]);
+    }}
+
+    test!{one_named_type_cannot_be_projected_two_ways, {
+        let schema = schema::from_str(EVENT).unwrap();
+        assert!(schema.project(&["source.port", "previous.host"]).is_err());
+    }}
+
+    test!{whole_field_wins_over_nested_paths, {
+        let schema = schema::from_str(EVENT).unwrap();
+        let projected = schema.project(&["source.port", "source"]).unwrap();
+        let source = projected.fields().unwrap()[0].field_type().unwrap();
+        assert_eq!(field_names(&source), vec!["host", "port", "kind"]);
+    }}
+
+    test!{unknown_fields, {
+        let schema = schema::from_str(EVENT).unwrap();
+        assert!(schema.project(&["nope"]).is_err());
+        assert!(schema.project(&["source.nope"]).is_err());
+        assert!(schema.project(&["id.nope"]).is_err());
+    }}
+}
+
+mod decode {
+    use ravro::container::{Codec, Reader, Writer};
+    use ravro::datum::Datum;
+    use ravro::encoding::{binary, resolve};
+    use ravro::schema;
+    use {event, EVENT};
+
+    test!{reads_only_projected_fields, {
+        let writer = schema::from_str(EVENT).unwrap();
+        let reader = writer.project(&["source.port", "kind"]).unwrap();
+        let bytes = binary::to_vec(&event(), &writer).unwrap();
+
+        let datum = resolve::decode(&writer, &reader, &mut &bytes[..]).unwrap();
+        assert_eq!(datum, Datum::Record(vec![
+            (String::from("source"), Datum::Record(vec![(String::from("port"), Datum::Int(80))])),
+            (String::from("kind"), Datum::Enum(String::from("A"))),
+        ]));
+
+        // The same as decoding everything and resolving afterwards.
+        assert_eq!(datum, resolve::resolve(&event(), &writer, &reader).unwrap());
+    }}
+
+    test!{skips_sized_blocks_without_reading_them, {
+        let writer = schema::from_str(r#"{"type": "record", "name": "R", "fields": [
+            {"name": "names", "type": {"type": "array", "items": "string"}},
+            {"name": "n", "type": "int"}
+        ]}"#).unwrap();
+        let reader = writer.project(&["n"]).unwrap();
+
+        // One block of one item with its byte size, holding a string that
+        // isn't UTF-8: decoding it would fail, skipping it doesn't.
+        let bytes = [0x01, 0x04, 0x02, 0xff, 0x00, 0x06];
+        assert!(binary::decode(&writer, &mut &bytes[..]).is_err());
+
+        let datum = resolve::decode(&writer, &reader, &mut &bytes[..]).unwrap();
+        assert_eq!(datum, Datum::Record(vec![(String::from("n"), Datum::Int(3))]));
+    }}
+
+    test!{container_reader_with_projection, {
+        let schema = schema::from_str(EVENT).unwrap();
+        let mut writer = Writer::new(Vec::new(), schema.clone(), Codec::Deflate);
+        for _ in 0..3 {
+            writer.append(&event()).unwrap();
+        }
+        let buf = writer.into_inner().unwrap();
+
+        let projection = schema.project(&["id"]).unwrap();
+        let reader = Reader::with_reader_schema(&buf[..], &projection).unwrap();
+        assert_eq!(reader.schema(), &schema);
+        let ids: Vec<Datum> = reader.map(|d| d.unwrap()).collect();
+        assert_eq!(ids, vec![Datum::Record(vec![(String::from("id"), Datum::Long(7))]); 3]);
+    }}
+}
+
+mod skip {
+    use ravro::encoding::binary::{self, Decoder};
+    use ravro::schema::names::Names;
+    use ravro::schema;
+    use serde::json::Value;
+    use {event, EVENT};
+
+    test!{moves_past_exactly_one_datum, {
+        let schema = schema::from_str(EVENT).unwrap();
+        let mut bytes = binary::to_vec(&event(), &schema).unwrap();
+        bytes.push(0x2a);
+
+        let names = Names::from_schema(&schema);
+        let mut cursor = &bytes[..];
+        Decoder::new(&names).skip(&Value::from(&schema), None, &mut cursor).unwrap();
+        assert_eq!(cursor, &[0x2a]);
+    }}
+}
@@ -0,0 +1,298 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+pub const MAIL: &'static str = r#"{
+    "protocol": "Mail",
+    "namespace": "example.proto",
+    "doc": "Sends mail",
+    "types": [
+        {"type": "record", "name": "Message", "fields": [
+            {"name": "to", "type": "string"},
+            {"name": "from", "type": "string"},
+            {"name": "body", "type": "string"}
+        ]},
+        {"type": "error", "name": "Bounced", "fields": [
+            {"name": "reason", "type": "string"}
+        ]}
+    ],
+    "messages": {
+        "send": {
+            "doc": "Send a message",
+            "request": [{"name": "message", "type": "Message"}],
+            "response": "string",
+            "errors": ["Bounced"]
+        },
+        "ping": {
+            "request": [],
+            "response": "null",
+            "one-way": true
+        }
+    }
+}"#;
+
+mod de {
+    use std::str::FromStr;
+    use ravro::protocol::Protocol;
+    use ravro::schema::Schema;
+    use MAIL;
+
+    test!{parses_protocol, {
+        let p = Protocol::from_str(MAIL).unwrap();
+
+        assert_eq!(p.protocol, "Mail");
+        assert_eq!(p.namespace, Some(String::from("example.proto")));
+        assert_eq!(p.doc, Some(String::from("Sends mail")));
+        assert_eq!(p.fullname(), "example.proto.Mail");
+        assert_eq!(p.types.len(), 2);
+        assert!(p.types[0].is_record());
+    }}
+
+    test!{parses_messages, {
+        let p = Protocol::from_str(MAIL).unwrap();
+
+        let send = p.message("send").unwrap();
+        assert_eq!(send.doc, Some(String::from("Send a message")));
+        assert_eq!(send.request.len(), 1);
+        assert_eq!(send.response, Schema::String(String::from("string")));
+        assert_eq!(send.errors, vec![Schema::String(String::from("Bounced"))]);
+        assert!(!send.one_way);
+
+        let ping = p.message("ping").unwrap();
+        assert!(ping.request.is_empty());
+        assert!(ping.one_way);
+    }}
+
+    test!{finds_types, {
+        let p = Protocol::from_str(MAIL).unwrap();
+
+        assert!(p.find_type("Message").is_some());
+        assert!(p.find_type("example.proto.Bounced").is_some());
+        assert!(p.find_type("Nope").is_none());
+    }}
+
+    test!{missing_protocol_name, {
+        let p = Protocol::from_str(r#"{"types": []}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{type_must_be_named, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "types": [{"type": "array", "items": "int"}]}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{type_name_must_be_well_formed, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "types": [
+            {"type": "record", "name": "1bad", "fields": []}
+        ]}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{type_defined_twice, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "namespace": "x", "types": [
+            {"type": "fixed", "name": "Id", "size": 4},
+            {"type": "fixed", "name": "Id", "namespace": "x", "size": 8}
+        ]}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{message_needs_response, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {"m": {"request": []}}}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{bad_parameter_name, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {
+            "m": {"request": [{"name": "1x", "type": "int"}], "response": "null"}
+        }}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{nullable_response, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "types": [
+            {"type": "record", "name": "Greeting", "fields": [{"name": "text", "type": "string"}]}
+        ], "messages": {
+            "hello": {"request": [], "response": ["null", "Greeting"]}
+        }}"#).unwrap();
+
+        assert_eq!(p.message("hello").unwrap().response, Schema::Union(vec![
+            Schema::String(String::from("null")),
+            Schema::String(String::from("Greeting"))]));
+    }}
+
+    test!{error_repeats_string, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {
+            "m": {"request": [], "response": "null", "errors": ["string"]}
+        }}"#);
+        assert!(p.is_err());
+    }}
+
+    test!{one_way_with_response, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {
+            "m": {"request": [], "response": "int", "one-way": true}
+        }}"#);
+        assert!(p.is_err());
+    }}
+}
+
+mod ser {
+    use std::str::FromStr;
+    use ravro::protocol::Protocol;
+    use MAIL;
+
+    test!{round_trip, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let p2 = Protocol::from_str(&p.to_string()).unwrap();
+
+        assert_eq!(p, p2);
+    }}
+
+    test!{compact_form, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {"m": {"request": [], "response": "null"}}}"#).unwrap();
+
+        let s = concat!(
+            "{\"protocol\":\"P\",",
+            "\"types\":[],",
+            "\"messages\":{\"m\":{\"request\":[],\"response\":\"null\"}}}"
+        );
+
+        assert_eq!(p.to_string(), s);
+    }}
+
+    test!{java_form, {
+        // What Java's Protocol.toString gives for the same protocol.
+        let p = Protocol::from_str(MAIL).unwrap();
+
+        let s = concat!(
+            "{\"protocol\":\"Mail\",\"namespace\":\"example.proto\",\"doc\":\"Sends mail\",",
+            "\"types\":[",
+            "{\"type\":\"record\",\"name\":\"Message\",\"fields\":[",
+            "{\"name\":\"to\",\"type\":\"string\"},",
+            "{\"name\":\"from\",\"type\":\"string\"},",
+            "{\"name\":\"body\",\"type\":\"string\"}]},",
+            "{\"type\":\"error\",\"name\":\"Bounced\",\"fields\":[",
+            "{\"name\":\"reason\",\"type\":\"string\"}]}],",
+            "\"messages\":{",
+            "\"send\":{\"doc\":\"Send a message\",",
+            "\"request\":[{\"name\":\"message\",\"type\":\"Message\"}],",
+            "\"response\":\"string\",\"errors\":[\"Bounced\"]},",
+            "\"ping\":{\"request\":[],\"response\":\"null\",\"one-way\":true}}}"
+        );
+
+        assert_eq!(p.to_string(), s);
+    }}
+
+    test!{java_namespaces_and_order, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "namespace": "a", "types": [
+            {"aliases": ["Old"], "doc": "d", "fields": [
+                {"name": "f", "order": "ascending", "type": {"type": "fixed", "name": "b.F", "size": 2}},
+                {"default": "xx", "name": "g", "type": "b.F"},
+                {"name": "h", "type": {"type": "long"}}
+            ], "name": "R", "namespace": "a", "type": "record"}
+        ], "messages": {
+            "m": {"request": [{"name": "r", "type": "a.R"}], "response": ["null", "a.R"]}
+        }}"#).unwrap();
+
+        let s = concat!(
+            "{\"protocol\":\"P\",\"namespace\":\"a\",",
+            "\"types\":[{\"type\":\"record\",\"name\":\"R\",\"doc\":\"d\",\"fields\":[",
+            "{\"name\":\"f\",\"type\":{\"type\":\"fixed\",\"name\":\"F\",\"namespace\":\"b\",\"size\":2}},",
+            "{\"name\":\"g\",\"type\":\"b.F\",\"default\":\"xx\"},",
+            "{\"name\":\"h\",\"type\":\"long\"}],",
+            "\"aliases\":[\"Old\"]}],",
+            "\"messages\":{\"m\":{\"request\":[{\"name\":\"r\",\"type\":\"R\"}],\"response\":[\"null\",\"R\"]}}}"
+        );
+
+        assert_eq!(p.to_string(), s);
+    }}
+
+    test!{md5, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {"m": {"request": [], "response": "null"}}}"#).unwrap();
+
+        // md5 of the compact form above
+        let expected = [0x03, 0x7d, 0xba, 0x8b, 0xae, 0x6a, 0x7b, 0xa2,
+                        0xe0, 0xdd, 0xbf, 0x8f, 0x44, 0x3b, 0x3d, 0xd9];
+
+        assert_eq!(p.md5(), expected);
+    }}
+
+    test!{keeps_message_order, {
+        let p = Protocol::from_str(r#"{"protocol": "P", "messages": {
+            "zeta": {"request": [], "response": "null"},
+            "alpha": {"request": [{"name": "messages", "type": {"type": "record", "name": "R", "fields": []}}], "response": "null"},
+            "mid\u0064le": {"request": [], "response": "null"}
+        }}"#).unwrap();
+
+        let names: Vec<&str> = p.messages.iter().map(|&(ref name, _)| &name[..]).collect();
+        assert_eq!(names, vec!["zeta", "alpha", "middle"]);
+        assert!(p.to_string().contains("\"messages\":{\"zeta\":"));
+        assert_eq!(Protocol::from_str(&p.to_string()).unwrap(), p);
+    }}
+
+    test!{md5_differs_between_protocols, {
+        let p1 = Protocol::from_str(r#"{"protocol": "P1"}"#).unwrap();
+        let p2 = Protocol::from_str(r#"{"protocol": "P2"}"#).unwrap();
+
+        assert!(p1.md5() != p2.md5());
+    }}
+}
+
+mod to_idl {
+    use std::str::FromStr;
+    use ravro::protocol::Protocol;
+    use MAIL;
+
+    test!{mail, {
+        let p = Protocol::from_str(MAIL).unwrap();
+
+        let idl = concat!(
+            "/** Sends mail */\n",
+            "@namespace(\"example.proto\")\n",
+            "protocol Mail {\n",
+            "  record Message {\n",
+            "    string to;\n",
+            "    string from;\n",
+            "    string body;\n",
+            "  }\n",
+            "\n",
+            "  error Bounced {\n",
+            "    string reason;\n",
+            "  }\n",
+            "\n",
+            "  /** Send a message */\n",
+            "  string send(Message message) throws Bounced;\n",
+            "\n",
+            "  void ping() oneway;\n",
+            "}\n"
+        );
+
+        assert_eq!(p.to_idl(), idl);
+    }}
+}
@@ -0,0 +1,214 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate rand;
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+use rand::{SeedableRng, StdRng};
+
+fn rng() -> StdRng {
+    StdRng::from_seed(&[42usize][..])
+}
+
+const EVERYTHING: &'static str = r#"{"type": "record", "name": "Everything", "namespace": "test", "fields": [
+    {"name": "n", "type": "null"},
+    {"name": "b", "type": "boolean"},
+    {"name": "i", "type": "int"},
+    {"name": "l", "type": "long"},
+    {"name": "f", "type": "float"},
+    {"name": "d", "type": "double"},
+    {"name": "by", "type": "bytes"},
+    {"name": "s", "type": "string"},
+    {"name": "e", "type": {"type": "enum", "name": "Suit", "symbols": ["SPADES", "HEARTS"]}},
+    {"name": "fx", "type": {"type": "fixed", "name": "MD5", "size": 16}},
+    {"name": "a", "type": {"type": "array", "items": "Suit"}},
+    {"name": "m", "type": {"type": "map", "values": "MD5"}},
+    {"name": "u", "type": ["null", "string", "Suit"]}
+]}"#;
+
+const LIST: &'static str = r#"{"type": "record", "name": "Node", "fields": [
+    {"name": "value", "type": "int"},
+    {"name": "next", "type": ["null", "Node"]}
+]}"#;
+
+const TREE: &'static str = r#"{"type": "record", "name": "Tree", "fields": [
+    {"name": "children", "type": {"type": "array", "items": "Tree"}}
+]}"#;
+
+// The number of nodes in a chain of Node records.
+fn list_length(datum: &ravro::datum::Datum) -> usize {
+    match datum.field("next").map(|n| n.unwrap_union()) {
+        Some(next @ &ravro::datum::Datum::Record(_)) => 1 + list_length(next),
+        _ => 1,
+    }
+}
+
+fn tree_depth(datum: &ravro::datum::Datum) -> usize {
+    match datum.field("children") {
+        Some(&ravro::datum::Datum::Array(ref children)) => 1 + children.iter().map(tree_depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+mod generate {
+    use ravro::datum::Datum;
+    use ravro::encoding::binary;
+    use ravro::random::{self, Options};
+    use ravro::schema;
+    use {rng, list_length, tree_depth, EVERYTHING, LIST, TREE};
+
+    test!{datums_can_be_written_with_the_schema, {
+        let schema = schema::from_str(EVERYTHING).unwrap();
+        let mut rng = rng();
+        for _ in 0..100 {
+            let datum = random::generate(&schema, &mut rng).unwrap();
+            let encoded = binary::to_vec(&datum, &schema).unwrap();
+            assert_eq!(binary::decode(&schema, &mut &encoded[..]).unwrap(), datum);
+        }
+    }}
+
+    test!{enums_use_the_symbols_and_fixed_the_size, {
+        let schema = schema::from_str(EVERYTHING).unwrap();
+        let mut rng = rng();
+        for _ in 0..20 {
+            let datum = random::generate(&schema, &mut rng).unwrap();
+            match datum.field("e") {
+                Some(&Datum::Enum(ref symbol)) => assert!(symbol == "SPADES" || symbol == "HEARTS"),
+                other => panic!("expected an enum, got {:?}", other),
+            }
+            match datum.field("fx") {
+                Some(&Datum::Fixed(ref bytes)) => assert_eq!(bytes.len(), 16),
+                other => panic!("expected a fixed, got {:?}", other),
+            }
+        }
+    }}
+
+    test!{options_limit_lengths, {
+        let schema = schema::from_str(EVERYTHING).unwrap();
+        let options = Options { max_collection_length: 2, max_string_length: 3, .. Options::default() };
+        let mut rng = rng();
+        for _ in 0..50 {
+            let datum = random::generate_with(&schema, &options, &mut rng).unwrap();
+            match (datum.field("s"), datum.field("a"), datum.field("m")) {
+                (Some(&Datum::String(ref s)), Some(&Datum::Array(ref a)), Some(&Datum::Map(ref m))) => {
+                    assert!(s.len() <= 3);
+                    assert!(a.len() <= 2);
+                    assert!(m.len() <= 2);
+                    assert!(m.keys().all(|k| k.len() <= 3));
+                },
+                other => panic!("unexpected fields {:?}", other),
+            }
+        }
+    }}
+
+    test!{null_probability, {
+        let schema = schema::from_str(r#"["null", "int"]"#).unwrap();
+        let mut rng = rng();
+
+        let always = Options { null_probability: 1.0, .. Options::default() };
+        for _ in 0..20 {
+            assert_eq!(random::generate_with(&schema, &always, &mut rng).unwrap(), Datum::Union(0, Box::new(Datum::Null)));
+        }
+
+        let never = Options { null_probability: 0.0, .. Options::default() };
+        for _ in 0..20 {
+            match random::generate_with(&schema, &never, &mut rng).unwrap() {
+                Datum::Union(1, _) => (),
+                other => panic!("expected an int, got {:?}", other),
+            }
+        }
+    }}
+
+    test!{logical_types_stay_in_range, {
+        let schema = schema::from_str(r#"{"type": "record", "name": "Times", "fields": [
+            {"name": "date", "type": {"type": "int", "logicalType": "date"}},
+            {"name": "time", "type": {"type": "int", "logicalType": "time-millis"}},
+            {"name": "ts", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "id", "type": {"type": "string", "logicalType": "uuid"}},
+            {"name": "price", "type": {"type": "bytes", "logicalType": "decimal", "precision": 4, "scale": 2}},
+            {"name": "cost", "type": {"type": "fixed", "name": "Cost", "size": 2, "logicalType": "decimal", "precision": 9}}
+        ]}"#).unwrap();
+        let mut rng = rng();
+        for _ in 0..100 {
+            let datum = random::generate(&schema, &mut rng).unwrap();
+            match datum.field("date") {
+                Some(&Datum::Int(days)) => assert!(days >= 0 && days <= 47482),
+                other => panic!("unexpected date {:?}", other),
+            }
+            match datum.field("time") {
+                Some(&Datum::Int(ms)) => assert!(ms >= 0 && ms < 86400000),
+                other => panic!("unexpected time {:?}", other),
+            }
+            match datum.field("ts") {
+                Some(&Datum::Long(ms)) => assert!(ms >= 0),
+                other => panic!("unexpected timestamp {:?}", other),
+            }
+            match datum.field("id") {
+                Some(&Datum::String(ref id)) => {
+                    assert_eq!(id.len(), 36);
+                    assert_eq!(&id[14..15], "4");
+                },
+                other => panic!("unexpected uuid {:?}", other),
+            }
+            match datum.field("price") {
+                Some(&Datum::Bytes(ref bytes)) => {
+                    // At most 9999 in magnitude, which takes two bytes.
+                    assert!(bytes.len() >= 1 && bytes.len() <= 2);
+                },
+                other => panic!("unexpected decimal {:?}", other),
+            }
+            match datum.field("cost") {
+                Some(&Datum::Fixed(ref bytes)) => assert_eq!(bytes.len(), 2),
+                other => panic!("unexpected decimal {:?}", other),
+            }
+        }
+    }}
+
+    test!{recursion_stops_at_the_depth_limit, {
+        let options = Options { max_recursion_depth: 2, null_probability: 0.0, .. Options::default() };
+        let mut rng = rng();
+
+        let list = schema::from_str(LIST).unwrap();
+        for _ in 0..20 {
+            assert_eq!(list_length(&random::generate_with(&list, &options, &mut rng).unwrap()), 3);
+        }
+
+        let tree = schema::from_str(TREE).unwrap();
+        for _ in 0..20 {
+            assert!(tree_depth(&random::generate_with(&tree, &options, &mut rng).unwrap()) <= 3);
+        }
+    }}
+
+    test!{recursion_that_cannot_stop, {
+        let schema = schema::from_str(r#"{"type": "record", "name": "Forever", "fields": [
+            {"name": "again", "type": "Forever"}
+        ]}"#).unwrap();
+        assert!(random::generate(&schema, &mut rng()).is_err());
+    }}
+}
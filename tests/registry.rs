@@ -0,0 +1,246 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const V1: &'static str = r#"{"type": "record", "name": "User", "namespace": "x", "fields": [
+    {"name": "name", "type": "string"}
+]}"#;
+
+const V2: &'static str = r#"{"type": "record", "name": "User", "namespace": "x", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": "int", "default": -1}
+]}"#;
+
+fn user() -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    Datum::Record(vec![(String::from("name"), Datum::from("ann"))])
+}
+
+mod wire_format {
+    use ravro::datum::Datum;
+    use ravro::encoding::registry::*;
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use {user, V1, V2};
+
+    test!{header, {
+        let schema = schema::from_str(V1).unwrap();
+        let buf = to_wire_format(0x01020304, &user(), &schema).unwrap();
+
+        assert_eq!(&buf[..5], &[0, 1, 2, 3, 4]);
+        assert_eq!(&buf[5..], &[0x06, 0x61, 0x6e, 0x6e]);
+        assert_eq!(read_schema_id(&mut &buf[..]).unwrap(), 0x01020304);
+    }}
+
+    test!{round_trip_with_resolution, {
+        let writer = schema::from_str(V1).unwrap();
+        let reader = schema::from_str(V2).unwrap();
+        let mut registry = InMemoryRegistry::new();
+        let id = registry.register("users-value", &writer).unwrap();
+
+        let buf = to_wire_format(id, &user(), &writer).unwrap();
+        assert_eq!(decode_wire_format(&mut registry, &writer, &mut &buf[..]).unwrap(), user());
+
+        let read = decode_wire_format(&mut registry, &reader, &mut &buf[..]).unwrap();
+        assert_eq!(read.field("age"), Some(&Datum::Int(-1)));
+    }}
+
+    test!{decoder, {
+        let v1 = schema::from_str(V1).unwrap();
+        let v2 = schema::from_str(V2).unwrap();
+        let mut registry = InMemoryRegistry::new();
+        let id1 = registry.register("users-value", &v1).unwrap();
+        let id2 = registry.register("users-value", &v2).unwrap();
+
+        let mut decoder = WireFormatDecoder::new(registry, v2.clone());
+        let old = to_wire_format(id1, &user(), &v1).unwrap();
+        let read = decoder.decode(&mut &old[..]).unwrap();
+        assert_eq!(read.field("age"), Some(&Datum::Int(-1)));
+        assert_eq!(decoder.decode(&mut &old[..]).unwrap(), read);
+
+        let new = to_wire_format(id2, &read, &v2).unwrap();
+        assert_eq!(decoder.decode(&mut &new[..]).unwrap(), read);
+    }}
+
+    test!{bad_magic, {
+        let schema = schema::from_str(V1).unwrap();
+        let mut registry = InMemoryRegistry::new();
+        let mut buf = to_wire_format(1, &user(), &schema).unwrap();
+        buf[0] = 1;
+
+        match decode_wire_format(&mut registry, &schema, &mut &buf[..]) {
+            Err(Error::DecodeError(ErrorCode::BadWireFormatMagic)) => (),
+            other => panic!("expected a bad magic byte error, got {:?}", other),
+        }
+    }}
+
+    test!{unknown_id, {
+        let schema = schema::from_str(V1).unwrap();
+        let mut registry = InMemoryRegistry::new();
+        let buf = to_wire_format(7, &user(), &schema).unwrap();
+
+        match decode_wire_format(&mut registry, &schema, &mut &buf[..]) {
+            Err(Error::DecodeError(ErrorCode::UnknownSchemaId)) => (),
+            other => panic!("expected an unknown id error, got {:?}", other),
+        }
+    }}
+}
+
+mod in_memory {
+    use ravro::encoding::registry::*;
+    use ravro::schema;
+    use {V1, V2};
+
+    test!{ids_and_versions, {
+        let v1 = schema::from_str(V1).unwrap();
+        let v2 = schema::from_str(V2).unwrap();
+        let mut registry = InMemoryRegistry::new();
+
+        assert_eq!(registry.register("a", &v1).unwrap(), 1);
+        assert_eq!(registry.register("a", &v1).unwrap(), 1);
+        assert_eq!(registry.latest("a").unwrap(), (1, v1.clone()));
+
+        assert_eq!(registry.register("a", &v2).unwrap(), 2);
+        assert_eq!(registry.latest("a").unwrap(), (2, v2.clone()));
+
+        // The same schema under another subject keeps its id.
+        assert_eq!(registry.register("b", &v1).unwrap(), 1);
+        assert_eq!(registry.latest("b").unwrap().0, 1);
+
+        assert_eq!(registry.get_by_id(2).unwrap(), v2);
+        assert!(registry.get_by_id(0).is_err());
+        assert!(registry.get_by_id(3).is_err());
+        assert!(registry.latest("c").is_err());
+    }}
+}
+
+mod caching {
+    use ravro::encoding::registry::*;
+    use ravro::schema::{self, Schema};
+    use ravro::schema::error::Error;
+    use {V1, V2};
+
+    struct Counting {
+        inner: InMemoryRegistry,
+        calls: usize,
+    }
+
+    impl SchemaRegistry for Counting {
+        fn register(&mut self, subject: &str, schema: &Schema) -> Result<u32, Error> {
+            self.calls += 1;
+            self.inner.register(subject, schema)
+        }
+
+        fn get_by_id(&mut self, id: u32) -> Result<Schema, Error> {
+            self.calls += 1;
+            self.inner.get_by_id(id)
+        }
+
+        fn latest(&mut self, subject: &str) -> Result<(u32, Schema), Error> {
+            self.calls += 1;
+            self.inner.latest(subject)
+        }
+    }
+
+    test!{only_new_requests_reach_the_registry, {
+        let v1 = schema::from_str(V1).unwrap();
+        let v2 = schema::from_str(V2).unwrap();
+        let mut registry = CachingRegistry::new(Counting { inner: InMemoryRegistry::new(), calls: 0 });
+
+        let id = registry.register("a", &v1).unwrap();
+        assert_eq!(registry.register("a", &v1).unwrap(), id);
+        assert_eq!(registry.get_by_id(id).unwrap(), v1);
+        assert_eq!(registry.inner().calls, 1);
+
+        let id2 = registry.inner().inner.register("a", &v2).unwrap();
+        assert_eq!(registry.get_by_id(id2).unwrap(), v2);
+        assert_eq!(registry.get_by_id(id2).unwrap(), v2);
+        assert_eq!(registry.inner().calls, 2);
+
+        // The latest version can change, so it isn't cached.
+        assert_eq!(registry.latest("a").unwrap().0, id2);
+        assert_eq!(registry.latest("a").unwrap().0, id2);
+        assert_eq!(registry.inner().calls, 4);
+    }}
+}
+
+mod file_system {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use ravro::encoding::registry::*;
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use {V1, V2};
+
+    test!{shared_directory, {
+        let dir = env::temp_dir().join(format!("ravro-registry-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let v1 = schema::from_str(V1).unwrap();
+        let v2 = schema::from_str(V2).unwrap();
+
+        {
+            let mut registry = FileSystemRegistry::open(&dir).unwrap();
+            assert_eq!(registry.register("users", &v1).unwrap(), 1);
+            assert_eq!(registry.register("users", &v2).unwrap(), 2);
+            assert_eq!(registry.register("users", &v1).unwrap(), 1);
+            assert!(dir.join("schemas").join("2.avsc").exists());
+        }
+
+        // Another client opening the same directory sees the same schemas.
+        let mut registry = FileSystemRegistry::open(&dir).unwrap();
+        assert_eq!(registry.get_by_id(1).unwrap().fingerprint(), v1.fingerprint());
+        assert_eq!(registry.latest("users").unwrap().0, 2);
+        assert_eq!(registry.register("other", &v2).unwrap(), 2);
+        assert!(registry.get_by_id(3).is_err());
+        assert!(registry.latest("nobody").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+
+    test!{subjects_must_be_file_names, {
+        let dir = env::temp_dir().join(format!("ravro-registry-subjects-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let v1 = schema::from_str(V1).unwrap();
+
+        let mut registry = FileSystemRegistry::open(&dir).unwrap();
+        for subject in &["", ".", "..", "../escape", "a/b", "a\\b"] {
+            match registry.register(subject, &v1) {
+                Err(Error::EncodeError(ErrorCode::InvalidSubject)) => (),
+                other => panic!("expected {:?} to be refused, got {:?}", subject, other),
+            }
+            assert!(registry.latest(subject).is_err());
+        }
+        assert!(!dir.join("escape").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }}
+}
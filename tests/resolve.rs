@@ -0,0 +1,121 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+mod resolve {
+    use ravro::datum::Datum;
+    use ravro::encoding::resolve::resolve;
+    use ravro::schema;
+
+    fn check(datum: Datum, writer: &str, reader: &str) -> Result<Datum, ravro::schema::error::Error> {
+        resolve(&datum, &schema::from_str(writer).unwrap(), &schema::from_str(reader).unwrap())
+    }
+
+    test!{promotions, {
+        assert_eq!(check(Datum::Int(3), r#""int""#, r#""long""#).unwrap(), Datum::Long(3));
+        assert_eq!(check(Datum::Long(3), r#""long""#, r#""double""#).unwrap(), Datum::Double(3.0));
+        assert_eq!(check(Datum::Float(1.5), r#""float""#, r#""double""#).unwrap(), Datum::Double(1.5));
+        assert_eq!(check(Datum::from("a"), r#""string""#, r#""bytes""#).unwrap(), Datum::Bytes(vec![0x61]));
+        assert_eq!(check(Datum::Bytes(vec![0x61]), r#""bytes""#, r#""string""#).unwrap(), Datum::from("a"));
+        assert!(check(Datum::Long(3), r#""long""#, r#""int""#).is_err());
+    }}
+
+    test!{record_fields, {
+        let writer = r#"{"type": "record", "name": "R", "fields": [
+            {"name": "a", "type": "int"},
+            {"name": "dropped", "type": "string"},
+            {"name": "old", "type": "string"}
+        ]}"#;
+        let reader = r#"{"type": "record", "name": "R", "fields": [
+            {"name": "new", "type": "string", "aliases": ["old"]},
+            {"name": "a", "type": "long"},
+            {"name": "added", "type": "boolean", "default": true}
+        ]}"#;
+        let datum = Datum::Record(vec![
+            (String::from("a"), Datum::Int(1)),
+            (String::from("dropped"), Datum::from("x")),
+            (String::from("old"), Datum::from("y")),
+        ]);
+
+        assert_eq!(check(datum, writer, reader).unwrap(), Datum::Record(vec![
+            (String::from("new"), Datum::from("y")),
+            (String::from("a"), Datum::Long(1)),
+            (String::from("added"), Datum::Boolean(true)),
+        ]));
+    }}
+
+    test!{reader_field_without_default, {
+        let writer = r#"{"type": "record", "name": "R", "fields": []}"#;
+        let reader = r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int"}]}"#;
+        assert!(check(Datum::Record(vec![]), writer, reader).is_err());
+    }}
+
+    test!{record_names_must_match, {
+        let writer = r#"{"type": "record", "name": "a.R", "fields": []}"#;
+        assert!(check(Datum::Record(vec![]), writer, r#"{"type": "record", "name": "b.R", "fields": []}"#).is_ok());
+        assert!(check(Datum::Record(vec![]), writer, r#"{"type": "record", "name": "S", "fields": []}"#).is_err());
+        assert!(check(Datum::Record(vec![]), writer, r#"{"type": "record", "name": "S", "aliases": ["a.R"], "fields": []}"#).is_ok());
+    }}
+
+    test!{enums, {
+        let writer = r#"{"type": "enum", "name": "E", "symbols": ["A", "B", "C"]}"#;
+        assert_eq!(check(Datum::Enum(String::from("A")), writer, r#"{"type": "enum", "name": "E", "symbols": ["B", "A"]}"#).unwrap(),
+                   Datum::Enum(String::from("A")));
+        assert!(check(Datum::Enum(String::from("C")), writer, r#"{"type": "enum", "name": "E", "symbols": ["A", "B"]}"#).is_err());
+        assert_eq!(check(Datum::Enum(String::from("C")), writer, r#"{"type": "enum", "name": "E", "symbols": ["A", "B"], "default": "A"}"#).unwrap(),
+                   Datum::Enum(String::from("A")));
+    }}
+
+    test!{fixed_sizes, {
+        let writer = r#"{"type": "fixed", "name": "F", "size": 2}"#;
+        assert!(check(Datum::Fixed(vec![1, 2]), writer, r#"{"type": "fixed", "name": "F", "size": 2}"#).is_ok());
+        assert!(check(Datum::Fixed(vec![1, 2]), writer, r#"{"type": "fixed", "name": "F", "size": 3}"#).is_err());
+    }}
+
+    test!{unions, {
+        // Writer's union to a plain reader type.
+        assert_eq!(check(Datum::Union(1, Box::new(Datum::Int(2))), r#"["null", "int"]"#, r#""long""#).unwrap(), Datum::Long(2));
+        assert!(check(Datum::Union(0, Box::new(Datum::Null)), r#"["null", "int"]"#, r#""long""#).is_err());
+
+        // Plain writer type to a reader's union, preferring an exact match.
+        assert_eq!(check(Datum::Int(2), r#""int""#, r#"["null", "long", "int"]"#).unwrap(), Datum::Union(2, Box::new(Datum::Int(2))));
+        assert_eq!(check(Datum::Int(2), r#""int""#, r#"["null", "double"]"#).unwrap(), Datum::Union(1, Box::new(Datum::Double(2.0))));
+
+        // Union to union.
+        assert_eq!(check(Datum::Union(1, Box::new(Datum::from("s"))), r#"["null", "string"]"#, r#"["string", "null"]"#).unwrap(),
+                   Datum::Union(0, Box::new(Datum::from("s"))));
+    }}
+
+    test!{arrays_and_maps, {
+        assert_eq!(check(Datum::Array(vec![Datum::Int(1)]), r#"{"type": "array", "items": "int"}"#, r#"{"type": "array", "items": "long"}"#).unwrap(),
+                   Datum::Array(vec![Datum::Long(1)]));
+        assert!(check(Datum::Array(vec![]), r#"{"type": "array", "items": "int"}"#, r#"{"type": "map", "values": "int"}"#).is_err());
+    }}
+}
@@ -0,0 +1,435 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+pub const MAIL: &'static str = r#"{
+    "protocol": "Mail",
+    "namespace": "example.proto",
+    "types": [
+        {"type": "record", "name": "Message", "fields": [
+            {"name": "to", "type": "string"},
+            {"name": "body", "type": "string"}
+        ]},
+        {"type": "error", "name": "Bounced", "fields": [
+            {"name": "reason", "type": "string"}
+        ]}
+    ],
+    "messages": {
+        "send": {
+            "request": [{"name": "message", "type": "Message"}],
+            "response": "string",
+            "errors": ["Bounced"]
+        },
+        "ping": {
+            "request": [],
+            "response": "null",
+            "one-way": true
+        }
+    }
+}"#;
+
+// The same protocol as the server sees it, with an extra message and a doc
+// string so that the hashes differ.
+pub const MAIL_SERVER: &'static str = r#"{
+    "protocol": "Mail",
+    "namespace": "example.proto",
+    "doc": "Server side",
+    "types": [
+        {"type": "record", "name": "Message", "fields": [
+            {"name": "to", "type": "string"},
+            {"name": "body", "type": "string"}
+        ]},
+        {"type": "error", "name": "Bounced", "fields": [
+            {"name": "reason", "type": "string"}
+        ]}
+    ],
+    "messages": {
+        "send": {
+            "request": [{"name": "message", "type": "Message"}],
+            "response": "string",
+            "errors": ["Bounced"]
+        },
+        "ping": {
+            "request": [],
+            "response": "null",
+            "one-way": true
+        },
+        "count": {
+            "request": [],
+            "response": "int"
+        }
+    }
+}"#;
+
+pub fn mail_handler(message: &str, request: &ravro::datum::Datum) -> Result<ravro::datum::Datum, ravro::datum::Datum> {
+    use ravro::datum::Datum;
+
+    match message {
+        "send" => {
+            let to = match request.field("message").and_then(|m| m.field("to")) {
+                Some(&Datum::String(ref to)) => to.clone(),
+                _ => return Err(Datum::from("bad request")),
+            };
+            if to == "nobody" {
+                Err(Datum::Record(vec![(String::from("reason"), Datum::from("no such user"))]))
+            } else {
+                Ok(Datum::String(format!("sent to {}", to)))
+            }
+        },
+        _ => Ok(Datum::Null),
+    }
+}
+
+pub fn send_request(to: &str) -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    Datum::Record(vec![(String::from("message"), Datum::Record(vec![
+        (String::from("to"), Datum::from(to)),
+        (String::from("body"), Datum::from("hi")),
+    ]))])
+}
+
+mod local {
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+    use ravro::datum::Datum;
+    use ravro::encoding::binary;
+    use ravro::protocol::Protocol;
+    use ravro::rpc::{HandshakeMatch, HandshakeRequest, HandshakeResponse, LocalTransceiver, Requestor, Responder};
+    use ravro::schema::error::Error;
+    use {MAIL, MAIL_SERVER, mail_handler, send_request};
+
+    test!{call, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let responder = Responder::new(p.clone(), mail_handler);
+        let mut requestor = Requestor::new(p, LocalTransceiver::new(responder));
+
+        let response = requestor.request("send", &send_request("bob")).unwrap();
+        assert_eq!(response, Datum::from("sent to bob"));
+        assert!(requestor.remote().is_some());
+
+        let response = requestor.request("send", &send_request("alice")).unwrap();
+        assert_eq!(response, Datum::from("sent to alice"));
+    }}
+
+    test!{declared_error, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let responder = Responder::new(p.clone(), mail_handler);
+        let mut requestor = Requestor::new(p, LocalTransceiver::new(responder));
+
+        match requestor.request("send", &send_request("nobody")) {
+            Err(Error::RemoteError(error)) => {
+                assert_eq!(error.field("reason"), Some(&Datum::from("no such user")));
+            },
+            other => panic!("expected a remote error, got {:?}", other),
+        }
+    }}
+
+    test!{one_way, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let responder = Responder::new(p.clone(), mail_handler);
+        let mut requestor = Requestor::new(p, LocalTransceiver::new(responder));
+
+        assert_eq!(requestor.request("ping", &Datum::Record(vec![])).unwrap(), Datum::Null);
+    }}
+
+    test!{unknown_message, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let responder = Responder::new(p.clone(), mail_handler);
+        let mut requestor = Requestor::new(p, LocalTransceiver::new(responder));
+
+        assert!(requestor.request("nope", &Datum::Record(vec![])).is_err());
+    }}
+
+    test!{different_server_protocol, {
+        let client = Protocol::from_str(MAIL).unwrap();
+        let server = Protocol::from_str(MAIL_SERVER).unwrap();
+        let responder = Responder::new(server.clone(), mail_handler);
+        let mut requestor = Requestor::new(client, LocalTransceiver::new(responder));
+
+        // The server doesn't know the client's protocol: the first attempt
+        // is answered with NONE, and the call is retried with it.
+        let response = requestor.request("send", &send_request("bob")).unwrap();
+        assert_eq!(response, Datum::from("sent to bob"));
+        assert_eq!(requestor.remote().unwrap().md5(), server.md5());
+
+        // After that the server's hash is known and the handshake matches.
+        let response = requestor.request("send", &send_request("carol")).unwrap();
+        assert_eq!(response, Datum::from("sent to carol"));
+    }}
+
+    test!{resolves_differing_fields, {
+        // The server's Message has a parameter field the client's lacks,
+        // and its Bounced a field the client's doesn't know about.
+        let server = Protocol::from_str(r#"{
+            "protocol": "Mail",
+            "namespace": "example.proto",
+            "types": [
+                {"type": "record", "name": "Message", "fields": [
+                    {"name": "to", "type": "string"},
+                    {"name": "body", "type": "string"},
+                    {"name": "priority", "type": "int", "default": 1}
+                ]},
+                {"type": "error", "name": "Bounced", "fields": [
+                    {"name": "reason", "type": "string"},
+                    {"name": "code", "type": "int"}
+                ]}
+            ],
+            "messages": {
+                "send": {
+                    "request": [{"name": "message", "type": "Message"}],
+                    "response": "string",
+                    "errors": ["Bounced"]
+                }
+            }
+        }"#).unwrap();
+        let handler = |_: &str, request: &Datum| -> Result<Datum, Datum> {
+            let message = request.field("message").unwrap();
+            match (message.field("to"), message.field("priority")) {
+                (Some(&Datum::String(ref to)), Some(&Datum::Int(priority))) if to != "nobody" => {
+                    Ok(Datum::String(format!("sent to {} at priority {}", to, priority)))
+                },
+                _ => Err(Datum::Record(vec![
+                    (String::from("reason"), Datum::from("no such user")),
+                    (String::from("code"), Datum::Int(550)),
+                ])),
+            }
+        };
+        let client = Protocol::from_str(MAIL).unwrap();
+        let mut requestor = Requestor::new(client, LocalTransceiver::new(Responder::new(server, handler)));
+
+        let response = requestor.request("send", &send_request("bob")).unwrap();
+        assert_eq!(response, Datum::from("sent to bob at priority 1"));
+
+        match requestor.request("send", &send_request("nobody")) {
+            Err(Error::RemoteError(error)) => {
+                assert_eq!(error, Datum::Record(vec![(String::from("reason"), Datum::from("no such user"))]));
+            },
+            other => panic!("expected a remote error, got {:?}", other),
+        }
+    }}
+
+    test!{message_the_server_lacks, {
+        let client = Protocol::from_str(MAIL_SERVER).unwrap();
+        let server = Protocol::from_str(MAIL).unwrap();
+        let responder = Responder::new(server, mail_handler);
+        let mut requestor = Requestor::new(client, LocalTransceiver::new(responder));
+
+        match requestor.request("count", &Datum::Record(vec![])) {
+            Err(Error::RemoteError(error)) => assert_eq!(error, Datum::from("unknown message")),
+            other => panic!("expected a remote error, got {:?}", other),
+        }
+    }}
+
+    test!{response_not_matching_schema, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let responder = Responder::new(p.clone(), |_: &str, _: &Datum| -> Result<Datum, Datum> { Ok(Datum::Int(1)) });
+        let mut requestor = Requestor::new(p, LocalTransceiver::new(responder));
+
+        match requestor.request("send", &send_request("bob")) {
+            Err(Error::RemoteError(Datum::String(_))) => (),
+            other => panic!("expected a system error, got {:?}", other),
+        }
+    }}
+
+    test!{same_protocol_needs_no_retry, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let mut responder = Responder::new(p.clone(), mail_handler);
+
+        let mut request = Vec::new();
+        HandshakeRequest {
+            client_hash: p.md5(),
+            client_protocol: None,
+            server_hash: p.md5(),
+            meta: None,
+        }.encode(&mut request).unwrap();
+        binary::write_long(0, &mut request).unwrap();
+        binary::write_string("ping", &mut request).unwrap();
+
+        let response = responder.respond(&[request]).unwrap().concat();
+        let mut reader = &response[..];
+        let handshake = HandshakeResponse::decode(&mut reader).unwrap();
+        assert_eq!(handshake.handshake_match, HandshakeMatch::Both);
+        assert!(handshake.server_protocol.is_none());
+    }}
+
+    test!{undecodable_params, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let mut responder = Responder::new(p.clone(), mail_handler);
+
+        let mut request = Vec::new();
+        HandshakeRequest {
+            client_hash: p.md5(),
+            client_protocol: Some(p.to_string()),
+            server_hash: p.md5(),
+            meta: None,
+        }.encode(&mut request).unwrap();
+        binary::write_long(0, &mut request).unwrap();
+        binary::write_string("send", &mut request).unwrap();
+        // The message record stops after a string length.
+        binary::write_long(10, &mut request).unwrap();
+
+        let response = responder.respond(&[request]).unwrap().concat();
+        let mut reader = &response[..];
+        assert_eq!(HandshakeResponse::decode(&mut reader).unwrap().handshake_match, HandshakeMatch::Both);
+        assert_eq!(binary::read_long(&mut reader).unwrap(), 0);
+        assert!(binary::read_boolean(&mut reader).unwrap());
+        assert_eq!(binary::read_long(&mut reader).unwrap(), 0);
+        assert!(binary::read_string(&mut reader).is_ok());
+    }}
+
+    test!{metadata, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let mut meta = BTreeMap::new();
+        meta.insert(String::from("trace"), vec![7]);
+
+        let mut responder = Responder::new(p.clone(), mail_handler);
+        responder.set_meta(meta.clone());
+        responder.set_handshake_meta(Some(meta.clone()));
+        let mut requestor = Requestor::new(p, LocalTransceiver::new(responder));
+        requestor.set_meta(meta.clone());
+        requestor.set_handshake_meta(Some(meta.clone()));
+
+        requestor.request("send", &send_request("bob")).unwrap();
+        assert_eq!(requestor.response_meta(), &meta);
+        assert_eq!(requestor.response_handshake_meta(), Some(&meta));
+        assert_eq!(requestor.transceiver().responder().request_meta(), &meta);
+        assert_eq!(requestor.transceiver().responder().request_handshake_meta(), Some(&meta));
+    }}
+}
+
+mod handshake {
+    use std::collections::BTreeMap;
+    use ravro::rpc::{HandshakeMatch, HandshakeRequest, HandshakeResponse};
+
+    test!{request_round_trip, {
+        let mut meta = BTreeMap::new();
+        meta.insert(String::from("k"), vec![1, 2, 3]);
+        let request = HandshakeRequest {
+            client_hash: [1; 16],
+            client_protocol: Some(String::from("{}")),
+            server_hash: [2; 16],
+            meta: Some(meta),
+        };
+
+        let mut buf = Vec::new();
+        request.encode(&mut buf).unwrap();
+        assert_eq!(HandshakeRequest::decode(&mut &buf[..]).unwrap(), request);
+    }}
+
+    test!{response_round_trip, {
+        let response = HandshakeResponse {
+            handshake_match: HandshakeMatch::Client,
+            server_protocol: Some(String::from("{}")),
+            server_hash: Some([3; 16]),
+            meta: None,
+        };
+
+        let mut buf = Vec::new();
+        response.encode(&mut buf).unwrap();
+        assert_eq!(buf[0], 2);
+        assert_eq!(HandshakeResponse::decode(&mut &buf[..]).unwrap(), response);
+    }}
+}
+
+mod framing {
+    use ravro::rpc::framing::{read_buffers, to_buffers, write_buffers, BUFFER_SIZE};
+
+    test!{round_trip, {
+        let data: Vec<u8> = (0..BUFFER_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let buffers = to_buffers(&data);
+        assert_eq!(buffers.len(), 3);
+
+        let mut out = Vec::new();
+        write_buffers(&buffers, &mut out).unwrap();
+        assert_eq!(&out[out.len() - 4..], &[0, 0, 0, 0]);
+
+        let mut reader = &out[..];
+        let read = read_buffers(&mut reader).unwrap().unwrap();
+        assert_eq!(read.concat(), data);
+        assert!(read_buffers(&mut reader).unwrap().is_none());
+    }}
+
+    test!{length_is_big_endian, {
+        let mut out = Vec::new();
+        write_buffers(&[vec![7; 3]], &mut out).unwrap();
+        assert_eq!(out, vec![0, 0, 0, 3, 7, 7, 7, 0, 0, 0, 0]);
+    }}
+
+    test!{truncated_frame, {
+        let mut reader = &[0u8, 0, 0, 5, 1][..];
+        assert!(read_buffers(&mut reader).is_err());
+    }}
+}
+
+mod tcp {
+    use std::str::FromStr;
+    use std::thread;
+    use ravro::datum::Datum;
+    use ravro::protocol::Protocol;
+    use ravro::rpc::{Requestor, Responder, TcpServer, TcpTransceiver};
+    use {MAIL, mail_handler, send_request};
+
+    test!{call_over_tcp, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let mut server = TcpServer::bind("127.0.0.1:0", Responder::new(p.clone(), mail_handler)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = thread::spawn(move || server.accept().unwrap());
+
+        {
+            let mut requestor = Requestor::new(p, TcpTransceiver::connect(addr).unwrap());
+            assert_eq!(requestor.request("send", &send_request("bob")).unwrap(), Datum::from("sent to bob"));
+            assert_eq!(requestor.request("ping", &Datum::Record(vec![])).unwrap(), Datum::Null);
+        }
+
+        handle.join().unwrap();
+    }}
+
+    test!{connection_survives_bad_response, {
+        let p = Protocol::from_str(MAIL).unwrap();
+        let handler = |message: &str, request: &Datum| {
+            match message {
+                "send" if request.field("message").and_then(|m| m.field("to")) == Some(&Datum::from("nobody")) => Ok(Datum::Int(1)),
+                _ => mail_handler(message, request),
+            }
+        };
+        let mut server = TcpServer::bind("127.0.0.1:0", Responder::new(p.clone(), handler)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = thread::spawn(move || server.accept().unwrap());
+
+        {
+            let mut requestor = Requestor::new(p, TcpTransceiver::connect(addr).unwrap());
+            assert!(requestor.request("send", &send_request("nobody")).is_err());
+            assert_eq!(requestor.request("send", &send_request("bob")).unwrap(), Datum::from("sent to bob"));
+        }
+
+        handle.join().unwrap();
+    }}
+}
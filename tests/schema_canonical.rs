@@ -0,0 +1,97 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+mod canonical_form {
+    use ravro::schema;
+
+    test!{primitives, {
+        assert_eq!(schema::from_str(r#""int""#).unwrap().canonical_form(), r#""int""#);
+        assert_eq!(schema::from_str(r#"{"type": "int"}"#).unwrap().canonical_form(), r#""int""#);
+        assert_eq!(schema::from_str(r#"{"type": "long", "logicalType": "timestamp-millis"}"#).unwrap().canonical_form(), r#""long""#);
+    }}
+
+    test!{strips_and_orders_attributes, {
+        let s = schema::from_str(r#"{
+            "fields": [
+                {"type": "int", "name": "a", "doc": "an int", "default": 1},
+                {"name": "b", "type": {"type": "array", "items": "string", "extra": 1}}
+            ],
+            "doc": "A record",
+            "name": "R",
+            "namespace": "x.y",
+            "aliases": ["Q"],
+            "type": "record"
+        }"#).unwrap();
+
+        assert_eq!(s.canonical_form(),
+            r#"{"name":"x.y.R","type":"record","fields":[{"name":"a","type":"int"},{"name":"b","type":{"type":"array","items":"string"}}]}"#);
+    }}
+
+    test!{full_names_for_references, {
+        let s = schema::from_str(r#"{"type": "record", "name": "Node", "namespace": "n", "fields": [
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["A", "B"]}},
+            {"name": "id", "type": {"type": "fixed", "name": "other.Id", "size": 4}},
+            {"name": "again", "type": "Kind"},
+            {"name": "next", "type": ["null", "Node"]}
+        ]}"#).unwrap();
+
+        assert_eq!(s.canonical_form(), concat!(
+            r#"{"name":"n.Node","type":"record","fields":["#,
+            r#"{"name":"kind","type":{"name":"n.Kind","type":"enum","symbols":["A","B"]}},"#,
+            r#"{"name":"id","type":{"name":"other.Id","type":"fixed","size":4}},"#,
+            r#"{"name":"again","type":"n.Kind"},"#,
+            r#"{"name":"next","type":["null","n.Node"]}]}"#));
+    }}
+
+    test!{errors_are_records, {
+        let s = schema::from_str(r#"{"type": "error", "name": "E", "fields": []}"#).unwrap();
+        assert_eq!(s.canonical_form(), r#"{"name":"E","type":"record","fields":[]}"#);
+    }}
+}
+
+mod fingerprint {
+    use ravro::schema::{self, canonical};
+
+    test!{spec_values, {
+        assert_eq!(canonical::crc64_avro(b"\"null\""), 0x63dd24e7cc258f8a);
+        assert_eq!(schema::from_str(r#""int""#).unwrap().fingerprint(), 0x7275d51a3f395c8f);
+        assert_eq!(schema::from_str(r#""boolean""#).unwrap().fingerprint() as i64, -6970731678124411036);
+    }}
+
+    test!{ignores_what_does_not_affect_reading, {
+        let a = schema::from_str(r#"{"type": "record", "name": "R", "doc": "x", "fields": [{"name": "a", "type": "int"}]}"#).unwrap();
+        let b = schema::from_str(r#"{"name": "R", "type": "record", "fields": [{"type": {"type": "int"}, "name": "a", "default": 0}]}"#).unwrap();
+        let c = schema::from_str(r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "long"}]}"#).unwrap();
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert!(a.fingerprint() != c.fingerprint());
+    }}
+}
@@ -0,0 +1,180 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+fn changes(old: &str, new: &str) -> Vec<ravro::schema::diff::Change> {
+    use ravro::schema;
+
+    schema::diff(&schema::from_str(old).unwrap(), &schema::from_str(new).unwrap()).unwrap().changes
+}
+
+mod fields {
+    use ravro::schema::diff::{Change, ChangeKind};
+    use serde::json::Value;
+    use changes;
+
+    test!{unchanged, {
+        let s = r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int"}]}"#;
+        assert!(changes(s, s).is_empty());
+    }}
+
+    test!{added_with_and_without_default, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int"}]}"#,
+            r#"{"type": "record", "name": "R", "fields": [
+                {"name": "a", "type": "int"},
+                {"name": "b", "type": "int", "default": 0},
+                {"name": "c", "type": "string"}
+            ]}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R"), kind: ChangeKind::FieldAdded(String::from("b")), backward: true, forward: true },
+            Change { path: String::from("R"), kind: ChangeKind::FieldAdded(String::from("c")), backward: false, forward: true },
+        ]);
+    }}
+
+    test!{removed, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int"}, {"name": "b", "type": "int", "default": 1}]}"#,
+            r#"{"type": "record", "name": "R", "fields": []}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R"), kind: ChangeKind::FieldRemoved(String::from("a")), backward: true, forward: false },
+            Change { path: String::from("R"), kind: ChangeKind::FieldRemoved(String::from("b")), backward: true, forward: true },
+        ]);
+    }}
+
+    test!{renamed_through_aliases, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "fields": [{"name": "old", "type": "int"}]}"#,
+            r#"{"type": "record", "name": "R", "fields": [{"name": "new", "type": "int", "aliases": ["old"]}]}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R"), kind: ChangeKind::FieldRenamed(String::from("old"), String::from("new")), backward: true, forward: false },
+        ]);
+    }}
+
+    test!{type_changes, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int"}, {"name": "b", "type": "string"}]}"#,
+            r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "long"}, {"name": "b", "type": ["null", "string"]}]}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R.a"), kind: ChangeKind::TypeChanged(String::from("int"), String::from("long")), backward: true, forward: false },
+            Change { path: String::from("R.b"), kind: ChangeKind::TypeChanged(String::from("string"), String::from("union<null, string>")), backward: true, forward: false },
+        ]);
+    }}
+
+    test!{defaults_and_docs, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int", "default": 1}]}"#,
+            r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int", "default": 2, "doc": "An a."}]}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R.a"), kind: ChangeKind::DefaultChanged(Some(Value::U64(1)), Some(Value::U64(2))), backward: true, forward: true },
+            Change { path: String::from("R.a"), kind: ChangeKind::DocChanged, backward: true, forward: true },
+        ]);
+    }}
+
+    test!{nested_records, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "fields": [{"name": "inner", "type": ["null", {"type": "record", "name": "I", "fields": [
+                {"name": "x", "type": "int"}
+            ]}]}]}"#,
+            r#"{"type": "record", "name": "R", "fields": [{"name": "inner", "type": [{"type": "record", "name": "I", "fields": [
+                {"name": "x", "type": "double"}
+            ]}, "null"]}]}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R.inner.x"), kind: ChangeKind::TypeChanged(String::from("int"), String::from("double")), backward: true, forward: false },
+        ]);
+    }}
+}
+
+mod named_types {
+    use ravro::schema::diff::{Change, ChangeKind};
+    use changes;
+
+    test!{enum_symbols, {
+        let found = changes(
+            r#"{"type": "enum", "name": "E", "symbols": ["A", "B"]}"#,
+            r#"{"type": "enum", "name": "E", "symbols": ["A", "C"]}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("E"), kind: ChangeKind::SymbolsAdded(vec![String::from("C")]), backward: true, forward: false },
+            Change { path: String::from("E"), kind: ChangeKind::SymbolsRemoved(vec![String::from("B")]), backward: false, forward: true },
+        ]);
+    }}
+
+    test!{namespace_moves, {
+        let found = changes(
+            r#"{"type": "record", "name": "R", "namespace": "a", "fields": []}"#,
+            r#"{"type": "record", "name": "R", "namespace": "b", "fields": []}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("R"), kind: ChangeKind::NamespaceMoved(Some(String::from("a")), Some(String::from("b"))), backward: true, forward: true },
+        ]);
+    }}
+
+    test!{type_renamed, {
+        let found = changes(
+            r#"{"type": "record", "name": "Old", "fields": []}"#,
+            r#"{"type": "record", "name": "New", "aliases": ["Old"], "fields": []}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("Old"), kind: ChangeKind::TypeRenamed(String::from("Old"), String::from("New")), backward: true, forward: false },
+        ]);
+    }}
+
+    test!{fixed_size, {
+        let found = changes(r#"{"type": "fixed", "name": "F", "size": 4}"#, r#"{"type": "fixed", "name": "F", "size": 8}"#);
+        assert_eq!(found, vec![
+            Change { path: String::from("F"), kind: ChangeKind::TypeChanged(String::from("F(4)"), String::from("F(8)")), backward: false, forward: false },
+        ]);
+    }}
+
+    test!{recursive_records, {
+        let s = r#"{"type": "record", "name": "Node", "fields": [{"name": "next", "type": ["null", "Node"]}]}"#;
+        assert!(changes(s, s).is_empty());
+    }}
+}
+
+mod report {
+    use ravro::schema;
+
+    test!{text, {
+        let diff = schema::diff(
+            &schema::from_str(r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "int"}]}"#).unwrap(),
+            &schema::from_str(r#"{"type": "record", "name": "R", "fields": [{"name": "a", "type": "long"}, {"name": "b", "type": "int"}]}"#).unwrap(),
+        ).unwrap();
+        assert!(!diff.is_backward_compatible());
+        assert!(!diff.is_forward_compatible());
+        assert_eq!(diff.to_string(), "\
+R.a: type changed from int to long (breaks forward compatibility)
+R: field b added (breaks backward compatibility)
+backward compatible: no, forward compatible: no");
+    }}
+
+    test!{no_changes, {
+        let s = schema::from_str(r#""string""#).unwrap();
+        assert_eq!(schema::diff(&s, &s).unwrap().to_string(), "no changes");
+    }}
+}
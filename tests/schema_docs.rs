@@ -0,0 +1,133 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const EVENT: &'static str = r#"{"type": "record", "name": "Event", "namespace": "ev", "doc": "Something that happened.", "fields": [
+    {"name": "id", "type": "long", "doc": "Unique | per source.", "order": "descending"},
+    {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["CLICK", "VIEW"], "default": "VIEW"}},
+    {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}, "aliases": ["time"]},
+    {"name": "source", "type": ["null", {"type": "record", "name": "Source", "fields": [
+        {"name": "host", "type": "string", "default": "localhost"}
+    ]}], "default": null},
+    {"name": "previous", "type": {"type": "array", "items": "Kind"}}
+]}"#;
+
+mod markdown {
+    use ravro::schema;
+    use ravro::schema::docs::{DocWriter, Format};
+    use EVENT;
+
+    fn render() -> String {
+        let mut writer = DocWriter::new(Format::Markdown);
+        writer.add_schema(&schema::from_str(EVENT).unwrap());
+        writer.render("Events")
+    }
+
+    test!{contents_in_reading_order, {
+        let doc = render();
+        assert!(doc.starts_with("# Events\n\n- [Event](#ev.Event)\n- [Kind](#ev.Kind)\n- [Source](#ev.Source)\n\n"));
+    }}
+
+    test!{record_section, {
+        let doc = render();
+        assert!(doc.contains("<a id=\"ev.Event\"></a>\n## Event\n\n*record* `ev.Event`\n\nSomething that happened.\n\n"));
+        assert!(doc.contains("| Field | Type | Default | Order | Aliases | Doc |\n| --- | --- | --- | --- | --- | --- |\n"));
+        assert!(doc.contains("| `id` | long |  | descending |  | Unique \\| per source. |\n"));
+        assert!(doc.contains("| `at` | long (timestamp-millis) |  |  | `time` |  |\n"));
+    }}
+
+    test!{links_to_named_types, {
+        let doc = render();
+        assert!(doc.contains("| `kind` | [Kind](#ev.Kind) |"));
+        assert!(doc.contains("| `source` | union&lt;null, [Source](#ev.Source)&gt; | `null` |"));
+        assert!(doc.contains("| `previous` | array&lt;[Kind](#ev.Kind)&gt; |"));
+    }}
+
+    test!{enum_section, {
+        let doc = render();
+        assert!(doc.contains("## Kind\n\n*enum* `ev.Kind`\n\nSymbols: `CLICK`, `VIEW`\n\nDefault: `VIEW`\n\n"));
+    }}
+
+    test!{clashing_short_names, {
+        let mut writer = DocWriter::new(Format::Markdown);
+        writer.add_schema(&schema::from_str(r#"{"type": "fixed", "name": "a.Id", "size": 4}"#).unwrap());
+        writer.add_schema(&schema::from_str(r#"{"type": "fixed", "name": "b.Id", "size": 8}"#).unwrap());
+        let doc = writer.render("Ids");
+        assert!(doc.contains("## a.Id\n"));
+        assert!(doc.contains("## b.Id\n"));
+        assert!(doc.contains("Size: 8 bytes"));
+    }}
+}
+
+mod html {
+    use ravro::schema;
+    use ravro::schema::docs::{DocWriter, Format};
+    use EVENT;
+
+    test!{document, {
+        let mut writer = DocWriter::new(Format::Html);
+        writer.add_schema(&schema::from_str(EVENT).unwrap());
+        let doc = writer.render("Events & more");
+
+        assert!(doc.starts_with("<!DOCTYPE html>\n"));
+        assert!(doc.contains("<title>Events &amp; more</title>"));
+        assert!(doc.contains("<h2 id=\"ev.Event\">Event</h2>"));
+        assert!(doc.contains("<td><code>kind</code></td><td><a href=\"#ev.Kind\">Kind</a></td>"));
+        assert!(doc.ends_with("</body>\n</html>\n"));
+    }}
+}
+
+mod protocols {
+    use std::str::FromStr;
+
+    use ravro::protocol::Protocol;
+    use ravro::schema::docs::{DocWriter, Format};
+
+    test!{messages, {
+        let protocol = Protocol::from_str(r#"{"protocol": "Mail", "namespace": "m", "types": [
+            {"type": "record", "name": "Letter", "fields": [{"name": "body", "type": "string"}]},
+            {"type": "error", "name": "Lost", "fields": [{"name": "reason", "type": "string"}]}
+        ], "messages": {
+            "send": {"doc": "Posts a letter.", "request": [{"name": "letter", "type": "Letter"}], "response": "boolean", "errors": ["Lost"]},
+            "ping": {"request": [], "response": "null", "one-way": true}
+        }}"#).unwrap();
+
+        let mut writer = DocWriter::new(Format::Markdown);
+        writer.add_protocol(&protocol);
+        let doc = writer.render("Mail");
+
+        assert!(doc.contains("- [Messages](#messages)\n"));
+        assert!(doc.contains("*error* `m.Lost`"));
+        assert!(doc.contains("<a id=\"messages.send\"></a>\n### send\n\nPosts a letter.\n\n"));
+        assert!(doc.contains("| `letter` | [Letter](#m.Letter) |  |  |\n"));
+        assert!(doc.contains("Response: boolean\n\nErrors: [Lost](#m.Lost)\n\n"));
+        assert!(doc.contains("One-way: there is no response."));
+    }}
+}
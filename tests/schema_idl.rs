@@ -0,0 +1,170 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+mod to_idl {
+    use ravro::schema::{self, Schema, FieldSortOrder, RecordBuilder, UnionBuilder};
+    use serde::json::Value;
+
+    test!{primitive, {
+        let s = Schema::String(String::from("int"));
+        assert_eq!(s.to_idl(), "schema int;\n");
+    }}
+
+    test!{union_of_primitives, {
+        let s = schema::from_str(r#"["null","string"]"#).unwrap();
+        assert_eq!(s.to_idl(), "schema union { null, string };\n");
+    }}
+
+    test!{record, {
+        let r = RecordBuilder::new()
+                    .name("User")
+                    .namespace("x.y")
+                    .doc("A user")
+                    .aliases(vec!["Person"])
+                    .fields(|fab|
+                        fab.push(|fb| fb.name("id").field_type(Schema::String(String::from("long"))) )
+                           .push(|fb| fb.name("name")
+                                        .doc("Display name")
+                                        .order(FieldSortOrder::Descending)
+                                        .aliases(vec!["login"])
+                                        .field_type(Schema::String(String::from("string")))
+                                        .default(Value::String(String::from("anon"))) )
+                           .push(|fb| fb.name("email")
+                                        .field_type(UnionBuilder::new()
+                                            .push_schema(Schema::String(String::from("null")))
+                                            .push_schema(Schema::String(String::from("string")))
+                                            .unwrap())
+                                        .default(Value::Null) )
+                    )
+                    .unwrap();
+
+        let idl = concat!(
+            "namespace x.y;\n",
+            "\n",
+            "schema User;\n",
+            "\n",
+            "/** A user */\n",
+            "@aliases([\"Person\"])\n",
+            "record User {\n",
+            "  long id;\n",
+            "  /** Display name */\n",
+            "  string @order(\"descending\") @aliases([\"login\"]) name = \"anon\";\n",
+            "  union { null, string } email = null;\n",
+            "}\n"
+        );
+
+        assert_eq!(r.to_idl(), idl);
+    }}
+
+    test!{dependency_order, {
+        let s = schema::from_str(r#"{
+            "type": "record", "name": "Order", "namespace": "shop",
+            "fields": [
+                {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["OPEN", "CLOSED"], "default": "OPEN"}},
+                {"name": "lines", "type": {"type": "array", "items": {
+                    "type": "record", "name": "Line", "fields": [
+                        {"name": "sku", "type": {"type": "fixed", "name": "Sku", "namespace": "catalog", "size": 8}},
+                        {"name": "qty", "type": "int"}
+                    ]}}},
+                {"name": "extra", "type": {"type": "map", "values": "Line"}}
+            ]
+        }"#).unwrap();
+
+        let idl = concat!(
+            "namespace shop;\n",
+            "\n",
+            "schema Order;\n",
+            "\n",
+            "enum Status {\n",
+            "  OPEN, CLOSED\n",
+            "} = OPEN;\n",
+            "\n",
+            "@namespace(\"catalog\")\n",
+            "fixed Sku(8);\n",
+            "\n",
+            "record Line {\n",
+            "  catalog.Sku sku;\n",
+            "  int qty;\n",
+            "}\n",
+            "\n",
+            "record Order {\n",
+            "  Status status;\n",
+            "  array<Line> lines;\n",
+            "  map<Line> extra;\n",
+            "}\n"
+        );
+
+        assert_eq!(s.to_idl(), idl);
+    }}
+
+    test!{logical_types_and_keywords, {
+        let s = schema::from_str(r#"{
+            "type": "record", "name": "Event",
+            "fields": [
+                {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+                {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 9, "scale": 2}},
+                {"name": "error", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+                {"name": "void", "type": "boolean"}
+            ]
+        }"#).unwrap();
+
+        let idl = concat!(
+            "schema Event;\n",
+            "\n",
+            "record Event {\n",
+            "  date day;\n",
+            "  decimal(9, 2) amount;\n",
+            "  @logicalType(\"timestamp-micros\") long `error`;\n",
+            "  boolean `void`;\n",
+            "}\n"
+        );
+
+        assert_eq!(s.to_idl(), idl);
+    }}
+
+    test!{protocol, {
+        let s = schema::from_str(r#"{
+            "type": "record", "name": "Ping", "namespace": "net",
+            "fields": [{"name": "seq", "type": "long"}]
+        }"#).unwrap();
+
+        let idl = concat!(
+            "@namespace(\"net\")\n",
+            "protocol Pinger {\n",
+            "  record Ping {\n",
+            "    long seq;\n",
+            "  }\n",
+            "}\n"
+        );
+
+        assert_eq!(s.to_idl_protocol("Pinger"), idl);
+    }}
+}
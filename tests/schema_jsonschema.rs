@@ -0,0 +1,202 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+fn json(text: &str) -> serde::json::Value {
+    serde::json::from_str(text).unwrap()
+}
+
+mod to_json_schema {
+    use ravro::schema;
+    use ravro::schema::jsonschema::to_json_schema;
+    use json;
+
+    test!{record, {
+        let s = schema::from_str(r#"{"type": "record", "name": "User", "doc": "A user", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "email", "type": ["null", "string"], "default": null},
+            {"name": "tags", "type": {"type": "array", "items": "string"}},
+            {"name": "scores", "type": {"type": "map", "values": "double"}},
+            {"name": "joined", "type": {"type": "int", "logicalType": "date"}}
+        ]}"#).unwrap();
+        let converted = to_json_schema(&s);
+        assert_eq!(converted.schema, json(r#"{
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "User",
+            "description": "A user",
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer", "format": "int64"},
+                "email": {"type": ["string", "null"], "default": null},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "scores": {"type": "object", "additionalProperties": {"type": "number", "format": "double"}},
+                "joined": {"type": "integer", "format": "date"}
+            },
+            "required": ["id", "tags", "scores", "joined"],
+            "additionalProperties": false
+        }"#));
+        assert!(converted.warnings.is_empty());
+    }}
+
+    test!{enums_and_unions, {
+        let s = schema::from_str(r#"{"type": "record", "name": "Card", "namespace": "game", "fields": [
+            {"name": "suit", "type": ["null", {"type": "enum", "name": "Suit", "symbols": ["SPADES", "HEARTS"]}]},
+            {"name": "value", "type": ["int", "string"]}
+        ]}"#).unwrap();
+        let converted = to_json_schema(&s);
+        assert_eq!(converted.schema.find("properties").unwrap(), &json(r##"{
+            "suit": {"oneOf": [{"type": "null"}, {"$ref": "#/definitions/game.Suit"}]},
+            "value": {"oneOf": [{"type": "integer", "format": "int32"}, {"type": "string"}]}
+        }"##));
+        assert_eq!(converted.schema.find("definitions").unwrap(), &json(r#"{
+            "game.Suit": {"title": "Suit", "type": "string", "enum": ["SPADES", "HEARTS"]}
+        }"#));
+    }}
+
+    test!{recursive_records, {
+        let s = schema::from_str(r#"{"type": "record", "name": "Node", "fields": [
+            {"name": "next", "type": ["null", "Node"]}
+        ]}"#).unwrap();
+        let converted = to_json_schema(&s);
+        assert_eq!(converted.schema.find("properties").and_then(|p| p.find("next")).unwrap(),
+            &json(r##"{"oneOf": [{"type": "null"}, {"$ref": "#"}]}"##));
+        assert!(converted.schema.find("definitions").is_none());
+    }}
+
+    test!{warnings, {
+        let s = schema::from_str(r#"{"type": "record", "name": "R", "aliases": ["Old"], "fields": [
+            {"name": "b", "type": "bytes"},
+            {"name": "f", "type": {"type": "fixed", "name": "F", "size": 4}},
+            {"name": "n", "type": ["int", "long"]},
+            {"name": "o", "type": "string", "order": "descending"}
+        ]}"#).unwrap();
+        let converted = to_json_schema(&s);
+        let paths: Vec<&str> = converted.warnings.iter().map(|w| &w.path[..]).collect();
+        assert_eq!(paths, vec!["R", "R.b", "F", "R.n", "R.o"]);
+        assert_eq!(converted.schema.find("definitions").and_then(|d| d.find("F")).unwrap(),
+            &json(r#"{"title": "F", "type": "string", "minLength": 4, "maxLength": 4}"#));
+    }}
+}
+
+mod from_json_schema {
+    use ravro::schema;
+    use ravro::schema::jsonschema::from_json_schema;
+    use json;
+
+    test!{object, {
+        let converted = from_json_schema(&json(r#"{
+            "title": "user",
+            "type": "object",
+            "description": "A user",
+            "properties": {
+                "id": {"type": "integer"},
+                "name": {"type": "string", "description": "Display name"},
+                "age": {"type": "integer", "format": "int32"},
+                "id2": {"type": "string", "format": "uuid"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "attrs": {"type": "object", "additionalProperties": {"type": "number"}}
+            },
+            "required": ["id", "name", "tags", "attrs", "id2"]
+        }"#), "Ignored");
+        assert_eq!(converted.schema, schema::from_str(r#"{"type": "record", "name": "User", "doc": "A user", "fields": [
+            {"name": "age", "type": ["null", "int"], "default": null},
+            {"name": "attrs", "type": {"type": "map", "values": "double"}},
+            {"name": "id", "type": "long"},
+            {"name": "id2", "type": {"type": "string", "logicalType": "uuid"}},
+            {"name": "name", "type": "string", "doc": "Display name"},
+            {"name": "tags", "type": {"type": "array", "items": "string"}}
+        ]}"#).unwrap());
+        assert!(converted.warnings.is_empty());
+        assert!(converted.schema.is_valid().is_ok());
+    }}
+
+    test!{enums_unions_and_refs, {
+        let converted = from_json_schema(&json(r##"{
+            "type": "object",
+            "properties": {
+                "suit": {"enum": ["SPADES", "HEARTS", null]},
+                "value": {"oneOf": [{"type": "integer"}, {"type": "string"}]},
+                "next": {"$ref": "#"},
+                "address": {"$ref": "#/definitions/address"}
+            },
+            "required": ["suit", "value", "next", "address"],
+            "definitions": {
+                "address": {"type": "object", "properties": {"city": {"type": "string"}}, "required": ["city"]}
+            }
+        }"##), "Card");
+        assert_eq!(converted.schema, schema::from_str(r#"{"type": "record", "name": "Card", "fields": [
+            {"name": "address", "type": {"type": "record", "name": "Address", "fields": [
+                {"name": "city", "type": "string"}
+            ]}},
+            {"name": "next", "type": "Card"},
+            {"name": "suit", "type": ["null", {"type": "enum", "name": "Suit", "symbols": ["SPADES", "HEARTS"]}]},
+            {"name": "value", "type": ["long", "string"]}
+        ]}"#).unwrap());
+        assert!(converted.warnings.is_empty());
+    }}
+
+    test!{warnings, {
+        let converted = from_json_schema(&json(r#"{
+            "type": "object",
+            "properties": {
+                "age": {"type": "integer", "minimum": 0},
+                "first-name": {"type": "string", "format": "email"},
+                "extra": {}
+            },
+            "required": ["age", "first-name", "extra"]
+        }"#), "Person");
+        let paths: Vec<&str> = converted.warnings.iter().map(|w| &w.path[..]).collect();
+        assert_eq!(paths, vec!["/properties/age", "/properties/extra", "/properties/first-name", "/properties/first-name"]);
+        assert!(converted.schema.fields().unwrap().iter().any(|f| f.name().map(|n| &n[..]) == Some("first_name")));
+    }}
+
+    test!{defaults_and_union_branches, {
+        let converted = from_json_schema(&json(r#"{
+            "type": "object",
+            "properties": {
+                "label": {"type": ["null", "string"], "default": "none"},
+                "count": {"type": "integer", "default": "many"},
+                "attrs": {"oneOf": [
+                    {"type": "object", "additionalProperties": {"type": "string"}},
+                    {"type": "object", "additionalProperties": {"type": "integer"}}
+                ]}
+            },
+            "required": ["label", "count", "attrs"]
+        }"#), "Item");
+        assert_eq!(converted.schema, schema::from_str(r#"{"type": "record", "name": "Item", "fields": [
+            {"name": "attrs", "type": {"type": "map", "values": "string"}},
+            {"name": "count", "type": "long"},
+            {"name": "label", "type": ["string", "null"], "default": "none"}
+        ]}"#).unwrap());
+        let paths: Vec<&str> = converted.warnings.iter().map(|w| &w.path[..]).collect();
+        assert_eq!(paths, vec!["/properties/attrs", "/properties/count"]);
+        assert!(converted.schema.is_valid().is_ok());
+    }}
+}
@@ -576,7 +576,7 @@ mod record {
     }
 
     mod is_valid {
-        use ravro::schema::{ArrayBuilder, MapBuilder, RecordBuilder, Schema};
+        use ravro::schema::{self, ArrayBuilder, FieldSortOrder, MapBuilder, RecordBuilder, Schema};
         use ravro::schema::error::{Error, ErrorCode};
         use serde::json::{self, Value};
 
@@ -1188,6 +1188,82 @@ mod record {
             let valid = r.is_valid();
             assert!(valid.is_ok());
         }}
+
+        test!{bad_order_for_field, {
+            let r = schema::from_str(r#"{"type": "record", "name": "foo", "fields": [
+                {"name": "bar", "type": "string", "order": "sideways"}
+            ]}"#).unwrap();
+
+            let valid = r.is_valid();
+            assert!(valid.is_err());
+
+            if let Some(Error::SyntaxError(code, _, _)) = valid.err() {
+                assert_eq!(code, ErrorCode::NotValidSortOrder);
+            } else {
+                assert!(false);
+            }
+        }}
+
+        test!{ok_order_for_field, {
+            let r = RecordBuilder::new()
+                .name("foo")
+                .fields(|fab|
+                    fab.push(|fb|
+                        fb
+                            .name("bar")
+                            .field_type(Schema::String(String::from("string")))
+                            .order(FieldSortOrder::Ignore)
+                    )
+                )
+                .unwrap();
+
+            let valid = r.is_valid();
+            assert!(valid.is_ok());
+        }}
+    }
+
+    mod fields {
+        use std::str::FromStr;
+        use ravro::schema::{self, FieldSortOrder, Schema};
+        use serde::json::Value;
+
+        test!{typed_accessors, {
+            let r = schema::from_str(r#"{"type": "record", "name": "foo", "fields": [
+                {"name": "bar", "type": "string", "doc": "a bar", "aliases": ["baz"],
+                 "default": "none", "order": "descending"},
+                {"name": "qux", "type": ["null", "int"]}
+            ]}"#).unwrap();
+
+            let fields = r.fields().unwrap();
+            assert_eq!(fields.len(), 2);
+
+            let bar = fields[0];
+            assert_eq!(bar.name().unwrap(), "bar");
+            assert_eq!(bar.field_type().unwrap(), Schema::String(String::from("string")));
+            assert_eq!(bar.doc().unwrap(), "a bar");
+            assert_eq!(bar.aliases().unwrap(), vec![String::from("baz")]);
+            assert_eq!(bar.default(), Some(&Value::String(String::from("none"))));
+            assert_eq!(bar.order().unwrap(), FieldSortOrder::Descending);
+
+            let qux = fields[1];
+            assert!(qux.field_type().unwrap().is_union());
+            assert!(qux.doc().is_none());
+            assert!(qux.aliases().is_none());
+            assert!(qux.default().is_none());
+            assert_eq!(qux.order().unwrap(), FieldSortOrder::Ascending);
+        }}
+
+        test!{not_a_record, {
+            assert!(Schema::String(String::from("int")).fields().is_none());
+        }}
+
+        test!{sort_order_from_str, {
+            for order in [FieldSortOrder::Ascending, FieldSortOrder::Descending, FieldSortOrder::Ignore].iter() {
+                assert_eq!(FieldSortOrder::from_str(&order.to_string()).unwrap(), *order);
+            }
+            assert!(FieldSortOrder::from_str("sideways").is_err());
+            assert!(FieldSortOrder::from_str("Ascending").is_err());
+        }}
     }
 }
 
@@ -0,0 +1,179 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const ORDER: &'static str = r#"{"type": "record", "name": "Order", "namespace": "shop", "doc": "An order.", "fields": [
+    {"name": "id", "type": "long", "doc": "The order's id."},
+    {"name": "note", "type": ["null", "string"]},
+    {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["OPEN", "SHIPPED"]}},
+    {"name": "total", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}},
+    {"name": "ship_to", "type": ["null", {"type": "record", "name": "Address", "fields": [
+        {"name": "street", "type": "string"},
+        {"name": "city", "type": "string", "doc": "Where it's going."}
+    ]}]},
+    {"name": "items", "type": {"type": "array", "items": "string"}}
+]}"#;
+
+mod dialects {
+    use ravro::schema;
+    use ravro::schema::sql::Dialect;
+    use ORDER;
+
+    test!{postgres, {
+        let ddl = schema::from_str(ORDER).unwrap().to_sql_ddl(Dialect::Postgres).unwrap();
+        assert_eq!(ddl, "\
+CREATE TYPE \"Status\" AS ENUM ('OPEN', 'SHIPPED');
+
+CREATE TABLE \"Order\" (
+    \"id\" BIGINT NOT NULL,
+    \"note\" TEXT NULL,
+    \"status\" \"Status\" NOT NULL,
+    \"total\" NUMERIC(10,2) NOT NULL,
+    \"ship_to_street\" TEXT NULL,
+    \"ship_to_city\" TEXT NULL,
+    \"items\" JSONB NOT NULL
+);
+
+COMMENT ON TABLE \"Order\" IS 'An order.';
+COMMENT ON COLUMN \"Order\".\"id\" IS 'The order''s id.';
+COMMENT ON COLUMN \"Order\".\"ship_to_city\" IS 'Where it''s going.';
+");
+    }}
+
+    test!{sqlite, {
+        let ddl = schema::from_str(ORDER).unwrap().to_sql_ddl(Dialect::Sqlite).unwrap();
+        assert_eq!(ddl, "\
+-- An order.
+CREATE TABLE \"Order\" (
+    \"id\" INTEGER NOT NULL, -- The order's id.
+    \"note\" TEXT NULL,
+    \"status\" TEXT NOT NULL CHECK (\"status\" IN ('OPEN', 'SHIPPED')),
+    \"total\" NUMERIC(10,2) NOT NULL,
+    \"ship_to_street\" TEXT NULL,
+    \"ship_to_city\" TEXT NULL, -- Where it's going.
+    \"items\" TEXT NOT NULL
+);
+");
+    }}
+
+    test!{ansi, {
+        let ddl = schema::from_str(ORDER).unwrap().to_sql_ddl(Dialect::Ansi).unwrap();
+        assert!(ddl.contains("    \"id\" BIGINT NOT NULL, -- The order's id.\n"));
+        assert!(ddl.contains("    \"note\" CLOB NULL,\n"));
+        assert!(ddl.contains("    \"status\" VARCHAR(7) NOT NULL CHECK (\"status\" IN ('OPEN', 'SHIPPED')),\n"));
+        assert!(ddl.contains("    \"items\" CLOB NOT NULL\n"));
+    }}
+}
+
+mod columns {
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+    use ravro::schema::sql::{Dialect, Options};
+    use ORDER;
+
+    test!{separator_and_table_name, {
+        let options = Options { dialect: Dialect::Postgres, separator: String::from("__"), table: Some(String::from("orders")) };
+        let ddl = schema::from_str(ORDER).unwrap().to_sql_ddl_with(&options).unwrap();
+        assert!(ddl.contains("CREATE TABLE \"orders\" (\n"));
+        assert!(ddl.contains("    \"ship_to__city\" TEXT NULL,\n"));
+        assert!(ddl.contains("COMMENT ON COLUMN \"orders\".\"ship_to__city\""));
+    }}
+
+    test!{logical_types, {
+        let s = schema::from_str(r#"{"type": "record", "name": "Times", "fields": [
+            {"name": "day", "type": {"type": "int", "logicalType": "date"}},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "id", "type": {"type": "string", "logicalType": "uuid"}},
+            {"name": "hash", "type": {"type": "fixed", "name": "Hash", "size": 16}}
+        ]}"#).unwrap();
+        let postgres = s.to_sql_ddl(Dialect::Postgres).unwrap();
+        assert!(postgres.contains("\"day\" DATE NOT NULL"));
+        assert!(postgres.contains("\"at\" TIMESTAMP WITH TIME ZONE NOT NULL"));
+        assert!(postgres.contains("\"id\" UUID NOT NULL"));
+        assert!(postgres.contains("\"hash\" BYTEA NOT NULL"));
+
+        let sqlite = s.to_sql_ddl(Dialect::Sqlite).unwrap();
+        assert!(sqlite.contains("\"day\" INTEGER NOT NULL"));
+        assert!(sqlite.contains("\"at\" INTEGER NOT NULL"));
+
+        assert!(s.to_sql_ddl(Dialect::Ansi).unwrap().contains("\"hash\" BINARY(16) NOT NULL"));
+    }}
+
+    test!{clashing_column_names, {
+        let s = schema::from_str(r#"{"type": "record", "name": "R", "fields": [
+            {"name": "a_b", "type": "int"},
+            {"name": "a", "type": {"type": "record", "name": "A", "fields": [{"name": "b", "type": "string"}]}}
+        ]}"#).unwrap();
+        let ddl = s.to_sql_ddl(Dialect::Sqlite).unwrap();
+        assert!(ddl.contains("    \"a_b\" INTEGER NOT NULL,\n"));
+        assert!(ddl.contains("    \"a_b2\" TEXT NOT NULL\n"));
+    }}
+
+    test!{decimal_without_precision, {
+        let s = schema::from_str(r#"{"type": "record", "name": "R", "fields": [
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal"}}
+        ]}"#).unwrap();
+        assert!(s.to_sql_ddl(Dialect::Ansi).unwrap().contains("\"amount\" NUMERIC NOT NULL"));
+    }}
+
+    test!{shared_enum_type, {
+        let s = schema::from_str(r#"{"type": "record", "name": "Move", "fields": [
+            {"name": "from", "type": {"type": "enum", "name": "Square", "symbols": ["A1", "A2"]}},
+            {"name": "to", "type": "Square"}
+        ]}"#).unwrap();
+        let ddl = s.to_sql_ddl(Dialect::Postgres).unwrap();
+        assert_eq!(ddl.matches("CREATE TYPE").count(), 1);
+        assert!(ddl.contains("\"to\" \"Square\" NOT NULL"));
+    }}
+
+    test!{wide_unions_as_json, {
+        let s = schema::from_str(r#"{"type": "record", "name": "R", "fields": [
+            {"name": "value", "type": ["null", "int", "string"]}
+        ]}"#).unwrap();
+        assert!(s.to_sql_ddl(Dialect::Postgres).unwrap().contains("\"value\" JSONB NULL"));
+    }}
+
+    test!{recursive_record, {
+        let s = schema::from_str(r#"{"type": "record", "name": "Node", "fields": [
+            {"name": "next", "type": ["null", "Node"]}
+        ]}"#).unwrap();
+        match s.to_sql_ddl(Dialect::Ansi) {
+            Err(Error::EncodeError(ErrorCode::NotRepresentableInSql)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }}
+
+    test!{not_a_record, {
+        match schema::from_str(r#""string""#).unwrap().to_sql_ddl(Dialect::Ansi) {
+            Err(Error::EncodeError(ErrorCode::NotRepresentableInSql)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }}
+}
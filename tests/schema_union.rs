@@ -308,3 +308,49 @@ mod builder {
         assert_eq!(s, pretty);
     }}
 }
+
+mod valid {
+    use ravro::schema;
+    use ravro::schema::error::{Error, ErrorCode};
+
+    fn code(text: &str) -> Option<ErrorCode> {
+        match schema::from_str(text).unwrap().is_valid() {
+            Ok(()) => None,
+            Err(Error::SyntaxError(code, _, _)) => Some(code),
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    test!{top_level_union, {
+        assert_eq!(code(r#"["null", "string"]"#), None);
+        assert_eq!(code(r#"["null", {"type": "record", "name": "R", "fields": []},
+                            {"type": "array", "items": "int"}, {"type": "map", "values": "int"}]"#), None);
+    }}
+
+    test!{named_branches_differ_by_fullname, {
+        assert_eq!(code(r#"[{"type": "fixed", "name": "a.F", "size": 1},
+                            {"type": "fixed", "name": "F", "namespace": "b", "size": 2}]"#), None);
+        assert_eq!(code(r#"[{"type": "fixed", "name": "a.F", "size": 1},
+                            {"type": "fixed", "name": "F", "namespace": "a", "size": 2}]"#),
+                   Some(ErrorCode::FieldTooManyElementsOfSameType));
+    }}
+
+    test!{duplicate_unnamed_branches, {
+        assert_eq!(code(r#"["int", "int"]"#), Some(ErrorCode::FieldTooManyElementsOfSameType));
+        assert_eq!(code(r#"["string", {"type": "string"}]"#), Some(ErrorCode::FieldTooManyElementsOfSameType));
+        assert_eq!(code(r#"[{"type": "array", "items": "int"}, {"type": "array", "items": "long"}]"#),
+                   Some(ErrorCode::FieldTooManyElementsOfSameType));
+    }}
+
+    test!{nested_unions, {
+        assert_eq!(code(r#"["null", ["int", "string"]]"#), Some(ErrorCode::CannotNestUnions));
+        assert_eq!(code(r#"{"type": "record", "name": "R", "fields": [
+                            {"name": "f", "type": ["null", ["int"]]}]}"#),
+                   Some(ErrorCode::CannotNestUnions));
+    }}
+
+    test!{invalid_branch, {
+        assert_eq!(code(r#"["null", {"type": "record", "name": "1R", "fields": []}]"#),
+                   Some(ErrorCode::NotWellFormedName));
+    }}
+}
@@ -0,0 +1,114 @@
+// Copyright 2015 Glenn McAllisters
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
+extern crate env_logger;
+
+extern crate ravro;
+extern crate serde;
+
+lazy_static! {
+    pub static ref LOGGER_INIT: () = env_logger::init().unwrap();
+}
+
+#[macro_use]
+mod macros;
+
+const V1: &'static str = r#"{"type": "record", "name": "User", "namespace": "x", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": "int"}
+]}"#;
+
+const V2: &'static str = r#"{"type": "record", "name": "User", "namespace": "x", "fields": [
+    {"name": "name", "type": "string"},
+    {"name": "age", "type": "long"},
+    {"name": "email", "type": ["null", "string"], "default": null}
+]}"#;
+
+fn user() -> ravro::datum::Datum {
+    use ravro::datum::Datum;
+
+    Datum::Record(vec![(String::from("name"), Datum::from("ann")), (String::from("age"), Datum::Int(30))])
+}
+
+mod single_object {
+    use ravro::datum::Datum;
+    use ravro::encoding::single_object::*;
+    use ravro::schema::{self, Schema};
+    use ravro::schema::error::{Error, ErrorCode};
+    use {user, V1, V2};
+
+    test!{header, {
+        let schema = schema::from_str(V1).unwrap();
+        let buf = to_single_object(&user(), &schema).unwrap();
+
+        assert_eq!(&buf[..2], &[0xc3, 0x01]);
+        let fingerprint = schema.fingerprint();
+        for i in 0..8 {
+            assert_eq!(buf[2 + i], (fingerprint >> (8 * i)) as u8);
+        }
+        assert_eq!(read_header(&mut &buf[..]).unwrap(), fingerprint);
+    }}
+
+    test!{round_trip, {
+        let schema = schema::from_str(V1).unwrap();
+        let mut store = InMemorySchemaStore::new();
+        store.add(schema.clone());
+
+        let buf = to_single_object(&user(), &schema).unwrap();
+        assert_eq!(decode_single_object(&store, &schema, &mut &buf[..]).unwrap(), user());
+    }}
+
+    test!{resolves_to_reader_schema, {
+        let writer = schema::from_str(V1).unwrap();
+        let reader = schema::from_str(V2).unwrap();
+        let mut store = InMemorySchemaStore::new();
+        store.add(writer.clone());
+        store.add(reader.clone());
+        assert_eq!(store.len(), 2);
+
+        let buf = to_single_object(&user(), &writer).unwrap();
+        let read = decode_single_object(&store, &reader, &mut &buf[..]).unwrap();
+        assert_eq!(read.field("age"), Some(&Datum::Long(30)));
+        assert_eq!(read.field("email"), Some(&Datum::Union(0, Box::new(Datum::Null))));
+    }}
+
+    test!{unknown_fingerprint, {
+        let schema = schema::from_str(V1).unwrap();
+        let store = InMemorySchemaStore::new();
+        let buf = to_single_object(&user(), &schema).unwrap();
+
+        match decode_single_object(&store, &schema, &mut &buf[..]) {
+            Err(Error::DecodeError(ErrorCode::UnknownFingerprint)) => (),
+            other => panic!("expected an unknown fingerprint error, got {:?}", other),
+        }
+    }}
+
+    test!{bad_marker, {
+        let schema: Schema = schema::from_str(V1).unwrap();
+        let mut store = InMemorySchemaStore::new();
+        store.add(schema.clone());
+        let mut buf = to_single_object(&user(), &schema).unwrap();
+        buf[1] = 0x02;
+
+        match decode_single_object(&store, &schema, &mut &buf[..]) {
+            Err(Error::DecodeError(ErrorCode::BadSingleObjectMarker)) => (),
+            other => panic!("expected a bad marker error, got {:?}", other),
+        }
+    }}
+}